@@ -0,0 +1,544 @@
+//! Derive macros for [`diesel-softdelete`](https://docs.rs/diesel-softdelete).
+//!
+//! Those are re-exported by `diesel-softdelete` with its `derive` feature, and should be used
+//! through it.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Delimiter, Span, TokenTree};
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, Attribute, AttributeArgs, Data, DeriveInput, Error, Fields, Ident, ItemMod,
+    Lit, LitStr, Meta, NestedMeta, Path, Result,
+};
+
+/// Implement `SoftIdentifiable` on a model.
+///
+/// The deleted flag is read from the `deleted` field, or from the field named by the
+/// `#[soft_delete(column = "...")]` attribute. It is either a `bool`, or an `Option` which is set
+/// once the row is soft-deleted, such as a deletion timestamp.
+///
+/// The `deleted_at` and `deleted_by` fields, if any, are declared the same way. The former is an
+/// `Option` of a type convertible into `SystemTime`, and also serves as the deleted flag when no
+/// `column` is given. The latter is an `Option<String>`.
+///
+/// ```rust,ignore
+/// #[derive(Queryable, SoftIdentifiable)]
+/// #[soft_delete(column = "removed_at", deleted_at = "removed_at", deleted_by = "removed_by")]
+/// struct User {
+///     id: i32,
+///     removed_at: Option<SystemTime>,
+///     removed_by: Option<String>,
+/// }
+/// ```
+#[proc_macro_derive(SoftIdentifiable, attributes(soft_delete))]
+pub fn derive_soft_identifiable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    soft_identifiable(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[derive(Default)]
+struct Columns {
+    column: Option<Ident>,
+    deleted_at: Option<Ident>,
+    deleted_by: Option<Ident>,
+}
+
+fn columns(input: &DeriveInput) -> Result<Columns> {
+    let mut columns = Columns::default();
+    for attr in input.attrs.iter().filter(|attr| attr.path.is_ident("soft_delete")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected `soft_delete(...)`")),
+        };
+        for nested in list.nested {
+            let pair = match nested {
+                NestedMeta::Meta(Meta::NameValue(pair)) => pair,
+                nested => return Err(Error::new_spanned(nested, "expected `name = \"value\"`")),
+            };
+            let value = match &pair.lit {
+                Lit::Str(value) => Ident::new(&value.value(), value.span()),
+                lit => return Err(Error::new_spanned(lit, "expected a string literal")),
+            };
+            let slot = match pair.path.get_ident().map(Ident::to_string).as_deref() {
+                Some("column") => &mut columns.column,
+                Some("deleted_at") => &mut columns.deleted_at,
+                Some("deleted_by") => &mut columns.deleted_by,
+                _ => {
+                    let message = "expected one of `column`, `deleted_at` or `deleted_by`";
+                    return Err(Error::new_spanned(pair.path, message));
+                }
+            };
+            *slot = Some(value);
+        }
+    }
+    Ok(columns)
+}
+
+fn soft_identifiable(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input, "expected a struct with named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input, "expected a struct")),
+    };
+    let columns = columns(&input)?;
+    let column = columns
+        .column
+        .or_else(|| columns.deleted_at.clone())
+        .unwrap_or_else(|| Ident::new("deleted", Span::call_site()));
+    for field in [Some(&column), columns.deleted_at.as_ref(), columns.deleted_by.as_ref()] {
+        let field = match field {
+            Some(field) => field,
+            None => continue,
+        };
+        if !fields.iter().any(|f| f.ident.as_ref() == Some(field)) {
+            return Err(Error::new(field.span(), format!("no field named `{}`", field)));
+        }
+    }
+
+    let deleted_at = columns.deleted_at.map(|field| {
+        quote! {
+            fn deleted_at(&self) -> ::std::option::Option<::std::time::SystemTime> {
+                ::std::clone::Clone::clone(&self.#field).map(::std::convert::Into::into)
+            }
+        }
+    });
+    let deleted_by = columns.deleted_by.map(|field| {
+        quote! {
+            fn deleted_by(&self) -> ::std::option::Option<&str> {
+                ::std::option::Option::as_deref(&self.#field)
+            }
+        }
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics diesel_softdelete::SoftIdentifiable for #name #ty_generics
+        #where_clause
+        {
+            fn is_deleted(&self) -> bool {
+                diesel_softdelete::__private::DeletedFlag::is_set(&self.#column)
+            }
+
+            #deleted_at
+            #deleted_by
+        }
+    })
+}
+
+/// Implement `Assigns` on a changeset struct, so that it can be wrapped in `ProtectDeleted`.
+///
+/// Each field sets the column of the same name, or the one named by its `#[column_name = "..."]`
+/// attribute, as with Diesel's `AsChangeset` derive.
+///
+/// ```rust,ignore
+/// #[derive(AsChangeset, Assigns)]
+/// #[table_name = "post"]
+/// struct PostForm {
+///     #[column_name = "title"]
+///     name: String,
+///     body: String,
+/// }
+/// ```
+#[proc_macro_derive(Assigns, attributes(column_name))]
+pub fn derive_assigns(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    assigns(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn assigns(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input, "expected a struct with named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input, "expected a struct")),
+    };
+    let mut columns = Vec::new();
+    for field in fields {
+        let mut column =
+            field.ident.as_ref().map(syn::ext::IdentExt::unraw).map(|ident| ident.to_string());
+        for attr in field.attrs.iter().filter(|attr| attr.path.is_ident("column_name")) {
+            column = match attr.parse_meta()? {
+                Meta::NameValue(pair) => match &pair.lit {
+                    Lit::Str(value) => Some(value.value()),
+                    lit => return Err(Error::new_spanned(lit, "expected a string literal")),
+                },
+                meta => return Err(Error::new_spanned(meta, "expected `column_name = \"...\"`")),
+            };
+        }
+        columns.extend(column);
+    }
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    generics.params.push(syn::parse_quote!(__Col: diesel_softdelete::__private::Column));
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics diesel_softdelete::write::Assigns<__Col> for #name #ty_generics
+        #where_clause
+        {
+            const ASSIGNS: bool = false #(|| diesel_softdelete::__private::same_name(
+                <__Col as diesel_softdelete::__private::Column>::NAME,
+                #columns,
+            ))*;
+        }
+    })
+}
+
+/// Implement `SoftDelete` and `SoftDeleteWrite` on a table, from a marker struct.
+///
+/// This is the same as the `soft_delete!` macro, with named options given by the
+/// `#[soft_delete(...)]` attribute:
+///
+/// - `table`, the path to the table module, is required;
+/// - `kind` is either `"flag"`, a boolean column and the default, or `"timestamp"`, a nullable
+///   column set when the row is soft-deleted;
+/// - `column` is the name of that column, by default `deleted` for a flag and `deleted_at` for a
+///   timestamp;
+/// - `revision` is the name of a revision column incremented on each deletion and restoration,
+///   for flags only.
+///
+/// ```rust,ignore
+/// #[derive(SoftDelete)]
+/// #[soft_delete(table = "user", column = "removed_at", kind = "timestamp")]
+/// struct UserSoftDelete;
+/// ```
+#[proc_macro_derive(SoftDelete, attributes(soft_delete))]
+pub fn derive_soft_delete(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    soft_delete(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[derive(Default)]
+struct TableOptions {
+    table: Option<Path>,
+    column: Option<Ident>,
+    timestamp: bool,
+    revision: Option<Ident>,
+}
+
+fn table_options(input: &DeriveInput) -> Result<TableOptions> {
+    let mut options = TableOptions::default();
+    for attr in input.attrs.iter().filter(|attr| attr.path.is_ident("soft_delete")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected `soft_delete(...)`")),
+        };
+        for nested in list.nested {
+            let pair = match nested {
+                NestedMeta::Meta(Meta::NameValue(pair)) => pair,
+                nested => return Err(Error::new_spanned(nested, "expected `name = \"value\"`")),
+            };
+            let value = match &pair.lit {
+                Lit::Str(value) => value,
+                lit => return Err(Error::new_spanned(lit, "expected a string literal")),
+            };
+            let ident = |value: &LitStr| Ident::new(&value.value(), value.span());
+            match pair.path.get_ident().map(Ident::to_string).as_deref() {
+                Some("table") => options.table = Some(value.parse()?),
+                Some("column") => options.column = Some(ident(value)),
+                Some("revision") => options.revision = Some(ident(value)),
+                Some("kind") => {
+                    options.timestamp = match value.value().as_str() {
+                        "flag" => false,
+                        "timestamp" => true,
+                        _ => {
+                            return Err(Error::new_spanned(value, "expected `flag` or `timestamp`"))
+                        }
+                    }
+                }
+                _ => {
+                    let message = "expected one of `table`, `column`, `kind` or `revision`";
+                    return Err(Error::new_spanned(pair.path, message));
+                }
+            }
+        }
+    }
+    Ok(options)
+}
+
+fn soft_delete(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let options = table_options(&input)?;
+    let table = match options.table {
+        Some(table) => table,
+        None => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "missing `#[soft_delete(table = \"...\")]`",
+            ))
+        }
+    };
+    let default = if options.timestamp { "deleted_at" } else { "deleted" };
+    let column = options.column.unwrap_or_else(|| Ident::new(default, Span::call_site()));
+
+    let columns = match (options.timestamp, options.revision) {
+        (true, Some(revision)) => {
+            let message = "`revision` is only supported with `kind = \"flag\"`";
+            return Err(Error::new(revision.span(), message));
+        }
+        (true, None) => quote!(timestamp(#table::#column)),
+        (false, Some(revision)) => quote!((#table::#column, revision = #table::#revision)),
+        (false, None) => quote!((#table::#column)),
+    };
+    Ok(quote! {
+        diesel_softdelete::soft_delete!(#table::table => #columns);
+    })
+}
+
+/// Implement `SoftDelete` and `SoftDeleteWrite` on every table of a schema having a soft-delete
+/// column.
+///
+/// The macro wraps the contents of a schema module, e.g. as generated by `diesel print-schema`,
+/// and adds a `soft_delete!` invocation for each `table!` having a column with one of the
+/// recognized names: a `Bool` column is a deleted flag, and a `Nullable` one a deletion timestamp.
+/// The recognized names are `deleted` and `deleted_at`, or those given by the `column` options of
+/// an inner `#![soft_delete(...)]` attribute, in order of preference.
+///
+/// The tables without such a column are skipped, unless the `missing = "error"` option is given.
+///
+/// ```rust,ignore
+/// soft_delete_schema! {
+///     #![soft_delete(column = "is_deleted", column = "removed_at", missing = "error")]
+///
+///     table! {
+///         user (id) {
+///             id -> Integer,
+///             is_deleted -> Bool,
+///         }
+///     }
+///
+///     table! {
+///         post (id) {
+///             id -> Integer,
+///             removed_at -> Nullable<Timestamp>,
+///         }
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn soft_delete_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as Schema);
+    soft_delete_schema_impl(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+struct Schema {
+    attrs: Vec<Attribute>,
+    body: proc_macro2::TokenStream,
+}
+
+impl Parse for Schema {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Ok(Self { attrs: input.call(Attribute::parse_inner)?, body: input.parse()? })
+    }
+}
+
+struct SchemaOptions {
+    columns: Vec<String>,
+    missing_is_error: bool,
+}
+
+fn schema_options(attrs: &[Attribute]) -> Result<SchemaOptions> {
+    let mut nested = Vec::new();
+    for attr in attrs {
+        match attr.parse_meta()? {
+            Meta::List(list) if list.path.is_ident("soft_delete") => nested.extend(list.nested),
+            meta => return Err(Error::new_spanned(meta, "expected `#![soft_delete(...)]`")),
+        }
+    }
+    table_scan_options(nested, false)
+}
+
+/// Parse the `column` and `missing` options of the macros scanning `table!` definitions.
+fn table_scan_options(
+    nested: impl IntoIterator<Item = NestedMeta>,
+    missing_is_error: bool,
+) -> Result<SchemaOptions> {
+    let mut options = SchemaOptions { columns: Vec::new(), missing_is_error };
+    for nested in nested {
+        let pair = match nested {
+            NestedMeta::Meta(Meta::NameValue(pair)) => pair,
+            nested => return Err(Error::new_spanned(nested, "expected `name = \"value\"`")),
+        };
+        let value = match &pair.lit {
+            Lit::Str(value) => value,
+            lit => return Err(Error::new_spanned(lit, "expected a string literal")),
+        };
+        match pair.path.get_ident().map(Ident::to_string).as_deref() {
+            Some("column") => options.columns.push(value.value()),
+            Some("missing") => {
+                options.missing_is_error = match value.value().as_str() {
+                    "skip" => false,
+                    "error" => true,
+                    _ => return Err(Error::new_spanned(value, "expected `skip` or `error`")),
+                }
+            }
+            _ => {
+                let message = "expected one of `column` or `missing`";
+                return Err(Error::new_spanned(pair.path, message));
+            }
+        }
+    }
+    if options.columns.is_empty() {
+        options.columns = vec!["deleted".to_owned(), "deleted_at".to_owned()];
+    }
+    Ok(options)
+}
+
+/// A table of the schema: its name, and its columns with their SQL type.
+struct SchemaTable {
+    name: Ident,
+    columns: Vec<(Ident, String)>,
+}
+
+/// Find the `table!` invocations at the top level of a schema.
+fn schema_tables(body: proc_macro2::TokenStream) -> Result<Vec<SchemaTable>> {
+    let tokens: Vec<_> = body.into_iter().collect();
+    let mut tables = Vec::new();
+    for window in tokens.windows(3) {
+        match window {
+            [TokenTree::Ident(ident), TokenTree::Punct(bang), TokenTree::Group(group)]
+                if ident == "table" && bang.as_char() == '!' =>
+            {
+                tables.push(schema_table(group.stream())?);
+            }
+            _ => {}
+        }
+    }
+    Ok(tables)
+}
+
+fn schema_table(definition: proc_macro2::TokenStream) -> Result<SchemaTable> {
+    let tokens: Vec<_> = definition.into_iter().collect();
+    let (columns, head) = match tokens.split_last() {
+        Some((TokenTree::Group(group), head)) if group.delimiter() == Delimiter::Brace => {
+            (group, head)
+        }
+        _ => return Err(Error::new(Span::call_site(), "expected a `table!` definition")),
+    };
+    // The table name is followed by its primary key, if any
+    let head = match head.split_last() {
+        Some((TokenTree::Group(group), rest)) if group.delimiter() == Delimiter::Parenthesis => {
+            rest
+        }
+        _ => head,
+    };
+    let name = match head.last() {
+        Some(TokenTree::Ident(name)) => name.clone(),
+        _ => return Err(Error::new(columns.span(), "expected a table name")),
+    };
+
+    let mut parsed = Vec::new();
+    let tokens: Vec<_> = columns.stream().into_iter().collect();
+    for column in tokens.split(|t| matches!(t, TokenTree::Punct(p) if p.as_char() == ',')) {
+        // Skip the attributes, e.g. `#[sql_name = "..."]`
+        let mut column = column;
+        while let [TokenTree::Punct(pound), TokenTree::Group(_), rest @ ..] = column {
+            if pound.as_char() != '#' {
+                break;
+            }
+            column = rest;
+        }
+        if let [TokenTree::Ident(ident), TokenTree::Punct(_), TokenTree::Punct(_), ty @ ..] = column
+        {
+            let ty: proc_macro2::TokenStream = ty.iter().cloned().collect();
+            parsed.push((ident.clone(), ty.to_string().replace(' ', "")));
+        }
+    }
+    Ok(SchemaTable { name, columns: parsed })
+}
+
+fn soft_delete_schema_impl(schema: Schema) -> Result<proc_macro2::TokenStream> {
+    let options = schema_options(&schema.attrs)?;
+    let impls = soft_delete_impls(schema.body.clone(), &options)?;
+    let body = schema.body;
+    Ok(quote! {
+        #body
+        #(#impls)*
+    })
+}
+
+/// The `soft_delete!` invocations of the tables defined in `body`.
+fn soft_delete_impls(
+    body: proc_macro2::TokenStream,
+    options: &SchemaOptions,
+) -> Result<Vec<proc_macro2::TokenStream>> {
+    let mut impls = Vec::new();
+    for table in schema_tables(body)? {
+        let found = options
+            .columns
+            .iter()
+            .find_map(|name| table.columns.iter().find(|(column, _)| column == name));
+        let (column, ty) = match found {
+            Some(found) => found,
+            None if options.missing_is_error => {
+                let message = format!(
+                    "table `{}` has no soft-delete column, expected one of `{}`",
+                    table.name,
+                    options.columns.join("`, `"),
+                );
+                return Err(Error::new(table.name.span(), message));
+            }
+            None => continue,
+        };
+        let name = &table.name;
+        let is = |sql_type: &str| ty == sql_type || ty.ends_with(&format!("::{}", sql_type));
+        let nullable = ty.starts_with("Nullable<") || ty.contains("::Nullable<");
+        impls.push(if is("Bool") {
+            quote!(diesel_softdelete::soft_delete!(#name::table => (#name::#column));)
+        } else if nullable {
+            quote!(diesel_softdelete::soft_delete!(#name::table => timestamp(#name::#column));)
+        } else {
+            let message = format!(
+                "soft-delete column `{}` of table `{}` must be `Bool` or `Nullable`",
+                column, name,
+            );
+            return Err(Error::new(column.span(), message));
+        });
+    }
+    Ok(impls)
+}
+
+/// Implement `SoftDelete` and `SoftDeleteWrite` on the tables defined in a module.
+///
+/// This is the attribute form of `soft_delete_schema!`, keeping the declaration next to the
+/// `table!` definitions. It takes the same `column` and `missing` options, but a table without a
+/// soft-delete column is an error unless `missing = "skip"` is given.
+///
+/// ```rust,ignore
+/// #[soft_deletable(column = "archived_at")]
+/// mod schema {
+///     table! {
+///         user (id) {
+///             id -> Integer,
+///             archived_at -> Nullable<Timestamp>,
+///         }
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn soft_deletable(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let module = parse_macro_input!(item as ItemMod);
+    soft_deletable_impl(args, module).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn soft_deletable_impl(
+    args: AttributeArgs,
+    mut module: ItemMod,
+) -> Result<proc_macro2::TokenStream> {
+    let options = table_scan_options(args, true)?;
+    let items = match &mut module.content {
+        Some((_, items)) => items,
+        None => return Err(Error::new_spanned(&module, "expected an inline module")),
+    };
+    let body = quote!(#(#items)*);
+    for tokens in soft_delete_impls(body, &options)? {
+        items.push(syn::parse2(tokens)?);
+    }
+    Ok(quote!(#module))
+}
@@ -0,0 +1,110 @@
+//! Two-phase restores, for the approval workflows where restores are reviewed before they happen.
+//!
+//! A table opting in with [`restore_requests!`](crate::restore_requests) has a boolean column
+//! flagging the soft-deleted rows whose restore is requested. A restore is first requested with
+//! [`request_restore`], listed for review with [`pending_restores`], then either applied with
+//! [`apply_pending_restores`] or rejected with [`reject_restore`]:
+//!
+//! ```rust,ignore
+//! restore_requests!(post::table => post::restore_requested);
+//!
+//! request_restore(post::table.find(id)).execute(&conn)?;
+//! let pending = pending_restores(post::table).load::<Post>(&conn)?;
+//! // Once approved
+//! apply_pending_restores(post::table.find(id)).execute(&conn)?;
+//! ```
+//!
+//! The rows stay soft-deleted until their restore is applied: a request is not visible to the soft
+//! methods.
+
+use diesel::{
+    associations::HasTable,
+    dsl::{And, Filter, Update},
+    query_builder::IntoUpdateTarget,
+    query_dsl::methods::FilterDsl,
+    sql_types::Bool,
+    BoolExpressionMethods, Column,
+};
+
+use crate::{
+    write::{flag_changeset, Deleted, FlagChangeset, SoftDeleteWrite},
+    SoftDelete,
+};
+
+/// A soft-deletable table whose restores are requested before being applied.
+///
+/// This is implemented by the [`restore_requests`](crate::restore_requests) macro.
+pub trait RestoreRequests: SoftDeleteWrite {
+    /// The column flagging the soft-deleted rows whose restore is requested.
+    type Requested: Column<Table = Self, SqlType = Bool> + Default;
+
+    fn requested_col(&self) -> Self::Requested;
+}
+
+type Requested<T> = <<T as HasTable>::Table as RestoreRequests>::Requested;
+type RestoreChangeset<T> = <<T as HasTable>::Table as SoftDeleteWrite>::RestoreChangeset;
+type Restorable<T> = <<T as HasTable>::Table as SoftDeleteWrite>::Restorable;
+
+/// The type returned by [`request_restore`].
+pub type RequestRestore<T> = Filter<Update<T, FlagChangeset<Requested<T>>>, Deleted<T>>;
+/// The type returned by [`reject_restore`].
+pub type RejectRestore<T> = Filter<Update<T, FlagChangeset<Requested<T>>>, Requested<T>>;
+/// The type returned by [`pending_restores`].
+pub type PendingRestores<T> = Filter<T, And<Deleted<T>, Requested<T>>>;
+/// The type returned by [`apply_pending_restores`].
+pub type ApplyPendingRestores<T> = Filter<
+    Update<T, (RestoreChangeset<T>, FlagChangeset<Requested<T>>)>,
+    And<Restorable<T>, Requested<T>>,
+>;
+
+/// Creates an `UPDATE` statement requesting the restore of the soft-deleted rows of `target`.
+pub fn request_restore<T>(target: T) -> RequestRestore<T>
+where
+    T: IntoUpdateTarget,
+    T::Table: RestoreRequests,
+    Update<T, FlagChangeset<Requested<T>>>: FilterDsl<Deleted<T>>,
+{
+    let table = T::table();
+    diesel::update(target)
+        .set(flag_changeset(table.requested_col(), true))
+        .filter(table.deleted_col())
+}
+
+/// Creates an `UPDATE` statement withdrawing the restore requests of the rows of `target`, which
+/// stay soft-deleted.
+pub fn reject_restore<T>(target: T) -> RejectRestore<T>
+where
+    T: IntoUpdateTarget,
+    T::Table: RestoreRequests,
+    Update<T, FlagChangeset<Requested<T>>>: FilterDsl<Requested<T>>,
+{
+    let table = T::table();
+    diesel::update(target)
+        .set(flag_changeset(table.requested_col(), false))
+        .filter(table.requested_col())
+}
+
+/// The soft-deleted rows of `source` whose restore is requested, to review.
+pub fn pending_restores<T>(source: T) -> PendingRestores<T>
+where
+    T: HasTable + FilterDsl<And<Deleted<T>, Requested<T>>>,
+    T::Table: RestoreRequests,
+{
+    let table = T::table();
+    source.filter(table.deleted_col().and(table.requested_col()))
+}
+
+/// Creates an `UPDATE` statement restoring the rows of `target` whose restore is requested, and
+/// clearing their requests. The other rows of `target` are left untouched, as are the rows a
+/// [`restore`](crate::write::restore) does not bring back, e.g. expired ones.
+pub fn apply_pending_restores<T>(target: T) -> ApplyPendingRestores<T>
+where
+    T: IntoUpdateTarget,
+    T::Table: RestoreRequests,
+    Update<T, (RestoreChangeset<T>, FlagChangeset<Requested<T>>)>:
+        FilterDsl<And<Restorable<T>, Requested<T>>>,
+{
+    let table = T::table();
+    let changeset = (table.restore_changeset(), flag_changeset(table.requested_col(), false));
+    diesel::update(target).set(changeset).filter(table.restorable().and(table.requested_col()))
+}
@@ -0,0 +1,411 @@
+//! Move the trashed rows out of the hot tables, into mirror archive tables.
+//!
+//! The tables of a [`SoftDeleteConfig`] registered with an
+//! [`archive`](crate::config::TableConfig::archive) table have their rows trashed for longer than
+//! the configured delay moved to it by [`archive_expired`]:
+//!
+//! ```rust,ignore
+//! let config = SoftDeleteConfig::<Pg>::new().table(
+//!     TableConfig::new(user::table)
+//!         .deleted_at(user::deleted_at)
+//!         .archive("user_archive", Duration::from_secs(90 * 24 * 3600)),
+//! );
+//! conn.batch_execute("CREATE TABLE user_archive (LIKE \"user\")")?;
+//! let archived = archive_expired(&config, &conn)?;
+//! ```
+//!
+//! The archive tables mirror the columns of their table, in the same order, as the rows are
+//! copied with `INSERT INTO .. SELECT *`. An archived row is moved back to its table and restored
+//! with [`unarchive`].
+//!
+//! The queries spanning both tables, e.g. a search including the archived rows, are built with
+//! [`with_archive`]:
+//!
+//! ```rust,ignore
+//! let users = with_archive(user::table, "user_archive")
+//!     .only_deleted()
+//!     .filter(user::name.like("Jo%"))
+//!     .load::<User>(&conn)?;
+//! ```
+
+use std::{error::Error as StdError, fmt};
+
+use diesel::{
+    backend::Backend,
+    deserialize::FromSql,
+    dsl::{And, Find, SqlTypeOf},
+    expression::{AppearsOnTable, SelectableExpression},
+    query_builder::{nodes::Identifier, AstPass, Query, QueryBuilder, QueryFragment, QueryId},
+    query_dsl::methods::FindDsl,
+    result::{DatabaseErrorKind, Error},
+    serialize::ToSql,
+    sql_types::{Bool, HasSqlType, Text},
+    BoolExpressionMethods, Column, Connection, Expression, QueryResult, RunQueryDsl, Table,
+};
+
+use crate::{
+    config::SoftDeleteConfig,
+    hooks::{Action, HookConnection},
+    migration::{self, MigrationBackend},
+    trace::{self, Target},
+    write::{restore, SoftDeleteTarget},
+    SoftDelete,
+};
+
+/// The error of [`archive_expired`].
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// The rows of `table` deleted after their copy to the archive table are not the rows copied,
+    /// e.g. as a concurrent write trashed a row in between. Nothing is archived.
+    Mismatch {
+        table: &'static str,
+        copied: usize,
+        deleted: usize,
+    },
+    Query(Error),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mismatch { table, copied, deleted } => write!(
+                f,
+                "copied {} rows of table `{}` to its archive table, but deleted {}",
+                copied, table, deleted,
+            ),
+            Self::Query(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for ArchiveError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Mismatch { .. } => None,
+            Self::Query(e) => Some(e),
+        }
+    }
+}
+
+impl From<Error> for ArchiveError {
+    fn from(e: Error) -> Self {
+        Self::Query(e)
+    }
+}
+
+/// Move the rows of the tables of `config` trashed for longer than their archive delay to their
+/// archive table, returning the number of rows moved per table, in registration order.
+///
+/// The tables without an archive table or a deletion timestamp are left out. The tables are
+/// archived in one transaction, each copying then deleting the rows trashed before the same
+/// instant, read once: if the rows deleted are not those copied, the transaction is rolled back.
+pub fn archive_expired<Conn>(
+    config: &SoftDeleteConfig<Conn::Backend>,
+    conn: &Conn,
+) -> Result<Vec<(&'static str, usize)>, ArchiveError>
+where
+    Conn: Connection,
+    Conn::Backend: MigrationBackend,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+    String: FromSql<Text, Conn::Backend> + ToSql<Text, Conn::Backend>,
+{
+    conn.transaction(|| {
+        let mut archived = Vec::new();
+        for table in config.tables() {
+            let ((archive, after), deleted_at) = match (table.archive(), table.deleted_at()) {
+                (Some(archive), Some(deleted_at)) => (archive, deleted_at),
+                _ => continue,
+            };
+            let cutoff = migration::cutoff(after, conn)?;
+            let mut expired = <Conn::Backend as Backend>::QueryBuilder::default();
+            expired.push_sql(&table.deleted_sql()?);
+            expired.push_sql(" AND ");
+            expired.push_sql(&table.quoted_column(deleted_at)?);
+            expired.push_sql(" < ");
+            <Conn::Backend as MigrationBackend>::push_cutoff(&mut expired);
+            let expired = expired.finish();
+
+            let quoted_name = table.quoted_name()?;
+            let insert = format!(
+                "INSERT INTO {} SELECT * FROM {} WHERE {}",
+                quoted_identifier::<Conn::Backend>(archive)?,
+                quoted_name,
+                expired,
+            );
+            let copied = diesel::sql_query(insert).bind::<Text, _>(&cutoff).execute(conn)?;
+            let delete = format!("DELETE FROM {} WHERE {}", quoted_name, expired);
+            let deleted = diesel::sql_query(delete).bind::<Text, _>(&cutoff).execute(conn)?;
+            if deleted != copied {
+                return Err(ArchiveError::Mismatch { table: table.name(), copied, deleted });
+            }
+            archived.push((table.name(), copied));
+        }
+        Ok(archived)
+    })
+}
+
+/// The error of [`unarchive`].
+#[derive(Debug)]
+pub enum UnarchiveError {
+    /// The table has no archive table in the configuration.
+    NotArchived,
+    /// There is no such row in the archive table.
+    NotFound,
+    /// The row conflicts with a row of the table on a unique constraint, e.g. an alive row with
+    /// the same unique key. It is left in the archive table.
+    Conflict(Error),
+    Query(Error),
+}
+
+impl fmt::Display for UnarchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotArchived => f.write_str("the table has no archive table"),
+            Self::NotFound => f.write_str("no archived row to restore"),
+            Self::Conflict(e) => write!(f, "the archived row conflicts with the table: {}", e),
+            Self::Query(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for UnarchiveError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::NotArchived | Self::NotFound => None,
+            Self::Conflict(e) | Self::Query(e) => Some(e),
+        }
+    }
+}
+
+impl From<Error> for UnarchiveError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Self::Conflict(e),
+            e => Self::Query(e),
+        }
+    }
+}
+
+/// Move the row `id` of the archive table of `table` back to the table, and restore it.
+///
+/// The whole move happens in a transaction: if the row conflicts with the rows of the table, it
+/// is left archived.
+pub fn unarchive<T, PK, Conn>(
+    config: &SoftDeleteConfig<Conn::Backend>,
+    table: T,
+    id: PK,
+    conn: &Conn,
+) -> Result<(), UnarchiveError>
+where
+    T: Table<FromClause = Identifier<'static>> + FindDsl<PK>,
+    T::PrimaryKey: Column,
+    PK: ToSql<SqlTypeOf<T::PrimaryKey>, Conn::Backend> + Clone,
+    SqlTypeOf<T::PrimaryKey>: QueryId,
+    Find<T, PK>: SoftDeleteTarget,
+    <Find<T, PK> as SoftDeleteTarget>::Restore: QueryFragment<Conn::Backend> + QueryId,
+    Conn: HookConnection,
+    Conn::Backend: HasSqlType<SqlTypeOf<T::PrimaryKey>>,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+{
+    let name = table.from_clause().0;
+    let config = config.get(name).ok_or(UnarchiveError::NotArchived)?;
+    let archive = match config.archive() {
+        Some((archive, _)) => quoted_identifier::<Conn::Backend>(archive)?,
+        None => return Err(UnarchiveError::NotArchived),
+    };
+    let mut by_id = <Conn::Backend as Backend>::QueryBuilder::default();
+    by_id.push_identifier(config.primary_key())?;
+    by_id.push_sql(" = ");
+    by_id.push_bind_param();
+    let by_id = by_id.finish();
+
+    conn.transaction(|| {
+        let insert = format!(
+            "INSERT INTO {} SELECT * FROM {} WHERE {}",
+            config.quoted_name()?,
+            archive,
+            by_id,
+        );
+        let moved = diesel::sql_query(insert)
+            .bind::<SqlTypeOf<T::PrimaryKey>, _>(id.clone())
+            .execute(conn)?;
+        if moved == 0 {
+            return Err(UnarchiveError::NotFound);
+        }
+        diesel::sql_query(format!("DELETE FROM {} WHERE {}", archive, by_id))
+            .bind::<SqlTypeOf<T::PrimaryKey>, _>(id.clone())
+            .execute(conn)?;
+        let target = Target { name, key: <T::PrimaryKey as Column>::NAME };
+        trace::write(Action::Restore, target, restore(table.find(id)), conn)?;
+        Ok(())
+    })
+}
+
+/// The rows of both arms of a [`ArchiveUnion`] kept by their deleted condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnionScope {
+    All,
+    Alive,
+    Deleted,
+}
+
+/// The filter of a [`ArchiveUnion`] without one.
+#[derive(Debug, Clone, Copy)]
+pub struct NoFilter;
+
+/// The filter of a [`ArchiveUnion`] applied to both of its arms.
+#[derive(Debug, Clone, Copy)]
+pub struct UnionFilter<P>(P);
+
+/// The `UNION ALL` of the rows of a table and those of its archive table, built by
+/// [`with_archive`].
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveUnion<T, S, F = NoFilter> {
+    table: T,
+    archive: &'static str,
+    select: S,
+    filter: F,
+    scope: UnionScope,
+}
+
+/// Query the rows of `table` along with those of its `archive` table.
+///
+/// Both arms of the union select the same columns, the archive table being aliased as the table,
+/// and are restricted by the same scope and filters. By default, all the rows are kept.
+pub fn with_archive<T>(table: T, archive: &'static str) -> ArchiveUnion<T, T::AllColumns>
+where
+    T: Table + SoftDelete,
+{
+    ArchiveUnion {
+        table,
+        archive,
+        select: T::all_columns(),
+        filter: NoFilter,
+        scope: UnionScope::All,
+    }
+}
+
+impl<T, S, F> ArchiveUnion<T, S, F> {
+    /// Keep only the alive rows of both tables.
+    pub fn alive(self) -> Self {
+        Self { scope: UnionScope::Alive, ..self }
+    }
+
+    /// Keep only the soft-deleted rows of both tables.
+    pub fn only_deleted(self) -> Self {
+        Self { scope: UnionScope::Deleted, ..self }
+    }
+
+    /// Select `select` from both tables.
+    pub fn select<S2>(self, select: S2) -> ArchiveUnion<T, S2, F>
+    where
+        S2: SelectableExpression<T>,
+    {
+        let Self { table, archive, filter, scope, .. } = self;
+        ArchiveUnion { table, archive, select, filter, scope }
+    }
+}
+
+impl<T, S> ArchiveUnion<T, S, NoFilter> {
+    /// Filter the rows of both tables with `predicate`.
+    pub fn filter<P>(self, predicate: P) -> ArchiveUnion<T, S, UnionFilter<P>>
+    where
+        P: Expression<SqlType = Bool> + AppearsOnTable<T>,
+    {
+        let Self { table, archive, select, scope, .. } = self;
+        ArchiveUnion { table, archive, select, filter: UnionFilter(predicate), scope }
+    }
+}
+
+impl<T, S, P> ArchiveUnion<T, S, UnionFilter<P>> {
+    /// Filter the rows of both tables with `predicate`, on top of the previous filters.
+    pub fn filter<Q>(self, predicate: Q) -> ArchiveUnion<T, S, UnionFilter<And<P, Q>>>
+    where
+        P: Expression<SqlType = Bool>,
+        Q: Expression<SqlType = Bool> + AppearsOnTable<T>,
+    {
+        let Self { table, archive, select, filter: UnionFilter(filter), scope } = self;
+        ArchiveUnion { table, archive, select, filter: UnionFilter(filter.and(predicate)), scope }
+    }
+}
+
+impl<T, S: Expression, F> Query for ArchiveUnion<T, S, F> {
+    type SqlType = S::SqlType;
+}
+
+impl<T, S, F> QueryId for ArchiveUnion<T, S, F> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<T, S, F, Conn> RunQueryDsl<Conn> for ArchiveUnion<T, S, F> {}
+
+/// A filter of a [`ArchiveUnion`], rendered after the scope of each arm.
+pub trait UnionWhereClause<DB: Backend> {
+    /// Render the filter, prefixed with `WHERE` if `first`, and `AND` otherwise.
+    fn walk_where(&self, first: bool, out: AstPass<DB>) -> QueryResult<()>;
+}
+
+impl<DB: Backend> UnionWhereClause<DB> for NoFilter {
+    fn walk_where(&self, _first: bool, _out: AstPass<DB>) -> QueryResult<()> {
+        Ok(())
+    }
+}
+
+impl<P: QueryFragment<DB>, DB: Backend> UnionWhereClause<DB> for UnionFilter<P> {
+    fn walk_where(&self, first: bool, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.push_sql(if first { " WHERE " } else { " AND " });
+        self.0.walk_ast(out)
+    }
+}
+
+impl<T, S, F, DB> QueryFragment<DB> for ArchiveUnion<T, S, F>
+where
+    T: Table + SoftDelete,
+    T::FromClause: QueryFragment<DB>,
+    T::Deleted: QueryFragment<DB>,
+    S: QueryFragment<DB>,
+    F: UnionWhereClause<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        for archived in [false, true] {
+            if archived {
+                out.push_sql(" UNION ALL ");
+            }
+            out.push_sql("SELECT ");
+            self.select.walk_ast(out.reborrow())?;
+            out.push_sql(" FROM ");
+            if archived {
+                out.push_identifier(self.archive)?;
+                out.push_sql(" AS ");
+            }
+            self.table.from_clause().walk_ast(out.reborrow())?;
+            match self.scope {
+                UnionScope::All => {}
+                UnionScope::Alive => {
+                    out.push_sql(" WHERE NOT (");
+                    self.table.deleted_col().walk_ast(out.reborrow())?;
+                    out.push_sql(")");
+                }
+                UnionScope::Deleted => {
+                    out.push_sql(" WHERE ");
+                    self.table.deleted_col().walk_ast(out.reborrow())?;
+                }
+            }
+            self.filter.walk_where(self.scope == UnionScope::All, out.reborrow())?;
+        }
+        Ok(())
+    }
+}
+
+/// The quoted `identifier`, e.g. `"user_archive"` on PostgreSQL.
+pub(crate) fn quoted_identifier<DB>(identifier: &str) -> QueryResult<String>
+where
+    DB: Backend,
+    DB::QueryBuilder: Default,
+{
+    let mut out = DB::QueryBuilder::default();
+    out.push_identifier(identifier)?;
+    Ok(out.finish())
+}
@@ -0,0 +1,233 @@
+//! An audit log of the soft deletions, restorations and purges, in a `soft_delete_audit` table.
+//!
+//! An installed [`AuditLog`] records one row per row affected by the soft deletions,
+//! restorations and purges of [`write`](crate::write) and of the modules built upon it, in the same
+//! transaction, as a [hook](crate::hooks):
+//!
+//! ```rust,ignore
+//! let ctx = Context::new().actor("admin@example.com").reason("spam");
+//! let _audit = AuditLog::new().install::<PgConnection>(ctx.clone());
+//! post::table.filter(post::user_id.eq(spammer)).soft_delete_rows(&conn)?;
+//! empty_trash(post::table, ConfirmEmptyTrash::i_really_want_to_destroy_trashed_rows(), &conn)?;
+//! AuditLog::new().purge_by_id::<_, _, Post, _, _>(post::table, id, &ctx, &conn)?;
+//! ```
+//!
+//! The audit table has the `table_name`, `row_pk` and `action` text columns, the nullable
+//! `actor`, `reason` and `snapshot` text columns, and the `performed_at` timestamp column. The
+//! snapshot is the JSON of the purged rows, the others being still in their table.
+
+use std::fmt::Display;
+
+use diesel::{
+    backend::Backend,
+    dsl::Find,
+    query_builder::{nodes::Identifier, AstPass, IntoUpdateTarget, QueryFragment, QueryId},
+    query_dsl::{
+        methods::{ExecuteDsl, FilterDsl, FindDsl},
+        LoadQuery,
+    },
+    serialize::ToSql,
+    sql_types::{Nullable, Text},
+    Connection, OptionalExtension, QueryResult, RunQueryDsl, Table,
+};
+use serde::Serialize;
+
+use crate::{
+    cdc::{self, Action, Context, Operation},
+    config::SoftDeleteConfig,
+    hooks::{self, HookGuard, WriteEvent},
+    trace,
+    write::{purge, Deleted, PurgeError, PurgeStatement},
+    SoftDelete,
+};
+
+/// The audit table of the soft-delete writes.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    table: &'static str,
+    audited: Option<Vec<&'static str>>,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self { table: "soft_delete_audit", audited: None }
+    }
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the writes in `table`, in place of `soft_delete_audit`.
+    pub fn table(mut self, table: &'static str) -> Self {
+        self.table = table;
+        self
+    }
+
+    /// Only record the writes to the tables of `config` registered with
+    /// [`audit`](crate::config::TableConfig::audit), the others being performed without a record.
+    pub fn config<DB: Backend>(mut self, config: &SoftDeleteConfig<DB>) -> Self {
+        let audited = config.tables().iter().filter(|table| table.is_audited());
+        self.audited = Some(audited.map(|table| table.name()).collect());
+        self
+    }
+
+    /// Whether the writes to `table` are recorded.
+    fn audits(&self, table: &str) -> bool {
+        self.audited.as_ref().is_none_or(|audited| audited.contains(&table))
+    }
+
+    /// Record the `operation` in the audit table, along with the JSON `snapshot` of the row, unless
+    /// its table is not audited.
+    pub fn record<PK, Conn>(
+        &self,
+        operation: &Operation<PK>,
+        snapshot: Option<String>,
+        conn: &Conn,
+    ) -> QueryResult<()>
+    where
+        PK: Display,
+        Conn: Connection,
+        String: ToSql<Text, Conn::Backend>,
+    {
+        if !self.audits(operation.table) {
+            return Ok(());
+        }
+        let record = Record {
+            table_name: operation.table,
+            row_pk: operation.pk.to_string(),
+            action: operation.action,
+            actor: operation.actor.clone(),
+            reason: operation.reason.clone(),
+            snapshot,
+        };
+        InsertRecords { table: self.table, records: &[record] }.execute(conn)?;
+        Ok(())
+    }
+
+    /// Record the soft deletions, restorations and purges executed by this crate on the current
+    /// thread with a connection of type `Conn`, performed with `ctx`, until the returned guard is
+    /// dropped.
+    ///
+    /// The writes are recorded by a [hook](crate::hooks), in their transaction, without snapshot.
+    pub fn install<Conn>(self, ctx: Context) -> HookGuard
+    where
+        Conn: Connection + 'static,
+        String: ToSql<Text, Conn::Backend>,
+    {
+        hooks::install(move |event: &WriteEvent, conn: &Conn| {
+            if !self.audits(event.table) {
+                return Ok(());
+            }
+            // Each statement inserts up to 1000 rows, binding 6 parameters per row.
+            for keys in event.keys.chunks(1000) {
+                let records = keys.iter().map(|key| Record {
+                    table_name: event.table,
+                    row_pk: key.clone(),
+                    action: event.action,
+                    actor: ctx.actor.clone(),
+                    reason: ctx.reason.clone(),
+                    snapshot: None,
+                });
+                let records = records.collect::<Vec<_>>();
+                InsertRecords { table: self.table, records: &records }.execute(conn)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Hard-delete the soft-deleted row `id` of `table`, and record its purge along with the JSON
+    /// snapshot of the row, loaded as `U`.
+    ///
+    /// The purge runs none of the installed hooks, so that an installed log does not record it a
+    /// second time without its snapshot.
+    pub fn purge_by_id<T, PK, U, Stmt, Conn>(
+        &self,
+        table: T,
+        id: PK,
+        ctx: &Context,
+        conn: &Conn,
+    ) -> Result<Operation<PK>, PurgeError>
+    where
+        T: Table<FromClause = Identifier<'static>> + FindDsl<PK> + Copy,
+        PK: Clone + Display,
+        Find<T, PK>: IntoUpdateTarget + RunQueryDsl<Conn> + LoadQuery<Conn, U>,
+        <Find<T, PK> as diesel::associations::HasTable>::Table: SoftDelete,
+        PurgeStatement<Find<T, PK>>: FilterDsl<Deleted<Find<T, PK>>, Output = Stmt>,
+        Stmt: ExecuteDsl<Conn>,
+        U: Serialize,
+        Conn: Connection,
+        String: ToSql<Text, Conn::Backend>,
+    {
+        conn.transaction(|| {
+            let row = table.find(id.clone()).get_result::<U>(conn).optional()?;
+            let row = row.ok_or(PurgeError::NotFound)?;
+            let name = table.from_clause().0;
+            if trace::execute("purge", name, purge(table.find(id.clone())), conn)? == 0 {
+                return Err(PurgeError::Alive);
+            }
+            let snapshot = serde_json::to_string(&row).map_err(|e| {
+                PurgeError::Query(diesel::result::Error::SerializationError(Box::new(e)))
+            })?;
+            let operation = cdc::operation(table, id, Action::Purge, ctx);
+            self.record(&operation, Some(snapshot), conn)?;
+            Ok(operation)
+        })
+    }
+}
+
+/// A row of the audit table.
+struct Record {
+    table_name: &'static str,
+    row_pk: String,
+    action: Action,
+    actor: Option<String>,
+    reason: Option<String>,
+    snapshot: Option<String>,
+}
+
+/// The `INSERT` statement of rows of the audit `table`.
+struct InsertRecords<'a> {
+    table: &'a str,
+    records: &'a [Record],
+}
+
+impl<DB> QueryFragment<DB> for InsertRecords<'_>
+where
+    DB: Backend,
+    String: ToSql<Text, DB>,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+        out.push_sql("INSERT INTO ");
+        out.push_identifier(self.table)?;
+        out.push_sql(
+            " (table_name, row_pk, action, actor, reason, snapshot, performed_at) VALUES ",
+        );
+        for (i, record) in self.records.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            out.push_sql("(");
+            out.push_bind_param::<Text, _>(&record.table_name.to_owned())?;
+            out.push_sql(", ");
+            out.push_bind_param::<Text, _>(&record.row_pk)?;
+            out.push_sql(", ");
+            out.push_bind_param::<Text, _>(&record.action.as_str().to_owned())?;
+            for value in [&record.actor, &record.reason, &record.snapshot] {
+                out.push_sql(", ");
+                out.push_bind_param::<Nullable<Text>, _>(value)?;
+            }
+            out.push_sql(", CURRENT_TIMESTAMP)");
+        }
+        Ok(())
+    }
+}
+
+impl QueryId for InsertRecords<'_> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<Conn> RunQueryDsl<Conn> for InsertRecords<'_> {}
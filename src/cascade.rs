@@ -0,0 +1,111 @@
+//! Soft-delete cascades: the parent → child edges between soft-deletable tables.
+//!
+//! The edges are declared with the [`soft_delete_cascade`](crate::soft_delete_cascade) macro,
+//! which implements [`SoftCascade`] on each parent table. The children of a table are typed, so
+//! that operations walking the graph build their queries from the joins between the tables.
+//!
+//! A child without a deletion state of its own, e.g. a 1:1 `user_profile` extension of `user`,
+//! inherits the one of its parent with the `inherit` form of [`soft_delete!`](crate::soft_delete):
+//!
+//! ```rust,ignore
+//! soft_delete!(user_profile::table => inherit(user::table, user_profile::user_id));
+//! ```
+//!
+//! Its rows are soft-deleted while their parent is, its soft scopes checking the parent with an
+//! `EXISTS` subquery, so that cascades treat it as implicitly deleted along with its parent.
+
+use diesel::{
+    backend::Backend,
+    expression::{AppearsOnTable, NonAggregate, SelectableExpression},
+    query_builder::{AstPass, QueryFragment, QueryId},
+    sql_types::Bool,
+    Expression, JoinTo, QueryResult, Table,
+};
+
+use crate::SoftDelete;
+
+/// A soft-deletable table whose children follow its rows when they are soft-deleted.
+pub trait SoftCascade: SoftDelete + Table {
+    /// The child tables, as a tuple.
+    type Children: CascadeChildren<Self>;
+    /// The names of the child tables, in declaration order.
+    const CHILDREN: &'static [&'static str];
+
+    fn children(&self) -> Self::Children;
+}
+
+/// A tuple of soft-deletable tables referencing the `Parent` table.
+pub trait CascadeChildren<Parent> {}
+
+macro_rules! impl_cascade_children {
+    ($($child:ident),+) => {
+        impl<Parent, $($child),+> CascadeChildren<Parent> for ($($child,)+)
+        where
+            $($child: SoftDelete + Table + JoinTo<Parent>),+
+        {
+        }
+    };
+}
+
+impl_cascade_children!(A);
+impl_cascade_children!(A, B);
+impl_cascade_children!(A, B, C);
+impl_cascade_children!(A, B, C, D);
+impl_cascade_children!(A, B, C, D, E);
+impl_cascade_children!(A, B, C, D, E, F);
+impl_cascade_children!(A, B, C, D, E, F, G);
+impl_cascade_children!(A, B, C, D, E, F, G, H);
+
+/// The deleted condition of the rows of a table inheriting the deletion state of the `P` parent
+/// table, i.e. the existence of a soft-deleted parent row referenced by the `FK` foreign key.
+#[derive(Debug, Clone, Copy)]
+pub struct InheritedDeletion<P, FK> {
+    parent: P,
+    foreign_key: FK,
+}
+
+/// The deleted condition of the rows referencing a `parent` row by their `foreign_key`.
+pub fn inherited_deletion<P, FK>(parent: P, foreign_key: FK) -> InheritedDeletion<P, FK>
+where
+    P: SoftDelete + Table,
+{
+    InheritedDeletion { parent, foreign_key }
+}
+
+impl<P, FK> Expression for InheritedDeletion<P, FK> {
+    type SqlType = Bool;
+}
+
+impl<P, FK> NonAggregate for InheritedDeletion<P, FK> {}
+
+impl<P, FK: AppearsOnTable<QS>, QS> AppearsOnTable<QS> for InheritedDeletion<P, FK> {}
+
+impl<P, FK: SelectableExpression<QS>, QS> SelectableExpression<QS> for InheritedDeletion<P, FK> {}
+
+impl<P, FK> QueryId for InheritedDeletion<P, FK> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<P, FK, DB> QueryFragment<DB> for InheritedDeletion<P, FK>
+where
+    P: SoftDelete + Table,
+    P::FromClause: QueryFragment<DB>,
+    P::PrimaryKey: QueryFragment<DB>,
+    P::Deleted: QueryFragment<DB>,
+    FK: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.push_sql("EXISTS (SELECT 1 FROM ");
+        self.parent.from_clause().walk_ast(out.reborrow())?;
+        out.push_sql(" WHERE ");
+        self.parent.primary_key().walk_ast(out.reborrow())?;
+        out.push_sql(" = ");
+        self.foreign_key.walk_ast(out.reborrow())?;
+        out.push_sql(" AND ");
+        self.parent.deleted_col().walk_ast(out.reborrow())?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+//! Change-data-capture records of the soft-delete and restore operations.
+//!
+//! The `*_tracked` functions perform the same writes as their [`write`](crate::write)
+//! counterparts, and return one [`Operation`] per affected row, suitable for publishing to a
+//! message broker or an outbox table. The keys of the affected rows are read from a `RETURNING`
+//! clause, which MySQL lacks.
+
+use std::{fmt::Display, time::SystemTime};
+
+use diesel::{
+    dsl::Filter,
+    query_builder::nodes::Identifier,
+    query_dsl::{methods::FilterDsl, LoadQuery},
+    QueryResult, Table,
+};
+
+/// The kind of write an [`Operation`] describes.
+pub use crate::hooks::Action;
+use crate::{
+    hooks::HookConnection,
+    trace,
+    write::{restore, returning, soft_delete, Returning, SoftDeleteTarget, SoftDeleteWrite},
+};
+
+/// Metadata attached to the operations of a tracked write.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Context {
+    pub actor: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set who performed the write.
+    pub fn actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    /// Set why the write was performed.
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+/// A single soft-delete or restore of a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Operation<PK> {
+    pub table: &'static str,
+    pub pk: PK,
+    pub action: Action,
+    pub actor: Option<String>,
+    pub reason: Option<String>,
+    pub timestamp: SystemTime,
+}
+
+/// Soft-delete the alive rows of `table` matching `predicate`, and describe each deletion.
+///
+/// The keys of the deleted rows are those returned by the `UPDATE` itself, so that concurrent
+/// writes cannot make them differ. This needs PostgreSQL or SQLite 3.35 or later, see
+/// [`returning`].
+pub fn soft_delete_tracked<T, P, PK, Conn>(
+    table: T,
+    predicate: P,
+    ctx: &Context,
+    conn: &Conn,
+) -> QueryResult<Vec<Operation<PK>>>
+where
+    T: Table<FromClause = Identifier<'static>> + SoftDeleteWrite + FilterDsl<P> + Copy,
+    Filter<T, P>: SoftDeleteTarget,
+    Returning<<Filter<T, P> as SoftDeleteTarget>::SoftDelete, T::PrimaryKey>: LoadQuery<Conn, PK>,
+    PK: Display,
+    Conn: HookConnection,
+{
+    let stmt = returning(soft_delete(table.filter(predicate)), table.primary_key());
+    let ids = trace::load(Action::SoftDelete, table.from_clause().0, stmt, conn)?;
+    Ok(operations(table, ids, Action::SoftDelete, ctx))
+}
+
+/// Restore the soft-deleted rows of `table` matching `predicate`, and describe each restoration.
+///
+/// As with [`soft_delete_tracked`], the keys of the restored rows are returned by the `UPDATE`.
+pub fn restore_tracked<T, P, PK, Conn>(
+    table: T,
+    predicate: P,
+    ctx: &Context,
+    conn: &Conn,
+) -> QueryResult<Vec<Operation<PK>>>
+where
+    T: Table<FromClause = Identifier<'static>> + SoftDeleteWrite + FilterDsl<P> + Copy,
+    Filter<T, P>: SoftDeleteTarget,
+    Returning<<Filter<T, P> as SoftDeleteTarget>::Restore, T::PrimaryKey>: LoadQuery<Conn, PK>,
+    PK: Display,
+    Conn: HookConnection,
+{
+    let stmt = returning(restore(table.filter(predicate)), table.primary_key());
+    let ids = trace::load(Action::Restore, table.from_clause().0, stmt, conn)?;
+    Ok(operations(table, ids, Action::Restore, ctx))
+}
+
+fn operations<T, PK>(table: T, ids: Vec<PK>, action: Action, ctx: &Context) -> Vec<Operation<PK>>
+where
+    T: Table<FromClause = Identifier<'static>> + Copy,
+{
+    let timestamp = SystemTime::now();
+    ids.into_iter().map(|pk| Operation { timestamp, ..operation(table, pk, action, ctx) }).collect()
+}
+
+/// Describe the `action` on the row `pk` of `table`, performed now.
+pub(crate) fn operation<T, PK>(table: T, pk: PK, action: Action, ctx: &Context) -> Operation<PK>
+where
+    T: Table<FromClause = Identifier<'static>>,
+{
+    Operation {
+        table: table.from_clause().0,
+        pk,
+        action,
+        actor: ctx.actor.clone(),
+        reason: ctx.reason.clone(),
+        timestamp: SystemTime::now(),
+    }
+}
@@ -0,0 +1,189 @@
+//! Soft deletion of many rows in chunks, each in its own transaction.
+//!
+//! Soft-deleting millions of rows matching a predicate with a single `UPDATE` holds its locks for
+//! as long as it runs. A [`ChunkedSoftDelete`] soft-deletes them a chunk at a time instead, in
+//! ascending order of primary key, committing each chunk and reporting its progress:
+//!
+//! ```rust,ignore
+//! let progress = ChunkedSoftDelete::new(post::table, post::user_id.eq(id))
+//!     .chunk_size(5000)
+//!     .run_with_progress(&conn, |progress| println!("{}", progress))?;
+//! ```
+//!
+//! Each chunk is the first alive rows matching the predicate, in a `LIMIT` subquery on the primary
+//! key of the table, which must be a single column. MySQL does not support such subqueries in an
+//! `UPDATE` of the same table.
+
+use std::fmt;
+
+use diesel::{
+    backend::Backend,
+    dsl::Filter,
+    expression::{AppearsOnTable, NonAggregate, SelectableExpression},
+    query_builder::{nodes::Identifier, AstPass, QueryFragment, QueryId},
+    query_dsl::methods::FilterDsl,
+    sql_types::Bool,
+    Column, Expression, QueryResult, Table,
+};
+
+use crate::{
+    hooks::{Action, HookConnection},
+    trace::{self, Target},
+    write::SoftDeleteTarget,
+    SoftDelete,
+};
+
+/// The condition of a chunk of the alive rows of a table matching a predicate, as in
+/// `post.id IN (SELECT post.id FROM post WHERE NOT (post.deleted) AND (...) LIMIT 1000)`.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftDeleteChunk<T, P> {
+    table: T,
+    predicate: P,
+    size: usize,
+}
+
+impl<T, P> Expression for SoftDeleteChunk<T, P> {
+    type SqlType = Bool;
+}
+
+impl<T, P> NonAggregate for SoftDeleteChunk<T, P> {}
+
+impl<T, P, QS> AppearsOnTable<QS> for SoftDeleteChunk<T, P>
+where
+    T: Table,
+    T::PrimaryKey: AppearsOnTable<QS>,
+{
+}
+
+impl<T, P, QS> SelectableExpression<QS> for SoftDeleteChunk<T, P>
+where
+    T: Table,
+    T::PrimaryKey: SelectableExpression<QS>,
+{
+}
+
+impl<T, P> QueryId for SoftDeleteChunk<T, P> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<T, P, DB> QueryFragment<DB> for SoftDeleteChunk<T, P>
+where
+    T: Table + SoftDelete,
+    T::FromClause: QueryFragment<DB>,
+    T::PrimaryKey: QueryFragment<DB>,
+    T::Deleted: QueryFragment<DB>,
+    P: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        let primary_key = self.table.primary_key();
+        primary_key.walk_ast(out.reborrow())?;
+        out.push_sql(" IN (SELECT ");
+        primary_key.walk_ast(out.reborrow())?;
+        out.push_sql(" FROM ");
+        self.table.from_clause().walk_ast(out.reborrow())?;
+        out.push_sql(" WHERE NOT (");
+        self.table.deleted_col().walk_ast(out.reborrow())?;
+        out.push_sql(") AND (");
+        self.predicate.walk_ast(out.reborrow())?;
+        out.push_sql(") ORDER BY ");
+        primary_key.walk_ast(out.reborrow())?;
+        out.push_sql(&format!(" LIMIT {})", self.size));
+        Ok(())
+    }
+}
+
+/// The progress of a [`ChunkedSoftDelete`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkProgress {
+    pub table: &'static str,
+    /// The number of rows soft-deleted so far.
+    pub deleted: usize,
+    /// The number of chunks committed so far.
+    pub chunks: usize,
+}
+
+impl fmt::Display for ChunkProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} rows soft-deleted in {} chunks", self.table, self.deleted, self.chunks)
+    }
+}
+
+/// The soft deletion of the alive rows of a table matching a predicate, in chunks of 1000 rows by
+/// default.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkedSoftDelete<T, P> {
+    table: T,
+    predicate: P,
+    chunk_size: usize,
+}
+
+impl<T, P> ChunkedSoftDelete<T, P> {
+    /// Soft-delete the alive rows of `table` matching `predicate`.
+    pub fn new(table: T, predicate: P) -> Self {
+        Self { table, predicate, chunk_size: 1000 }
+    }
+
+    /// Soft-delete `chunk_size` rows at a time.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "the chunk size must be positive");
+        self.chunk_size = chunk_size;
+        self
+    }
+}
+
+impl<T, P> ChunkedSoftDelete<T, P>
+where
+    T: Table<FromClause = Identifier<'static>> + Copy,
+    P: Clone,
+{
+    /// Run the soft deletion, returning its final progress.
+    pub fn run<Conn>(&self, conn: &Conn) -> QueryResult<ChunkProgress>
+    where
+        T: FilterDsl<SoftDeleteChunk<T, P>>,
+        T::PrimaryKey: Column,
+        Filter<T, SoftDeleteChunk<T, P>>: SoftDeleteTarget,
+        <Filter<T, SoftDeleteChunk<T, P>> as SoftDeleteTarget>::SoftDelete:
+            QueryFragment<Conn::Backend> + QueryId,
+        Conn: HookConnection,
+    {
+        self.run_with_progress(conn, |_| {})
+    }
+
+    /// Run the soft deletion, calling `progress` after each chunk is committed.
+    pub fn run_with_progress<Conn>(
+        &self,
+        conn: &Conn,
+        mut progress: impl FnMut(&ChunkProgress),
+    ) -> QueryResult<ChunkProgress>
+    where
+        T: FilterDsl<SoftDeleteChunk<T, P>>,
+        T::PrimaryKey: Column,
+        Filter<T, SoftDeleteChunk<T, P>>: SoftDeleteTarget,
+        <Filter<T, SoftDeleteChunk<T, P>> as SoftDeleteTarget>::SoftDelete:
+            QueryFragment<Conn::Backend> + QueryId,
+        Conn: HookConnection,
+    {
+        let mut report = ChunkProgress { table: self.table.from_clause().0, deleted: 0, chunks: 0 };
+        loop {
+            let chunk = SoftDeleteChunk {
+                table: self.table,
+                predicate: self.predicate.clone(),
+                size: self.chunk_size,
+            };
+            let deleted = conn.transaction(|| {
+                let stmt = self.table.filter(chunk).soft_delete();
+                trace::write(Action::SoftDelete, Target::of(self.table), stmt, conn)
+            })?;
+            if deleted > 0 {
+                report.deleted += deleted;
+                report.chunks += 1;
+                progress(&report);
+            }
+            if deleted < self.chunk_size {
+                return Ok(report);
+            }
+        }
+    }
+}
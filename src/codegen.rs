@@ -0,0 +1,330 @@
+//! Generate the `soft_delete!` declarations of a Diesel `schema.rs`, from a build script.
+//!
+//! The [`SchemaScanner`] finds the tables with a soft-delete column, by default `deleted`,
+//! `deleted_at` or `is_deleted`, and declares them with the form matching the column type: a
+//! `Bool` column is a flag, and a nullable timestamp one a deletion timestamp. Optionally, the
+//! `joinable!` edges between soft-deletable tables are declared as cascades.
+//!
+//! ```rust,ignore
+//! // build.rs
+//! fn main() {
+//!     let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("soft_delete.rs");
+//!     diesel_softdelete::codegen::SchemaScanner::new()
+//!         .cascades(true)
+//!         .write("src/schema.rs", out)
+//!         .unwrap();
+//!     println!("cargo:rerun-if-changed=src/schema.rs");
+//! }
+//!
+//! // src/lib.rs, the generated code importing the table modules it declares
+//! mod soft_delete {
+//!     include!(concat!(env!("OUT_DIR"), "/soft_delete.rs"));
+//! }
+//! ```
+
+use std::{error::Error as StdError, fmt, fs, io, path::Path, str::FromStr};
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+
+/// Scan a `schema.rs` for soft-deletable tables, and generate their declarations.
+#[derive(Debug, Clone)]
+pub struct SchemaScanner {
+    columns: Vec<String>,
+    schema_path: String,
+    cascades: bool,
+}
+
+impl Default for SchemaScanner {
+    fn default() -> Self {
+        Self { columns: Vec::new(), schema_path: "crate::schema".to_owned(), cascades: false }
+    }
+}
+
+impl SchemaScanner {
+    /// A scanner of the default soft-delete columns, declaring the tables of `crate::schema`
+    /// without cascades.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look for a soft-delete column named `name`, in place of the default ones. The columns are
+    /// looked for in the order they are added.
+    pub fn column(mut self, name: impl Into<String>) -> Self {
+        self.columns.push(name.into());
+        self
+    }
+
+    /// The path of the schema module from the generated code, `crate::schema` by default.
+    pub fn schema_path(mut self, path: impl Into<String>) -> Self {
+        self.schema_path = path.into();
+        self
+    }
+
+    /// Also declare the `joinable!` edges between soft-deletable tables as cascades, from the
+    /// referenced table to the referencing one.
+    pub fn cascades(mut self, cascades: bool) -> Self {
+        self.cascades = cascades;
+        self
+    }
+
+    /// Generate the declarations of the tables of the `schema.rs` source code.
+    pub fn generate(&self, source: &str) -> Result<String, CodegenError> {
+        let tokens =
+            TokenStream::from_str(source).map_err(|e| CodegenError::Parse(e.to_string()))?;
+        let mut schema = Schema::default();
+        schema.scan(tokens, &[])?;
+
+        let defaults = ["deleted", "deleted_at", "is_deleted"].map(String::from);
+        let names = if self.columns.is_empty() { &defaults[..] } else { &self.columns[..] };
+        let mut code = String::from("// @generated by diesel-softdelete, do not edit.\n\n");
+        let mut declared = Vec::new();
+        for table in &schema.tables {
+            let found = names
+                .iter()
+                .find_map(|name| table.columns.iter().find(|(column, _)| column == name));
+            let (column, ty) = match found {
+                Some(found) => found,
+                None => continue,
+            };
+            let form = if is_sql_type(ty, "Bool") {
+                format!("({}::{})", table.name, column)
+            } else if is_nullable_timestamp(ty) {
+                format!("timestamp({}::{})", table.name, column)
+            } else {
+                return Err(CodegenError::ColumnType {
+                    table: table.name.clone(),
+                    column: column.clone(),
+                    ty: ty.clone(),
+                });
+            };
+            let path = table.module.iter().chain([&table.name]).cloned().collect::<Vec<_>>();
+            code += &format!("use {}::{};\n", self.schema_path, path.join("::"));
+            declared.push((&table.name, form));
+        }
+        if declared.is_empty() {
+            return Ok(code);
+        }
+
+        code += "\ndiesel_softdelete::soft_delete!(\n";
+        for (name, form) in &declared {
+            code += &format!("    {}::table => {},\n", name, form);
+        }
+        code += ");\n";
+
+        let cascades = if self.cascades { schema.cascades(&declared)? } else { Vec::new() };
+        if !cascades.is_empty() {
+            code += "\ndiesel_softdelete::soft_delete_cascade!(\n";
+            for (parent, children) in cascades {
+                code += &format!("    {} => [{}],\n", parent, children.join(", "));
+            }
+            code += ");\n";
+        }
+        Ok(code)
+    }
+
+    /// Generate the declarations of the tables of the `schema` file into the `out` file.
+    pub fn write(
+        &self,
+        schema: impl AsRef<Path>,
+        out: impl AsRef<Path>,
+    ) -> Result<(), CodegenError> {
+        let code = self.generate(&fs::read_to_string(schema)?)?;
+        Ok(fs::write(out, code)?)
+    }
+}
+
+/// The error of [`SchemaScanner::generate`].
+#[derive(Debug)]
+pub enum CodegenError {
+    /// The schema is not valid Rust code.
+    Parse(String),
+    /// The soft-delete column of a table is neither a `Bool` nor a nullable timestamp.
+    ColumnType {
+        table: String,
+        column: String,
+        ty: String,
+    },
+    /// A table has more children than a cascade can declare.
+    TooManyChildren {
+        table: String,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "failed to parse the schema: {}", e),
+            Self::ColumnType { table, column, ty } => write!(
+                f,
+                "soft-delete column `{}` of table `{}` must be `Bool` or a nullable timestamp, \
+                 not `{}`",
+                column, table, ty,
+            ),
+            Self::TooManyChildren { table } => {
+                write!(f, "table `{}` has more than 8 soft-deletable children", table)
+            }
+            Self::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for CodegenError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CodegenError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A table of the schema: the module it is nested in, its name, and its columns with their SQL
+/// type.
+#[derive(Debug)]
+struct Table {
+    module: Vec<String>,
+    name: String,
+    columns: Vec<(String, String)>,
+}
+
+/// The `table!` and `joinable!` invocations of a schema.
+#[derive(Debug, Default)]
+struct Schema {
+    tables: Vec<Table>,
+    /// The `(child, parent)` pairs of the `joinable!` invocations.
+    joins: Vec<(String, String)>,
+}
+
+impl Schema {
+    /// Scan the items of `tokens`, nested in `module`, including those of its inline modules,
+    /// e.g. the PostgreSQL schemas.
+    fn scan(&mut self, tokens: TokenStream, module: &[String]) -> Result<(), CodegenError> {
+        let tokens: Vec<_> = tokens.into_iter().collect();
+        for window in tokens.windows(3) {
+            match window {
+                [TokenTree::Ident(ident), TokenTree::Punct(bang), TokenTree::Group(group)]
+                    if bang.as_char() == '!' && ident == "table" =>
+                {
+                    self.tables.push(table(group.stream(), module)?);
+                }
+                [TokenTree::Ident(ident), TokenTree::Punct(bang), TokenTree::Group(group)]
+                    if bang.as_char() == '!' && ident == "joinable" =>
+                {
+                    self.joins.extend(joinable(group.stream()));
+                }
+                [TokenTree::Ident(keyword), TokenTree::Ident(name), TokenTree::Group(group)]
+                    if keyword == "mod" && group.delimiter() == Delimiter::Brace =>
+                {
+                    let module =
+                        module.iter().cloned().chain([name.to_string()]).collect::<Vec<_>>();
+                    self.scan(group.stream(), &module)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The children of each parent among the `declared` tables, in declaration order.
+    fn cascades(
+        &self,
+        declared: &[(&String, String)],
+    ) -> Result<Vec<(String, Vec<String>)>, CodegenError> {
+        let is_declared = |name: &String| declared.iter().any(|(table, _)| *table == name);
+        let mut cascades: Vec<(String, Vec<String>)> = Vec::new();
+        for (child, parent) in &self.joins {
+            if !is_declared(child) || !is_declared(parent) {
+                continue;
+            }
+            match cascades.iter_mut().find(|(table, _)| table == parent) {
+                Some((_, children)) if children.contains(child) => {}
+                Some((table, children)) if children.len() == 8 => {
+                    return Err(CodegenError::TooManyChildren { table: table.clone() });
+                }
+                Some((_, children)) => children.push(child.clone()),
+                None => cascades.push((parent.clone(), vec![child.clone()])),
+            }
+        }
+        Ok(cascades)
+    }
+}
+
+/// Parse the body of a `table!` invocation.
+fn table(definition: TokenStream, module: &[String]) -> Result<Table, CodegenError> {
+    let tokens: Vec<_> = definition.into_iter().collect();
+    let invalid = || CodegenError::Parse("expected a `table!` definition".to_owned());
+    let (columns, head) = match tokens.split_last() {
+        Some((TokenTree::Group(group), head)) if group.delimiter() == Delimiter::Brace => {
+            (group, head)
+        }
+        _ => return Err(invalid()),
+    };
+    // The table name is followed by its primary key, if any
+    let head = match head.split_last() {
+        Some((TokenTree::Group(group), rest)) if group.delimiter() == Delimiter::Parenthesis => {
+            rest
+        }
+        _ => head,
+    };
+    let name = match head.last() {
+        Some(TokenTree::Ident(name)) => name.to_string(),
+        _ => return Err(invalid()),
+    };
+
+    let mut parsed = Vec::new();
+    let tokens: Vec<_> = columns.stream().into_iter().collect();
+    for column in tokens.split(|t| matches!(t, TokenTree::Punct(p) if p.as_char() == ',')) {
+        // Skip the attributes, e.g. `#[sql_name = "..."]`
+        let mut column = column;
+        while let [TokenTree::Punct(pound), TokenTree::Group(_), rest @ ..] = column {
+            if pound.as_char() != '#' {
+                break;
+            }
+            column = rest;
+        }
+        if let [TokenTree::Ident(ident), TokenTree::Punct(_), TokenTree::Punct(_), ty @ ..] = column
+        {
+            let ty: TokenStream = ty.iter().cloned().collect();
+            parsed.push((ident.to_string(), ty.to_string().replace(' ', "")));
+        }
+    }
+    Ok(Table { module: module.to_vec(), name, columns: parsed })
+}
+
+/// Parse the body of a `joinable!(child -> parent (foreign_key))` invocation.
+fn joinable(definition: TokenStream) -> Option<(String, String)> {
+    let tokens: Vec<_> = definition.into_iter().collect();
+    let ident = |i: usize| match tokens.get(i) {
+        Some(TokenTree::Ident(ident)) => Some(ident.to_string()),
+        _ => None,
+    };
+    // The arrow is two punctuations.
+    match tokens.get(1..3) {
+        Some([TokenTree::Punct(_), TokenTree::Punct(_)]) => Some((ident(0)?, ident(3)?)),
+        _ => None,
+    }
+}
+
+fn is_sql_type(ty: &str, sql_type: &str) -> bool {
+    ty == sql_type || ty.ends_with(&format!("::{}", sql_type))
+}
+
+/// Whether `ty` is a `Nullable` timestamp, `Timestamp`, `Timestamptz`, `Datetime` or `Date`.
+fn is_nullable_timestamp(ty: &str) -> bool {
+    let inner = ty
+        .strip_suffix('>')
+        .and_then(|ty| ty.split_once("Nullable<"))
+        .filter(|(path, _)| path.is_empty() || path.ends_with("::"));
+    match inner {
+        Some((_, inner)) => ["Timestamp", "Timestamptz", "Datetime", "Date"]
+            .iter()
+            .any(|sql_type| is_sql_type(inner, sql_type)),
+        None => false,
+    }
+}
@@ -0,0 +1,205 @@
+//! Compound queries whose every arm is restricted to its alive rows.
+//!
+//! Diesel does not build `UNION`, `INTERSECT` nor `EXCEPT` queries, and a raw compound query easily
+//! leaves one of its arms unscoped, e.g. the trashed posts of a feed merging the posts of a user
+//! with the posts mentioning them. The [`SoftCombineDsl`] methods combine two queries, each
+//! restricted to its alive rows as by [`soft_deleted`](SoftDeleteDsl::soft_deleted):
+//!
+//! ```rust,ignore
+//! let feed = post::table
+//!     .filter(post::user_id.eq(id))
+//!     .select(post::id)
+//!     .soft_union(mention::table.filter(mention::user_id.eq(id)).select(mention::post_id))
+//!     .load::<i32>(&conn)?;
+//! // SELECT "post"."id" FROM "post" WHERE "post"."user_id" = $1 AND NOT ("post"."deleted")
+//! // UNION SELECT "mention"."post_id" FROM "mention" WHERE ...
+//! ```
+//!
+//! Further arms are appended with the methods of [`SoftCombination`], scoped as well, and combined
+//! in order: `a.soft_union(b).intersect(c)` is the intersection of `c` with the union of `a` and
+//! `b`. As `INTERSECT` binds tighter than the other operators on PostgreSQL and MySQL, the arms it
+//! follows are then wrapped in a subquery. The arms are otherwise rendered without parentheses,
+//! which SQLite does not support: they must not have an `ORDER BY` nor a `LIMIT` clause of their
+//! own.
+
+use diesel::{
+    backend::Backend,
+    query_builder::{AstPass, Query, QueryFragment, QueryId},
+    QueryResult, RunQueryDsl,
+};
+
+use crate::methods::SoftDeleteDsl;
+
+/// The operator combining the arms of a [`SoftCombination`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombinationOp {
+    /// `UNION`, without the duplicate rows.
+    Union,
+    /// `UNION ALL`, with the duplicate rows.
+    UnionAll,
+    /// `INTERSECT`, the rows of both arms.
+    Intersect,
+    /// `EXCEPT`, the rows of the left arm missing from the right one.
+    Except,
+}
+
+impl CombinationOp {
+    fn sql(self) -> &'static str {
+        match self {
+            CombinationOp::Union => " UNION ",
+            CombinationOp::UnionAll => " UNION ALL ",
+            CombinationOp::Intersect => " INTERSECT ",
+            CombinationOp::Except => " EXCEPT ",
+        }
+    }
+}
+
+/// Two queries combined by a [`CombinationOp`], built by the [`SoftCombineDsl`] methods.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftCombination<L, R> {
+    lhs: L,
+    /// The operator of the left arm, if it is itself a combination.
+    lhs_op: Option<CombinationOp>,
+    op: CombinationOp,
+    rhs: R,
+}
+
+impl<L: Query, R> SoftCombination<L, R> {
+    /// Append the alive rows of `rhs` with `UNION`.
+    pub fn union<Rhs>(self, rhs: Rhs) -> SoftCombination<Self, Rhs::Output>
+    where
+        Rhs: SoftDeleteDsl,
+        Rhs::Output: Query<SqlType = L::SqlType>,
+    {
+        self.combine(CombinationOp::Union, rhs)
+    }
+
+    /// Append the alive rows of `rhs` with `UNION ALL`.
+    pub fn union_all<Rhs>(self, rhs: Rhs) -> SoftCombination<Self, Rhs::Output>
+    where
+        Rhs: SoftDeleteDsl,
+        Rhs::Output: Query<SqlType = L::SqlType>,
+    {
+        self.combine(CombinationOp::UnionAll, rhs)
+    }
+
+    /// Keep the rows also among the alive rows of `rhs`, with `INTERSECT`.
+    pub fn intersect<Rhs>(self, rhs: Rhs) -> SoftCombination<Self, Rhs::Output>
+    where
+        Rhs: SoftDeleteDsl,
+        Rhs::Output: Query<SqlType = L::SqlType>,
+    {
+        self.combine(CombinationOp::Intersect, rhs)
+    }
+
+    /// Remove the rows among the alive rows of `rhs`, with `EXCEPT`.
+    pub fn except<Rhs>(self, rhs: Rhs) -> SoftCombination<Self, Rhs::Output>
+    where
+        Rhs: SoftDeleteDsl,
+        Rhs::Output: Query<SqlType = L::SqlType>,
+    {
+        self.combine(CombinationOp::Except, rhs)
+    }
+
+    fn combine<Rhs: SoftDeleteDsl>(
+        self,
+        op: CombinationOp,
+        rhs: Rhs,
+    ) -> SoftCombination<Self, Rhs::Output> {
+        let lhs_op = Some(self.op);
+        SoftCombination { lhs: self, lhs_op, op, rhs: rhs.soft_deleted() }
+    }
+}
+
+impl<L: Query, R> Query for SoftCombination<L, R> {
+    type SqlType = L::SqlType;
+}
+
+impl<L, R> QueryId for SoftCombination<L, R> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<L, R, Conn> RunQueryDsl<Conn> for SoftCombination<L, R> {}
+
+impl<L, R, DB> QueryFragment<DB> for SoftCombination<L, R>
+where
+    L: QueryFragment<DB>,
+    R: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        // Unwrapped, the last arm of the left combination would be intersected first.
+        let nested = match self.lhs_op {
+            Some(lhs_op) => self.op == CombinationOp::Intersect && lhs_op != self.op,
+            None => false,
+        };
+        if nested {
+            out.push_sql("SELECT * FROM (");
+        }
+        self.lhs.walk_ast(out.reborrow())?;
+        if nested {
+            out.push_sql(") AS ");
+            out.push_identifier("combined")?;
+        }
+        out.push_sql(self.op.sql());
+        self.rhs.walk_ast(out)
+    }
+}
+
+/// The methods combining the alive rows of two queries.
+pub trait SoftCombineDsl: SoftDeleteDsl + Sized {
+    /// The alive rows of both queries, without duplicates, with `UNION`.
+    fn soft_union<Rhs>(self, rhs: Rhs) -> SoftCombination<Self::Output, Rhs::Output>
+    where
+        Self::Output: Query,
+        Rhs: SoftDeleteDsl,
+        Rhs::Output: Query<SqlType = <Self::Output as Query>::SqlType>,
+    {
+        soft_combination(self, CombinationOp::Union, rhs)
+    }
+
+    /// The alive rows of both queries, with duplicates, with `UNION ALL`.
+    fn soft_union_all<Rhs>(self, rhs: Rhs) -> SoftCombination<Self::Output, Rhs::Output>
+    where
+        Self::Output: Query,
+        Rhs: SoftDeleteDsl,
+        Rhs::Output: Query<SqlType = <Self::Output as Query>::SqlType>,
+    {
+        soft_combination(self, CombinationOp::UnionAll, rhs)
+    }
+
+    /// The alive rows of this query also among the alive rows of `rhs`, with `INTERSECT`.
+    fn soft_intersect<Rhs>(self, rhs: Rhs) -> SoftCombination<Self::Output, Rhs::Output>
+    where
+        Self::Output: Query,
+        Rhs: SoftDeleteDsl,
+        Rhs::Output: Query<SqlType = <Self::Output as Query>::SqlType>,
+    {
+        soft_combination(self, CombinationOp::Intersect, rhs)
+    }
+
+    /// The alive rows of this query missing from the alive rows of `rhs`, with `EXCEPT`.
+    fn soft_except<Rhs>(self, rhs: Rhs) -> SoftCombination<Self::Output, Rhs::Output>
+    where
+        Self::Output: Query,
+        Rhs: SoftDeleteDsl,
+        Rhs::Output: Query<SqlType = <Self::Output as Query>::SqlType>,
+    {
+        soft_combination(self, CombinationOp::Except, rhs)
+    }
+}
+
+impl<T: SoftDeleteDsl> SoftCombineDsl for T {}
+
+fn soft_combination<L, R>(
+    lhs: L,
+    op: CombinationOp,
+    rhs: R,
+) -> SoftCombination<L::Output, R::Output>
+where
+    L: SoftDeleteDsl,
+    R: SoftDeleteDsl,
+{
+    SoftCombination { lhs: lhs.soft_deleted(), lhs_op: None, op, rhs: rhs.soft_deleted() }
+}
@@ -0,0 +1,43 @@
+//! SQL comment tagging of the alive condition.
+//!
+//! With the `sql-comments` feature, the alive condition added by the soft methods is followed by a
+//! `/* softdelete:alive */` comment, so the soft-scoped queries can be identified and grouped in
+//! `pg_stat_statements` and slow-query logs.
+
+use diesel::{
+    backend::Backend,
+    expression::{AppearsOnTable, NonAggregate, SelectableExpression},
+    query_builder::{AstPass, QueryFragment, QueryId},
+    Expression, QueryResult,
+};
+
+/// The comment following the alive condition.
+pub const ALIVE_TAG: &str = "/* softdelete:alive */";
+
+/// An expression followed by the [`ALIVE_TAG`] comment.
+#[derive(Debug, Clone, Copy)]
+pub struct Tagged<E>(pub(crate) E);
+
+impl<E: Expression> Expression for Tagged<E> {
+    type SqlType = E::SqlType;
+}
+
+impl<E: NonAggregate> NonAggregate for Tagged<E> {}
+
+impl<E: AppearsOnTable<QS>, QS> AppearsOnTable<QS> for Tagged<E> {}
+
+impl<E: SelectableExpression<QS>, QS> SelectableExpression<QS> for Tagged<E> {}
+
+impl<E: QueryId> QueryId for Tagged<E> {
+    type QueryId = Tagged<E::QueryId>;
+    const HAS_STATIC_QUERY_ID: bool = E::HAS_STATIC_QUERY_ID;
+}
+
+impl<E: QueryFragment<DB>, DB: Backend> QueryFragment<DB> for Tagged<E> {
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        self.0.walk_ast(out.reborrow())?;
+        out.push_sql(" ");
+        out.push_sql(ALIVE_TAG);
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+//! The registry of the soft-deletable tables, shared by the subsystems working on all of them.
+//!
+//! A [`SoftDeleteConfig`] describes each table once: how it is soft-deleted, its metadata
+//! columns, how long its trashed rows are kept, whether they are on hold or audited, and its
+//! cascade children. The subsystems then consume the same registry instead of each having their
+//! own:
+//!
+//! ```rust,ignore
+//! let config = SoftDeleteConfig::<Pg>::new()
+//!     .table(TableConfig::new(user::table).deleted_at(user::deleted_at).cascades().audit())
+//!     .table(TableConfig::new(post::table).retention(Duration::from_secs(30 * 24 * 3600)))
+//!     .table(TableConfig::new(invoice::table).hold().audit());
+//!
+//! let drift = config.validate_soft_delete_schema(&conn);
+//! let registry = TrashRegistry::new().config(&config);
+//! let collector = TrashCollector::config(&config, pool.get()?)?;
+//! let log = AuditLog::new().config(&config);
+//! let conn = GuardedConnection::new(conn).config(&config);
+//! ```
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use diesel::{
+    backend::Backend,
+    query_builder::{nodes::Identifier, QueryBuilder, QueryFragment},
+    Column, QueryResult, Table,
+};
+
+use crate::{cascade::SoftCascade, SoftDelete};
+
+/// How the rows of a table are soft-deleted, i.e. the form of its [`soft_delete!`] declaration.
+///
+/// [`soft_delete!`]: crate::soft_delete
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeletionKind {
+    /// A deleted flag.
+    Flag,
+    /// A deleted flag, the rows also being deleted once their `expires_at` column has passed.
+    Expiring { expires_at: &'static str },
+    /// A nullable deletion timestamp.
+    Timestamp,
+    /// A deletion timestamp holding the [`ALIVE_SENTINEL`](crate::write::ALIVE_SENTINEL) on the
+    /// alive rows.
+    Sentinel,
+    /// A nullable deletion timestamp, the rows being deleted once it has passed.
+    Scheduled,
+    /// No deletion state: the rows are deleted with their parent, through a foreign key.
+    Inherited,
+    /// The deletions of the rows are recorded in a deletions table, by primary key.
+    Recorded,
+}
+
+/// The soft-delete column of a table and how its rows are soft-deleted, implemented by
+/// [`soft_delete!`].
+///
+/// [`soft_delete!`]: crate::soft_delete
+pub trait SoftDeleteColumn: SoftDelete {
+    const KIND: DeletionKind;
+    /// The name of the soft-delete column: the deleted flag, the deletion timestamp, the foreign
+    /// key to the parent of an inherited deletion, or the primary key of a recorded one.
+    const COLUMN: &'static str;
+}
+
+/// The description of a soft-deletable table, to register in a [`SoftDeleteConfig`].
+#[derive(Debug, Clone, Copy)]
+pub struct TableConfig<T> {
+    table: T,
+    deleted_at: Option<&'static str>,
+    deleted_by: Option<&'static str>,
+    label: Option<&'static str>,
+    archive: Option<(&'static str, Duration)>,
+    retention: Option<Duration>,
+    held: bool,
+    audited: bool,
+    children: &'static [&'static str],
+}
+
+impl<T: Table> TableConfig<T> {
+    pub fn new(table: T) -> Self {
+        Self {
+            table,
+            deleted_at: None,
+            deleted_by: None,
+            label: None,
+            archive: None,
+            retention: None,
+            held: false,
+            audited: false,
+            children: &[],
+        }
+    }
+
+    /// The column recording when the rows were soft-deleted, besides the deleted flag. It is the
+    /// soft-delete column of the tables soft-deleted with a timestamp.
+    pub fn deleted_at<C: Column<Table = T>>(mut self, _column: C) -> Self {
+        self.deleted_at = Some(C::NAME);
+        self
+    }
+
+    /// The column recording who soft-deleted the rows.
+    pub fn deleted_by<C: Column<Table = T>>(mut self, _column: C) -> Self {
+        self.deleted_by = Some(C::NAME);
+        self
+    }
+
+    /// The column describing the rows to a human, e.g. a name or a title, shown in the listings of
+    /// the trash.
+    pub fn label<C: Column<Table = T>>(mut self, _column: C) -> Self {
+        self.label = Some(C::NAME);
+        self
+    }
+
+    /// Keep the trashed rows for `retention` after their deletion, before they can be purged.
+    pub fn retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Move the trashed rows to the `archive` table `after` their deletion, out of the hot table.
+    /// The archive table mirrors the columns of the table, in the same order.
+    pub fn archive(mut self, archive: &'static str, after: Duration) -> Self {
+        self.archive = Some((archive, after));
+        self
+    }
+
+    /// Put the table on hold: its trashed rows are never purged, e.g. during a legal hold.
+    pub fn hold(mut self) -> Self {
+        self.held = true;
+        self
+    }
+
+    /// Record the soft deletions, restorations and purges of the table in the audit log of the
+    /// `audit` feature, when it is restricted to the audited tables of the registry.
+    pub fn audit(mut self) -> Self {
+        self.audited = true;
+        self
+    }
+}
+
+impl<T: SoftCascade> TableConfig<T> {
+    /// Record the cascade children of the table, declared with
+    /// [`soft_delete_cascade!`](crate::soft_delete_cascade).
+    pub fn cascades(mut self) -> Self {
+        self.children = T::CHILDREN;
+        self
+    }
+}
+
+/// A soft-deletable table registered in a [`SoftDeleteConfig`].
+pub struct SoftDeleteTable<DB: Backend> {
+    name: &'static str,
+    primary_key: &'static str,
+    column: &'static str,
+    kind: DeletionKind,
+    deleted: Arc<dyn QueryFragment<DB> + Send + Sync>,
+    deleted_at: Option<&'static str>,
+    deleted_by: Option<&'static str>,
+    label: Option<&'static str>,
+    archive: Option<(&'static str, Duration)>,
+    retention: Option<Duration>,
+    held: bool,
+    audited: bool,
+    children: &'static [&'static str],
+}
+
+impl<DB: Backend> Clone for SoftDeleteTable<DB> {
+    fn clone(&self) -> Self {
+        Self { deleted: Arc::clone(&self.deleted), ..*self }
+    }
+}
+
+impl<DB: Backend> fmt::Debug for SoftDeleteTable<DB> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SoftDeleteTable")
+            .field("name", &self.name)
+            .field("column", &self.column)
+            .field("kind", &self.kind)
+            .field("deleted_at", &self.deleted_at)
+            .field("deleted_by", &self.deleted_by)
+            .field("label", &self.label)
+            .field("archive", &self.archive)
+            .field("retention", &self.retention)
+            .field("held", &self.held)
+            .field("audited", &self.audited)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+impl<DB: Backend> SoftDeleteTable<DB> {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The name of the single-column primary key.
+    pub fn primary_key(&self) -> &'static str {
+        self.primary_key
+    }
+
+    /// The name of the soft-delete column, as described by [`SoftDeleteColumn::COLUMN`].
+    pub fn column(&self) -> &'static str {
+        self.column
+    }
+
+    pub fn kind(&self) -> DeletionKind {
+        self.kind
+    }
+
+    /// The column recording when the rows were soft-deleted, if known: the soft-delete column
+    /// itself for the tables soft-deleted with a timestamp.
+    pub fn deleted_at(&self) -> Option<&'static str> {
+        match self.kind {
+            DeletionKind::Timestamp | DeletionKind::Sentinel | DeletionKind::Scheduled => {
+                Some(self.deleted_at.unwrap_or(self.column))
+            }
+            _ => self.deleted_at,
+        }
+    }
+
+    pub fn deleted_by(&self) -> Option<&'static str> {
+        self.deleted_by
+    }
+
+    /// The column describing the rows to a human, if any.
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    /// The archive table of the trashed rows, and how long after their deletion they are moved to
+    /// it.
+    pub fn archive(&self) -> Option<(&'static str, Duration)> {
+        self.archive
+    }
+
+    pub fn retention(&self) -> Option<Duration> {
+        self.retention
+    }
+
+    /// Whether the trashed rows of the table must never be purged.
+    pub fn is_held(&self) -> bool {
+        self.held
+    }
+
+    /// Whether the writes to the table are recorded in the audit log.
+    pub fn is_audited(&self) -> bool {
+        self.audited
+    }
+
+    /// The names of the cascade children of the table, in declaration order.
+    pub fn children(&self) -> &'static [&'static str] {
+        self.children
+    }
+}
+
+impl<DB> SoftDeleteTable<DB>
+where
+    DB: Backend,
+    DB::QueryBuilder: Default,
+{
+    /// The quoted name of the table, e.g. `"user"` on PostgreSQL.
+    pub(crate) fn quoted_name(&self) -> QueryResult<String> {
+        let mut out = DB::QueryBuilder::default();
+        out.push_identifier(self.name)?;
+        Ok(out.finish())
+    }
+
+    /// The quoted column `column` of the table, e.g. `"user"."name"` on PostgreSQL.
+    #[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn quoted_column(&self, column: &str) -> QueryResult<String> {
+        let mut out = DB::QueryBuilder::default();
+        out.push_identifier(self.name)?;
+        out.push_sql(".");
+        out.push_identifier(column)?;
+        Ok(out.finish())
+    }
+
+    /// The condition of the soft-deleted rows, e.g. `"user"."deleted"`.
+    pub(crate) fn deleted_sql(&self) -> QueryResult<String> {
+        let mut out = DB::QueryBuilder::default();
+        self.deleted.to_sql(&mut out)?;
+        Ok(out.finish())
+    }
+
+    /// The alive condition, e.g. `NOT ("user"."deleted")`, as the soft methods render it.
+    pub(crate) fn not_deleted_sql(&self) -> QueryResult<String> {
+        Ok(format!("NOT ({})", self.deleted_sql()?))
+    }
+}
+
+/// The soft-deletable tables of a database using the backend `DB`.
+pub struct SoftDeleteConfig<DB: Backend> {
+    tables: Vec<SoftDeleteTable<DB>>,
+}
+
+impl<DB: Backend> Default for SoftDeleteConfig<DB> {
+    fn default() -> Self {
+        Self { tables: Vec::new() }
+    }
+}
+
+impl<DB: Backend> Clone for SoftDeleteConfig<DB> {
+    fn clone(&self) -> Self {
+        Self { tables: self.tables.clone() }
+    }
+}
+
+impl<DB: Backend> fmt::Debug for SoftDeleteConfig<DB> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SoftDeleteConfig").field("tables", &self.tables).finish()
+    }
+}
+
+impl<DB> SoftDeleteConfig<DB>
+where
+    DB: Backend,
+    DB::QueryBuilder: Default,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a soft-deletable table.
+    pub fn table<T>(mut self, config: TableConfig<T>) -> Self
+    where
+        T: Table<FromClause = Identifier<'static>> + SoftDeleteColumn,
+        T::PrimaryKey: Column,
+        T::Deleted: QueryFragment<DB> + Send + Sync + 'static,
+    {
+        self.tables.push(SoftDeleteTable {
+            name: config.table.from_clause().0,
+            primary_key: <T::PrimaryKey as Column>::NAME,
+            column: T::COLUMN,
+            kind: T::KIND,
+            deleted: Arc::new(config.table.deleted_col()),
+            deleted_at: config.deleted_at,
+            deleted_by: config.deleted_by,
+            label: config.label,
+            archive: config.archive,
+            retention: config.retention,
+            held: config.held,
+            audited: config.audited,
+            children: config.children,
+        });
+        self
+    }
+}
+
+impl<DB: Backend> SoftDeleteConfig<DB> {
+    /// The registered tables, in registration order.
+    pub fn tables(&self) -> &[SoftDeleteTable<DB>] {
+        &self.tables
+    }
+
+    /// The registered table named `name`.
+    pub fn get(&self, name: &str) -> Option<&SoftDeleteTable<DB>> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+}
@@ -0,0 +1,73 @@
+//! Bulk export of the alive rows with `COPY` (PostgreSQL).
+//!
+//! Analytics extracts of whole tables are better served by `COPY` than by loading the rows through
+//! the query builder. [`soft_copy`] wraps a query in `COPY (...) TO STDOUT`, restricted to its
+//! alive rows, in CSV or in the binary format:
+//!
+//! ```rust,ignore
+//! let copy = soft_copy(post::table.select((post::id, post::title)), CopyFormat::Csv { header: true });
+//! // Streamed with a client supporting `COPY`, e.g. `postgres::Client::copy_out`
+//! let reader = client.copy_out(copy.sql()?.as_str())?;
+//! ```
+//!
+//! Diesel itself does not read the `COPY` protocol, hence the statement being handed to another
+//! client. Alternatively, [`SoftCopy::to_file`] writes the rows to a file of the database server.
+//!
+//! `COPY` does not take bind parameters: the query must not have any, e.g. no filter on a Rust
+//! value. The statement of a query with some keeps their placeholders, which the server rejects.
+
+use diesel::{
+    pg::{Pg, PgConnection},
+    query_builder::{QueryBuilder, QueryFragment},
+    Connection, QueryResult,
+};
+
+use crate::methods::SoftDeleteDsl;
+
+/// The format of the rows written by `COPY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyFormat {
+    /// CSV, with a header row of the column names if `header` is set.
+    Csv { header: bool },
+    /// The PostgreSQL binary format.
+    Binary,
+}
+
+/// A `COPY` of the alive rows of a query.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftCopy<Q> {
+    query: Q,
+    format: CopyFormat,
+}
+
+/// The `COPY` of the alive rows of `query` in `format`.
+pub fn soft_copy<Q: SoftDeleteDsl>(query: Q, format: CopyFormat) -> SoftCopy<Q::Output> {
+    SoftCopy { query: query.soft_deleted(), format }
+}
+
+impl<Q: QueryFragment<Pg>> SoftCopy<Q> {
+    /// The `COPY ... TO STDOUT` statement, to run with a client reading the `COPY` protocol.
+    pub fn sql(&self) -> QueryResult<String> {
+        self.statement("STDOUT")
+    }
+
+    /// Write the rows to the file `path` of the database server, returning the number of rows
+    /// written. This requires the `pg_write_server_files` role.
+    pub fn to_file(&self, path: &str, conn: &PgConnection) -> QueryResult<usize> {
+        conn.execute(&self.statement(&format!("'{}'", path.replace('\'', "''")))?)
+    }
+
+    fn statement(&self, target: &str) -> QueryResult<String> {
+        let mut sql = <Pg as diesel::backend::Backend>::QueryBuilder::default();
+        sql.push_sql("COPY (");
+        self.query.to_sql(&mut sql)?;
+        sql.push_sql(") TO ");
+        sql.push_sql(target);
+        sql.push_sql(match self.format {
+            CopyFormat::Csv { header: false } => " WITH (FORMAT csv)",
+            CopyFormat::Csv { header: true } => " WITH (FORMAT csv, HEADER true)",
+            CopyFormat::Binary => " WITH (FORMAT binary)",
+        });
+        Ok(sql.finish())
+    }
+}
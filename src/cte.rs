@@ -0,0 +1,79 @@
+//! Common table expressions whose body is restricted to the alive rows.
+//!
+//! Filtering the rows of a CTE after the fact does not restrict its body: an aggregate or a
+//! `LIMIT` in it would still count the soft-deleted rows. [`soft_cte`] applies the alive condition
+//! inside the body instead, which is a typed Diesel query, the main query being SQL reading from
+//! the CTE:
+//!
+//! ```rust,ignore
+//! let latest = soft_cte("latest", post::table.select(post::title).order(post::id.desc()).limit(10))
+//!     .query::<Text>("SELECT title FROM latest ORDER BY title")
+//!     .load::<String>(&conn)?;
+//! ```
+//!
+//! As with `diesel::dsl::sql`, the SQL type of the rows of the main query is not checked.
+
+use std::marker::PhantomData;
+
+use diesel::{
+    backend::Backend,
+    query_builder::{AstPass, Query, QueryFragment, QueryId},
+    QueryResult, RunQueryDsl,
+};
+
+use crate::methods::SoftDeleteDsl;
+
+/// A named common table expression over the alive rows of its body.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftCte<Body> {
+    name: &'static str,
+    body: Body,
+}
+
+/// The common table expression `name`, whose `body` is restricted to the alive rows.
+pub fn soft_cte<Body: SoftDeleteDsl>(name: &'static str, body: Body) -> SoftCte<Body::Output> {
+    SoftCte { name, body: body.soft_deleted() }
+}
+
+impl<Body> SoftCte<Body> {
+    /// The main query, in SQL, returning rows of the SQL type `ST` from the CTE.
+    pub fn query<ST>(self, sql: &str) -> WithSoftCte<Body, ST> {
+        WithSoftCte { cte: self, sql: sql.to_owned(), sql_type: PhantomData }
+    }
+}
+
+/// A query reading from a [`SoftCte`], in a `WITH` clause.
+#[derive(Debug, Clone)]
+pub struct WithSoftCte<Body, ST> {
+    cte: SoftCte<Body>,
+    sql: String,
+    sql_type: PhantomData<ST>,
+}
+
+impl<Body, ST> Query for WithSoftCte<Body, ST> {
+    type SqlType = ST;
+}
+
+impl<Body, ST> QueryId for WithSoftCte<Body, ST> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<Body, ST, DB> QueryFragment<DB> for WithSoftCte<Body, ST>
+where
+    Body: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+        out.push_sql("WITH ");
+        out.push_identifier(self.cte.name)?;
+        out.push_sql(" AS (");
+        self.cte.body.walk_ast(out.reborrow())?;
+        out.push_sql(") ");
+        out.push_sql(&self.sql);
+        Ok(())
+    }
+}
+
+impl<Body, ST, Conn> RunQueryDsl<Conn> for WithSoftCte<Body, ST> {}
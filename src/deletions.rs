@@ -0,0 +1,194 @@
+//! Soft deletion recorded in a central `deletions` table, shared by all the tables, rather than in
+//! a flag of each table.
+//!
+//! The deletions table has a `table_name` text column and a `row_id` column of the type of the
+//! primary keys, each soft-deleted row having a row in it. A table declared with the `recorded`
+//! form of [`soft_delete!`](crate::soft_delete) is soft-deleted while a row of the deletions table
+//! names it, the soft scopes becoming a `NOT EXISTS` anti-join against it:
+//!
+//! ```rust,ignore
+//! table! {
+//!     deletions (table_name, row_id) {
+//!         table_name -> Text,
+//!         row_id -> Integer,
+//!     }
+//! }
+//!
+//! soft_delete!(user::table => recorded(deletions::table));
+//!
+//! soft_delete_recorded(user::table, id, &conn)?;
+//! let alive = user::table.soft_deleted().load::<User>(&conn)?;
+//! ```
+//!
+//! As the tables do not change, their rows are soft-deleted and restored with
+//! [`soft_delete_recorded`] and [`restore_recorded`], which run the [`hooks`](crate::hooks) as
+//! [`SoftWriteDsl`](crate::write::SoftWriteDsl) does. The tables do not implement
+//! [`SoftDeleteWrite`](crate::write::SoftDeleteWrite), so the [`write`](crate::write)
+//! statements, which would not record the deletions, are rejected:
+//!
+//! ```compile_fail
+//! # #[macro_use]
+//! # extern crate diesel;
+//! # use diesel::QueryDsl;
+//! # use diesel_softdelete::{soft_delete, write};
+//! table! {
+//!     deletions (table_name, row_id) {
+//!         table_name -> Text,
+//!         row_id -> Integer,
+//!     }
+//! }
+//!
+//! table! {
+//!     user (id) {
+//!         id -> Integer,
+//!     }
+//! }
+//!
+//! soft_delete!(user::table => recorded(deletions::table));
+//!
+//! # fn main() {
+//! write::soft_delete(user::table.find(1));
+//! # }
+//! ```
+
+use diesel::{
+    backend::Backend,
+    dsl::SqlTypeOf,
+    expression::{AppearsOnTable, NonAggregate, SelectableExpression},
+    query_builder::{nodes::Identifier, AstPass, QueryBuilder, QueryFragment, QueryId},
+    serialize::ToSql,
+    sql_types::{Bool, HasSqlType},
+    Expression, QueryResult, Table,
+};
+
+use crate::{
+    hooks::{Action, HookConnection},
+    trace::{self, Target},
+    SoftDelete,
+};
+
+/// The deleted condition of the rows of a table recorded in the `D` deletions table, i.e. the
+/// existence of a row naming the `C` primary key of the row.
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedDeletion<D, C> {
+    deletions: D,
+    table: &'static str,
+    primary_key: C,
+}
+
+/// The deleted condition of the rows of `table` recorded in the `deletions` table.
+pub fn recorded_deletion<D, T>(deletions: D, table: T) -> RecordedDeletion<D, T::PrimaryKey>
+where
+    D: Table,
+    T: Table<FromClause = Identifier<'static>>,
+{
+    RecordedDeletion { deletions, table: table.from_clause().0, primary_key: table.primary_key() }
+}
+
+impl<D, C> Expression for RecordedDeletion<D, C> {
+    type SqlType = Bool;
+}
+
+impl<D, C> NonAggregate for RecordedDeletion<D, C> {}
+
+impl<D, C: AppearsOnTable<QS>, QS> AppearsOnTable<QS> for RecordedDeletion<D, C> {}
+
+impl<D, C: SelectableExpression<QS>, QS> SelectableExpression<QS> for RecordedDeletion<D, C> {}
+
+impl<D, C> QueryId for RecordedDeletion<D, C> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<D, C, DB> QueryFragment<DB> for RecordedDeletion<D, C>
+where
+    D: Table,
+    D::FromClause: QueryFragment<DB>,
+    C: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.push_sql("EXISTS (SELECT 1 FROM ");
+        self.deletions.from_clause().walk_ast(out.reborrow())?;
+        out.push_sql(" WHERE ");
+        self.deletions.from_clause().walk_ast(out.reborrow())?;
+        out.push_sql(".");
+        out.push_identifier("table_name")?;
+        out.push_sql(" = '");
+        out.push_sql(&self.table.replace('\'', "''"));
+        out.push_sql("' AND ");
+        self.deletions.from_clause().walk_ast(out.reborrow())?;
+        out.push_sql(".");
+        out.push_identifier("row_id")?;
+        out.push_sql(" = ");
+        self.primary_key.walk_ast(out.reborrow())?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+/// Soft-delete the alive row `id` of `table` by recording it in its deletions table, returning the
+/// number of rows soft-deleted.
+///
+/// The hooks are run with the `row_id` of the recorded row.
+pub fn soft_delete_recorded<T, D, PK, Conn>(table: T, id: PK, conn: &Conn) -> QueryResult<usize>
+where
+    T: Table<FromClause = Identifier<'static>>
+        + SoftDelete<Deleted = RecordedDeletion<D, T::PrimaryKey>>,
+    T::PrimaryKey: QueryFragment<Conn::Backend>,
+    D: Table,
+    D::FromClause: QueryFragment<Conn::Backend>,
+    PK: ToSql<SqlTypeOf<T::PrimaryKey>, Conn::Backend>,
+    SqlTypeOf<T::PrimaryKey>: QueryId,
+    Conn: HookConnection,
+    Conn::Backend: HasSqlType<SqlTypeOf<T::PrimaryKey>>,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+{
+    let deleted = table.deleted_col();
+    let mut sql = <Conn::Backend as Backend>::QueryBuilder::default();
+    sql.push_sql("INSERT INTO ");
+    deleted.deletions.from_clause().to_sql(&mut sql)?;
+    sql.push_sql(" (table_name, row_id) SELECT '");
+    sql.push_sql(&deleted.table.replace('\'', "''"));
+    sql.push_sql("', ");
+    table.primary_key().to_sql(&mut sql)?;
+    sql.push_sql(" FROM ");
+    sql.push_identifier(deleted.table)?;
+    sql.push_sql(" WHERE ");
+    table.primary_key().to_sql(&mut sql)?;
+    sql.push_sql(" = ");
+    sql.push_bind_param();
+    sql.push_sql(" AND NOT (");
+    deleted.to_sql(&mut sql)?;
+    sql.push_sql(")");
+    let insert = diesel::sql_query(sql.finish()).bind::<SqlTypeOf<T::PrimaryKey>, _>(id);
+    trace::write(Action::SoftDelete, Target { name: deleted.table, key: "row_id" }, insert, conn)
+}
+
+/// Restore the soft-deleted row `id` of `table` by removing it from its deletions table,
+/// returning the number of rows restored.
+///
+/// The hooks are run with the `row_id` of the removed row.
+pub fn restore_recorded<T, D, PK, Conn>(table: T, id: PK, conn: &Conn) -> QueryResult<usize>
+where
+    T: Table<FromClause = Identifier<'static>>
+        + SoftDelete<Deleted = RecordedDeletion<D, T::PrimaryKey>>,
+    D: Table,
+    D::FromClause: QueryFragment<Conn::Backend>,
+    PK: ToSql<SqlTypeOf<T::PrimaryKey>, Conn::Backend>,
+    SqlTypeOf<T::PrimaryKey>: QueryId,
+    Conn: HookConnection,
+    Conn::Backend: HasSqlType<SqlTypeOf<T::PrimaryKey>>,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+{
+    let deleted = table.deleted_col();
+    let mut sql = <Conn::Backend as Backend>::QueryBuilder::default();
+    sql.push_sql("DELETE FROM ");
+    deleted.deletions.from_clause().to_sql(&mut sql)?;
+    sql.push_sql(" WHERE table_name = '");
+    sql.push_sql(&deleted.table.replace('\'', "''"));
+    sql.push_sql("' AND row_id = ");
+    sql.push_bind_param();
+    let delete = diesel::sql_query(sql.finish()).bind::<SqlTypeOf<T::PrimaryKey>, _>(id);
+    trace::write(Action::Restore, Target { name: deleted.table, key: "row_id" }, delete, conn)
+}
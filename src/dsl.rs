@@ -0,0 +1,179 @@
+//! Helper types and functions naming the soft queries, in the fashion of [`diesel::dsl`].
+//!
+//! Those let function signatures and reusable query builders name the type of a soft query,
+//! e.g. a function returning `SoftFind<user::table, i32>` rather than the expanded filter:
+//!
+//! ```rust,ignore
+//! fn find_user(id: i32) -> SoftFind<user::table, i32> {
+//!     user::table.soft_find(id)
+//! }
+//!
+//! struct Feed {
+//!     query: SoftLeftJoin<user::table, post::table>,
+//! }
+//! ```
+//!
+//! Each soft method has a type named after it, e.g. `SoftInnerJoin` for `soft_inner_join`. The
+//! module is also exported as [`helper_types`](crate::helper_types), as Diesel does.
+
+use diesel::{
+    dsl::{Filter, IntoBoxed, Select},
+    helper_types::{avg, max, min, sum},
+    query_dsl::methods::FilterDsl,
+    query_source::joins::{Inner, LeftOuter},
+};
+
+#[cfg(feature = "postgres")]
+pub use crate::sample::SoftSample;
+use crate::{
+    combination::SoftCombination,
+    methods::{
+        AsOfDsl, OnlyDeletedDsl, SoftDeleteDsl, SoftFilterDsl, SoftFindDsl, SoftFindManyDsl,
+        SoftOrFilterDsl, TrashedBetweenDsl,
+    },
+    query_dsl::{FlaggedLeftJoin, SoftJoin, SoftScopeAllDsl, Through, ThroughWith},
+    scope::{ScopeDsl, ScopedJoin},
+    SoftDelete, SoftDeleteHistory,
+};
+pub use crate::{
+    methods::SoftExists,
+    query_dsl::{SoftAntiJoin, SoftJoinDyn, SoftSemiJoin},
+};
+
+/// The type returned by [`soft_deleted`](SoftDeleteDsl::soft_deleted) and [`alive`].
+pub type SoftAll<T> = <T as SoftDeleteDsl>::Output;
+
+/// The type returned by [`soft_find`](SoftFindDsl::soft_find).
+pub type SoftFind<T, PK> = <T as SoftFindDsl<PK>>::Output;
+
+/// The type returned by [`soft_find_many`](SoftFindManyDsl::soft_find_many).
+pub type SoftFindMany<T, I> = <T as SoftFindManyDsl<I>>::Output;
+
+/// The type returned by [`soft_find_any`](crate::methods::SoftFindAnyDsl::soft_find_any).
+#[cfg(feature = "postgres")]
+pub type SoftFindAny<T, I> = <T as crate::methods::SoftFindAnyDsl<I>>::Output;
+
+/// The type returned by [`soft_filter`](SoftFilterDsl::soft_filter).
+pub type SoftFilter<T, Predicate> = <T as SoftFilterDsl<Predicate>>::Output;
+
+/// The type returned by [`soft_or_filter`](SoftOrFilterDsl::soft_or_filter).
+pub type SoftOrFilter<T, Predicate> = <T as SoftOrFilterDsl<Predicate>>::Output;
+
+/// The type returned by [`only_deleted`](OnlyDeletedDsl::only_deleted).
+pub type OnlyDeleted<T> = <T as OnlyDeletedDsl>::Output;
+
+/// The type returned by [`trashed`].
+pub type Trashed<T> = Filter<T, <T as SoftDelete>::Deleted>;
+
+/// The type returned by [`soft_deleted_boxed`](crate::methods::SoftBoxedDsl::soft_deleted_boxed).
+pub type SoftDeletedBoxed<'a, T, DB> = IntoBoxed<'a, SoftAll<T>, DB>;
+
+/// The type returned by [`soft_find_boxed`](crate::methods::SoftBoxedDsl::soft_find_boxed).
+pub type SoftFindBoxed<'a, T, PK, DB> = IntoBoxed<'a, SoftFind<T, PK>, DB>;
+
+/// The type returned by [`soft_filter_boxed`](crate::methods::SoftBoxedDsl::soft_filter_boxed).
+pub type SoftFilterBoxed<'a, T, Predicate, DB> = IntoBoxed<'a, SoftFilter<T, Predicate>, DB>;
+
+/// The type returned by [`soft_scoped_boxed`](crate::methods::SoftBoxedDsl::soft_scoped_boxed).
+pub type SoftScopedBoxed<'a, T, DB> = IntoBoxed<'a, T, DB>;
+
+/// The type returned by [`soft_sum`](crate::methods::SoftAggregateDsl::soft_sum).
+pub type SoftSum<T, C> = Select<SoftAll<T>, sum<C>>;
+
+/// The type returned by [`soft_avg`](crate::methods::SoftAggregateDsl::soft_avg).
+pub type SoftAvg<T, C> = Select<SoftAll<T>, avg<C>>;
+
+/// The type returned by [`soft_max`](crate::methods::SoftAggregateDsl::soft_max).
+pub type SoftMax<T, C> = Select<SoftAll<T>, max<C>>;
+
+/// The type returned by [`soft_min`](crate::methods::SoftAggregateDsl::soft_min).
+pub type SoftMin<T, C> = Select<SoftAll<T>, min<C>>;
+
+/// The type returned by [`as_of`](AsOfDsl::as_of).
+pub type AsOf<T, Ts> = <T as AsOfDsl<Ts>>::Output;
+
+/// The type returned by [`trashed_between`](TrashedBetweenDsl::trashed_between).
+pub type TrashedBetween<T, Ts> = <T as TrashedBetweenDsl<Ts>>::Output;
+
+/// The type returned by [`oldest_trash`](crate::methods::OldestTrashDsl::oldest_trash).
+pub type OldestTrash<T> = Select<T, min<<T as SoftDeleteHistory>::DeletedAt>>;
+
+/// The type returned by [`soft_search`](crate::search::SoftSearchDsl::soft_search).
+#[cfg(feature = "postgres")]
+pub type SoftSearch<T, V, Q> = SoftFilter<T, crate::search::TsMatches<V, Q>>;
+
+/// The type returned by [`soft_inner_join`](crate::query_dsl::SoftJoinDsl::soft_inner_join).
+pub type SoftInnerJoin<Lhs, Rhs> = <Lhs as SoftJoin<Rhs, Inner>>::Output;
+
+/// The type returned by [`soft_left_join`](crate::query_dsl::SoftJoinDsl::soft_left_join).
+pub type SoftLeftJoin<Lhs, Rhs> = <Lhs as SoftJoin<Rhs, LeftOuter>>::Output;
+
+/// The type returned by
+/// [`soft_inner_join_both`](crate::query_dsl::SoftJoinDsl::soft_inner_join_both).
+pub type SoftInnerJoinBoth<Lhs, Rhs> = SoftAll<SoftInnerJoin<Lhs, Rhs>>;
+
+/// The type returned by
+/// [`soft_left_join_both`](crate::query_dsl::SoftJoinDsl::soft_left_join_both).
+pub type SoftLeftJoinBoth<Lhs, Rhs> = SoftAll<SoftLeftJoin<Lhs, Rhs>>;
+
+/// The type returned by
+/// [`soft_inner_join_boxed`](crate::query_dsl::SoftJoinDsl::soft_inner_join_boxed).
+pub type SoftInnerJoinBoxed<'a, Lhs, Rhs, DB> = IntoBoxed<'a, SoftInnerJoin<Lhs, Rhs>, DB>;
+
+/// The type returned by
+/// [`soft_left_join_boxed`](crate::query_dsl::SoftJoinDsl::soft_left_join_boxed).
+pub type SoftLeftJoinBoxed<'a, Lhs, Rhs, DB> = IntoBoxed<'a, SoftLeftJoin<Lhs, Rhs>, DB>;
+
+/// The type returned by
+/// [`soft_left_join_flagged`](crate::query_dsl::SoftJoinDsl::soft_left_join_flagged).
+pub type SoftLeftJoinFlagged<Lhs, Rhs> = FlaggedLeftJoin<Lhs, Rhs>;
+
+/// The type returned by
+/// [`soft_through_join`](crate::query_dsl::SoftJoinDsl::soft_through_join).
+pub type SoftThroughJoin<Lhs, Pivot, Far> = Through<Lhs, Pivot, Far, Inner>;
+
+/// The type returned by
+/// [`soft_left_through_join`](crate::query_dsl::SoftJoinDsl::soft_left_through_join).
+pub type SoftLeftThroughJoin<Lhs, Pivot, Far> = Through<Lhs, Pivot, Far, LeftOuter>;
+
+/// The type returned by
+/// [`soft_through_join_with`](crate::query_dsl::SoftJoinDsl::soft_through_join_with).
+pub type SoftThroughJoinWith<Lhs, Pivot, Far, Policy> = ThroughWith<Lhs, Pivot, Far, Inner, Policy>;
+
+/// The type returned by
+/// [`soft_left_through_join_with`](crate::query_dsl::SoftJoinDsl::soft_left_through_join_with).
+pub type SoftLeftThroughJoinWith<Lhs, Pivot, Far, Policy> =
+    ThroughWith<Lhs, Pivot, Far, LeftOuter, Policy>;
+
+/// The type returned by [`soft_union`](crate::combination::SoftCombineDsl::soft_union) and the
+/// other compound queries of [`SoftCombineDsl`](crate::combination::SoftCombineDsl).
+pub type SoftCombined<Lhs, Rhs> = SoftCombination<SoftAll<Lhs>, SoftAll<Rhs>>;
+
+/// The type returned by [`soft_scope_all`](SoftScopeAllDsl::soft_scope_all).
+pub type SoftScopeAll<T> = <T as SoftScopeAllDsl>::Output;
+
+/// The type returned by [`soft_scope_all_with`](SoftScopeAllDsl::soft_scope_all_with).
+pub type SoftScopeAllWith<T, S> = <SoftScopeAll<T> as ScopeDsl<S>>::Output;
+
+/// The type returned by [`scoped`](ScopeDsl::scoped).
+pub type ScopedBy<T, S> = <T as ScopeDsl<S>>::Output;
+
+/// The type returned by [`scoped_inner_join`](crate::scope::ScopedJoinDsl::scoped_inner_join).
+pub type ScopedInnerJoin<Lhs, Rhs, S> = ScopedJoin<Lhs, Rhs, Inner, S>;
+
+/// The type returned by [`scoped_left_join`](crate::scope::ScopedJoinDsl::scoped_left_join).
+pub type ScopedLeftJoin<Lhs, Rhs, S> = ScopedJoin<Lhs, Rhs, LeftOuter, S>;
+
+/// The alive rows of `table`, the same as `table.soft_deleted()`.
+pub fn alive<T: SoftDeleteDsl>(table: T) -> SoftAll<T> {
+    table.soft_deleted()
+}
+
+/// The soft-deleted rows of `table`.
+pub fn trashed<T>(table: T) -> Trashed<T>
+where
+    T: SoftDelete + FilterDsl<<T as SoftDelete>::Deleted>,
+{
+    let deleted = table.deleted_col();
+    table.filter(deleted)
+}
@@ -0,0 +1,232 @@
+//! The right to erasure: the data of a subject, e.g. a user, is erased from all the tables holding
+//! some with a single [`Erasure::erase`] call.
+//!
+//! The erasure combines the subsystems of the tables of a [`SoftDeleteConfig`]:
+//!
+//! - the rows of the subject are soft-deleted right away with
+//!   [`soft_delete_rows`](crate::write::SoftWriteDsl::soft_delete_rows), and their personal data is
+//!   scrubbed,
+//! - they are hard-deleted by [`purge_expired`](crate::write::purge_expired) once the retention
+//!   of their table, the statutory window, has elapsed,
+//! - the soft deletions run the [`hooks`](crate::hooks), so that the erasure is recorded by an
+//!   installed audit log.
+//!
+//! ```rust,ignore
+//! let erasure = Erasure::new(&config)
+//!     .table(ErasureTable::new(user::table, user::id).scrub(user::email))
+//!     .table(ErasureTable::new(address::table, address::user_id).scrub_with(address::line, ""));
+//! let _audit = AuditLog::new().install::<PgConnection>(Context::new().reason("erasure request"));
+//! erasure.erase(user_id, &conn)?;
+//! ```
+
+use std::{fmt, rc::Rc};
+
+use diesel::{
+    backend::Backend,
+    dsl::{Eq, Filter},
+    expression::AsExpression,
+    query_builder::{nodes::Identifier, QueryBuilder, QueryFragment, QueryId},
+    query_dsl::methods::FilterDsl,
+    serialize::ToSql,
+    sql_types::HasSqlType,
+    Column, Connection, ExpressionMethods, QueryResult, RunQueryDsl, Table,
+};
+
+use crate::{
+    archive::quoted_identifier,
+    config::{SoftDeleteConfig, SoftDeleteTable},
+    hooks::HookConnection,
+    migration::MigrationBackend,
+    write::{SoftDeleteTarget, SoftWriteDsl},
+};
+
+/// The erasure of the rows of a subject from a table: their soft deletion, then the execution of
+/// the bound scrubbing statement, if any.
+type Erase<Conn, S> = Rc<dyn Fn(S, Option<&str>, &Conn) -> QueryResult<usize>>;
+
+/// An erased table, along with its configuration.
+type Registered<'a, Conn, S> =
+    (&'a SoftDeleteTable<<Conn as Connection>::Backend>, ErasureTable<Conn, S>);
+
+/// A table holding data of the subjects of an [`Erasure`], identified by a value of type `S`, and
+/// how to erase it.
+pub struct ErasureTable<Conn, S> {
+    table: &'static str,
+    subject: &'static str,
+    /// The scrubbed columns, and the SQL of their new value.
+    scrubbed: Vec<(&'static str, String)>,
+    erase: Erase<Conn, S>,
+}
+
+impl<Conn, S> Clone for ErasureTable<Conn, S> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table,
+            subject: self.subject,
+            scrubbed: self.scrubbed.clone(),
+            erase: self.erase.clone(),
+        }
+    }
+}
+
+impl<Conn, S> fmt::Debug for ErasureTable<Conn, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ErasureTable")
+            .field("table", &self.table)
+            .field("subject", &self.subject)
+            .field("scrubbed", &self.scrubbed)
+            .finish()
+    }
+}
+
+impl<Conn, S> ErasureTable<Conn, S>
+where
+    Conn: HookConnection,
+    S: Clone + 'static,
+{
+    /// The rows of `table` whose `subject` column is the subject.
+    pub fn new<T, C>(table: T, subject: C) -> Self
+    where
+        T: Table<FromClause = Identifier<'static>> + FilterDsl<Eq<C, S>> + Copy + 'static,
+        T::PrimaryKey: Column,
+        C: Column<Table = T> + ExpressionMethods + Copy + 'static,
+        S: AsExpression<C::SqlType> + ToSql<C::SqlType, Conn::Backend>,
+        C::SqlType: QueryId,
+        Conn::Backend: HasSqlType<C::SqlType>,
+        Filter<T, Eq<C, S>>: SoftDeleteTarget + diesel::associations::HasTable<Table = T>,
+        <Filter<T, Eq<C, S>> as SoftDeleteTarget>::SoftDelete:
+            QueryFragment<Conn::Backend> + QueryId,
+    {
+        let erase = move |id: S, scrub: Option<&str>, conn: &Conn| {
+            let deleted = table.filter(subject.eq(id.clone())).soft_delete_rows(conn)?;
+            if let Some(scrub) = scrub {
+                diesel::sql_query(scrub).bind::<C::SqlType, _>(id).execute(conn)?;
+            }
+            Ok(deleted)
+        };
+        Self {
+            table: table.from_clause().0,
+            subject: C::NAME,
+            scrubbed: Vec::new(),
+            erase: Rc::new(erase),
+        }
+    }
+}
+
+impl<Conn, S> ErasureTable<Conn, S> {
+    /// Scrub the `column` by setting it to `NULL`.
+    pub fn scrub<C: Column>(mut self, _column: C) -> Self {
+        self.scrubbed.push((C::NAME, "NULL".to_owned()));
+        self
+    }
+
+    /// Scrub the `column` by setting it to the text `value`.
+    pub fn scrub_with<C: Column>(mut self, _column: C, value: &str) -> Self {
+        self.scrubbed.push((C::NAME, format!("'{}'", value.replace('\'', "''"))));
+        self
+    }
+}
+
+/// The erasure of the data of the subjects identified by a value of type `S`, from the tables of
+/// a [`SoftDeleteConfig`].
+pub struct Erasure<'a, Conn: Connection, S> {
+    config: &'a SoftDeleteConfig<Conn::Backend>,
+    tables: Vec<Registered<'a, Conn, S>>,
+}
+
+impl<Conn: Connection, S> Clone for Erasure<'_, Conn, S> {
+    fn clone(&self) -> Self {
+        Self { config: self.config, tables: self.tables.clone() }
+    }
+}
+
+impl<Conn: Connection, S> fmt::Debug for Erasure<'_, Conn, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tables = self.tables.iter().map(|(_, table)| table).collect::<Vec<_>>();
+        f.debug_struct("Erasure").field("tables", &tables).finish()
+    }
+}
+
+impl<'a, Conn: Connection, S> Erasure<'a, Conn, S> {
+    pub fn new(config: &'a SoftDeleteConfig<Conn::Backend>) -> Self {
+        Self { config, tables: Vec::new() }
+    }
+
+    /// Erase the data of the subjects from `table`.
+    ///
+    /// # Panics
+    ///
+    /// If the table is not registered in the configuration with a retention and a deletion
+    /// timestamp, as its erased rows would never be purged.
+    pub fn table(mut self, table: ErasureTable<Conn, S>) -> Self {
+        let config = self
+            .config
+            .get(table.table)
+            .unwrap_or_else(|| panic!("table {} is not registered", table.table));
+        assert!(
+            config.retention().is_some() && config.deleted_at().is_some(),
+            "table {} must have a retention and a deletion timestamp to be purged",
+            table.table,
+        );
+        self.tables.push((config, table));
+        self
+    }
+}
+
+impl<Conn, S> Erasure<'_, Conn, S>
+where
+    Conn: Connection,
+    Conn::Backend: MigrationBackend,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+    S: Clone,
+{
+    /// Erase the data of `subject`, returning the number of rows soft-deleted per table, in
+    /// registration order.
+    ///
+    /// The rows are soft-deleted and scrubbed, the already soft-deleted ones being scrubbed as
+    /// well, in one transaction. The rows of the tables whose deletion timestamp is not their
+    /// soft-delete column are stamped as well, unless they already are, for their purge.
+    pub fn erase(&self, subject: S, conn: &Conn) -> QueryResult<Vec<(&'static str, usize)>> {
+        conn.transaction(|| {
+            let mut erased = Vec::new();
+            for (config, table) in &self.tables {
+                let scrub = scrub_sql(config, table)?;
+                let deleted = (table.erase)(subject.clone(), scrub.as_deref(), conn)?;
+                erased.push((config.name(), deleted));
+            }
+            Ok(erased)
+        })
+    }
+}
+
+/// The `UPDATE` statement scrubbing the rows of the bound subject from `table`, and stamping their
+/// deletion timestamp if it is not the soft-delete column, if there is anything to set.
+fn scrub_sql<DB, Conn, S>(
+    config: &SoftDeleteTable<DB>,
+    table: &ErasureTable<Conn, S>,
+) -> QueryResult<Option<String>>
+where
+    DB: Backend,
+    DB::QueryBuilder: Default,
+{
+    let mut changes = Vec::new();
+    for (column, value) in &table.scrubbed {
+        changes.push(format!("{} = {}", quoted_identifier::<DB>(column)?, value));
+    }
+    match config.deleted_at() {
+        Some(deleted_at) if deleted_at != config.column() => {
+            let deleted_at = quoted_identifier::<DB>(deleted_at)?;
+            changes.push(format!("{0} = COALESCE({0}, CURRENT_TIMESTAMP)", deleted_at));
+        }
+        _ => {}
+    }
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    let mut out = DB::QueryBuilder::default();
+    out.push_sql(&format!("UPDATE {} SET {} WHERE ", config.quoted_name()?, changes.join(", ")));
+    out.push_identifier(table.subject)?;
+    out.push_sql(" = ");
+    out.push_bind_param();
+    Ok(Some(out.finish()))
+}
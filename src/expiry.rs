@@ -0,0 +1,113 @@
+//! Rows expiring at a given time, soft-deleted by time rather than by an explicit action.
+//!
+//! A table declared with the `expires` variant of the flag form of
+//! [`soft_delete!`](crate::soft_delete) has a nullable `expires_at` timestamp column besides its
+//! flag, e.g. sessions, invitations or cache entries with a time to live. Its rows are deleted for
+//! the soft methods once their flag is set, or once their expiry time has passed:
+//!
+//! ```rust,ignore
+//! soft_delete!(session::table => (session::deleted, expires = session::expires_at));
+//!
+//! let alive = session::table.soft_find(id).first::<Session>(&conn).optional()?;
+//! // ... WHERE NOT (("session"."deleted" OR "session"."expires_at" IS NOT NULL
+//! //     AND "session"."expires_at" <= CURRENT_TIMESTAMP)) AND "session"."id" = $1
+//! ```
+//!
+//! The expired rows are listed with [`expired`], and removed for good with [`purge_past_expiry`].
+//!
+//! The write statements only touch the flag: [`restore`](crate::write::restore) does not extend
+//! the expiry time of the rows, and only restores the flagged rows which have not expired. An
+//! expired row is brought back by moving its `expires_at` column forward, then restoring it if
+//! flagged.
+
+use diesel::{
+    associations::HasTable,
+    dsl::{not, And, Filter, Or, SqlTypeOf},
+    helper_types::not as Not,
+    query_builder::{DeleteStatement, IntoUpdateTarget},
+    query_dsl::methods::FilterDsl,
+    sql_types::{Bool, SingleValue},
+    BoolExpressionMethods, Column, Expression,
+};
+
+use crate::{
+    write::{scheduled_deleted, ScheduledDeleted},
+    SoftDelete,
+};
+
+/// A soft-deletable table whose rows expire.
+///
+/// This is implemented by the `expires` variant of [`soft_delete!`](crate::soft_delete).
+pub trait Expiring: SoftDelete + diesel::Table {
+    /// The nullable timestamp column holding the expiry time of the rows.
+    type ExpiresAt: Column<Table = Self> + Copy;
+
+    fn expires_at_col(&self) -> Self::ExpiresAt;
+}
+
+/// The deleted flag of a table whose rows expire: its flag, or its expiry time having passed.
+pub type ExpiringDeleted<D, E> = Or<D, ScheduledDeleted<E>>;
+
+/// Build the deleted flag of a table whose rows expire, i.e. `(deleted OR expires_at IS NOT NULL
+/// AND expires_at <= CURRENT_TIMESTAMP)`.
+pub fn expiring_deleted<D, E>(deleted: D, expires_at: E) -> ExpiringDeleted<D, E>
+where
+    D: Expression<SqlType = Bool>,
+    E: Column + Copy,
+    E::SqlType: SingleValue,
+{
+    deleted.or(scheduled_deleted(expires_at))
+}
+
+/// The rows a restoration brings back on a table whose rows expire: the flagged rows which have
+/// not expired.
+pub type ExpiringRestorable<D, E> = And<D, Not<ScheduledDeleted<E>>>;
+
+/// Build the condition of the rows a restoration brings back on a table whose rows expire, i.e.
+/// `deleted AND NOT (expires_at IS NOT NULL AND expires_at <= CURRENT_TIMESTAMP)`.
+pub fn expiring_restorable<D, E>(deleted: D, expires_at: E) -> ExpiringRestorable<D, E>
+where
+    D: Expression<SqlType = Bool>,
+    E: Column + Copy,
+    E::SqlType: SingleValue,
+{
+    deleted.and(not(scheduled_deleted(expires_at)))
+}
+
+type ExpiresAt<T> = <<T as HasTable>::Table as Expiring>::ExpiresAt;
+
+/// The type returned by [`expired`].
+pub type Expired<T> = Filter<T, ScheduledDeleted<ExpiresAt<T>>>;
+/// The type returned by [`purge_past_expiry`].
+pub type PurgePastExpiry<T> = Filter<
+    DeleteStatement<<T as HasTable>::Table, <T as IntoUpdateTarget>::WhereClause>,
+    ScheduledDeleted<ExpiresAt<T>>,
+>;
+
+/// The rows of `source` whose expiry time has passed, whatever their flag.
+pub fn expired<T>(source: T) -> Expired<T>
+where
+    T: HasTable + FilterDsl<ScheduledDeleted<ExpiresAt<T>>>,
+    T::Table: Expiring,
+    SqlTypeOf<ExpiresAt<T>>: SingleValue,
+{
+    source.filter(scheduled_deleted(T::table().expires_at_col()))
+}
+
+/// Creates a `DELETE` statement removing the expired rows of `target`, whatever their flag.
+///
+/// As [`write::purge`](crate::write::purge), this does not need an
+/// [`AllowHardDelete`](crate::write::AllowHardDelete) token: the rows were already deleted from the
+/// application's point of view. Unlike [`write::purge_expired`](crate::write::purge_expired), which
+/// purges the trash past the retention of the configured tables, this reads the expiry time of
+/// the rows.
+pub fn purge_past_expiry<T>(target: T) -> PurgePastExpiry<T>
+where
+    T: IntoUpdateTarget,
+    T::Table: Expiring,
+    SqlTypeOf<ExpiresAt<T>>: SingleValue,
+    DeleteStatement<T::Table, T::WhereClause>: FilterDsl<ScheduledDeleted<ExpiresAt<T>>>,
+{
+    let expires_at = T::table().expires_at_col();
+    diesel::delete(target).filter(scheduled_deleted(expires_at))
+}
@@ -0,0 +1,95 @@
+//! Query plans of soft-scoped queries.
+//!
+//! [`explain_soft`] runs `EXPLAIN` on a query and returns the plan, e.g. to check that the partial
+//! indexes declared with `WHERE NOT deleted` are used by the alive condition. It is available on
+//! PostgreSQL and SQLite, with the `postgres` and `sqlite` features.
+
+use std::marker::PhantomData;
+
+use diesel::{
+    backend::Backend,
+    query_builder::{AsQuery, AstPass, Query, QueryFragment, QueryId},
+    Connection, QueryResult, RunQueryDsl,
+};
+
+use crate::scoped::SoftScoped;
+
+/// A query prefixed with `EXPLAIN`, whose rows describe the plan of the query.
+#[derive(Debug, Clone, Copy)]
+pub struct Explain<Q, ST> {
+    query: Q,
+    prefix: &'static str,
+    _row: PhantomData<ST>,
+}
+
+impl<Q, ST> Query for Explain<Q, ST> {
+    type SqlType = ST;
+}
+
+impl<Q, ST> QueryId for Explain<Q, ST> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<Q: QueryFragment<DB>, ST, DB: Backend> QueryFragment<DB> for Explain<Q, ST> {
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.push_sql(self.prefix);
+        self.query.walk_ast(out.reborrow())
+    }
+}
+
+impl<Q, ST, Conn> RunQueryDsl<Conn> for Explain<Q, ST> {}
+
+/// A backend whose query plans can be explained.
+pub trait ExplainBackend: Backend + Sized {
+    /// Run `EXPLAIN` on `query`, returning the plan with one line per node.
+    fn explain<Q, Conn>(query: Q, analyze: bool, conn: &Conn) -> QueryResult<String>
+    where
+        Q: QueryFragment<Self>,
+        Conn: Connection<Backend = Self>;
+}
+
+#[cfg(feature = "postgres")]
+impl ExplainBackend for diesel::pg::Pg {
+    fn explain<Q, Conn>(query: Q, analyze: bool, conn: &Conn) -> QueryResult<String>
+    where
+        Q: QueryFragment<Self>,
+        Conn: Connection<Backend = Self>,
+    {
+        let prefix = if analyze { "EXPLAIN ANALYZE " } else { "EXPLAIN " };
+        let explain = Explain::<_, diesel::sql_types::Text> { query, prefix, _row: PhantomData };
+        Ok(explain.load::<String>(conn)?.join("\n"))
+    }
+}
+
+/// SQLite has no `EXPLAIN ANALYZE`: the plan is the one of `EXPLAIN QUERY PLAN` either way.
+#[cfg(feature = "sqlite")]
+impl ExplainBackend for diesel::sqlite::Sqlite {
+    fn explain<Q, Conn>(query: Q, _analyze: bool, conn: &Conn) -> QueryResult<String>
+    where
+        Q: QueryFragment<Self>,
+        Conn: Connection<Backend = Self>,
+    {
+        use diesel::sql_types::{Integer, Text};
+
+        let prefix = "EXPLAIN QUERY PLAN ";
+        let explain =
+            Explain::<_, (Integer, Integer, Integer, Text)> { query, prefix, _row: PhantomData };
+        let nodes = explain.load::<(i32, i32, i32, String)>(conn)?;
+        Ok(nodes.into_iter().map(|(_, _, _, detail)| detail).collect::<Vec<_>>().join("\n"))
+    }
+}
+
+/// Run `EXPLAIN` on a soft-scoped `query`, or `EXPLAIN ANALYZE` if `analyze` is set and the
+/// backend supports it, and return the plan.
+///
+/// With `analyze`, the query is actually run.
+pub fn explain_soft<Q, P, Conn>(query: Q, analyze: bool, conn: &Conn) -> QueryResult<String>
+where
+    Q: AsQuery + SoftScoped<P>,
+    Q::Query: QueryFragment<Conn::Backend>,
+    Conn: Connection,
+    Conn::Backend: ExplainBackend,
+{
+    <Conn::Backend as ExplainBackend>::explain(query.as_query(), analyze, conn)
+}
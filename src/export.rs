@@ -0,0 +1,426 @@
+//! Export of soft-deleted rows, for ETL pipelines and data-warehouse sync.
+//!
+//! Rows are written one record at a time, either as JSON lines or as CSV. Either the full rows can
+//! be exported with [`export_trashed`], or only minimal [`Tombstone`]s with
+//! [`export_tombstones`]. Both load the rows in batches of [`BATCH_SIZE`], in ascending order of
+//! primary key, so that a large trash is not held in memory at once. The primary key must be a
+//! single column.
+//!
+//! The trashed rows of a table can also be copied between databases, e.g. from production to a
+//! staging database to investigate a support case: [`export_trash`] bundles them in a
+//! [`TrashExport`] along with the version of the schema, and [`import_trash`] inserts them back
+//! as they were, deleted flags and deletion timestamps included.
+
+use std::{fmt, io::Write};
+
+use diesel::{
+    associations::HasTable,
+    backend::Backend,
+    dsl::{EqAny, Filter, Limit, Order, Select, SqlTypeOf},
+    expression::{
+        array_comparison::AsInExpression, AppearsOnTable, NonAggregate, SelectableExpression,
+    },
+    query_builder::{nodes::Identifier, AstPass, InsertStatement, QueryFragment, QueryId},
+    query_dsl::{
+        methods::{ExecuteDsl, FilterDsl, LimitDsl, OrderDsl, SelectDsl},
+        LoadQuery,
+    },
+    serialize::ToSql,
+    sql_types::{Bool, HasSqlType},
+    Connection, Expression, ExpressionMethods, Insertable, QueryResult, RunQueryDsl, Table,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::SoftDelete;
+
+/// The number of rows [`export_trashed`] and [`export_tombstones`] load at a time.
+pub const BATCH_SIZE: i64 = 500;
+
+/// The output format of a [`TombstoneWriter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line.
+    JsonLines,
+    /// CSV, with a header row derived from the first record.
+    Csv,
+}
+
+/// A minimal record of a soft-deleted row: the table it lives in and its primary key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Tombstone<PK> {
+    pub table: &'static str,
+    pub id: PK,
+}
+
+/// An error that occurred while exporting rows.
+#[derive(Debug)]
+pub enum Error {
+    Query(diesel::result::Error),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Csv(csv::Error),
+    /// The imported [`TrashExport`] is not one of the table.
+    Table {
+        expected: &'static str,
+        found: String,
+    },
+    /// The imported [`TrashExport`] comes from another version of the schema, or of its format.
+    Version {
+        expected: String,
+        found: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Query(e) => write!(f, "failed to load rows: {}", e),
+            Self::Io(e) => write!(f, "failed to write rows: {}", e),
+            Self::Json(e) => write!(f, "failed to serialize row as JSON: {}", e),
+            Self::Csv(e) => write!(f, "failed to serialize row as CSV: {}", e),
+            Self::Table { expected, found } => {
+                write!(f, "expected rows of table `{}`, found `{}`", expected, found)
+            }
+            Self::Version { expected, found } => {
+                write!(f, "expected rows of version {}, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Query(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::Csv(e) => Some(e),
+            Self::Table { .. } | Self::Version { .. } => None,
+        }
+    }
+}
+
+impl From<diesel::result::Error> for Error {
+    fn from(e: diesel::result::Error) -> Self {
+        Self::Query(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Self::Csv(e)
+    }
+}
+
+/// Writes serializable rows to an underlying writer, one record at a time.
+pub struct TombstoneWriter<W: Write> {
+    inner: Inner<W>,
+}
+
+enum Inner<W: Write> {
+    JsonLines(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+impl<W: Write> TombstoneWriter<W> {
+    pub fn new(writer: W, format: Format) -> Self {
+        let inner = match format {
+            Format::JsonLines => Inner::JsonLines(writer),
+            Format::Csv => Inner::Csv(Box::new(csv::Writer::from_writer(writer))),
+        };
+        Self { inner }
+    }
+
+    /// Write a single record.
+    pub fn write<T: Serialize>(&mut self, row: &T) -> Result<(), Error> {
+        match &mut self.inner {
+            Inner::JsonLines(w) => {
+                serde_json::to_writer(&mut *w, row)?;
+                w.write_all(b"\n")?;
+            }
+            Inner::Csv(w) => w.serialize(row)?,
+        }
+        Ok(())
+    }
+
+    /// Flush the pending records and return the underlying writer.
+    pub fn into_inner(self) -> Result<W, Error> {
+        match self.inner {
+            Inner::JsonLines(mut w) => {
+                w.flush()?;
+                Ok(w)
+            }
+            Inner::Csv(w) => w.into_inner().map_err(|e| Error::Io(e.into_error())),
+        }
+    }
+}
+
+/// The condition of the soft-deleted rows of a table following the primary key `last` of the
+/// previous batch, if any, as in `post.deleted AND post.id > 42`.
+#[derive(Debug, Clone)]
+pub struct TrashedAfter<T, PK> {
+    table: T,
+    last: Option<PK>,
+}
+
+impl<T, PK> Expression for TrashedAfter<T, PK> {
+    type SqlType = Bool;
+}
+
+impl<T, PK> NonAggregate for TrashedAfter<T, PK> {}
+
+impl<T, PK, QS> AppearsOnTable<QS> for TrashedAfter<T, PK>
+where
+    T: Table + SoftDelete,
+    T::PrimaryKey: AppearsOnTable<QS>,
+    T::Deleted: AppearsOnTable<QS>,
+{
+}
+
+impl<T, PK, QS> SelectableExpression<QS> for TrashedAfter<T, PK>
+where
+    T: Table + SoftDelete,
+    T::PrimaryKey: SelectableExpression<QS>,
+    T::Deleted: SelectableExpression<QS>,
+{
+}
+
+impl<T, PK> QueryId for TrashedAfter<T, PK> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<T, PK, DB> QueryFragment<DB> for TrashedAfter<T, PK>
+where
+    T: Table + SoftDelete,
+    T::PrimaryKey: QueryFragment<DB>,
+    T::Deleted: QueryFragment<DB>,
+    PK: ToSql<SqlTypeOf<T::PrimaryKey>, DB>,
+    DB: Backend + HasSqlType<SqlTypeOf<T::PrimaryKey>>,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        self.table.deleted_col().walk_ast(out.reborrow())?;
+        if let Some(last) = &self.last {
+            out.push_sql(" AND ");
+            self.table.primary_key().walk_ast(out.reborrow())?;
+            out.push_sql(" > ");
+            out.push_bind_param::<SqlTypeOf<T::PrimaryKey>, PK>(last)?;
+        }
+        Ok(())
+    }
+}
+
+/// The query of a batch of primary keys of soft-deleted rows.
+type TrashedBatch<T, PK> = Limit<
+    Select<
+        Order<Filter<T, TrashedAfter<T, PK>>, <T as Table>::PrimaryKey>,
+        <T as Table>::PrimaryKey,
+    >,
+>;
+
+/// Call `f` with the primary keys of the soft-deleted rows of `table` in ascending order, a batch
+/// of [`BATCH_SIZE`] keys at a time.
+fn trashed_batches<T, PK, Conn, F>(table: T, conn: &Conn, mut f: F) -> Result<(), Error>
+where
+    T: Table + SoftDelete + FilterDsl<TrashedAfter<T, PK>> + Copy,
+    Filter<T, TrashedAfter<T, PK>>: OrderDsl<T::PrimaryKey>,
+    Order<Filter<T, TrashedAfter<T, PK>>, T::PrimaryKey>: SelectDsl<T::PrimaryKey>,
+    Select<Order<Filter<T, TrashedAfter<T, PK>>, T::PrimaryKey>, T::PrimaryKey>: LimitDsl,
+    TrashedBatch<T, PK>: LoadQuery<Conn, PK>,
+    PK: Clone,
+    F: FnMut(Vec<PK>) -> Result<(), Error>,
+{
+    let mut last = None;
+    loop {
+        let after = TrashedAfter { table, last: last.take() };
+        let ids = table
+            .filter(after)
+            .order(table.primary_key())
+            .select(table.primary_key())
+            .limit(BATCH_SIZE)
+            .load::<PK>(conn)?;
+        let done = (ids.len() as i64) < BATCH_SIZE;
+        last = ids.last().cloned();
+        if !ids.is_empty() {
+            f(ids)?;
+        }
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+type TrashedRows<T, PK> = Order<
+    Filter<Filter<T, <T as SoftDelete>::Deleted>, EqAny<TablePrimaryKey<T>, Vec<PK>>>,
+    TablePrimaryKey<T>,
+>;
+type TablePrimaryKey<T> = <<T as HasTable>::Table as Table>::PrimaryKey;
+
+/// Export all soft-deleted rows of `source`, returning the number of rows written.
+///
+/// The rows are loaded in batches, by primary key of the table of `source`.
+pub fn export_trashed<T, PK, U, Conn, W>(
+    source: T,
+    conn: &Conn,
+    out: &mut TombstoneWriter<W>,
+) -> Result<usize, Error>
+where
+    T: HasTable + SoftDelete + FilterDsl<<T as SoftDelete>::Deleted> + Clone,
+    T::Table: SoftDelete + FilterDsl<TrashedAfter<T::Table, PK>> + Copy,
+    Filter<T::Table, TrashedAfter<T::Table, PK>>: OrderDsl<TablePrimaryKey<T>>,
+    Order<Filter<T::Table, TrashedAfter<T::Table, PK>>, TablePrimaryKey<T>>:
+        SelectDsl<TablePrimaryKey<T>>,
+    Select<
+        Order<Filter<T::Table, TrashedAfter<T::Table, PK>>, TablePrimaryKey<T>>,
+        TablePrimaryKey<T>,
+    >: LimitDsl,
+    TrashedBatch<T::Table, PK>: LoadQuery<Conn, PK>,
+    Filter<T, T::Deleted>: FilterDsl<EqAny<TablePrimaryKey<T>, Vec<PK>>>,
+    Filter<Filter<T, T::Deleted>, EqAny<TablePrimaryKey<T>, Vec<PK>>>: OrderDsl<TablePrimaryKey<T>>,
+    TrashedRows<T, PK>: LoadQuery<Conn, U>,
+    TablePrimaryKey<T>: ExpressionMethods,
+    Vec<PK>: AsInExpression<SqlTypeOf<TablePrimaryKey<T>>>,
+    PK: Clone,
+    U: Serialize,
+    W: Write,
+{
+    let table = T::table();
+    let mut count = 0;
+    trashed_batches(table, conn, |ids| {
+        let deleted = source.deleted_col();
+        let rows = source
+            .clone()
+            .filter(deleted)
+            .filter(table.primary_key().eq_any(ids))
+            .order(table.primary_key())
+            .load::<U>(conn)?;
+        for row in &rows {
+            out.write(row)?;
+        }
+        count += rows.len();
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+/// Export a [`Tombstone`] for each soft-deleted row of `table`, returning the number of
+/// tombstones written.
+///
+/// The primary keys are loaded in batches.
+pub fn export_tombstones<T, PK, Conn, W>(
+    table: T,
+    conn: &Conn,
+    out: &mut TombstoneWriter<W>,
+) -> Result<usize, Error>
+where
+    T: Table<FromClause = Identifier<'static>> + SoftDelete + FilterDsl<TrashedAfter<T, PK>> + Copy,
+    Filter<T, TrashedAfter<T, PK>>: OrderDsl<T::PrimaryKey>,
+    Order<Filter<T, TrashedAfter<T, PK>>, T::PrimaryKey>: SelectDsl<T::PrimaryKey>,
+    Select<Order<Filter<T, TrashedAfter<T, PK>>, T::PrimaryKey>, T::PrimaryKey>: LimitDsl,
+    TrashedBatch<T, PK>: LoadQuery<Conn, PK>,
+    PK: Serialize + Clone,
+    W: Write,
+{
+    let name = table.from_clause().0;
+    let mut count = 0;
+    trashed_batches(table, conn, |ids| {
+        for id in ids {
+            out.write(&Tombstone { table: name, id })?;
+            count += 1;
+        }
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+/// The version of the format of [`TrashExport`].
+pub const TRASH_EXPORT_FORMAT: u32 = 1;
+
+/// The trashed rows of a table, exported by [`export_trash`] to be imported in another database
+/// by [`import_trash`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashExport<U> {
+    /// The version of the format of the export, [`TRASH_EXPORT_FORMAT`].
+    pub format: u32,
+    /// The version of the crate that exported the rows.
+    pub crate_version: String,
+    /// The version of the schema of the database the rows were exported from, e.g. its last
+    /// migration.
+    pub schema_version: String,
+    pub table: String,
+    pub rows: Vec<U>,
+}
+
+/// Export the soft-deleted rows of `table`, from a database at `schema_version`.
+///
+/// The rows should include all the columns of the table, so that they are imported as they were.
+pub fn export_trash<T, U, Conn>(
+    table: T,
+    schema_version: &str,
+    conn: &Conn,
+) -> Result<TrashExport<U>, Error>
+where
+    T: Table<FromClause = Identifier<'static>> + SoftDelete + FilterDsl<<T as SoftDelete>::Deleted>,
+    Filter<T, T::Deleted>: LoadQuery<Conn, U>,
+{
+    let name = table.from_clause().0;
+    let deleted = table.deleted_col();
+    Ok(TrashExport {
+        format: TRASH_EXPORT_FORMAT,
+        crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+        schema_version: schema_version.to_owned(),
+        table: name.to_owned(),
+        rows: table.filter(deleted).load(conn)?,
+    })
+}
+
+/// Insert the rows of `export` into `table`, of a database at `schema_version`, returning the
+/// number of rows inserted.
+///
+/// The export must come from the same table at the same schema version. The rows are inserted as
+/// they were exported, in one transaction.
+pub fn import_trash<T, U, Conn>(
+    table: T,
+    export: TrashExport<U>,
+    schema_version: &str,
+    conn: &Conn,
+) -> Result<usize, Error>
+where
+    T: Table<FromClause = Identifier<'static>> + Copy,
+    U: Insertable<T>,
+    InsertStatement<T, U::Values>: ExecuteDsl<Conn>,
+    Conn: Connection,
+{
+    let name = table.from_clause().0;
+    if export.table != name {
+        return Err(Error::Table { expected: name, found: export.table });
+    }
+    if export.format != TRASH_EXPORT_FORMAT {
+        let (expected, found) = (TRASH_EXPORT_FORMAT.to_string(), export.format.to_string());
+        return Err(Error::Version { expected, found });
+    }
+    if export.schema_version != schema_version {
+        let expected = schema_version.to_owned();
+        return Err(Error::Version { expected, found: export.schema_version });
+    }
+    let rows = export.rows;
+    conn.transaction(|| {
+        let mut inserted = 0;
+        for row in rows {
+            inserted += diesel::insert_into(table).values(row).execute(conn)?;
+        }
+        Ok(inserted)
+    })
+}
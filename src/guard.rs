@@ -0,0 +1,400 @@
+//! Runtime detection of queries that are not soft-scoped.
+//!
+//! The [`GuardedConnection`] wraps a connection, and checks the SQL of every query built with the
+//! query builder against a set of registered soft-deletable tables. This is meant to ease the
+//! migration of a large codebase: unscoped queries are reported instead of silently seeing
+//! trashed rows.
+
+use std::{error::Error as StdError, fmt};
+
+use diesel::{
+    backend::{Backend, UsesAnsiSavepointSyntax},
+    connection::{AnsiTransactionManager, SimpleConnection},
+    deserialize::{Queryable, QueryableByName},
+    query_builder::{nodes::Identifier, AsQuery, QueryBuilder, QueryFragment, QueryId},
+    result::{ConnectionResult, Error},
+    sql_types::HasSqlType,
+    Column, Connection, QueryResult, Table,
+};
+
+use crate::{
+    config::{DeletionKind, SoftDeleteConfig},
+    SoftDelete,
+};
+
+/// A query touching a soft-deletable table without checking its deleted flag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnscopedQuery {
+    pub table: &'static str,
+    pub sql: String,
+}
+
+impl fmt::Display for UnscopedQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "query on `{}` is not soft-scoped: {}", self.table, self.sql)
+    }
+}
+
+impl StdError for UnscopedQuery {}
+
+/// A connection reporting the queries that touch registered soft-deletable tables without
+/// checking their deleted flag.
+///
+/// By default, such queries still run, and are reported to the warning hook, which logs them with
+/// `tracing` when the feature is enabled and drops them otherwise. In [strict](Self::strict) mode,
+/// they fail instead.
+///
+/// Only `SELECT` statements are checked: a query is scoped when one of its `WHERE` or `ON` clauses
+/// checks the table's deleted flag in a condition every row has to satisfy. A check in only one
+/// branch of an `OR`, in a string literal or in a comment does not count. Raw SQL is not checked.
+///
+/// This is a safety net for migrations, not a proof: e.g. a condition comparing the flag to
+/// itself passes. Use [`SoftScoped`](crate::scoped::SoftScoped) to refuse unscoped queries at
+/// compile time.
+pub struct GuardedConnection<C> {
+    inner: C,
+    /// The registered tables, and the tokens of the check of their deleted flag.
+    tables: Vec<(&'static str, Vec<Token>)>,
+    strict: bool,
+    on_unscoped: Box<dyn Fn(&UnscopedQuery) + Send>,
+}
+
+impl<C> GuardedConnection<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner, tables: Vec::new(), strict: false, on_unscoped: Box::new(warn_unscoped) }
+    }
+
+    /// Register a soft-deletable table whose queries are checked.
+    pub fn table<T>(mut self, table: T) -> Self
+    where
+        T: Table<FromClause = Identifier<'static>> + SoftDelete,
+        T::Deleted: Column,
+    {
+        let name = table.from_clause().0;
+        self.tables.push((name, column_check(name, <T::Deleted as Column>::NAME)));
+        self
+    }
+
+    /// Fail the unscoped queries instead of reporting them.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Set the hook the unscoped queries are reported to, e.g. to log them.
+    pub fn on_unscoped(mut self, hook: impl Fn(&UnscopedQuery) + Send + 'static) -> Self {
+        self.on_unscoped = Box::new(hook);
+        self
+    }
+
+    /// The underlying connection.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    fn check_sql(&self, sql: &str) -> QueryResult<()> {
+        let tokens = tokenize(sql);
+        if tokens.first() != Some(&Token::Word("SELECT".to_owned())) {
+            return Ok(());
+        }
+        for (table, check) in &self.tables {
+            let table = *table;
+            let touched = tokens.iter().any(|token| matches!(token, Token::Ident(i) if i == table));
+            if !touched || conditions(&tokens).any(|cond| scopes(cond, check)) {
+                continue;
+            }
+            let unscoped = UnscopedQuery { table, sql: sql.to_owned() };
+            if self.strict {
+                return Err(Error::QueryBuilderError(Box::new(unscoped)));
+            }
+            (self.on_unscoped)(&unscoped);
+        }
+        Ok(())
+    }
+}
+
+fn warn_unscoped(query: &UnscopedQuery) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(table = query.table, sql = %query.sql, "query is not soft-scoped");
+    #[cfg(not(feature = "tracing"))]
+    let _ = query;
+}
+
+/// A token of the SQL, without the literals, comments and whitespace.
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// A quoted identifier.
+    Ident(String),
+    /// A bare word, uppercased.
+    Word(String),
+    Open,
+    Close,
+    Dot,
+    Other,
+}
+
+fn tokenize(sql: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                // Quotes are escaped by doubling them, which reads as two literals
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                }
+            }
+            '"' | '`' => {
+                let mut ident = String::new();
+                while let Some(next) = chars.next() {
+                    if next == c {
+                        if chars.peek() != Some(&c) {
+                            break;
+                        }
+                        chars.next();
+                    }
+                    ident.push(next);
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut star = false;
+                for c in chars.by_ref() {
+                    if star && c == '/' {
+                        break;
+                    }
+                    star = c == '*';
+                }
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '.' => tokens.push(Token::Dot),
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = c.to_uppercase().collect::<String>();
+                while let Some(&next) = chars.peek() {
+                    if !next.is_alphanumeric() && next != '_' {
+                        break;
+                    }
+                    word.extend(next.to_uppercase());
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+            c if c.is_whitespace() => {}
+            _ => tokens.push(Token::Other),
+        }
+    }
+    tokens
+}
+
+/// The keywords ending a `WHERE` or `ON` clause.
+const CLAUSE_ENDS: &[&str] = &[
+    "WHERE",
+    "GROUP",
+    "HAVING",
+    "ORDER",
+    "LIMIT",
+    "OFFSET",
+    "FETCH",
+    "FOR",
+    "WINDOW",
+    "INNER",
+    "LEFT",
+    "RIGHT",
+    "FULL",
+    "CROSS",
+    "JOIN",
+    "UNION",
+    "INTERSECT",
+    "EXCEPT",
+    "RETURNING",
+];
+
+/// The `WHERE` and `ON` clauses of the statement and of its subqueries.
+fn conditions(tokens: &[Token]) -> impl Iterator<Item = &[Token]> {
+    tokens.iter().enumerate().filter_map(move |(start, token)| {
+        match token {
+            Token::Word(word) if word == "WHERE" || word == "ON" => {}
+            _ => return None,
+        }
+        let clause = &tokens[start + 1..];
+        let mut depth = 0usize;
+        let end = clause.iter().position(|token| match token {
+            Token::Open => {
+                depth += 1;
+                false
+            }
+            Token::Close if depth == 0 => true,
+            Token::Close => {
+                depth -= 1;
+                false
+            }
+            Token::Word(word) => depth == 0 && CLAUSE_ENDS.contains(&word.as_str()),
+            _ => false,
+        });
+        Some(&clause[..end.unwrap_or(clause.len())])
+    })
+}
+
+/// Split the condition on the `keyword` operator, outside of parentheses.
+fn split<'a>(cond: &'a [Token], keyword: &str) -> Vec<&'a [Token]> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, token) in cond.iter().enumerate() {
+        match token {
+            Token::Open => depth += 1,
+            Token::Close => depth = depth.saturating_sub(1),
+            Token::Word(word) if depth == 0 && word == keyword => {
+                parts.push(&cond[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&cond[start..]);
+    parts
+}
+
+/// Remove the parentheses around the whole condition.
+fn ungroup(mut cond: &[Token]) -> &[Token] {
+    while let [Token::Open, inner @ .., Token::Close] = cond {
+        let mut depth = 0usize;
+        let balanced = inner.iter().all(|token| {
+            match token {
+                Token::Open => depth += 1,
+                Token::Close if depth == 0 => return false,
+                Token::Close => depth -= 1,
+                _ => {}
+            }
+            true
+        });
+        if !balanced {
+            break;
+        }
+        cond = inner;
+    }
+    cond
+}
+
+/// The tokens of the `column` of `table`, e.g. `"user"."deleted"`.
+fn column_check(table: &str, column: &str) -> Vec<Token> {
+    vec![Token::Ident(table.to_owned()), Token::Dot, Token::Ident(column.to_owned())]
+}
+
+/// Whether every row satisfying the condition had its deleted flag checked with `check`.
+fn scopes(cond: &[Token], check: &[Token]) -> bool {
+    let cond = ungroup(cond);
+    let disjuncts = split(cond, "OR");
+    if disjuncts.len() > 1 {
+        return disjuncts.iter().all(|cond| scopes(cond, check));
+    }
+    let conjuncts = split(cond, "AND");
+    if conjuncts.len() > 1 {
+        return conjuncts.iter().any(|cond| scopes(cond, check));
+    }
+    cond.windows(check.len()).any(|tokens| tokens == check)
+}
+
+impl<C> GuardedConnection<C>
+where
+    C: Connection,
+    <C::Backend as Backend>::QueryBuilder: Default,
+{
+    /// Register the soft-deletable tables of `config`, whichever their soft-delete column.
+    ///
+    /// The tables whose deletion is [inherited](DeletionKind::Inherited) or
+    /// [recorded](DeletionKind::Recorded), whose soft-delete column is a key, are checked for
+    /// their whole deleted condition instead.
+    ///
+    /// # Panics
+    ///
+    /// If the deleted condition of such a table cannot be rendered.
+    pub fn config(mut self, config: &SoftDeleteConfig<C::Backend>) -> Self {
+        for table in config.tables() {
+            let check = match table.kind() {
+                DeletionKind::Inherited | DeletionKind::Recorded => {
+                    let deleted = table.deleted_sql().unwrap_or_else(|e| {
+                        panic!(
+                            "the deleted condition of {} cannot be rendered: {}",
+                            table.name(),
+                            e
+                        )
+                    });
+                    tokenize(&deleted)
+                }
+                _ => column_check(table.name(), table.column()),
+            };
+            self.tables.push((table.name(), check));
+        }
+        self
+    }
+
+    fn check<T: QueryFragment<C::Backend>>(&self, query: &T) -> QueryResult<()> {
+        let mut builder = <C::Backend as Backend>::QueryBuilder::default();
+        query.to_sql(&mut builder)?;
+        self.check_sql(&builder.finish())
+    }
+}
+
+impl<C: SimpleConnection> SimpleConnection for GuardedConnection<C> {
+    fn batch_execute(&self, query: &str) -> QueryResult<()> {
+        self.inner.batch_execute(query)
+    }
+}
+
+impl<C> Connection for GuardedConnection<C>
+where
+    C: Connection<TransactionManager = AnsiTransactionManager>,
+    C::Backend: UsesAnsiSavepointSyntax,
+    <C::Backend as Backend>::QueryBuilder: Default,
+{
+    type Backend = C::Backend;
+    type TransactionManager = AnsiTransactionManager;
+
+    fn establish(database_url: &str) -> ConnectionResult<Self> {
+        C::establish(database_url).map(Self::new)
+    }
+
+    fn execute(&self, query: &str) -> QueryResult<usize> {
+        self.inner.execute(query)
+    }
+
+    fn query_by_index<T, U>(&self, source: T) -> QueryResult<Vec<U>>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Self::Backend> + QueryId,
+        Self::Backend: HasSqlType<T::SqlType>,
+        U: Queryable<T::SqlType, Self::Backend>,
+    {
+        let query = source.as_query();
+        self.check(&query)?;
+        self.inner.query_by_index(query)
+    }
+
+    fn query_by_name<T, U>(&self, source: &T) -> QueryResult<Vec<U>>
+    where
+        T: QueryFragment<Self::Backend> + QueryId,
+        U: QueryableByName<Self::Backend>,
+    {
+        self.check(source)?;
+        self.inner.query_by_name(source)
+    }
+
+    fn execute_returning_count<T>(&self, source: &T) -> QueryResult<usize>
+    where
+        T: QueryFragment<Self::Backend> + QueryId,
+    {
+        self.check(source)?;
+        self.inner.execute_returning_count(source)
+    }
+
+    fn transaction_manager(&self) -> &Self::TransactionManager {
+        self.inner.transaction_manager()
+    }
+}
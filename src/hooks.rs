@@ -0,0 +1,183 @@
+//! Hooks run after the soft deletions, restorations and purges executed by this crate.
+//!
+//! A hook is installed on the current thread for a connection type, and runs after each write of
+//! [`write`](crate::write) and of the modules built upon it, in the same transaction, with the
+//! primary keys of the affected rows:
+//!
+//! ```rust,ignore
+//! let _hook = hooks::install(|event: &WriteEvent, conn: &PgConnection| {
+//!     println!("{} {:?} of {}", event.action.as_str(), event.keys, event.table);
+//!     Ok(())
+//! });
+//! post::table.find(42).soft_delete_rows(&conn)?;
+//! ```
+//!
+//! The hook is uninstalled when the returned [`HookGuard`] is dropped. An error of a hook fails
+//! the write, which is rolled back along with its transaction.
+//!
+//! The statements built by [`soft_delete`](crate::write::soft_delete) and
+//! [`restore`](crate::write::restore) and executed by Diesel itself run no hook: execute them with
+//! [`SoftWriteDsl`](crate::write::SoftWriteDsl) instead. With a hook installed, the keys are read
+//! from a `RETURNING` clause, which MySQL lacks. The written tables have a single-column primary
+//! key.
+
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+    rc::Rc,
+};
+
+use diesel::{
+    backend::Backend,
+    deserialize::FromSql,
+    query_builder::{AstPass, Query, QueryFragment, QueryId},
+    sql_types::Text,
+    Connection, QueryResult, RunQueryDsl,
+};
+
+/// The kind of write a hook is run after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "snake_case"))]
+pub enum Action {
+    SoftDelete,
+    Restore,
+    /// A hard delete of soft-deleted rows.
+    Purge,
+}
+
+impl Action {
+    /// The name of the action, e.g. `soft_delete`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SoftDelete => "soft_delete",
+            Self::Restore => "restore",
+            Self::Purge => "purge",
+        }
+    }
+}
+
+/// A write executed by this crate, passed to the hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteEvent<'a> {
+    /// The table written, as named by its `FROM` clause.
+    pub table: &'static str,
+    pub action: Action,
+    /// The primary keys of the affected rows, as text.
+    pub keys: &'a [String],
+}
+
+type Hook<Conn> = Rc<dyn Fn(&WriteEvent, &Conn) -> QueryResult<()>>;
+
+/// A hook installed on the current thread, a [`Hook`] of its connection type.
+struct Installed {
+    id: usize,
+    hook: Box<dyn Any>,
+}
+
+thread_local! {
+    static HOOKS: RefCell<Vec<Installed>> = const { RefCell::new(Vec::new()) };
+    static NEXT_ID: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Run `hook` after each write of this crate executed on the current thread with a connection of
+/// type `Conn`, until the returned guard is dropped.
+pub fn install<Conn, F>(hook: F) -> HookGuard
+where
+    Conn: Connection + 'static,
+    F: Fn(&WriteEvent, &Conn) -> QueryResult<()> + 'static,
+{
+    let id = NEXT_ID.with(|next| next.replace(next.get() + 1));
+    let hook: Hook<Conn> = Rc::new(hook);
+    HOOKS.with(|hooks| hooks.borrow_mut().push(Installed { id, hook: Box::new(hook) }));
+    HookGuard { id, _thread: PhantomData }
+}
+
+/// An installed hook, uninstalled when dropped.
+#[derive(Debug)]
+#[must_use = "the hook is uninstalled when the guard is dropped"]
+pub struct HookGuard {
+    id: usize,
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for HookGuard {
+    fn drop(&mut self) {
+        HOOKS.with(|hooks| hooks.borrow_mut().retain(|installed| installed.id != self.id));
+    }
+}
+
+/// The hooks installed on the current thread for `Conn`, in installation order.
+fn hooks<Conn: 'static>() -> Vec<Hook<Conn>> {
+    HOOKS.with(|hooks| {
+        let hooks = hooks.borrow();
+        hooks.iter().filter_map(|installed| installed.hook.downcast_ref().cloned()).collect()
+    })
+}
+
+/// Whether hooks are installed on the current thread for `Conn`.
+pub(crate) fn installed<Conn: 'static>() -> bool {
+    HOOKS.with(|hooks| hooks.borrow().iter().any(|installed| installed.hook.is::<Hook<Conn>>()))
+}
+
+/// Run the hooks installed for `Conn` after the write `event`.
+pub(crate) fn run<Conn: 'static>(event: &WriteEvent, conn: &Conn) -> QueryResult<()> {
+    // The hooks are cloned out of the registry, so that they can themselves write.
+    hooks::<Conn>().iter().try_for_each(|hook| hook(event, conn))
+}
+
+/// A connection whose writes run the installed hooks, reading the keys of the affected rows as
+/// text.
+///
+/// This is implemented for the connections of every backend.
+pub trait HookConnection: Connection + 'static {
+    /// Execute the write `stmt`, returning the `key` column of each row it affects as text.
+    #[doc(hidden)]
+    fn load_keys<S>(&self, stmt: S, key: &str) -> QueryResult<Vec<String>>
+    where
+        S: QueryFragment<Self::Backend> + QueryId;
+}
+
+impl<Conn> HookConnection for Conn
+where
+    Conn: Connection + 'static,
+    String: FromSql<Text, Conn::Backend>,
+{
+    fn load_keys<S>(&self, stmt: S, key: &str) -> QueryResult<Vec<String>>
+    where
+        S: QueryFragment<Self::Backend> + QueryId,
+    {
+        ReturningKeys { stmt, key }.load(self)
+    }
+}
+
+/// A write statement returning the key of each row it affects as text.
+struct ReturningKeys<'a, S> {
+    stmt: S,
+    key: &'a str,
+}
+
+impl<S> Query for ReturningKeys<'_, S> {
+    type SqlType = Text;
+}
+
+impl<S> QueryId for ReturningKeys<'_, S> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<S, DB> QueryFragment<DB> for ReturningKeys<'_, S>
+where
+    S: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        self.stmt.walk_ast(out.reborrow())?;
+        out.push_sql(" RETURNING CAST(");
+        out.push_identifier(self.key)?;
+        out.push_sql(" AS TEXT)");
+        Ok(())
+    }
+}
+
+impl<S, Conn> RunQueryDsl<Conn> for ReturningKeys<'_, S> {}
@@ -20,6 +20,48 @@
 //!   [`inner_join`](diesel::query_dsl::QueryDsl::inner_join) /
 //!   [`left_join`](diesel::query_dsl::QueryDsl::left_join),
 //!   but with the soft-delete filter applied to the `ON` clause, not the `WHERE` clause.
+//! - [`soft_exists_in`](methods::soft_exists_in) which builds an `EXISTS` expression on the
+//!   alive rows of a subquery.
+//! - [`soft_paginate`](load::SoftPaginateDsl::soft_paginate) which loads a page of alive rows
+//!   along with their total count.
+//! - [`soft_children_for`](load::soft_children_for) which loads the alive children of a slice of
+//!   parents, grouped by parent.
+//! - [`as_of`](methods::AsOfDsl::as_of) which, on tables recording when rows were created and
+//!   soft-deleted, keeps only the rows that existed at a given point in time.
+//! - [`soft_union`](combination::SoftCombineDsl::soft_union) and the other compound queries,
+//!   which restrict each of their arms to its alive rows.
+//!
+//! Models gain the soft finders and writes as inherent methods with [`soft_model!`], e.g.
+//! `User::soft_find(&conn, id)` and `user.soft_delete(&conn)`.
+//!
+//! The types of those queries are named by the helper types of the [`dsl`] module, also exported
+//! as [`helper_types`], e.g. `dsl::SoftFind<user::table, i32>` or
+//! `helper_types::SoftInnerJoin<user::table, post::table>`.
+//!
+//! Rows are soft-deleted and restored with the [`write::soft_delete`] and [`write::restore`]
+//! statements, which are analogous to [`diesel::delete`]. The [`chunked`] module soft-deletes
+//! many rows in chunks, each in its own transaction.
+//! The [`approval`] module splits a restore in a request and its application, for the restores
+//! to be reviewed.
+//! The [`schedule`] module soft-deletes the rows at a later time, unless the deletion is
+//! cancelled before, and the [`expiry`] module the rows whose time to live has passed.
+//!
+//! The alive condition is one kind of default scope: the [`scope`](mod@scope) module generalizes
+//! it to other conditions, such as the rows of a tenant, declared with [`scope!`].
+//!
+//! The parent → child edges along which soft deletions cascade are declared with
+//! [`soft_delete_cascade!`], e.g. `soft_delete_cascade!(user => [post, comment])`. A child without
+//! a deletion state of its own inherits the one of its parent, e.g.
+//! `soft_delete!(user_profile::table => inherit(user::table, user_profile::user_id))`. The
+//! [`orphans`] module finds the alive children of soft-deleted parents, left behind by writes that
+//! did not follow the cascades.
+//!
+//! Updates are restricted to alive rows either through their target, e.g.
+//! `diesel::update(user::table.soft_filter(predicate))`, or with the soft methods on the update
+//! statement itself, e.g. `diesel::update(user::table).set(changes).soft_deleted()`. Likewise,
+//! [`only_deleted`](methods::OnlyDeletedDsl::only_deleted) restricts a hard delete to the rows
+//! already soft-deleted, e.g. `diesel::delete(user::table.only_deleted())`, as
+//! [`write::purge`] does.
 //!
 //! # Usage
 //!
@@ -28,6 +70,113 @@
 //! `soft_find` and other functions are ready to be used in place of the regular `find` etc macros
 //! once the prelude is imported.
 //!
+//! The `deleted` column does not need to be part of your model structs: as the soft methods only
+//! filter the rows, select the other columns explicitly to load a model without the flag, e.g.
+//! `user::table.soft_deleted().select((user::id, user::name)).load::<User>(&conn)`.
+//!
+//! Models that do load their deletion state can implement [`SoftIdentifiable`], either by hand or
+//! with `#[derive(SoftIdentifiable)]` with the `derive` feature.
+//!
+//! With the `derive` feature, tables can also be declared with `#[derive(SoftDelete)]` on a marker
+//! struct, e.g. `#[soft_delete(table = "user", kind = "timestamp")]`, instead of the macro, and
+//! `soft_delete_schema!` or the `#[soft_deletable]` module attribute declare every table of a
+//! schema having a soft-delete column. Without proc macros, the `codegen` feature generates those
+//! declarations from a build script instead.
+//!
+//! # Generated SQL
+//!
+//! The alive condition is always rendered as `NOT (<deleted>)`, e.g. `NOT ("user"."deleted")`,
+//! and never as `deleted = false` or a double negation. It is added to the `WHERE` clause where
+//! the soft method is chained, like any other filter: `.soft_filter(p)` puts it before `p`, while
+//! `.filter(p).soft_deleted()` puts it after. Both select the same rows, but their SQL differs,
+//! so chaining the soft methods first keeps prepared statement caches and `pg_stat_statements`
+//! entries unfragmented. This rendering makes partial indexes declared with `WHERE NOT deleted`
+//! usable by the queries built with [`soft_deleted`](methods::SoftDeleteDsl::soft_deleted),
+//! [`soft_find`](methods::SoftFindDsl::soft_find) and
+//! [`soft_filter`](methods::SoftFilterDsl::soft_filter):
+//!
+//! ```sql
+//! CREATE INDEX user_alive_id ON user (id) WHERE NOT deleted;
+//! ```
+//!
+//! With the `sql-comments` feature, the alive condition of those methods is followed by a
+//! `/* softdelete:alive */` comment, e.g. `NOT ("user"."deleted") /* softdelete:alive */`, to
+//! identify the soft-scoped queries in `pg_stat_statements` and slow-query logs.
+//!
+//! The soft methods compose with `DISTINCT ON`, e.g. to load the latest alive row per key with
+//! `post::table.soft_deleted().distinct_on(post::user_id).order((post::user_id, post::id.desc()))`.
+//! With the `postgres` feature, [`Scoped`](scoped::Scoped) supports `distinct_on` as well.
+//! They compose with the locking clauses too, e.g. to lock an alive row with
+//! `post::table.soft_find(id).for_update().skip_locked()`.
+//! With the `postgres` feature, the `search` module combines full-text search with the alive
+//! condition, and the `copy` module exports the alive rows in bulk with `COPY`.
+//!
+//! The [`uniqueness`] module finds the keys shared by several alive rows, which would prevent the
+//! creation of a unique index on the alive rows.
+//!
+//! With the `postgres` or `sqlite` feature, `explain::explain_soft` returns the plan of a
+//! soft-scoped query, e.g. to check that a partial index is used.
+//! With the `postgres` feature, `migration::unique_alive_index` generates the DDL of such an
+//! index, enforcing a uniqueness among the alive rows only. With the `postgres`, `sqlite` or
+//! `mysql` feature, `migration::adopt_soft_delete` generates the migration of an existing table
+//! adopting soft delete, `migration::IndexLinter` reports the tables whose soft-delete column has
+//! no index, and `migration::SchemaValidator` the columns differing from their declaration.
+//!
+//! The soft-deletable tables are described once in a [`SoftDeleteConfig`](config::SoftDeleteConfig)
+//! registry, with the kind and column declared by `soft_delete!`, their retention, holds, audit and
+//! cascades, which the subsystems working on all of them consume: the trash statistics and their
+//! Prometheus collector, the audit log, the query guard, and with the `postgres`, `sqlite` or
+//! `mysql` feature, the purge of the expired trash and the checks of the indexes and columns
+//! against the database.
+//! `write::ExpiredPurge` runs that purge in batches, which on PostgreSQL are bounded by statement
+//! and lock timeouts.
+//!
+//! The [`trash`] module is the backend of the "recycle bin" admin screens: a paged and sorted
+//! listing of the trashed rows of a table, and their restore and purge one by one, as well as the
+//! "recently deleted" listing spanning every table of a `SoftDeleteConfig`. With the `postgres` or
+//! `sqlite` feature, the `archive` module moves the old trashed rows to mirror archive tables, and
+//! back, and queries a table along with its archive table.
+//!
+//! The soft deletions, restorations and purges executed by this crate run the [`hooks`] installed
+//! on the thread, with the keys of the affected rows. With the `audit` feature, the `audit` module
+//! installs one recording them in an audit table, in the same transaction as the writes.
+//!
+//! With the `postgres` or `sqlite` feature, the `erasure` module erases the data of a subject, e.g.
+//! for the GDPR, by soft-deleting and scrubbing their rows, which are purged after the statutory
+//! window.
+//!
+//! The [`cte`] module builds common table expressions whose body is restricted to the alive rows,
+//! for the bodies aggregating or limiting them.
+//!
+//! The [`deletions`] module supports the schemas tracking the deletions of all the tables in a
+//! single central `deletions` table rather than in per-table flags.
+//!
+//! [`debug_soft_sql`](testing::debug_soft_sql) renders the normalized SQL of a query, to
+//! snapshot-test that it carries the alive condition, and [`assert_sql_eq!`] compares it with an
+//! expected string. With the `testing` feature, the `testing::fixtures` module provides an
+//! in-memory SQLite database with users, posts and comments to test soft-delete logic against.
+//!
+//! With the `tracing` feature, applying the alive condition emits a trace event, and the writes
+//! executed by this crate, such as [`write::empty_trash`], run in a span recording the table, the
+//! rows affected and the duration. The `log` feature also emits those as `log` records.
+//!
+//! # Backends
+//!
+//! The soft methods, joins, scopes and writes only build queries, and work with any Diesel
+//! backend without enabling a feature of this crate. The helpers relying on the SQL of a given
+//! backend are behind the feature named after it, which also enables it in Diesel:
+//!
+//! - `postgres`: `distinct_on` on [`Scoped`](scoped::Scoped), full-text search, the partial index
+//!   DDL and the adoption migrations of the `migration` module, `EXPLAIN` / `EXPLAIN ANALYZE`,
+//!   the `COPY` exports, the `TABLESAMPLE` samples and the `NOTIFY` of the writes;
+//! - `sqlite`: the adoption migrations of the `migration` module, and `EXPLAIN QUERY PLAN`. The
+//!   `testing` feature enables it for the fixtures database.
+//!
+//! - `mysql`: the adoption migrations of the `migration` module, with indexes ending with the
+//!   soft-delete column instead of partial indexes, which MySQL does not have, the unique keys on
+//!   the alive rows of `migration::unique_alive_key`, the checks of the indexes and columns of a
+//!   `SoftDeleteConfig` against the database and the purge of its expired trash.
+//!
 //! # Example
 //!
 //! ```rust
@@ -63,42 +212,289 @@
 //! # }
 //! ```
 
-#[cfg(test)]
+#[cfg(any(test, feature = "testing"))]
 #[macro_use]
 extern crate diesel;
+#[cfg(test)]
+extern crate self as diesel_softdelete;
+
+use std::time::SystemTime;
+
+use diesel::{
+    expression::{AppearsOnTable, NonAggregate},
+    sql_types::Bool,
+    Expression,
+};
 
-use diesel::{expression::NonAggregate, sql_types::Bool, Expression, SelectableExpression};
+use crate::query_source::SoftSource;
 
+pub mod approval;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub mod archive;
+#[cfg(feature = "audit")]
+pub mod audit;
+pub mod cascade;
+#[cfg(feature = "cdc")]
+pub mod cdc;
+pub mod chunked;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+pub mod combination;
+#[cfg(feature = "sql-comments")]
+pub mod comment;
+pub mod config;
+#[cfg(feature = "postgres")]
+pub mod copy;
+pub mod cte;
+pub mod deletions;
+pub mod dsl;
+/// The helper types naming the soft queries, under the name Diesel gives its own. This is the same
+/// module as [`dsl`].
+pub use dsl as helper_types;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub mod erasure;
+pub mod expiry;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub mod explain;
+#[cfg(feature = "serde")]
+pub mod export;
+pub mod guard;
+pub mod hooks;
+pub mod load;
 mod macros;
 pub mod methods;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+pub mod migration;
+#[cfg(feature = "postgres")]
+pub mod notify;
+pub mod orphans;
 pub mod query_dsl;
 mod query_source;
+#[cfg(feature = "postgres")]
+pub mod sample;
+pub mod schedule;
+pub mod scope;
+pub mod scoped;
+#[cfg(feature = "postgres")]
+pub mod search;
+pub mod stats;
+pub mod testing;
+mod trace;
+pub mod trash;
+pub mod uniqueness;
+pub mod window;
+pub mod write;
 
 pub mod prelude {
-    pub use crate::soft_delete;
-    pub use crate::{methods::*, query_dsl::*};
+    pub use crate::{
+        assert_soft_scoped, assert_sql_eq, restore_requests, scope, soft_delete,
+        soft_delete_cascade, soft_delete_history, soft_join_path, soft_joinable, soft_model,
+        SoftDelete, SoftIdentifiable,
+    };
+    pub use crate::{
+        combination::SoftCombineDsl,
+        load::*,
+        methods::*,
+        query_dsl::*,
+        scope::{NotDeleted, ScopeDsl, ScopedJoinDsl},
+        scoped::{soft_query, Scoped, SoftScoped},
+    };
 }
 
 #[cfg(test)]
 mod tests;
 
+#[doc(hidden)]
+pub mod __private {
+    pub use diesel::{
+        associations::HasTable,
+        dsl::{Eq, IsNotNull},
+        query_builder::{QueryFragment, QueryId},
+        query_dsl::{methods::ExecuteDsl, LoadQuery},
+        Column, Connection, ExpressionMethods, JoinTo, OptionalExtension, QueryResult, RunQueryDsl,
+        Table,
+    };
+
+    /// Whether the column names `a` and `b` are the same, at build time.
+    pub const fn same_name(a: &str, b: &str) -> bool {
+        let (a, b) = (a.as_bytes(), b.as_bytes());
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut i = 0;
+        while i < a.len() {
+            if a[i] != b[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    /// A deleted flag field of a model, for the `SoftIdentifiable` derive.
+    pub trait DeletedFlag {
+        fn is_set(&self) -> bool;
+    }
+
+    impl DeletedFlag for bool {
+        fn is_set(&self) -> bool {
+            *self
+        }
+    }
+
+    impl<T> DeletedFlag for Option<T> {
+        fn is_set(&self) -> bool {
+            self.is_some()
+        }
+    }
+}
+
+#[cfg(feature = "derive")]
+pub use diesel_softdelete_derive::{
+    soft_deletable, soft_delete_schema, Assigns, SoftDelete, SoftIdentifiable,
+};
+
 /// A SQL database table that makes use of Soft Delete
 pub trait SoftDelete: Sized {
     /// The type returned by `deleted_col`
-    type Deleted: SelectableExpression<Self> + NonAggregate + Expression<SqlType = Bool>;
+    type Deleted: NonAggregate + Expression<SqlType = Bool>;
 
     fn deleted_col(&self) -> Self::Deleted;
+
+    /// The condition of the alive rows, `NOT (deleted)`, to compose custom expressions such as
+    /// `ON` clauses.
+    fn not_deleted(&self) -> diesel::helper_types::not<Self::Deleted> {
+        diesel::dsl::not(self.deleted_col())
+    }
+
+    /// The condition of the soft-deleted rows, i.e. the deleted flag itself.
+    fn is_deleted(&self) -> Self::Deleted {
+        self.deleted_col()
+    }
+}
+
+/// A SQL database table recording when its rows were created and soft-deleted
+pub trait SoftDeleteHistory: Sized {
+    /// The type returned by `created_at_col`
+    type CreatedAt: NonAggregate + Expression;
+    /// The type returned by `deleted_at_col`, the nullable counterpart of `CreatedAt`
+    type DeletedAt: NonAggregate + Expression;
+
+    fn created_at_col(&self) -> Self::CreatedAt;
+    fn deleted_at_col(&self) -> Self::DeletedAt;
 }
 
-impl<F, S, D, W, O, L, Of, G> SoftDelete
-    for diesel::query_builder::SelectStatement<F, S, D, W, O, L, Of, G>
+/// A loaded model whose deletion state is known
+pub trait SoftIdentifiable {
+    /// Whether the row was soft-deleted when it was loaded
+    fn is_deleted(&self) -> bool;
+
+    /// When the row was soft-deleted, for models recording it
+    fn deleted_at(&self) -> Option<SystemTime> {
+        None
+    }
+
+    /// Who soft-deleted the row, for models recording it
+    fn deleted_by(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl<T: SoftIdentifiable> SoftIdentifiable for &T {
+    fn is_deleted(&self) -> bool {
+        T::is_deleted(self)
+    }
+
+    fn deleted_at(&self) -> Option<SystemTime> {
+        T::deleted_at(self)
+    }
+
+    fn deleted_by(&self) -> Option<&str> {
+        T::deleted_by(self)
+    }
+}
+
+impl<F, S, D, W, O, L, Of, G, LC> SoftDelete
+    for diesel::query_builder::SelectStatement<F, S, D, W, O, L, Of, G, LC>
+where
+    F: SoftSource,
+    <F::Table as SoftDelete>::Deleted: AppearsOnTable<F>,
+{
+    type Deleted = <F::Table as SoftDelete>::Deleted;
+
+    fn deleted_col(&self) -> Self::Deleted {
+        F::soft_table().deleted_col()
+    }
+}
+
+impl<'a, ST, QS, DB> SoftDelete for diesel::query_builder::BoxedSelectStatement<'a, ST, QS, DB>
+where
+    QS: SoftSource,
+    <QS::Table as SoftDelete>::Deleted: AppearsOnTable<QS>,
+{
+    type Deleted = <QS::Table as SoftDelete>::Deleted;
+
+    fn deleted_col(&self) -> Self::Deleted {
+        QS::soft_table().deleted_col()
+    }
+}
+
+impl<T, U, V, Ret> SoftDelete for diesel::query_builder::UpdateStatement<T, U, V, Ret>
 where
-    F: SoftDelete + diesel::associations::HasTable<Table = F>,
-    F::Deleted: SelectableExpression<Self>,
+    T: SoftSource,
+    <T::Table as SoftDelete>::Deleted: AppearsOnTable<T>,
 {
-    type Deleted = F::Deleted;
+    type Deleted = <T::Table as SoftDelete>::Deleted;
 
     fn deleted_col(&self) -> Self::Deleted {
-        F::deleted_col(&F::table())
+        T::soft_table().deleted_col()
+    }
+}
+
+impl<T, U, Ret> SoftDelete for diesel::query_builder::DeleteStatement<T, U, Ret>
+where
+    T: SoftSource,
+    <T::Table as SoftDelete>::Deleted: AppearsOnTable<T>,
+{
+    type Deleted = <T::Table as SoftDelete>::Deleted;
+
+    fn deleted_col(&self) -> Self::Deleted {
+        T::soft_table().deleted_col()
+    }
+}
+
+impl<F, S, D, W, O, L, Of, G, LC> SoftDeleteHistory
+    for diesel::query_builder::SelectStatement<F, S, D, W, O, L, Of, G, LC>
+where
+    F: SoftDeleteHistory + diesel::associations::HasTable<Table = F>,
+{
+    type CreatedAt = F::CreatedAt;
+    type DeletedAt = F::DeletedAt;
+
+    fn created_at_col(&self) -> Self::CreatedAt {
+        F::created_at_col(&F::table())
+    }
+
+    fn deleted_at_col(&self) -> Self::DeletedAt {
+        F::deleted_at_col(&F::table())
+    }
+}
+
+impl<'a, ST, QS, DB> SoftDeleteHistory
+    for diesel::query_builder::BoxedSelectStatement<'a, ST, QS, DB>
+where
+    QS: SoftDeleteHistory + diesel::associations::HasTable<Table = QS>,
+{
+    type CreatedAt = QS::CreatedAt;
+    type DeletedAt = QS::DeletedAt;
+
+    fn created_at_col(&self) -> Self::CreatedAt {
+        QS::created_at_col(&QS::table())
+    }
+
+    fn deleted_at_col(&self) -> Self::DeletedAt {
+        QS::deleted_at_col(&QS::table())
     }
 }
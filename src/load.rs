@@ -0,0 +1,302 @@
+//! Helpers loading soft-scoped queries.
+
+use std::{borrow::Borrow, error::Error as StdError, fmt, ops::Deref};
+
+use diesel::{
+    associations::{BelongsTo, GroupedBy, Identifiable},
+    backend::Backend,
+    deserialize::{FromSqlRow, Queryable},
+    dsl::{CountStar, Find, Limit, Offset, Order, Select},
+    query_dsl::{
+        methods::{FindDsl, LimitDsl, OffsetDsl, OrderDsl, SelectDsl},
+        BelongingToDsl, LoadQuery,
+    },
+    sql_types::Bool,
+    Expression, QueryResult, RunQueryDsl,
+};
+
+use crate::{methods::SoftDeleteDsl, query_source::LoadForUpdate, SoftIdentifiable};
+
+type AliveQuery<T> = <T as SoftDeleteDsl>::Output;
+
+/// The `soft_paginate` method.
+pub trait SoftPaginateDsl: SoftDeleteDsl + Clone {
+    /// Load a page of alive rows sorted by `order`, along with the total number of alive rows.
+    ///
+    /// Pages are numbered from 1. Both the page and the count queries are soft-scoped, so the
+    /// total is consistent with the rows. The ordering should be total, e.g. end with the primary
+    /// key, for the pages not to overlap.
+    fn soft_paginate<O, U, Conn>(
+        self,
+        order: O,
+        page: i64,
+        per_page: i64,
+        conn: &Conn,
+    ) -> QueryResult<(Vec<U>, i64)>
+    where
+        O: Expression,
+        AliveQuery<Self>: OrderDsl<O> + SelectDsl<CountStar>,
+        Order<AliveQuery<Self>, O>: LimitDsl,
+        Limit<Order<AliveQuery<Self>, O>>: OffsetDsl,
+        Offset<Limit<Order<AliveQuery<Self>, O>>>: LoadQuery<Conn, U>,
+        Select<AliveQuery<Self>, CountStar>: LoadQuery<Conn, i64>,
+    {
+        let offset = (page.max(1) - 1) * per_page;
+        let total =
+            self.clone().soft_deleted().select(diesel::dsl::count_star()).get_result(conn)?;
+        let rows = self.soft_deleted().order(order).limit(per_page).offset(offset).load(conn)?;
+        Ok((rows, total))
+    }
+}
+
+impl<T> SoftPaginateDsl for T where T: SoftDeleteDsl + Clone {}
+
+/// The `soft_load_partitioned` method.
+pub trait SoftLoadPartitionedDsl<Conn>: RunQueryDsl<Conn> + Sized {
+    /// Load the rows of the query, alive or not, split into the alive and the soft-deleted ones,
+    /// in a single round-trip.
+    ///
+    /// The query must not be soft-scoped, and the loaded model must know its deletion state
+    /// through [`SoftIdentifiable`].
+    fn soft_load_partitioned<U>(self, conn: &Conn) -> QueryResult<(Vec<U>, Vec<U>)>
+    where
+        Self: LoadQuery<Conn, U>,
+        U: SoftIdentifiable,
+    {
+        let rows = self.load::<U>(conn)?;
+        Ok(rows.into_iter().partition(|row| !row.is_deleted()))
+    }
+}
+
+impl<T, Conn> SoftLoadPartitionedDsl<Conn> for T where T: RunQueryDsl<Conn> {}
+
+/// The outcome of [`soft_find_for_update`](SoftFindForUpdateDsl::soft_find_for_update).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindForUpdate<T> {
+    /// The row is alive, and locked until the end of the transaction.
+    Locked(T),
+    /// The row is soft-deleted. It is locked as well, so it cannot be restored concurrently.
+    Deleted(T),
+    /// There is no such row.
+    Missing,
+}
+
+impl<T> FindForUpdate<T> {
+    /// The locked row if it is alive.
+    pub fn locked(self) -> Option<T> {
+        match self {
+            Self::Locked(row) => Some(row),
+            Self::Deleted(_) | Self::Missing => None,
+        }
+    }
+}
+
+/// The `soft_find_for_update` method.
+pub trait SoftFindForUpdateDsl<PK>: FindDsl<PK> + Sized {
+    /// Find a row by primary key and lock it with `FOR UPDATE`, telling an alive row apart from a
+    /// soft-deleted or missing one.
+    ///
+    /// This is meant to start the transactions changing the state of a row, and is only available
+    /// on backends supporting row locking. The loaded model must know its deletion state through
+    /// [`SoftIdentifiable`].
+    fn soft_find_for_update<U, Conn>(self, id: PK, conn: &Conn) -> QueryResult<FindForUpdate<U>>
+    where
+        Find<Self, PK>: LoadForUpdate<Conn, U>,
+        U: SoftIdentifiable,
+    {
+        Ok(match self.find(id).load_for_update(conn)? {
+            Some(row) if row.is_deleted() => FindForUpdate::Deleted(row),
+            Some(row) => FindForUpdate::Locked(row),
+            None => FindForUpdate::Missing,
+        })
+    }
+}
+
+impl<T, PK> SoftFindForUpdateDsl<PK> for T where T: FindDsl<PK> {}
+
+/// The `optional_alive` method, giving alive-only semantics to rows loaded without them, e.g.
+/// from a cache or a raw query.
+pub trait OptionalAlive {
+    /// The row type.
+    type Row;
+
+    /// Turn a soft-deleted row into `None`.
+    fn optional_alive(self) -> Option<Self::Row>;
+}
+
+impl<T: SoftIdentifiable> OptionalAlive for T {
+    type Row = T;
+
+    fn optional_alive(self) -> Option<T> {
+        (!self.is_deleted()).then_some(self)
+    }
+}
+
+impl<T: SoftIdentifiable> OptionalAlive for Option<T> {
+    type Row = T;
+
+    fn optional_alive(self) -> Option<T> {
+        self.filter(|row| !row.is_deleted())
+    }
+}
+
+/// A loaded row that is known to be alive.
+///
+/// It is only built by checking the row's deletion state, so that signatures can require alive
+/// rows, e.g. `fn notify(user: Alive<User>)`.
+///
+/// The blanket `TryFrom` implementation of the standard library prevents implementing it for any
+/// model: it is implemented from [`Row`], and [`Alive::new`] does the checked conversion of a
+/// [`SoftIdentifiable`] model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Alive<T>(T);
+
+impl<T> Alive<T> {
+    /// Check that `row` is alive, giving it back otherwise.
+    pub fn new(row: T) -> Result<Self, TrashedRow<T>>
+    where
+        T: SoftIdentifiable,
+    {
+        match row.is_deleted() {
+            false => Ok(Self(row)),
+            true => Err(TrashedRow(row)),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Alive<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> TryFrom<Row<T>> for Alive<T> {
+    type Error = TrashedRow<T>;
+
+    fn try_from(row: Row<T>) -> Result<Self, Self::Error> {
+        match row {
+            Row::Alive(row) => Ok(Self(row)),
+            Row::Trashed(row) => Err(TrashedRow(row)),
+        }
+    }
+}
+
+impl<T> SoftIdentifiable for Alive<T> {
+    fn is_deleted(&self) -> bool {
+        false
+    }
+}
+
+/// The error of converting a soft-deleted row into [`Alive`], giving the row back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrashedRow<T>(pub T);
+
+impl<T> fmt::Display for TrashedRow<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the row is soft-deleted")
+    }
+}
+
+impl<T: fmt::Debug> StdError for TrashedRow<T> {}
+
+/// A loaded row, tagged with its deletion state.
+///
+/// It is loaded from the model's columns followed by the deleted flag, e.g.
+/// `post::table.select((post::all_columns, post::deleted)).load::<Row<Post>>(&conn)`, so the
+/// model itself does not need the flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Row<T> {
+    Alive(T),
+    Trashed(T),
+}
+
+impl<T> Row<T> {
+    pub fn is_alive(&self) -> bool {
+        matches!(self, Self::Alive(_))
+    }
+
+    /// The row, whether it is alive or not.
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Alive(row) | Self::Trashed(row) => row,
+        }
+    }
+
+    /// The row if it is alive.
+    pub fn alive(self) -> Option<T> {
+        match self {
+            Self::Alive(row) => Some(row),
+            Self::Trashed(_) => None,
+        }
+    }
+}
+
+impl<T> SoftIdentifiable for Row<T> {
+    fn is_deleted(&self) -> bool {
+        !self.is_alive()
+    }
+}
+
+impl<T, ST, DB> Queryable<(ST, Bool), DB> for Row<T>
+where
+    T: Queryable<ST, DB>,
+    DB: Backend,
+    (T::Row, bool): FromSqlRow<(ST, Bool), DB>,
+{
+    type Row = (T::Row, bool);
+
+    fn build((row, deleted): Self::Row) -> Self {
+        match deleted {
+            false => Self::Alive(T::build(row)),
+            true => Self::Trashed(T::build(row)),
+        }
+    }
+}
+
+type Children<'a, Child, Parent> = <Child as BelongingToDsl<&'a [Parent]>>::Output;
+
+/// Load the alive children of each of `parents`.
+///
+/// This is the soft counterpart to `Child::belonging_to(parents).load(conn)?.grouped_by(parents)`:
+/// the returned groups are in the same order as `parents`, and parents without alive children get
+/// an empty group.
+pub fn soft_children_for<'a, Child, Parent, Conn>(
+    parents: &'a [Parent],
+    conn: &Conn,
+) -> QueryResult<Vec<Vec<Child>>>
+where
+    Child: BelongingToDsl<&'a [Parent]> + BelongsTo<Parent>,
+    Children<'a, Child, Parent>: SoftDeleteDsl,
+    AliveQuery<Children<'a, Child, Parent>>: LoadQuery<Conn, Child>,
+    &'a Parent: Identifiable,
+    <&'a Parent as Identifiable>::Id: Borrow<Child::ForeignKey>,
+{
+    let children = Child::belonging_to(parents).soft_deleted().load::<Child>(conn)?;
+    Ok(children.grouped_by(parents))
+}
+
+/// The `soft_grouped_by` method.
+///
+/// This is the soft counterpart to [`GroupedBy`], for children that were already loaded: it
+/// partitions them per parent while dropping the soft-deleted ones.
+pub trait SoftGroupedBy<'a, Parent>: IntoIterator + Sized {
+    fn soft_grouped_by(self, parents: &'a [Parent]) -> Vec<Vec<Self::Item>>;
+}
+
+impl<'a, Parent: 'a, Child, Iter> SoftGroupedBy<'a, Parent> for Iter
+where
+    Iter: IntoIterator<Item = Child>,
+    Child: BelongsTo<Parent> + SoftIdentifiable,
+    &'a Parent: Identifiable,
+    <&'a Parent as Identifiable>::Id: Borrow<Child::ForeignKey>,
+{
+    fn soft_grouped_by(self, parents: &'a [Parent]) -> Vec<Vec<Child>> {
+        self.into_iter().filter(|child| !child.is_deleted()).grouped_by(parents)
+    }
+}
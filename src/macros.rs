@@ -1,6 +1,7 @@
 /**
- * Implement the `SoftDelete` trait on a Diesel table. By default, assumes the deleted flag name is
- * `deleted`.
+ * Implement the `SoftDelete` and `SoftDeleteWrite` traits on a Diesel table, and the
+ * `config::SoftDeleteColumn` trait describing the declaration to the registry. By default, assumes
+ * the deleted flag name is `deleted`.
  *
  * # Example
  *
@@ -25,14 +26,741 @@
  * }
  * soft_delete!(user::user => (user::is_deleted));
  * ```
+ *
+ * A revision column, incremented each time a row is soft-deleted or restored, can also be
+ * declared for optimistic locking:
+ *
+ * ```rust,ignore
+ * table! {
+ *     user (id) {
+ *         id -> Integer,
+ *         deleted -> Bool,
+ *         lock_version -> Integer,
+ *     }
+ * }
+ * soft_delete!(user::table => (user::deleted, revision = user::lock_version));
+ * ```
+ *
+ * Rows expiring at a given time, e.g. sessions, are also deleted once the nullable timestamp
+ * column declared with `expires` has passed, as handled by the [`expiry`](crate::expiry) module:
+ *
+ * ```rust,ignore
+ * soft_delete!(session::table => (session::deleted, expires = session::expires_at));
+ * ```
+ *
+ * Tables recording when their rows were soft-deleted in a nullable timestamp column rather than
+ * a flag use the `timestamp` form: a row is deleted once the column is set, and it is set to the
+ * current time (`CURRENT_TIMESTAMP`, as with `diesel::dsl::now`) on deletion and cleared on
+ * restoration.
+ *
+ * ```rust,ignore
+ * table! {
+ *     user (id) {
+ *         id -> Integer,
+ *         deleted_at -> Nullable<Timestamp>,
+ *     }
+ * }
+ * soft_delete!(user::table => timestamp(user::deleted_at));
+ * ```
+ *
+ * Backends without partial indexes, such as MySQL, can only enforce uniqueness among the alive
+ * rows with a plain unique index including the deletion timestamp, e.g. on `(email, deleted_at)`.
+ * As `NULL`s never conflict, the `sentinel` form stores the
+ * [`ALIVE_SENTINEL`](crate::write::ALIVE_SENTINEL) timestamp, `'1970-01-01 00:00:01'`, in the
+ * non-nullable column of the alive rows instead:
+ *
+ * ```rust,ignore
+ * table! {
+ *     user (id) {
+ *         id -> Integer,
+ *         email -> Text,
+ *         deleted_at -> Timestamp,
+ *     }
+ * }
+ * soft_delete!(user::table => sentinel(user::deleted_at));
+ * ```
+ *
+ * Rows sharing a key and soft-deleted within the same second still conflict, unless the column
+ * stores fractions of a second. On a MySQL `TIMESTAMP` column, the sentinel is only valid in a
+ * session whose `time_zone` is UTC: MySQL converts it from the session time zone, and rejects it
+ * ahead of UTC as before the epoch. Use a `DATETIME` column, which is not converted, or set the
+ * `time_zone` of the sessions to `'+00:00'`.
+ *
+ * Tables whose rows are soft-deleted at a scheduled time, e.g. a deletion requested now but
+ * effective in 30 days unless cancelled, use the `scheduled` form around a nullable timestamp
+ * column: a row is deleted once the column is set and has passed. The deletions are scheduled and
+ * cancelled with the [`schedule`](crate::schedule) functions, and the write statements delete
+ * and restore the rows immediately:
+ *
+ * ```rust,ignore
+ * soft_delete!(account::table => scheduled(account::delete_at));
+ * ```
+ *
+ * Views declared with `table!`, e.g. read models, use the `view` form, around either of the flag
+ * and timestamp forms. Their queries are soft-scoped as those of the tables, but they do not
+ * implement [`SoftDeleteWrite`](crate::write::SoftDeleteWrite), the write DSL being unavailable:
+ *
+ * ```rust,ignore
+ * soft_delete!(
+ *     post_summary::table => view(post_summary::deleted),
+ *     user_summary::table => view(timestamp(user_summary::deleted_at)),
+ * );
+ * ```
+ *
+ * Tables without a deletion state of their own, e.g. 1:1 extension tables, inherit the one of
+ * their parent with the `inherit` form, naming the parent table and the foreign key to it:
+ *
+ * ```rust,ignore
+ * soft_delete!(user_profile::table => inherit(user::table, user_profile::user_id));
+ * ```
+ *
+ * Schemas tracking the deletions in a single `deletions (table_name, row_id)` table shared by all
+ * the tables use the `recorded` form, the rows being soft-deleted while recorded in it:
+ *
+ * ```rust,ignore
+ * soft_delete!(user::table => recorded(deletions::table));
+ * ```
+ *
+ * Such tables are written with the [`deletions`](crate::deletions) functions instead of the
+ * [`write`](crate::write) statements.
+ *
+ * Tables declared in other modules are named by their path, including those of PostgreSQL
+ * schema-qualified `table!` definitions such as `billing.invoice (id) { ... }`:
+ *
+ * ```rust,ignore
+ * soft_delete!(crate::schema::billing::invoice);
+ * soft_delete!(
+ *     crate::schema::billing::invoice::table => (crate::schema::billing::invoice::is_void)
+ * );
+ * ```
+ *
+ * Several tables can be declared at once, in either form:
+ *
+ * ```rust,ignore
+ * soft_delete!(user, post, comment);
+ * soft_delete!(
+ *     user::table => (user::is_deleted),
+ *     post::table => timestamp(post::deleted_at),
+ * );
+ * ```
  */
 #[macro_export]
 macro_rules! soft_delete {
-    ($table:path => ($deleted:path)) => {
+    (@soft_delete $table:path => $deleted:path) => {
         impl $crate::SoftDelete for $table {
             type Deleted = $deleted;
             fn deleted_col(&self) -> Self::Deleted { $deleted }
         }
+        impl $crate::query_dsl::SoftJoinTarget for $table {}
+        $crate::soft_delete!(@column $table => Flag, $deleted);
+    };
+    (@column $table:path => $kind:expr, $column:path) => {
+        impl $crate::config::SoftDeleteColumn for $table {
+            const KIND: $crate::config::DeletionKind = {
+                use $crate::config::DeletionKind::*;
+                $kind
+            };
+            const COLUMN: &'static str = <$column as $crate::__private::Column>::NAME;
+        }
+    };
+    ($table:path => ($($columns:tt)*), $($rest:tt)*) => {
+        $crate::soft_delete!($table => ($($columns)*));
+        $crate::soft_delete!($($rest)*);
+    };
+    ($table:path => timestamp($deleted_at:path), $($rest:tt)*) => {
+        $crate::soft_delete!($table => timestamp($deleted_at));
+        $crate::soft_delete!($($rest)*);
+    };
+    ($table:path => sentinel($deleted_at:path), $($rest:tt)*) => {
+        $crate::soft_delete!($table => sentinel($deleted_at));
+        $crate::soft_delete!($($rest)*);
+    };
+    ($table:path => scheduled($delete_at:path), $($rest:tt)*) => {
+        $crate::soft_delete!($table => scheduled($delete_at));
+        $crate::soft_delete!($($rest)*);
+    };
+    ($table:path => view($($columns:tt)*), $($rest:tt)*) => {
+        $crate::soft_delete!($table => view($($columns)*));
+        $crate::soft_delete!($($rest)*);
+    };
+    ($table:path => inherit($parent:path, $foreign_key:path), $($rest:tt)*) => {
+        $crate::soft_delete!($table => inherit($parent, $foreign_key));
+        $crate::soft_delete!($($rest)*);
+    };
+    ($table:path => recorded($deletions:path), $($rest:tt)*) => {
+        $crate::soft_delete!($table => recorded($deletions));
+        $crate::soft_delete!($($rest)*);
+    };
+    ($table:path => ($deleted:path, revision = $revision:path)) => {
+        $crate::soft_delete!(@soft_delete $table => $deleted);
+        impl $crate::write::SoftDeleteWrite for $table {
+            type DeleteChangeset = $crate::write::RevisionChangeset<$deleted, $revision>;
+            type RestoreChangeset = $crate::write::RevisionChangeset<$deleted, $revision>;
+            type Restorable = <Self as $crate::SoftDelete>::Deleted;
+            fn delete_changeset(&self) -> Self::DeleteChangeset {
+                $crate::write::revision_changeset($deleted, true, $revision)
+            }
+            fn restore_changeset(&self) -> Self::RestoreChangeset {
+                $crate::write::revision_changeset($deleted, false, $revision)
+            }
+            fn restorable(&self) -> Self::Restorable {
+                $crate::SoftDelete::deleted_col(self)
+            }
+        }
+    };
+    ($table:path => ($deleted:path, expires = $expires_at:path)) => {
+        impl $crate::SoftDelete for $table {
+            type Deleted = $crate::expiry::ExpiringDeleted<$deleted, $expires_at>;
+            fn deleted_col(&self) -> Self::Deleted {
+                $crate::expiry::expiring_deleted($deleted, $expires_at)
+            }
+        }
+        impl $crate::query_dsl::SoftJoinTarget for $table {}
+        $crate::soft_delete!(
+            @column $table => Expiring {
+                expires_at: <$expires_at as $crate::__private::Column>::NAME,
+            },
+            $deleted
+        );
+        impl $crate::write::SoftDeleteWrite for $table {
+            type DeleteChangeset = $crate::write::FlagChangeset<$deleted>;
+            type RestoreChangeset = $crate::write::FlagChangeset<$deleted>;
+            type Restorable = $crate::expiry::ExpiringRestorable<$deleted, $expires_at>;
+            fn delete_changeset(&self) -> Self::DeleteChangeset {
+                $crate::write::flag_changeset($deleted, true)
+            }
+            fn restore_changeset(&self) -> Self::RestoreChangeset {
+                $crate::write::flag_changeset($deleted, false)
+            }
+            fn restorable(&self) -> Self::Restorable {
+                $crate::expiry::expiring_restorable($deleted, $expires_at)
+            }
+        }
+        impl $crate::expiry::Expiring for $table {
+            type ExpiresAt = $expires_at;
+            fn expires_at_col(&self) -> Self::ExpiresAt { $expires_at }
+        }
+    };
+    ($table:path => timestamp($deleted_at:path)) => {
+        impl $crate::SoftDelete for $table {
+            type Deleted = $crate::__private::IsNotNull<$deleted_at>;
+            fn deleted_col(&self) -> Self::Deleted {
+                $crate::__private::ExpressionMethods::is_not_null($deleted_at)
+            }
+        }
+        impl $crate::query_dsl::SoftJoinTarget for $table {}
+        $crate::soft_delete!(@column $table => Timestamp, $deleted_at);
+        impl $crate::write::SoftDeleteWrite for $table {
+            type DeleteChangeset = $crate::write::TimestampChangeset<$deleted_at>;
+            type RestoreChangeset = $crate::write::TimestampChangeset<$deleted_at>;
+            type Restorable = <Self as $crate::SoftDelete>::Deleted;
+            fn delete_changeset(&self) -> Self::DeleteChangeset {
+                $crate::write::timestamp_changeset($deleted_at, true)
+            }
+            fn restore_changeset(&self) -> Self::RestoreChangeset {
+                $crate::write::timestamp_changeset($deleted_at, false)
+            }
+            fn restorable(&self) -> Self::Restorable {
+                $crate::SoftDelete::deleted_col(self)
+            }
+        }
+    };
+    ($table:path => sentinel($deleted_at:path)) => {
+        impl $crate::SoftDelete for $table {
+            type Deleted = $crate::write::SentinelDeleted<$deleted_at>;
+            fn deleted_col(&self) -> Self::Deleted {
+                $crate::write::sentinel_deleted($deleted_at)
+            }
+        }
+        impl $crate::query_dsl::SoftJoinTarget for $table {}
+        $crate::soft_delete!(@column $table => Sentinel, $deleted_at);
+        impl $crate::write::SoftDeleteWrite for $table {
+            type DeleteChangeset = $crate::write::TimestampChangeset<$deleted_at>;
+            type RestoreChangeset = $crate::write::TimestampChangeset<$deleted_at>;
+            type Restorable = <Self as $crate::SoftDelete>::Deleted;
+            fn delete_changeset(&self) -> Self::DeleteChangeset {
+                $crate::write::sentinel_changeset($deleted_at, true)
+            }
+            fn restore_changeset(&self) -> Self::RestoreChangeset {
+                $crate::write::sentinel_changeset($deleted_at, false)
+            }
+            fn restorable(&self) -> Self::Restorable {
+                $crate::SoftDelete::deleted_col(self)
+            }
+        }
+    };
+    ($table:path => scheduled($delete_at:path)) => {
+        impl $crate::SoftDelete for $table {
+            type Deleted = $crate::write::ScheduledDeleted<$delete_at>;
+            fn deleted_col(&self) -> Self::Deleted {
+                $crate::write::scheduled_deleted($delete_at)
+            }
+        }
+        impl $crate::query_dsl::SoftJoinTarget for $table {}
+        $crate::soft_delete!(@column $table => Scheduled, $delete_at);
+        impl $crate::write::SoftDeleteWrite for $table {
+            type DeleteChangeset = $crate::write::TimestampChangeset<$delete_at>;
+            type RestoreChangeset = $crate::write::TimestampChangeset<$delete_at>;
+            type Restorable = <Self as $crate::SoftDelete>::Deleted;
+            fn delete_changeset(&self) -> Self::DeleteChangeset {
+                $crate::write::timestamp_changeset($delete_at, true)
+            }
+            fn restore_changeset(&self) -> Self::RestoreChangeset {
+                $crate::write::timestamp_changeset($delete_at, false)
+            }
+            fn restorable(&self) -> Self::Restorable {
+                $crate::SoftDelete::deleted_col(self)
+            }
+        }
+        impl $crate::schedule::ScheduledSoftDelete for $table {
+            type DeleteAt = $delete_at;
+            fn delete_at_col(&self) -> Self::DeleteAt { $delete_at }
+        }
+    };
+    ($table:path => view(timestamp($deleted_at:path))) => {
+        impl $crate::SoftDelete for $table {
+            type Deleted = $crate::__private::IsNotNull<$deleted_at>;
+            fn deleted_col(&self) -> Self::Deleted {
+                $crate::__private::ExpressionMethods::is_not_null($deleted_at)
+            }
+        }
+        impl $crate::query_dsl::SoftJoinTarget for $table {}
+        $crate::soft_delete!(@column $table => Timestamp, $deleted_at);
+    };
+    ($table:path => view($deleted:path)) => {
+        $crate::soft_delete!(@soft_delete $table => $deleted);
+    };
+    ($table:path => inherit($parent:path, $foreign_key:path)) => {
+        impl $crate::SoftDelete for $table {
+            type Deleted = $crate::cascade::InheritedDeletion<$parent, $foreign_key>;
+            fn deleted_col(&self) -> Self::Deleted {
+                $crate::cascade::inherited_deletion($parent, $foreign_key)
+            }
+        }
+        impl $crate::query_dsl::SoftJoinTarget for $table {}
+        $crate::soft_delete!(@column $table => Inherited, $foreign_key);
+    };
+    ($table:path => recorded($deletions:path)) => {
+        impl $crate::SoftDelete for $table {
+            type Deleted = $crate::deletions::RecordedDeletion<
+                $deletions,
+                <$table as $crate::__private::Table>::PrimaryKey,
+            >;
+            fn deleted_col(&self) -> Self::Deleted {
+                $crate::deletions::recorded_deletion($deletions, *self)
+            }
+        }
+        impl $crate::query_dsl::SoftJoinTarget for $table {}
+        impl $crate::config::SoftDeleteColumn for $table {
+            const KIND: $crate::config::DeletionKind = $crate::config::DeletionKind::Recorded;
+            const COLUMN: &'static str = <
+                <$table as $crate::__private::Table>::PrimaryKey as $crate::__private::Column
+            >::NAME;
+        }
+    };
+    ($table:path => ($deleted:path)) => {
+        $crate::soft_delete!(@soft_delete $table => $deleted);
+        impl $crate::write::SoftDeleteWrite for $table {
+            type DeleteChangeset = $crate::write::FlagChangeset<$deleted>;
+            type RestoreChangeset = $crate::write::FlagChangeset<$deleted>;
+            type Restorable = <Self as $crate::SoftDelete>::Deleted;
+            fn delete_changeset(&self) -> Self::DeleteChangeset {
+                $crate::write::flag_changeset($deleted, true)
+            }
+            fn restore_changeset(&self) -> Self::RestoreChangeset {
+                $crate::write::flag_changeset($deleted, false)
+            }
+            fn restorable(&self) -> Self::Restorable {
+                $crate::SoftDelete::deleted_col(self)
+            }
+        }
+    };
+    ($($module:ident)::+, $($rest:tt)*) => {
+        $crate::soft_delete!($($module)::+);
+        $crate::soft_delete!($($rest)*);
+    };
+    ($($module:ident)::+) => {
+        $crate::soft_delete!($($module)::+::table => ($($module)::+::deleted));
+    };
+    () => {};
+}
+
+/**
+ * Implement the `SoftDeleteHistory` trait on a Diesel table. By default, assumes the timestamp
+ * columns are named `created_at` and `deleted_at`.
+ *
+ * # Example
+ *
+ * ```rust,ignore
+ * table! {
+ *     user (id) {
+ *         id -> Integer,
+ *         created_at -> Timestamp,
+ *         deleted_at -> Nullable<Timestamp>,
+ *     }
+ * }
+ * soft_delete_history!(user);
+ * ```
+ *
+ * or
+ *
+ * ```rust,ignore
+ * soft_delete_history!(user::table => (user::inserted_at, user::removed_at));
+ * ```
+ */
+#[macro_export]
+macro_rules! soft_delete_history {
+    ($table:path => ($created_at:path, $deleted_at:path)) => {
+        impl $crate::SoftDeleteHistory for $table {
+            type CreatedAt = $created_at;
+            type DeletedAt = $deleted_at;
+            fn created_at_col(&self) -> Self::CreatedAt { $created_at }
+            fn deleted_at_col(&self) -> Self::DeletedAt { $deleted_at }
+        }
+    };
+    ($table:ident) => {
+        $crate::soft_delete_history!($table::table => ($table::created_at, $table::deleted_at));
+    };
+}
+
+/**
+ * Implement the `RestoreRequests` trait on a soft-deletable Diesel table, for its restores to be
+ * requested then applied with the `approval` module. By default, assumes the column flagging the
+ * requests is named `restore_requested`.
+ *
+ * # Example
+ *
+ * ```rust,ignore
+ * table! {
+ *     user (id) {
+ *         id -> Integer,
+ *         deleted -> Bool,
+ *         restore_requested -> Bool,
+ *     }
+ * }
+ * soft_delete!(user);
+ * restore_requests!(user);
+ * ```
+ *
+ * or
+ *
+ * ```rust,ignore
+ * restore_requests!(user::table => user::undelete_pending);
+ * ```
+ */
+#[macro_export]
+macro_rules! restore_requests {
+    ($table:path => $requested:path) => {
+        impl $crate::approval::RestoreRequests for $table {
+            type Requested = $requested;
+            fn requested_col(&self) -> Self::Requested { $requested }
+        }
+    };
+    ($table:ident) => {
+        $crate::restore_requests!($table::table => $table::restore_requested);
+    };
+}
+
+/**
+ * Build the nested soft joins along a chain of `joinable!` relationships, each table being
+ * soft-joined to the rest of the chain. The join kind is either `Inner` or `LeftOuter`, and
+ * defaults to `Inner`.
+ *
+ * # Example
+ *
+ * ```rust,ignore
+ * soft_join_path!(user => post => comment, LeftOuter)
+ * ```
+ *
+ * expands to
+ *
+ * ```rust,ignore
+ * user::table.soft_left_join(post::table.soft_left_join(comment::table))
+ * ```
+ */
+#[macro_export]
+macro_rules! soft_join_path {
+    (@join Inner, $lhs:expr, $rhs:expr) => {
+        $crate::query_dsl::SoftJoinDsl::soft_inner_join($lhs, $rhs)
+    };
+    (@join LeftOuter, $lhs:expr, $rhs:expr) => {
+        $crate::query_dsl::SoftJoinDsl::soft_left_join($lhs, $rhs)
+    };
+    (@nest $kind:ident; $table:ident) => { $table::table };
+    (@nest $kind:ident; $table:ident => $($rest:ident)=>+) => {
+        $crate::soft_join_path!(
+            @join $kind,
+            $table::table,
+            $crate::soft_join_path!(@nest $kind; $($rest)=>+)
+        )
+    };
+    ($table:ident => $($rest:ident)=>+, $kind:ident) => {
+        $crate::soft_join_path!(@nest $kind; $table => $($rest)=>+)
+    };
+    ($table:ident => $($rest:ident)=>+) => {
+        $crate::soft_join_path!(@nest Inner; $table => $($rest)=>+)
+    };
+}
+
+/**
+ * Declare a relationship between two soft-deletable tables, in place of Diesel's `joinable`.
+ *
+ * Without the `implicit-soft-joins` feature, this is the same as `joinable`. With it, the plain
+ * `inner_join` and `left_join` between the two tables also check that the joined row is alive,
+ * as `soft_inner_join` and `soft_left_join` do. The soft joins check it only once either way, so
+ * their SQL does not depend on the feature.
+ *
+ * # Example
+ *
+ * ```rust,ignore
+ * soft_joinable!(post -> user (user_id));
+ *
+ * // With `implicit-soft-joins`, only joins alive users
+ * post::table.inner_join(user::table)
+ * ```
+ */
+#[macro_export]
+macro_rules! soft_joinable {
+    (@join $left:path => $right:path, $foreign_key:path, $parent:path) => {
+        impl $crate::__private::JoinTo<$right> for $left {
+            type FromClause = $right;
+            type OnClause = <$right as $crate::query_dsl::ImplicitOnClause<
+                $foreign_key,
+                <$parent as $crate::__private::Table>::PrimaryKey,
+            >>::OnClause;
+
+            fn join_target(rhs: $right) -> (Self::FromClause, Self::OnClause) {
+                let primary_key = $crate::__private::Table::primary_key(&$parent);
+                let on_clause = $crate::query_dsl::ImplicitOnClause::implicit_on_clause(
+                    &rhs,
+                    $foreign_key,
+                    primary_key,
+                );
+                (rhs, on_clause)
+            }
+        }
+    };
+    ($child:ident -> $parent:ident ($foreign_key:ident)) => {
+        $crate::soft_joinable!(
+            @join $child::table => $parent::table, $child::$foreign_key, $parent::table
+        );
+        $crate::soft_joinable!(
+            @join $parent::table => $child::table, $child::$foreign_key, $parent::table
+        );
+    };
+}
+
+/**
+ * Assert at compile time that a query only sees alive rows, evaluating to the query itself.
+ *
+ * This fails to compile unless the query's type implements
+ * [`SoftScoped`](crate::scoped::SoftScoped), i.e. its `WHERE` clause was proven to include the
+ * alive condition of the table it starts from.
+ *
+ * # Example
+ *
+ * ```rust,ignore
+ * let query = assert_soft_scoped!(user::table.soft_filter(user::name.eq("Joe")));
+ *
+ * // Does not compile
+ * let query = assert_soft_scoped!(user::table.filter(user::name.eq("Joe")));
+ * ```
+ */
+#[macro_export]
+macro_rules! assert_soft_scoped {
+    ($query:expr) => {
+        $crate::scoped::assert_soft_scoped($query)
+    };
+}
+
+/**
+ * Assert that the SQL generated for a query on a backend is the expected one.
+ *
+ * The comparison ignores whitespace differences and the binds, and bind placeholders can be
+ * written as `?` for any backend: both sides are normalized with
+ * [`normalize_sql`](crate::testing::normalize_sql).
+ *
+ * # Example
+ *
+ * ```rust,ignore
+ * assert_sql_eq!(
+ *     Pg,
+ *     user::table.soft_find(1).select(user::name),
+ *     r#"SELECT "user"."name" FROM "user"
+ *        WHERE NOT ("user"."deleted") AND "user"."id" = ?"#,
+ * );
+ * ```
+ */
+#[macro_export]
+macro_rules! assert_sql_eq {
+    ($backend:ty, $query:expr, $expected:expr $(,)?) => {
+        assert_eq!(
+            $crate::testing::normalized_sql::<$backend, _>(&$query),
+            $crate::testing::normalize_sql($expected),
+        )
+    };
+}
+
+/**
+ * Declare the soft-delete cascades from parent tables to their children, implementing
+ * [`SoftCascade`](crate::cascade::SoftCascade) on the parents.
+ *
+ * Each child must be soft-deletable and joinable to its parent, e.g. with `joinable!`. A table
+ * has up to 8 children.
+ *
+ * # Example
+ *
+ * ```rust,ignore
+ * soft_delete_cascade!(user => [post, comment], post => [comment]);
+ * ```
+ */
+#[macro_export]
+macro_rules! soft_delete_cascade {
+    ($($parent:ident => [$($child:ident),+ $(,)?]),+ $(,)?) => {
+        $(
+            impl $crate::cascade::SoftCascade for $parent::table {
+                type Children = ($($child::table,)+);
+                const CHILDREN: &'static [&'static str] = &[$(stringify!($child)),+];
+
+                fn children(&self) -> Self::Children {
+                    ($($child::table,)+)
+                }
+            }
+        )+
+    };
+}
+
+/**
+ * Declare the [scopes](mod@crate::scope) of a table, implementing [`Scope`](crate::scope::Scope) on
+ * each scope type.
+ *
+ * A scope is either a unit struct keeping the rows where a boolean column is set, or a tuple
+ * struct of a single value keeping the rows where a column equals it. All the scopes of a table
+ * are declared in a single invocation.
+ *
+ * # Example
+ *
+ * ```rust,ignore
+ * pub struct Published;
+ * pub struct Tenant(pub i32);
+ *
+ * scope!(post::table => { Published = post::published, Tenant(i32) = post::tenant_id });
+ * ```
+ */
+#[macro_export]
+macro_rules! scope {
+    (@scope $table:path, $name:ident = $column:path) => {
+        impl $crate::scope::Scope<$table> for $name {
+            type Predicate = $column;
+            fn predicate(self, _: &$table) -> Self::Predicate { $column }
+        }
+    };
+    (@scope $table:path, $name:ident($value:ty) = $column:path) => {
+        impl $crate::scope::Scope<$table> for $name {
+            type Predicate = $crate::__private::Eq<$column, $value>;
+            fn predicate(self, _: &$table) -> Self::Predicate {
+                $crate::__private::ExpressionMethods::eq($column, self.0)
+            }
+        }
+    };
+    ($table:path => { $($name:ident $(($value:ty))? = $column:path),* $(,)? }) => {
+        $($crate::scope!(@scope $table, $name $(($value))? = $column);)*
+    };
+}
+
+/**
+ * Implement the soft-delete operations of a model as inherent methods, for repositories to call
+ * them directly on the model.
+ *
+ * The model is `Identifiable` on a soft-deletable table, and `Queryable` from its columns. It
+ * gains:
+ *
+ * - `Model::soft_find(conn, id)`, loading the alive row with the primary key `id`, if any;
+ * - `Model::soft_all(conn)`, loading the alive rows;
+ * - `model.is_deleted()`, reading the deleted field, `deleted` by default, which is either a
+ *   `bool` or an `Option` set once the row is soft-deleted;
+ * - `model.soft_delete(conn)` and `model.restore(conn)`, returning the number of rows changed,
+ *   and running the [`hooks`](crate::hooks).
+ *
+ * # Example
+ *
+ * ```rust,ignore
+ * soft_model!(User => user);
+ * // or
+ * soft_model!(User => user::table, removed_at);
+ *
+ * if let Some(user) = User::soft_find(&conn, 1)? {
+ *     user.soft_delete(&conn)?;
+ * }
+ * ```
+ */
+#[macro_export]
+macro_rules! soft_model {
+    ($model:ty => $table:ident) => {
+        $crate::soft_model!($model => $table::table, deleted);
+    };
+    ($model:ty => $table:path, $deleted:ident) => {
+        impl $model {
+            /// Load the alive row with the primary key `id`, if any.
+            pub fn soft_find<PK, Conn>(
+                conn: &Conn,
+                id: PK,
+            ) -> $crate::__private::QueryResult<::std::option::Option<Self>>
+            where
+                $table: $crate::methods::SoftFindDsl<PK>,
+                <$table as $crate::methods::SoftFindDsl<PK>>::Output:
+                    $crate::__private::LoadQuery<Conn, Self>,
+            {
+                let query = $crate::methods::SoftFindDsl::soft_find($table, id);
+                $crate::__private::OptionalExtension::optional(
+                    $crate::__private::RunQueryDsl::get_result(query, conn),
+                )
+            }
+
+            /// Load the alive rows.
+            pub fn soft_all<Conn>(
+                conn: &Conn,
+            ) -> $crate::__private::QueryResult<::std::vec::Vec<Self>>
+            where
+                $table: $crate::methods::SoftDeleteDsl,
+                <$table as $crate::methods::SoftDeleteDsl>::Output:
+                    $crate::__private::LoadQuery<Conn, Self>,
+            {
+                let query = $crate::methods::SoftDeleteDsl::soft_deleted($table);
+                $crate::__private::RunQueryDsl::load(query, conn)
+            }
+
+            /// Whether the row was soft-deleted when it was loaded.
+            pub fn is_deleted(&self) -> bool {
+                $crate::__private::DeletedFlag::is_set(&self.$deleted)
+            }
+
+            /// Soft-delete the row, returning the number of rows changed.
+            pub fn soft_delete<'a, Conn>(
+                &'a self,
+                conn: &Conn,
+            ) -> $crate::__private::QueryResult<usize>
+            where
+                Conn: $crate::hooks::HookConnection,
+                &'a Self: $crate::write::SoftDeleteTarget
+                    + $crate::__private::HasTable<Table = $table>,
+                <&'a Self as $crate::write::SoftDeleteTarget>::SoftDelete:
+                    $crate::__private::QueryFragment<Conn::Backend> + $crate::__private::QueryId,
+            {
+                $crate::write::SoftWriteDsl::soft_delete_rows(self, conn)
+            }
+
+            /// Restore the row, returning the number of rows changed.
+            pub fn restore<'a, Conn>(&'a self, conn: &Conn) -> $crate::__private::QueryResult<usize>
+            where
+                Conn: $crate::hooks::HookConnection,
+                &'a Self: $crate::write::SoftDeleteTarget
+                    + $crate::__private::HasTable<Table = $table>,
+                <&'a Self as $crate::write::SoftDeleteTarget>::Restore:
+                    $crate::__private::QueryFragment<Conn::Backend> + $crate::__private::QueryId,
+            {
+                $crate::write::SoftWriteDsl::restore_rows(self, conn)
+            }
+        }
     };
-    ($table:ident) => { soft_delete!($table::table => ($table::deleted)); };
 }
@@ -1,14 +1,64 @@
 //! Expression methods implemented on the table.
 
+use std::ops::{Bound, RangeBounds};
+
 use diesel::{
-    dsl::{not, Filter},
-    helper_types::not as Not,
-    query_dsl::methods::{FilterDsl, FindDsl},
+    associations::HasTable,
+    backend::Backend,
+    dsl::{
+        exists, not, And, AsExpr, EqAny, Filter, Gt, IntoBoxed, IsNull, LtEq, Or, OrFilter, Select,
+        SqlTypeOf,
+    },
+    expression::{
+        array_comparison::AsInExpression,
+        exists::Exists,
+        functions::{
+            aggregate_folding::{avg, sum},
+            aggregate_ordering::{max, min},
+        },
+        AppearsOnTable, AsExpression, NonAggregate,
+    },
+    expression_methods::EqAll,
+    helper_types::{avg as Avg, max as Max, min as Min, not as Not, sum as Sum},
+    query_builder::{AstPass, QueryBuilder, QueryFragment, QueryId},
+    query_dsl::methods::{BoxedDsl, FilterDsl, OrFilterDsl, SelectDsl},
+    sql_types::{Bool, Foldable, IntoNullable, SingleValue, SqlOrd},
+    BoolExpressionMethods, BoxableExpression, Expression, ExpressionMethods, QueryResult,
+    SelectableExpression, Table,
 };
 
-use super::SoftDelete;
+#[cfg(feature = "postgres")]
+use diesel::pg::expression::array_comparison::{Any, AsArrayExpression};
+
+use super::{SoftDelete, SoftDeleteHistory};
+
+/// The alive condition added by the soft methods, followed by a comment with the `sql-comments`
+/// feature.
+#[cfg(feature = "sql-comments")]
+pub type AliveCondition<D> = crate::comment::Tagged<Not<D>>;
+/// The alive condition added by the soft methods, followed by a comment with the `sql-comments`
+/// feature.
+#[cfg(not(feature = "sql-comments"))]
+pub type AliveCondition<D> = Not<D>;
+
+pub(crate) fn alive_condition<D: Expression<SqlType = Bool>>(deleted: D) -> AliveCondition<D> {
+    crate::trace::soft_scope();
+    #[cfg(feature = "sql-comments")]
+    return crate::comment::Tagged(not(deleted));
+    #[cfg(not(feature = "sql-comments"))]
+    return not(deleted);
+}
 
-pub trait SoftDeleteDsl: SoftDelete {
+/// The `soft_deleted` method.
+///
+/// The condition always lands in the `WHERE` clause, so on grouped queries only alive rows are
+/// aggregated.
+///
+/// Applying the soft methods several times to the same query repeats the alive condition. To
+/// compose helpers that each soft-scope their query, hand them a [`Scoped`](crate::scoped::Scoped)
+/// table, on which the soft methods do not add it again. This is why `SoftDelete` is not a
+/// supertrait of the soft methods: generic code also needing it requires it separately.
+pub trait SoftDeleteDsl {
     /// The type returned by `.soft_deleted`.
     type Output;
     fn soft_deleted(self) -> Self::Output;
@@ -16,44 +66,192 @@ pub trait SoftDeleteDsl: SoftDelete {
 
 impl<T> SoftDeleteDsl for T
 where
-    T: SoftDelete + FilterDsl<Not<Self::Deleted>>,
+    T: SoftDelete + FilterDsl<AliveCondition<T::Deleted>>,
 {
-    type Output = Filter<Self, Not<Self::Deleted>>;
+    type Output = Filter<Self, AliveCondition<T::Deleted>>;
     fn soft_deleted(self) -> Self::Output {
         let deleted = self.deleted_col();
-        self.filter(not(deleted))
+        self.filter(alive_condition(deleted))
+    }
+}
+
+/// Render the alive condition of `table` as an SQL fragment for the backend `DB`, e.g.
+/// `NOT ("user"."deleted")` on PostgreSQL, to keep hand-written `sql_query` consistent with the
+/// soft methods.
+///
+/// ```rust,ignore
+/// let alive = alive_sql::<Pg, _>(&user::table)?;
+/// let users = sql_query(format!("SELECT * FROM \"user\" WHERE {} AND name ~ $1", alive))
+///     .bind::<Text, _>(pattern)
+///     .load::<User>(&conn)?;
+/// ```
+pub fn alive_sql<DB, T>(table: &T) -> QueryResult<String>
+where
+    DB: Backend,
+    DB::QueryBuilder: Default,
+    T: SoftDelete,
+    AliveCondition<T::Deleted>: QueryFragment<DB>,
+{
+    let mut builder = DB::QueryBuilder::default();
+    alive_condition(table.deleted_col()).to_sql(&mut builder)?;
+    Ok(builder.finish())
+}
+
+/// The `only_deleted` method.
+///
+/// This is the opposite of [`soft_deleted`](SoftDeleteDsl::soft_deleted), keeping only the
+/// soft-deleted rows. On a `DELETE` statement or its target, it guarantees that the hard delete
+/// cannot touch alive rows.
+pub trait OnlyDeletedDsl: SoftDelete {
+    /// The type returned by `.only_deleted`.
+    type Output;
+    fn only_deleted(self) -> Self::Output;
+}
+
+impl<T> OnlyDeletedDsl for T
+where
+    T: SoftDelete + FilterDsl<Self::Deleted>,
+{
+    type Output = Filter<Self, Self::Deleted>;
+    fn only_deleted(self) -> Self::Output {
+        let deleted = self.deleted_col();
+        self.filter(deleted)
     }
 }
 
 /// The `soft_find` method
-pub trait SoftFindDsl<PK>: SoftDelete {
+///
+/// The soft-delete and primary key conditions are combined in a single `WHERE` predicate, which is
+/// the same as the one of `.soft_deleted().find(id)`.
+pub trait SoftFindDsl<PK> {
     /// The type returned by `.soft_find`.
     type Output;
     fn soft_find(self, id: PK) -> Self::Output;
 }
 
+type SoftFindPredicate<T, PK> = And<
+    AliveCondition<<T as SoftDelete>::Deleted>,
+    <<<T as HasTable>::Table as Table>::PrimaryKey as EqAll<PK>>::Output,
+>;
+
 impl<T, PK> SoftFindDsl<PK> for T
 where
-    T: SoftDelete + FindDsl<PK>,
-    <T as FindDsl<PK>>::Output: FilterDsl<Not<Self::Deleted>>,
+    T: SoftDelete + HasTable + FilterDsl<SoftFindPredicate<T, PK>>,
+    <T::Table as Table>::PrimaryKey: EqAll<PK>,
 {
-    type Output = Filter<<T as FindDsl<PK>>::Output, Not<T::Deleted>>;
+    type Output = Filter<T, SoftFindPredicate<T, PK>>;
 
     fn soft_find(self, id: PK) -> Self::Output {
-        let deleted = self.deleted_col();
-        self.find(id).filter(not(deleted))
+        let predicate =
+            alive_condition(self.deleted_col()).and(T::table().primary_key().eq_all(id));
+        self.filter(predicate)
+    }
+}
+
+/// The `soft_find_many` method
+///
+/// The batched counterpart of [`soft_find`](SoftFindDsl::soft_find), keeping the alive rows whose
+/// primary key is one of `ids`, as in `id IN ($1, $2, ...)` with one bind per key. The keys may be
+/// of any type usable in a `find`, such as newtypes deriving `AsExpression` and UUIDs. On
+/// PostgreSQL, `soft_find_any` binds them as a single array instead.
+pub trait SoftFindManyDsl<I> {
+    /// The type returned by `.soft_find_many`.
+    type Output;
+    fn soft_find_many(self, ids: I) -> Self::Output;
+}
+
+type SoftFindManyPredicate<T, I> = And<
+    AliveCondition<<T as SoftDelete>::Deleted>,
+    EqAny<<<T as HasTable>::Table as Table>::PrimaryKey, I>,
+>;
+
+impl<T, I> SoftFindManyDsl<I> for T
+where
+    T: SoftDelete + HasTable + FilterDsl<SoftFindManyPredicate<T, I>>,
+    <T::Table as Table>::PrimaryKey: ExpressionMethods,
+    I: AsInExpression<SqlTypeOf<<T::Table as Table>::PrimaryKey>>,
+{
+    type Output = Filter<T, SoftFindManyPredicate<T, I>>;
+
+    fn soft_find_many(self, ids: I) -> Self::Output {
+        let predicate =
+            alive_condition(self.deleted_col()).and(T::table().primary_key().eq_any(ids));
+        self.filter(predicate)
     }
 }
 
+/// The `soft_find_any` method
+///
+/// Like [`soft_find_many`](SoftFindManyDsl::soft_find_many), but as `= ANY($1)`: the keys are
+/// bound as a single array, so the SQL is the same whatever their number, and it can be a
+/// prepared statement.
+#[cfg(feature = "postgres")]
+pub trait SoftFindAnyDsl<I> {
+    /// The type returned by `.soft_find_any`.
+    type Output;
+    fn soft_find_any(self, ids: I) -> Self::Output;
+}
+
+#[cfg(feature = "postgres")]
+type SoftFindAnyPredicate<T, I> =
+    And<
+        AliveCondition<<T as SoftDelete>::Deleted>,
+        diesel::dsl::Eq<
+            <<T as HasTable>::Table as Table>::PrimaryKey,
+            Any<
+                <I as AsArrayExpression<
+                    SqlTypeOf<<<T as HasTable>::Table as Table>::PrimaryKey>,
+                >>::Expression,
+            >,
+        >,
+    >;
+
+#[cfg(feature = "postgres")]
+impl<T, I> SoftFindAnyDsl<I> for T
+where
+    T: SoftDelete + HasTable + FilterDsl<SoftFindAnyPredicate<T, I>>,
+    <T::Table as Table>::PrimaryKey: ExpressionMethods,
+    I: AsArrayExpression<SqlTypeOf<<T::Table as Table>::PrimaryKey>>,
+{
+    type Output = Filter<T, SoftFindAnyPredicate<T, I>>;
+
+    fn soft_find_any(self, ids: I) -> Self::Output {
+        let predicate = alive_condition(self.deleted_col())
+            .and(T::table().primary_key().eq(diesel::dsl::any(ids)));
+        self.filter(predicate)
+    }
+}
+
+/// The type returned by [`soft_exists_in`].
+pub type SoftExists<Q> = Exists<<Q as SoftDeleteDsl>::Output>;
+
+/// An `EXISTS` expression on the alive rows of a subquery, for use in filters.
+///
+/// The subquery may reference the columns of the outer query, e.g. to keep the users having at
+/// least one alive post:
+///
+/// ```rust,ignore
+/// user::table.soft_filter(soft_exists_in(post::table.filter(post::user_id.eq(user::id))))
+/// ```
+pub fn soft_exists_in<Q: SoftDeleteDsl>(subquery: Q) -> SoftExists<Q> {
+    exists(subquery.soft_deleted())
+}
+
+/// A dynamically-built predicate, usable with [`soft_filter`](SoftFilterDsl::soft_filter) on
+/// both regular and boxed queries.
+pub type BoxedPredicate<'a, QS, DB> = Box<dyn BoxableExpression<QS, DB, SqlType = Bool> + 'a>;
+
 /// The `soft_filter` method.
 ///
 /// This trait is used to automatically add soft-delete filtering on regular `filter` in queries.
-/// It only needs to be put once per query.
+/// It only needs to be put once per query. The predicate and soft-delete conditions are combined
+/// in a single `NOT deleted AND predicate` expression, which is the same as the one of
+/// `.soft_deleted().filter(predicate)`.
 ///
 /// Be careful with it, as it is often incorrect to use it on left-joined tables. For such cases,
 /// use the [`soft_left_join`](crate::query_dsl::SoftJoinDsl::soft_left_join) method to join the
 /// table and don't filter on the deleted status.
-pub trait SoftFilterDsl<Predicate>: SoftDelete {
+pub trait SoftFilterDsl<Predicate> {
     /// The type returned by `.soft_filter`.
     type Output;
     fn soft_filter(self, predicate: Predicate) -> Self::Output;
@@ -61,13 +259,298 @@ pub trait SoftFilterDsl<Predicate>: SoftDelete {
 
 impl<T, Predicate> SoftFilterDsl<Predicate> for T
 where
-    T: SoftDelete + FilterDsl<Predicate>,
-    <T as FilterDsl<Predicate>>::Output: FilterDsl<Not<Self::Deleted>>,
+    T: SoftDelete + FilterDsl<And<AliveCondition<T::Deleted>, Predicate>>,
+    Predicate: Expression<SqlType = Bool>,
 {
-    type Output = Filter<<T as FilterDsl<Predicate>>::Output, Not<T::Deleted>>;
+    type Output = Filter<T, And<AliveCondition<T::Deleted>, Predicate>>;
 
     fn soft_filter(self, predicate: Predicate) -> Self::Output {
         let deleted = self.deleted_col();
-        self.filter(predicate).filter(not(deleted))
+        self.filter(alive_condition(deleted).and(predicate))
+    }
+}
+
+/// The `soft_or_filter` method.
+///
+/// A plain `or_filter` after a soft method ORs past the alive condition: the rows matching the new
+/// predicate are returned even when soft-deleted. This instead keeps the alive condition ANDed at
+/// the top level, as in `(previous conditions OR predicate) AND NOT deleted`.
+pub trait SoftOrFilterDsl<Predicate>: SoftDelete {
+    /// The type returned by `.soft_or_filter`.
+    type Output;
+    fn soft_or_filter(self, predicate: Predicate) -> Self::Output;
+}
+
+impl<T, Predicate> SoftOrFilterDsl<Predicate> for T
+where
+    T: SoftDelete + OrFilterDsl<Predicate>,
+    OrFilter<T, Predicate>: FilterDsl<AliveCondition<T::Deleted>>,
+{
+    type Output = Filter<OrFilter<T, Predicate>, AliveCondition<T::Deleted>>;
+
+    fn soft_or_filter(self, predicate: Predicate) -> Self::Output {
+        let deleted = self.deleted_col();
+        self.or_filter(predicate).filter(alive_condition(deleted))
+    }
+}
+
+/// Boxed variants of the `soft_deleted`, `soft_find` and `soft_filter` methods.
+///
+/// Those box the resulting query, which trades a little runtime cost for much smaller types when
+/// building complex queries, and thus faster builds.
+pub trait SoftBoxedDsl: Sized {
+    fn soft_deleted_boxed<'a, DB>(self) -> IntoBoxed<'a, <Self as SoftDeleteDsl>::Output, DB>
+    where
+        Self: SoftDeleteDsl,
+        <Self as SoftDeleteDsl>::Output: BoxedDsl<'a, DB>,
+        DB: Backend,
+    {
+        self.soft_deleted().internal_into_boxed()
+    }
+
+    fn soft_find_boxed<'a, PK, DB>(
+        self,
+        id: PK,
+    ) -> IntoBoxed<'a, <Self as SoftFindDsl<PK>>::Output, DB>
+    where
+        Self: SoftFindDsl<PK>,
+        <Self as SoftFindDsl<PK>>::Output: BoxedDsl<'a, DB>,
+        DB: Backend,
+    {
+        self.soft_find(id).internal_into_boxed()
+    }
+
+    fn soft_filter_boxed<'a, Predicate, DB>(
+        self,
+        predicate: Predicate,
+    ) -> IntoBoxed<'a, <Self as SoftFilterDsl<Predicate>>::Output, DB>
+    where
+        Self: SoftFilterDsl<Predicate>,
+        <Self as SoftFilterDsl<Predicate>>::Output: BoxedDsl<'a, DB>,
+        DB: Backend,
+    {
+        self.soft_filter(predicate).internal_into_boxed()
+    }
+
+    /// Box the query, keeping only the alive rows unless `include_deleted`, e.g. from a request
+    /// flag of an admin endpoint. Both cases return the same type, for a single code path.
+    fn soft_scoped_boxed<'a, DB>(self, include_deleted: bool) -> IntoBoxed<'a, Self, DB>
+    where
+        Self: SoftDelete + BoxedDsl<'a, DB>,
+        IntoBoxed<'a, Self, DB>:
+            FilterDsl<AliveCondition<Self::Deleted>, Output = IntoBoxed<'a, Self, DB>>,
+        DB: Backend,
+    {
+        let deleted = self.deleted_col();
+        let query = self.internal_into_boxed();
+        if include_deleted {
+            query
+        } else {
+            query.filter(alive_condition(deleted))
+        }
+    }
+}
+
+impl<T> SoftBoxedDsl for T where T: Sized {}
+
+type Aggregate<T, Function> = Select<<T as SoftDeleteDsl>::Output, Function>;
+
+/// Aggregates over the alive rows, e.g. `order::table.soft_sum(order::amount)`.
+///
+/// Those are shortcuts for selecting the aggregate of the [`soft_deleted`](SoftDeleteDsl::soft_deleted)
+/// query, so that reporting code does not forget to soft-scope it.
+pub trait SoftAggregateDsl: SoftDeleteDsl + Sized {
+    fn soft_sum<C>(self, column: C) -> Aggregate<Self, Sum<C>>
+    where
+        C: Expression,
+        C::SqlType: Foldable,
+        Self::Output: SelectDsl<Sum<C>>,
+    {
+        self.soft_deleted().select(sum(column))
+    }
+
+    fn soft_avg<C>(self, column: C) -> Aggregate<Self, Avg<C>>
+    where
+        C: Expression,
+        C::SqlType: Foldable,
+        Self::Output: SelectDsl<Avg<C>>,
+    {
+        self.soft_deleted().select(avg(column))
+    }
+
+    fn soft_max<C>(self, column: C) -> Aggregate<Self, Max<C>>
+    where
+        C: Expression,
+        C::SqlType: SqlOrd + IntoNullable,
+        Self::Output: SelectDsl<Max<C>>,
+    {
+        self.soft_deleted().select(max(column))
+    }
+
+    fn soft_min<C>(self, column: C) -> Aggregate<Self, Min<C>>
+    where
+        C: Expression,
+        C::SqlType: SqlOrd + IntoNullable,
+        Self::Output: SelectDsl<Min<C>>,
+    {
+        self.soft_deleted().select(min(column))
+    }
+}
+
+impl<T> SoftAggregateDsl for T where T: SoftDeleteDsl {}
+
+type CreatedAt<T> = <T as SoftDeleteHistory>::CreatedAt;
+type DeletedAt<T> = <T as SoftDeleteHistory>::DeletedAt;
+type ExistedAt<T, Ts> = LtEq<CreatedAt<T>, Ts>;
+type NotDeletedAt<T, Ts> = Or<IsNull<DeletedAt<T>>, Gt<DeletedAt<T>, Ts>>;
+
+/// The `as_of` method.
+///
+/// This trait is used to reconstruct the historical state of a table: only the rows that were
+/// created, and not yet soft-deleted, at the given point in time are kept.
+#[allow(clippy::wrong_self_convention)]
+pub trait AsOfDsl<Ts>: SoftDeleteHistory {
+    /// The type returned by `.as_of`.
+    type Output;
+    fn as_of(self, at: Ts) -> Self::Output;
+}
+
+impl<T, Ts> AsOfDsl<Ts> for T
+where
+    T: SoftDeleteHistory + FilterDsl<And<ExistedAt<T, Ts>, NotDeletedAt<T, Ts>>>,
+    Ts: AsExpression<SqlTypeOf<T::CreatedAt>> + AsExpression<SqlTypeOf<T::DeletedAt>> + Clone,
+    SqlTypeOf<T::CreatedAt>: SingleValue,
+    SqlTypeOf<T::DeletedAt>: SingleValue,
+    ExistedAt<T, Ts>: Expression<SqlType = Bool>,
+    NotDeletedAt<T, Ts>: Expression<SqlType = Bool>,
+    IsNull<T::DeletedAt>: Expression<SqlType = Bool>,
+    Gt<T::DeletedAt, Ts>: Expression<SqlType = Bool>,
+{
+    type Output = Filter<T, And<ExistedAt<T, Ts>, NotDeletedAt<T, Ts>>>;
+
+    fn as_of(self, at: Ts) -> Self::Output {
+        let existed = self.created_at_col().le(at.clone());
+        let not_deleted = self.deleted_at_col().is_null().or(self.deleted_at_col().gt(at));
+        self.filter(existed.and(not_deleted))
+    }
+}
+
+/// The condition of a deletion time within a range, built by
+/// [`trashed_between`](TrashedBetweenDsl::trashed_between).
+///
+/// Each bound is a comparison of the deletion time with the bound value, and the unbounded ends
+/// are left out. Without any bound, this keeps all the soft-deleted rows.
+#[derive(Debug, Clone, Copy)]
+pub struct DeletedWithin<C, E> {
+    deleted_at: C,
+    start: Bound<E>,
+    end: Bound<E>,
+}
+
+impl<C, E> Expression for DeletedWithin<C, E> {
+    type SqlType = Bool;
+}
+
+impl<C: NonAggregate, E: NonAggregate> NonAggregate for DeletedWithin<C, E> {}
+
+impl<C, E, QS> AppearsOnTable<QS> for DeletedWithin<C, E>
+where
+    C: AppearsOnTable<QS>,
+    E: AppearsOnTable<QS>,
+{
+}
+
+impl<C, E, QS> SelectableExpression<QS> for DeletedWithin<C, E>
+where
+    C: SelectableExpression<QS>,
+    E: SelectableExpression<QS>,
+{
+}
+
+impl<C, E> QueryId for DeletedWithin<C, E> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<C, E, DB> QueryFragment<DB> for DeletedWithin<C, E>
+where
+    C: QueryFragment<DB>,
+    E: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        let bounds = [(&self.start, " >= ", " > "), (&self.end, " <= ", " < ")];
+        let mut first = true;
+        for (bound, included, excluded) in bounds {
+            let (operator, value) = match bound {
+                Bound::Included(value) => (included, value),
+                Bound::Excluded(value) => (excluded, value),
+                Bound::Unbounded => continue,
+            };
+            if !first {
+                out.push_sql(" AND ");
+            }
+            first = false;
+            self.deleted_at.walk_ast(out.reborrow())?;
+            out.push_sql(operator);
+            value.walk_ast(out.reborrow())?;
+        }
+        if first {
+            self.deleted_at.walk_ast(out.reborrow())?;
+            out.push_sql(" IS NOT NULL");
+        }
+        Ok(())
     }
 }
+
+type Within<T, Ts> = DeletedWithin<DeletedAt<T>, AsExpr<Ts, DeletedAt<T>>>;
+
+/// The `trashed_between` method.
+///
+/// This keeps the rows soft-deleted within a time range, e.g. for audit screens and retention
+/// tooling. It is a plain filter, so it composes with ordering and pagination.
+pub trait TrashedBetweenDsl<Ts>: SoftDeleteHistory {
+    /// The type returned by `.trashed_between`.
+    type Output;
+    /// Keep the rows deleted within `range`, e.g. `from..to` for the rows deleted at or after
+    /// `from` and strictly before `to`, or `..=to` for the rows deleted at or before `to`.
+    fn trashed_between<R: RangeBounds<Ts>>(self, range: R) -> Self::Output;
+}
+
+impl<T, Ts> TrashedBetweenDsl<Ts> for T
+where
+    T: SoftDeleteHistory + FilterDsl<Within<T, Ts>>,
+    Ts: AsExpression<SqlTypeOf<T::DeletedAt>> + Clone,
+    SqlTypeOf<T::DeletedAt>: SingleValue,
+{
+    type Output = Filter<T, Within<T, Ts>>;
+
+    fn trashed_between<R: RangeBounds<Ts>>(self, range: R) -> Self::Output {
+        let bound = |bound: Bound<&Ts>| match bound {
+            Bound::Included(value) => Bound::Included(value.clone().as_expression()),
+            Bound::Excluded(value) => Bound::Excluded(value.clone().as_expression()),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let (start, end) = (bound(range.start_bound()), bound(range.end_bound()));
+        let deleted_at = self.deleted_at_col();
+        self.filter(DeletedWithin { deleted_at, start, end })
+    }
+}
+
+/// The `oldest_trash` method.
+///
+/// This is meant for monitoring: the age of the oldest trashed row of a table grows when the
+/// retention jobs stop running, and alerting on it catches them.
+pub trait OldestTrashDsl: SoftDeleteHistory + Sized {
+    /// Select the deletion time of the oldest soft-deleted row, or `NULL` when the trash is empty.
+    fn oldest_trash(self) -> Select<Self, Min<DeletedAt<Self>>>
+    where
+        SqlTypeOf<Self::DeletedAt>: SqlOrd + IntoNullable,
+        Self: SelectDsl<Min<DeletedAt<Self>>>,
+    {
+        let deleted_at = self.deleted_at_col();
+        self.select(min(deleted_at))
+    }
+}
+
+impl<T> OldestTrashDsl for T where T: SoftDeleteHistory {}
@@ -0,0 +1,91 @@
+//! Prometheus gauges of the trash statistics.
+//!
+//! The [`TrashCollector`] reports the [statistics](crate::stats) of its registered tables, e.g.
+//! the tables of a [`SoftDeleteConfig`], every time it is collected, e.g. when the registry it is
+//! registered to is scraped.
+
+use std::{sync::Mutex, time::UNIX_EPOCH};
+
+use diesel::{backend::Backend, deserialize::FromSql, sql_types::BigInt, Connection};
+use prometheus::{
+    core::{Collector, Desc},
+    proto::MetricFamily,
+    GaugeVec, Opts,
+};
+
+use crate::{config::SoftDeleteConfig, stats::TrashRegistry};
+
+/// A Prometheus collector refreshing the trash statistics on demand.
+///
+/// It exports the `softdelete_alive_rows`, `softdelete_trashed_rows`, `softdelete_trash_ratio`
+/// and `softdelete_oldest_trash_timestamp_seconds` gauges, labelled with the `table`. The latter
+/// is only exported for the tables registered with their deletion history. When the statistics
+/// cannot be queried, no gauge is exported.
+pub struct TrashCollector<Conn> {
+    registry: TrashRegistry<Conn>,
+    conn: Mutex<Conn>,
+    alive: GaugeVec,
+    trashed: GaugeVec,
+    ratio: GaugeVec,
+    oldest: GaugeVec,
+}
+
+impl<Conn> TrashCollector<Conn> {
+    /// Collect the statistics of the tables of `registry`, querying them on `conn`.
+    pub fn new(registry: TrashRegistry<Conn>, conn: Conn) -> prometheus::Result<Self> {
+        let gauge = |name: &str, help: &str| GaugeVec::new(Opts::new(name, help), &["table"]);
+        Ok(Self {
+            registry,
+            conn: Mutex::new(conn),
+            alive: gauge("softdelete_alive_rows", "Number of alive rows")?,
+            trashed: gauge("softdelete_trashed_rows", "Number of soft-deleted rows")?,
+            ratio: gauge("softdelete_trash_ratio", "Share of soft-deleted rows")?,
+            oldest: gauge(
+                "softdelete_oldest_trash_timestamp_seconds",
+                "Deletion time of the oldest soft-deleted row",
+            )?,
+        })
+    }
+
+    /// Collect the statistics of the tables of `config`, querying them on `conn`.
+    pub fn config(config: &SoftDeleteConfig<Conn::Backend>, conn: Conn) -> prometheus::Result<Self>
+    where
+        Conn: Connection + 'static,
+        <Conn::Backend as Backend>::QueryBuilder: Default,
+        i64: FromSql<BigInt, Conn::Backend>,
+    {
+        Self::new(TrashRegistry::new().config(config), conn)
+    }
+
+    fn gauges(&self) -> [&GaugeVec; 4] {
+        [&self.alive, &self.trashed, &self.ratio, &self.oldest]
+    }
+}
+
+impl<Conn: Send> Collector for TrashCollector<Conn> {
+    fn desc(&self) -> Vec<&Desc> {
+        self.gauges().into_iter().flat_map(Collector::desc).collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.gauges().into_iter().for_each(GaugeVec::reset);
+        let stats = {
+            let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+            match self.registry.soft_delete_stats(&conn) {
+                Ok(stats) => stats,
+                Err(_) => return Vec::new(),
+            }
+        };
+        for stats in stats {
+            let table = &[stats.table];
+            self.alive.with_label_values(table).set(stats.alive as f64);
+            self.trashed.with_label_values(table).set(stats.trashed as f64);
+            self.ratio.with_label_values(table).set(stats.trash_ratio());
+            if let Some(oldest) = stats.oldest_trashed {
+                let since_epoch = oldest.duration_since(UNIX_EPOCH).unwrap_or_default();
+                self.oldest.with_label_values(table).set(since_epoch.as_secs_f64());
+            }
+        }
+        self.gauges().into_iter().flat_map(Collector::collect).collect()
+    }
+}
@@ -0,0 +1,1278 @@
+//! DDL for the migrations of soft-deletable tables.
+//!
+//! Tables adopt soft delete with the migration built by [`adopt_soft_delete`], adding the flag and
+//! optionally the deletion timestamp and author, marking the rows that were deleted, and creating
+//! an index on the alive rows:
+//!
+//! ```rust,ignore
+//! let adoption = adopt_soft_delete("user")
+//!     .deleted_at("deleted_at")
+//!     .backfill("banned")
+//!     .index("user_email_alive_idx", &["email"]);
+//! let up = adoption.up_sql::<Pg>()?;
+//! // ALTER TABLE "user" ADD COLUMN "deleted" BOOLEAN NOT NULL DEFAULT FALSE;
+//! // ALTER TABLE "user" ADD COLUMN "deleted_at" TIMESTAMPTZ;
+//! // UPDATE "user" SET "deleted" = TRUE, "deleted_at" = CURRENT_TIMESTAMP WHERE banned;
+//! // CREATE INDEX "user_email_alive_idx" ON "user" ("email") WHERE NOT ("user"."deleted");
+//! ```
+//!
+//! MySQL has no partial indexes: its index is on the columns followed by the soft-delete column,
+//! e.g. ``CREATE INDEX `user_email_alive_idx` ON `user` (`email`, `deleted`)``.
+//!
+//! The statements depend on the backend, and are the same for every table: the migrations of
+//! dozens of tables are generated in a loop, either as the `up.sql` / `down.sql` files of
+//! `diesel migration`, or as [`Migration`]s run by `diesel_migrations`.
+//!
+//! The tables of a [`SoftDeleteConfig`] are checked against the catalog of the database:
+//! [`missing_indexes`](SoftDeleteConfig::missing_indexes) reports the tables without an index on
+//! their soft-delete column with the DDL of one, and
+//! [`validate_soft_delete_schema`](SoftDeleteConfig::validate_soft_delete_schema) the soft-delete
+//! columns differing from their declaration, e.g. at startup. The [`IndexLinter`] and the
+//! [`SchemaValidator`] check the tables listed to them, without a registry:
+//!
+//! ```rust,ignore
+//! let validator = SchemaValidator::<Pg>::new().table(user::table).table(post::table);
+//! if let Err(errors) = validator.validate_soft_delete_schema(&conn) {
+//!     for error in errors {
+//!         eprintln!("{}", error);
+//!     }
+//! }
+//! ```
+//!
+//! Unique constraints are also restricted to the alive rows, with a partial index on PostgreSQL,
+//! behind the `postgres` feature, and with a generated column on MySQL.
+//!
+//! Soft-deleted rows stay in their table, so a plain unique constraint keeps refusing the values
+//! of the trashed rows, e.g. signing up again with the email of a deleted account. A partial
+//! unique index only enforces the uniqueness among the alive rows:
+//!
+//! ```rust,ignore
+//! let index = unique_alive_index("user_email_alive_key", user::table).column(user::email);
+//! conn.batch_execute(&index.create_sql()?)?;
+//! // CREATE UNIQUE INDEX "user_email_alive_key" ON "user" ("email") WHERE NOT ("user"."deleted")
+//! ```
+//!
+//! The index condition is the alive condition of the table, e.g. `NOT ("note"."deleted_at" IS
+//! NOT NULL)` for a deletion timestamp, which is the one of the soft methods: the planner can use
+//! the index for the soft-scoped queries as well.
+//!
+//! Upserts only use a partial unique index when their conflict target repeats its condition,
+//! which [`conflict_target`](UniqueAliveIndex::conflict_target) builds from the same definition:
+//!
+//! ```rust,ignore
+//! diesel::insert_into(user::table)
+//!     .values(&new_user)
+//!     .on_conflict(index.conflict_target()?)
+//!     .do_update()
+//!     .set(user::name.eq(excluded(user::name)))
+//!     .execute(&conn)?;
+//! // ... ON CONFLICT ("email") WHERE NOT ("user"."deleted") DO UPDATE SET ...
+//! ```
+//!
+//! MySQL has no partial indexes either: [`unique_alive_key`] builds the same constraint from a
+//! generated column, null on the soft-deleted rows, and the unique key of its registered table:
+//!
+//! ```rust,ignore
+//! let key = unique_alive_key("user_email_alive_key", config.get("user").unwrap())
+//!     .columns(&["email"]);
+//! conn.batch_execute(&key.up_sql()?)?;
+//! // ALTER TABLE `user` ADD COLUMN `key_if_alive` TINYINT
+//! //     GENERATED ALWAYS AS (IF(NOT `deleted`, 1, NULL)) STORED,
+//! //     ADD UNIQUE INDEX `user_email_alive_key` (`email`, `key_if_alive`);
+//! ```
+
+use std::{error::Error as StdError, fmt, marker::PhantomData, time::Duration};
+
+use diesel::{
+    backend::Backend,
+    connection::SimpleConnection,
+    deserialize::{self, QueryableByName},
+    migration::{Migration, RunMigrationsError},
+    query_builder::{nodes::Identifier, QueryBuilder, QueryFragment},
+    result::Error,
+    row::NamedRow,
+    sql_types::Text,
+    Column, Connection, QueryResult, RunQueryDsl, Table,
+};
+#[cfg(feature = "postgres")]
+use diesel::{
+    dsl::sql,
+    expression::SqlLiteral,
+    helper_types::not,
+    pg::{Pg, PgQueryBuilder},
+    sql_types::Bool,
+};
+
+#[cfg(feature = "mysql")]
+use diesel::mysql::{Mysql, MysqlQueryBuilder};
+
+use crate::{
+    config::{DeletionKind, SoftDeleteColumn, SoftDeleteConfig, SoftDeleteTable, TableConfig},
+    hooks::HookConnection,
+    write::ALIVE_SENTINEL,
+};
+
+/// A backend whose DDL and catalog are known: the adoption migrations, the schema checks and the
+/// retention of the trashed rows are generated for it.
+pub trait MigrationBackend: Backend {
+    /// The column definition of the deletion flag, alive by default.
+    const FLAG: &'static str;
+    /// The SQL type of the deletion timestamp.
+    const TIMESTAMP: &'static str;
+    /// The true literal.
+    const TRUE: &'static str;
+    /// The SQL type of the text a value is cast to.
+    const TEXT: &'static str = "TEXT";
+    /// Whether the backend has partial indexes. The indexes of the alive rows of the backends
+    /// without them are on their columns followed by the soft-delete column instead.
+    const PARTIAL_INDEXES: bool = true;
+    /// Whether `DROP INDEX` names the table of the index.
+    const DROP_INDEX_ON_TABLE: bool = false;
+
+    fn query_builder() -> Self::QueryBuilder;
+
+    /// Whether an index of `table` has `column` in its key, or is a partial index whose condition
+    /// depends on it, as listed by the catalog. `alive` is the rendered alive condition.
+    fn has_index_on<Conn>(table: &str, column: &str, alive: &str, conn: &Conn) -> QueryResult<bool>
+    where
+        Conn: Connection<Backend = Self>;
+
+    /// The columns of `table`, none if it does not exist.
+    fn column_definitions<Conn>(table: &str, conn: &Conn) -> QueryResult<Vec<ColumnDefinition>>
+    where
+        Conn: Connection<Backend = Self>;
+
+    /// Whether the catalog type `sql_type` is a boolean.
+    fn is_flag_type(sql_type: &str) -> bool;
+    /// Whether the catalog type `sql_type` can hold a deletion timestamp.
+    fn is_timestamp_type(sql_type: &str) -> bool;
+    /// Whether the catalog `default` is false.
+    fn is_false(default: &str) -> bool;
+
+    /// The condition of the rendered timestamp `column` being older than `age`.
+    fn older_than(column: &str, age: Duration) -> String;
+
+    /// The instant `age` ago, as text: it is read once by the statements comparing their rows to
+    /// the same instant, and bound to each of them with [`push_cutoff`](Self::push_cutoff).
+    fn cutoff_text(age: Duration) -> String;
+
+    /// Push the bound text of an instant read with [`cutoff_text`](Self::cutoff_text), as a value
+    /// comparable to the deletion timestamps. The text is compared as is by default.
+    fn push_cutoff(out: &mut Self::QueryBuilder) {
+        out.push_bind_param();
+    }
+
+    /// The statements bounding, until the end of the transaction, the duration of each statement
+    /// and of its waits for locks. None by default, the backend having no such settings.
+    fn local_timeouts(_statement: Option<Duration>, _lock: Option<Duration>) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Execute the `DELETE` statement `sql` of a batch of a purge, returning the number of rows it
+    /// deleted.
+    fn purge_batch<Conn>(sql: &str, conn: &Conn) -> QueryResult<usize>
+    where
+        Conn: Connection<Backend = Self>,
+    {
+        diesel::sql_query(sql).execute(conn)
+    }
+
+    /// Execute the `DELETE` statement `sql` of a batch of a purge, returning the `key` column of
+    /// each row it deleted, as text, for the [`hooks`](crate::hooks).
+    fn purge_batch_keys<Conn>(sql: &str, key: &str, conn: &Conn) -> QueryResult<Vec<String>>
+    where
+        Conn: HookConnection<Backend = Self>,
+    {
+        conn.load_keys(diesel::sql_query(sql), key)
+    }
+
+    /// Whether `error` is the cancellation of a [`purge_batch`](Self::purge_batch) by its
+    /// timeouts, after which it can be retried. Never by default.
+    fn is_timeout(_error: &Error) -> bool {
+        false
+    }
+}
+
+/// The block of a batch of a purge on PostgreSQL, running `body` with the variable `purged`
+/// declared by `declare`, storing it in the `softdelete.purged` setting, and re-raising the
+/// cancellations with their SQLSTATE as detail.
+#[cfg(feature = "postgres")]
+fn purge_block(declare: &str, body: &str) -> String {
+    format!(
+        "DO $purge$ DECLARE {}; BEGIN {}; \
+         PERFORM set_config('softdelete.purged', purged::TEXT, TRUE); \
+         EXCEPTION WHEN query_canceled OR lock_not_available THEN \
+         RAISE EXCEPTION USING ERRCODE = SQLSTATE, MESSAGE = SQLERRM, \
+         DETAIL = 'SQLSTATE ' || SQLSTATE; END $purge$",
+        declare, body,
+    )
+}
+
+/// The result of an `EXISTS` query.
+struct Exists(bool);
+
+impl<DB: Backend> QueryableByName<DB> for Exists
+where
+    bool: deserialize::FromSql<diesel::sql_types::Bool, DB>,
+{
+    fn build<R: NamedRow<DB>>(row: &R) -> deserialize::Result<Self> {
+        row.get::<diesel::sql_types::Bool, bool>("exists").map(Self)
+    }
+}
+
+/// The result of a [`cutoff`] query.
+struct Cutoff(String);
+
+impl<DB: Backend> QueryableByName<DB> for Cutoff
+where
+    String: deserialize::FromSql<Text, DB>,
+{
+    fn build<R: NamedRow<DB>>(row: &R) -> deserialize::Result<Self> {
+        row.get::<Text, String>("cutoff").map(Self)
+    }
+}
+
+/// Read the instant `age` ago, to bind to the statements comparing their rows to it.
+pub(crate) fn cutoff<Conn>(age: Duration, conn: &Conn) -> QueryResult<String>
+where
+    Conn: Connection,
+    Conn::Backend: MigrationBackend,
+    String: deserialize::FromSql<Text, Conn::Backend>,
+{
+    let query =
+        format!("SELECT {} AS cutoff", <Conn::Backend as MigrationBackend>::cutoff_text(age));
+    let Cutoff(cutoff) = diesel::sql_query(query).get_result(conn)?;
+    Ok(cutoff)
+}
+
+/// A row of the index names of a table.
+#[cfg(feature = "sqlite")]
+struct IndexName(String);
+
+#[cfg(feature = "sqlite")]
+impl QueryableByName<diesel::sqlite::Sqlite> for IndexName {
+    fn build<R: NamedRow<diesel::sqlite::Sqlite>>(row: &R) -> deserialize::Result<Self> {
+        row.get::<Text, String>("name").map(Self)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl MigrationBackend for Pg {
+    const FLAG: &'static str = "BOOLEAN NOT NULL DEFAULT FALSE";
+    const TIMESTAMP: &'static str = "TIMESTAMPTZ";
+    const TRUE: &'static str = "TRUE";
+
+    fn query_builder() -> PgQueryBuilder {
+        PgQueryBuilder::default()
+    }
+
+    /// The key columns of the indexes are listed by `pg_index`, and the columns of their
+    /// expressions and conditions are their dependencies in `pg_depend`.
+    fn has_index_on<Conn>(table: &str, column: &str, _alive: &str, conn: &Conn) -> QueryResult<bool>
+    where
+        Conn: Connection<Backend = Self>,
+    {
+        let Exists(exists) = diesel::sql_query(
+            "SELECT EXISTS (SELECT FROM pg_index i \
+             JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attname = $2 \
+             WHERE i.indrelid = to_regclass(quote_ident($1)) AND (a.attnum = ANY (i.indkey) \
+             OR EXISTS (SELECT FROM pg_depend d WHERE d.classid = 'pg_class'::regclass \
+             AND d.objid = i.indexrelid AND d.refclassid = 'pg_class'::regclass \
+             AND d.refobjid = i.indrelid AND d.refobjsubid = a.attnum))) AS \"exists\"",
+        )
+        .bind::<Text, _>(table)
+        .bind::<Text, _>(column)
+        .get_result(conn)?;
+        Ok(exists)
+    }
+
+    fn column_definitions<Conn>(table: &str, conn: &Conn) -> QueryResult<Vec<ColumnDefinition>>
+    where
+        Conn: Connection<Backend = Self>,
+    {
+        diesel::sql_query(
+            "SELECT column_name AS name, data_type AS sql_type, is_nullable = 'YES' AS nullable, \
+             column_default AS \"default\" FROM information_schema.columns \
+             WHERE table_name = $1 AND table_schema = ANY (current_schemas(true))",
+        )
+        .bind::<Text, _>(table)
+        .load(conn)
+    }
+
+    fn is_flag_type(sql_type: &str) -> bool {
+        sql_type == "boolean"
+    }
+
+    fn is_timestamp_type(sql_type: &str) -> bool {
+        sql_type.starts_with("timestamp") || sql_type == "date"
+    }
+
+    fn is_false(default: &str) -> bool {
+        default == "false"
+    }
+
+    fn older_than(column: &str, age: Duration) -> String {
+        format!("{} < CURRENT_TIMESTAMP - INTERVAL '{} seconds'", column, age.as_secs())
+    }
+
+    fn cutoff_text(age: Duration) -> String {
+        format!("CAST(CURRENT_TIMESTAMP - INTERVAL '{} seconds' AS TEXT)", age.as_secs())
+    }
+
+    fn push_cutoff(out: &mut PgQueryBuilder) {
+        out.push_sql("CAST(");
+        out.push_bind_param();
+        out.push_sql(" AS TIMESTAMPTZ)");
+    }
+
+    fn local_timeouts(statement: Option<Duration>, lock: Option<Duration>) -> Vec<String> {
+        let statement =
+            statement.map(|t| format!("SET LOCAL statement_timeout = {}", t.as_millis().max(1)));
+        let lock = lock.map(|t| format!("SET LOCAL lock_timeout = {}", t.as_millis().max(1)));
+        statement.into_iter().chain(lock).collect()
+    }
+
+    /// Diesel does not expose the SQLSTATE of the errors, and their messages depend on
+    /// `lc_messages`: the `DELETE` runs in a block re-raising its cancellations with their
+    /// SQLSTATE as detail, and storing its row count in a setting local to the transaction.
+    fn purge_batch<Conn>(delete: &str, conn: &Conn) -> QueryResult<usize>
+    where
+        Conn: Connection<Backend = Self>,
+    {
+        conn.execute(&purge_block(
+            "purged BIGINT",
+            &format!("{}; GET DIAGNOSTICS purged = ROW_COUNT", delete),
+        ))?;
+        let purged = diesel::select(sql::<diesel::sql_types::BigInt>(
+            "current_setting('softdelete.purged')::BIGINT",
+        ))
+        .get_result::<i64>(conn)?;
+        Ok(purged as usize)
+    }
+
+    /// As with [`purge_batch`](Self::purge_batch), the `DELETE` runs in a block, which stores the
+    /// JSON array of the deleted keys in the setting.
+    fn purge_batch_keys<Conn>(delete: &str, key: &str, conn: &Conn) -> QueryResult<Vec<String>>
+    where
+        Conn: HookConnection<Backend = Self>,
+    {
+        let mut quoted_key = PgQueryBuilder::default();
+        quoted_key.push_identifier(key)?;
+        conn.execute(&purge_block(
+            "purged TEXT",
+            &format!(
+                "WITH deleted AS ({} RETURNING CAST({} AS TEXT) AS key) \
+                 SELECT CAST(COALESCE(json_agg(key), '[]') AS TEXT) INTO purged FROM deleted",
+                delete,
+                quoted_key.finish(),
+            ),
+        ))?;
+        diesel::select(sql::<Text>(
+            "json_array_elements_text(CAST(current_setting('softdelete.purged') AS JSON))",
+        ))
+        .load(conn)
+    }
+
+    /// The `statement_timeout` and `lock_timeout` cancellations, SQLSTATE 57014 and 55P03, as
+    /// re-raised by [`purge_batch`](Self::purge_batch).
+    fn is_timeout(error: &Error) -> bool {
+        match error {
+            Error::DatabaseError(_, info) => {
+                matches!(info.details(), Some("SQLSTATE 57014") | Some("SQLSTATE 55P03"))
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl MigrationBackend for diesel::sqlite::Sqlite {
+    const FLAG: &'static str = "BOOLEAN NOT NULL DEFAULT 0";
+    const TIMESTAMP: &'static str = "TIMESTAMP";
+    const TRUE: &'static str = "1";
+
+    fn query_builder() -> diesel::sqlite::SqliteQueryBuilder {
+        diesel::sqlite::SqliteQueryBuilder::default()
+    }
+
+    /// The key columns of the indexes are listed by `pragma_index_xinfo`, but the columns of
+    /// their conditions are not: a partial index counts when the `alive` condition can use it,
+    /// SQLite failing to plan a query restricted to an index that does not apply.
+    fn has_index_on<Conn>(table: &str, column: &str, alive: &str, conn: &Conn) -> QueryResult<bool>
+    where
+        Conn: Connection<Backend = Self>,
+    {
+        let Exists(exists) = diesel::sql_query(
+            "SELECT EXISTS (SELECT 1 FROM pragma_index_list(?) list \
+             JOIN pragma_index_xinfo(list.name) info WHERE info.key AND info.name = ?) AS \"exists\"",
+        )
+        .bind::<Text, _>(table)
+        .bind::<Text, _>(column)
+        .get_result(conn)?;
+        if exists {
+            return Ok(true);
+        }
+        let partial = diesel::sql_query("SELECT name FROM pragma_index_list(?) WHERE partial")
+            .bind::<Text, _>(table)
+            .load::<IndexName>(conn)?;
+        for IndexName(index) in partial {
+            let mut query = Self::query_builder();
+            query.push_sql("EXPLAIN QUERY PLAN SELECT 1 FROM ");
+            query.push_identifier(table)?;
+            query.push_sql(" INDEXED BY ");
+            query.push_identifier(&index)?;
+            query.push_sql(" WHERE ");
+            query.push_sql(alive);
+            match conn.execute(&query.finish()) {
+                Ok(_) => return Ok(true),
+                Err(Error::DatabaseError(..)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(false)
+    }
+
+    fn column_definitions<Conn>(table: &str, conn: &Conn) -> QueryResult<Vec<ColumnDefinition>>
+    where
+        Conn: Connection<Backend = Self>,
+    {
+        diesel::sql_query(
+            "SELECT name, type AS sql_type, \"notnull\" = 0 AS nullable, dflt_value AS \"default\" \
+             FROM pragma_table_info(?)",
+        )
+        .bind::<Text, _>(table)
+        .load(conn)
+    }
+
+    fn is_flag_type(sql_type: &str) -> bool {
+        sql_type.to_uppercase().starts_with("BOOL")
+    }
+
+    /// The timestamps of SQLite are stored as text or numbers, of any declared type.
+    fn is_timestamp_type(_sql_type: &str) -> bool {
+        true
+    }
+
+    fn is_false(default: &str) -> bool {
+        default.eq_ignore_ascii_case("false") || default == "0"
+    }
+
+    fn older_than(column: &str, age: Duration) -> String {
+        format!("{} < datetime('now', '-{} seconds')", column, age.as_secs())
+    }
+
+    fn cutoff_text(age: Duration) -> String {
+        format!("datetime('now', '-{} seconds')", age.as_secs())
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl MigrationBackend for Mysql {
+    const FLAG: &'static str = "BOOLEAN NOT NULL DEFAULT FALSE";
+    const TIMESTAMP: &'static str = "DATETIME(6)";
+    const TRUE: &'static str = "TRUE";
+    const TEXT: &'static str = "CHAR";
+    const PARTIAL_INDEXES: bool = false;
+    const DROP_INDEX_ON_TABLE: bool = true;
+
+    fn query_builder() -> MysqlQueryBuilder {
+        MysqlQueryBuilder::default()
+    }
+
+    /// MySQL has no partial indexes: the columns of the indexes are listed by
+    /// `information_schema.statistics`.
+    fn has_index_on<Conn>(table: &str, column: &str, _alive: &str, conn: &Conn) -> QueryResult<bool>
+    where
+        Conn: Connection<Backend = Self>,
+    {
+        let Exists(exists) = diesel::sql_query(
+            "SELECT EXISTS (SELECT 1 FROM information_schema.statistics \
+             WHERE table_schema = DATABASE() AND table_name = ? AND column_name = ?) AS `exists`",
+        )
+        .bind::<Text, _>(table)
+        .bind::<Text, _>(column)
+        .get_result(conn)?;
+        Ok(exists)
+    }
+
+    fn column_definitions<Conn>(table: &str, conn: &Conn) -> QueryResult<Vec<ColumnDefinition>>
+    where
+        Conn: Connection<Backend = Self>,
+    {
+        diesel::sql_query(
+            "SELECT column_name AS name, data_type AS sql_type, is_nullable = 'YES' AS nullable, \
+             column_default AS `default` FROM information_schema.columns \
+             WHERE table_schema = DATABASE() AND table_name = ?",
+        )
+        .bind::<Text, _>(table)
+        .load(conn)
+    }
+
+    /// `BOOLEAN` is an alias of `TINYINT(1)`.
+    fn is_flag_type(sql_type: &str) -> bool {
+        sql_type == "tinyint"
+    }
+
+    fn is_timestamp_type(sql_type: &str) -> bool {
+        matches!(sql_type, "datetime" | "timestamp" | "date")
+    }
+
+    fn is_false(default: &str) -> bool {
+        default == "0"
+    }
+
+    fn older_than(column: &str, age: Duration) -> String {
+        format!("{} < CURRENT_TIMESTAMP - INTERVAL {} SECOND", column, age.as_secs())
+    }
+
+    fn cutoff_text(age: Duration) -> String {
+        format!("CAST(CURRENT_TIMESTAMP - INTERVAL {} SECOND AS CHAR)", age.as_secs())
+    }
+}
+
+/// The migration of a table adopting soft delete, built by [`adopt_soft_delete`].
+#[derive(Debug, Clone)]
+pub struct AdoptSoftDelete {
+    table: String,
+    flag: Option<String>,
+    deleted_at: Option<String>,
+    deleted_by: Option<(String, String)>,
+    backfill: Option<String>,
+    index: Option<(String, Vec<String>)>,
+}
+
+/// Start the migration of `table` adopting soft delete, with a `deleted` flag.
+pub fn adopt_soft_delete(table: impl Into<String>) -> AdoptSoftDelete {
+    AdoptSoftDelete {
+        table: table.into(),
+        flag: Some("deleted".to_owned()),
+        deleted_at: None,
+        deleted_by: None,
+        backfill: None,
+        index: None,
+    }
+}
+
+impl AdoptSoftDelete {
+    /// Name the deletion flag `name` instead of `deleted`.
+    pub fn flag(mut self, name: impl Into<String>) -> Self {
+        self.flag = Some(name.into());
+        self
+    }
+
+    /// Add no deletion flag, for the tables soft-deleted with a timestamp only, which requires
+    /// [`deleted_at`](Self::deleted_at).
+    pub fn without_flag(mut self) -> Self {
+        self.flag = None;
+        self
+    }
+
+    /// Also add the nullable deletion timestamp `name`.
+    pub fn deleted_at(mut self, name: impl Into<String>) -> Self {
+        self.deleted_at = Some(name.into());
+        self
+    }
+
+    /// Also add the nullable column `name` of type `sql_type` recording who deleted the row, e.g.
+    /// `deleted_by("deleted_by", "INTEGER REFERENCES \"user\" (id)")`.
+    pub fn deleted_by(mut self, name: impl Into<String>, sql_type: impl Into<String>) -> Self {
+        self.deleted_by = Some((name.into(), sql_type.into()));
+        self
+    }
+
+    /// Mark the existing rows matching the SQL `condition` as deleted, e.g. the rows of a former
+    /// `status = 'archived'` convention, at the time of the migration.
+    pub fn backfill(mut self, condition: impl Into<String>) -> Self {
+        self.backfill = Some(condition.into());
+        self
+    }
+
+    /// Create the index `name` on the `columns` of the alive rows.
+    pub fn index(mut self, name: impl Into<String>, columns: &[&str]) -> Self {
+        self.index = Some((name.into(), columns.iter().map(|&c| c.to_owned()).collect()));
+        self
+    }
+
+    /// The statements adding the columns, backfilling them and creating the index, for the
+    /// `up.sql` of a migration.
+    ///
+    /// This fails if the table has neither a flag nor a deletion timestamp, or if the index has
+    /// no column.
+    pub fn up_sql<DB: MigrationBackend>(&self) -> QueryResult<String> {
+        let deleted = self.deleted()?;
+        let mut out = DB::query_builder();
+        for (column, definition) in self.columns::<DB>() {
+            out.push_sql("ALTER TABLE ");
+            out.push_identifier(&self.table)?;
+            out.push_sql(" ADD COLUMN ");
+            out.push_identifier(column)?;
+            out.push_sql(" ");
+            out.push_sql(definition);
+            out.push_sql(";\n");
+        }
+
+        if let Some(condition) = &self.backfill {
+            out.push_sql("UPDATE ");
+            out.push_identifier(&self.table)?;
+            out.push_sql(" SET ");
+            if let Some(flag) = &self.flag {
+                out.push_identifier(flag)?;
+                out.push_sql(" = ");
+                out.push_sql(DB::TRUE);
+                if self.deleted_at.is_some() {
+                    out.push_sql(", ");
+                }
+            }
+            if let Some(deleted_at) = &self.deleted_at {
+                out.push_identifier(deleted_at)?;
+                out.push_sql(" = CURRENT_TIMESTAMP");
+            }
+            out.push_sql(" WHERE ");
+            out.push_sql(condition);
+            out.push_sql(";\n");
+        }
+
+        if let Some((name, columns)) = &self.index {
+            if columns.is_empty() {
+                return Err(Error::QueryBuilderError(
+                    format!("the index {:?} has no column", name).into(),
+                ));
+            }
+            out.push_sql("CREATE INDEX ");
+            out.push_identifier(name)?;
+            out.push_sql(" ON ");
+            out.push_identifier(&self.table)?;
+            out.push_sql(" (");
+            for (i, column) in columns.iter().enumerate() {
+                if i > 0 {
+                    out.push_sql(", ");
+                }
+                out.push_identifier(column)?;
+            }
+            if DB::PARTIAL_INDEXES {
+                // The alive condition as the soft methods render it, for the planner to match it
+                out.push_sql(") WHERE NOT (");
+                out.push_identifier(&self.table)?;
+                out.push_sql(".");
+                out.push_identifier(deleted)?;
+                if self.flag.is_none() {
+                    out.push_sql(" IS NOT NULL");
+                }
+            } else {
+                out.push_sql(", ");
+                out.push_identifier(deleted)?;
+            }
+            out.push_sql(");\n");
+        }
+        Ok(out.finish())
+    }
+
+    /// The statements dropping the index and the columns, for the `down.sql` of a migration.
+    ///
+    /// The deletion state of the rows is lost.
+    pub fn down_sql<DB: MigrationBackend>(&self) -> QueryResult<String> {
+        self.deleted()?;
+        let mut out = DB::query_builder();
+        if let Some((name, _)) = &self.index {
+            out.push_sql("DROP INDEX ");
+            out.push_identifier(name)?;
+            if DB::DROP_INDEX_ON_TABLE {
+                out.push_sql(" ON ");
+                out.push_identifier(&self.table)?;
+            }
+            out.push_sql(";\n");
+        }
+        for (column, _) in self.columns::<DB>().iter().rev() {
+            out.push_sql("ALTER TABLE ");
+            out.push_identifier(&self.table)?;
+            out.push_sql(" DROP COLUMN ");
+            out.push_identifier(column)?;
+            out.push_sql(";\n");
+        }
+        Ok(out.finish())
+    }
+
+    /// The migration `version`, to run along the other migrations of `diesel_migrations`.
+    pub fn migration<DB: MigrationBackend>(self, version: impl Into<String>) -> Adoption<DB> {
+        Adoption { version: version.into(), adoption: self, _backend: PhantomData }
+    }
+
+    /// The column the soft methods filter on: the flag, or else the deletion timestamp.
+    fn deleted(&self) -> QueryResult<&String> {
+        self.flag.as_ref().or(self.deleted_at.as_ref()).ok_or_else(|| {
+            Error::QueryBuilderError(
+                format!("the table {:?} has neither a flag nor a deletion timestamp", self.table)
+                    .into(),
+            )
+        })
+    }
+
+    /// The added columns and their definition.
+    fn columns<DB: MigrationBackend>(&self) -> Vec<(&String, &str)> {
+        let flag = self.flag.iter().map(|flag| (flag, DB::FLAG));
+        let deleted_at = self.deleted_at.iter().map(|column| (column, DB::TIMESTAMP));
+        let deleted_by = self.deleted_by.iter().map(|(column, ty)| (column, ty.as_str()));
+        flag.chain(deleted_at).chain(deleted_by).collect()
+    }
+}
+
+/// The [`Migration`] of a table adopting soft delete on the backend `DB`.
+#[derive(Debug, Clone)]
+pub struct Adoption<DB> {
+    version: String,
+    adoption: AdoptSoftDelete,
+    _backend: PhantomData<DB>,
+}
+
+impl<DB: MigrationBackend> Migration for Adoption<DB> {
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn run(&self, conn: &dyn SimpleConnection) -> Result<(), RunMigrationsError> {
+        Ok(conn.batch_execute(&self.adoption.up_sql::<DB>()?)?)
+    }
+
+    fn revert(&self, conn: &dyn SimpleConnection) -> Result<(), RunMigrationsError> {
+        Ok(conn.batch_execute(&self.adoption.down_sql::<DB>()?)?)
+    }
+}
+
+/// A soft-deletable table without an index on its soft-delete column, reported by
+/// [`missing_indexes`](SoftDeleteConfig::missing_indexes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingIndex {
+    pub table: &'static str,
+    pub column: String,
+    /// The DDL of a partial index on the primary key of the alive rows, or of an index on the
+    /// soft-delete column on the backends without partial indexes.
+    pub suggestion: String,
+}
+
+impl fmt::Display for MissingIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "table `{}` has no index on its soft-delete column `{}`, e.g. {}",
+            self.table, self.column, self.suggestion,
+        )
+    }
+}
+
+/// A difference between the declaration of a soft-deletable table and the database, reported by
+/// [`validate_soft_delete_schema`](SoftDeleteConfig::validate_soft_delete_schema).
+#[derive(Debug)]
+pub enum SchemaError {
+    /// The catalog of the database could not be queried.
+    Query(Error),
+    MissingTable {
+        table: &'static str,
+    },
+    MissingColumn {
+        table: &'static str,
+        column: String,
+    },
+    /// The soft-delete column differs from what its declaration expects, e.g. `NOT NULL`.
+    Mismatch {
+        table: &'static str,
+        column: String,
+        expected: &'static str,
+        found: String,
+    },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Query(e) => write!(f, "failed to query the schema: {}", e),
+            Self::MissingTable { table } => write!(f, "table `{}` does not exist", table),
+            Self::MissingColumn { table, column } => {
+                write!(f, "soft-delete column `{}` of table `{}` does not exist", column, table)
+            }
+            Self::Mismatch { table, column, expected, found } => write!(
+                f,
+                "soft-delete column `{}` of table `{}` should be {}, found {}",
+                column, table, expected, found,
+            ),
+        }
+    }
+}
+
+impl StdError for SchemaError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Query(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A column of a table, as described by the catalog of the database.
+#[derive(Debug, Clone)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub sql_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+}
+
+impl<DB: Backend> QueryableByName<DB> for ColumnDefinition
+where
+    String: deserialize::FromSql<Text, DB>,
+    bool: deserialize::FromSql<diesel::sql_types::Bool, DB>,
+    Option<String>: deserialize::FromSql<diesel::sql_types::Nullable<Text>, DB>,
+{
+    fn build<R: NamedRow<DB>>(row: &R) -> deserialize::Result<Self> {
+        use diesel::sql_types::{Bool, Nullable};
+
+        Ok(Self {
+            name: row.get::<Text, _>("name")?,
+            sql_type: row.get::<Text, _>("sql_type")?,
+            nullable: row.get::<Bool, _>("nullable")?,
+            default: row.get::<Nullable<Text>, _>("default")?,
+        })
+    }
+}
+
+/// Check that a set of soft-deletable tables have an index on their soft-delete column in the
+/// database, as [`SoftDeleteConfig::missing_indexes`] does for the tables of a registry.
+pub struct IndexLinter<DB: Backend> {
+    config: SoftDeleteConfig<DB>,
+}
+
+impl<DB: Backend> Default for IndexLinter<DB> {
+    fn default() -> Self {
+        Self { config: SoftDeleteConfig::default() }
+    }
+}
+
+impl<DB> IndexLinter<DB>
+where
+    DB: MigrationBackend,
+    DB::QueryBuilder: Default,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a soft-deletable table to check.
+    pub fn table<T>(mut self, table: T) -> Self
+    where
+        T: Table<FromClause = Identifier<'static>> + SoftDeleteColumn,
+        T::PrimaryKey: Column,
+        T::Deleted: QueryFragment<DB> + Send + Sync + 'static,
+    {
+        self.config = self.config.table(TableConfig::new(table));
+        self
+    }
+
+    /// The registered tables without an index on their soft-delete column, in registration
+    /// order.
+    pub fn check<Conn>(&self, conn: &Conn) -> QueryResult<Vec<MissingIndex>>
+    where
+        Conn: Connection<Backend = DB>,
+    {
+        self.config.missing_indexes(conn)
+    }
+}
+
+/// Check that the soft-delete columns of a set of tables exist in the database as they are
+/// declared, as [`SoftDeleteConfig::validate_soft_delete_schema`] does for the tables of a
+/// registry.
+pub struct SchemaValidator<DB: Backend> {
+    config: SoftDeleteConfig<DB>,
+}
+
+impl<DB: Backend> Default for SchemaValidator<DB> {
+    fn default() -> Self {
+        Self { config: SoftDeleteConfig::default() }
+    }
+}
+
+impl<DB> SchemaValidator<DB>
+where
+    DB: MigrationBackend,
+    DB::QueryBuilder: Default,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a soft-deletable table to validate.
+    pub fn table<T>(mut self, table: T) -> Self
+    where
+        T: Table<FromClause = Identifier<'static>> + SoftDeleteColumn,
+        T::PrimaryKey: Column,
+        T::Deleted: QueryFragment<DB> + Send + Sync + 'static,
+    {
+        self.config = self.config.table(TableConfig::new(table));
+        self
+    }
+
+    /// Validate the registered tables against the database, returning every difference found.
+    pub fn validate_soft_delete_schema<Conn>(&self, conn: &Conn) -> Result<(), Vec<SchemaError>>
+    where
+        Conn: Connection<Backend = DB>,
+    {
+        self.config.validate_soft_delete_schema(conn)
+    }
+}
+
+impl<DB> SoftDeleteConfig<DB>
+where
+    DB: MigrationBackend,
+    DB::QueryBuilder: Default,
+{
+    /// The registered tables without an index on their soft-delete column in the database, i.e.
+    /// neither an index on the column nor a partial index whose condition depends on it, in
+    /// registration order.
+    ///
+    /// Without one, the alive condition of every soft-scoped query is a sequential scan. The
+    /// suggested index is a partial one on the alive rows, unless the backend has no partial
+    /// indexes or the alive condition reads the clock or another table, which index predicates
+    /// cannot: it is then an index on the soft-delete column. The tables whose deletions are
+    /// recorded are not checked, their deletions being looked up by primary key.
+    pub fn missing_indexes<Conn>(&self, conn: &Conn) -> QueryResult<Vec<MissingIndex>>
+    where
+        Conn: Connection<Backend = DB>,
+    {
+        let mut missing = Vec::new();
+        for table in self.tables() {
+            // The recorded deletions are looked up by primary key, which is indexed.
+            if table.kind() == DeletionKind::Recorded {
+                continue;
+            }
+            let alive = table.not_deleted_sql()?;
+            let partial = DB::PARTIAL_INDEXES
+                && matches!(
+                    table.kind(),
+                    DeletionKind::Flag | DeletionKind::Timestamp | DeletionKind::Sentinel
+                );
+            if DB::has_index_on(table.name(), table.column(), &alive, conn)? {
+                continue;
+            }
+            let mut out = DB::query_builder();
+            out.push_sql("CREATE INDEX ");
+            out.push_identifier(&format!("{}_alive_idx", table.name()))?;
+            out.push_sql(" ON ");
+            out.push_sql(&table.quoted_name()?);
+            out.push_sql(" (");
+            if partial {
+                out.push_identifier(table.primary_key())?;
+                out.push_sql(") WHERE ");
+                out.push_sql(&alive);
+            } else {
+                out.push_identifier(table.column())?;
+                out.push_sql(")");
+            }
+            missing.push(MissingIndex {
+                table: table.name(),
+                column: table.column().to_owned(),
+                suggestion: out.finish(),
+            });
+        }
+        Ok(missing)
+    }
+
+    /// Check that the soft-delete columns of the registered tables exist in the database as they
+    /// are declared, e.g. at startup, to catch the drift between the `table!` declarations and
+    /// the actual schema, returning every difference found:
+    ///
+    /// - a deleted flag is a `NOT NULL` boolean, false by default, and its expiry column a
+    ///   timestamp;
+    /// - a deletion timestamp, scheduled or not, is a nullable timestamp;
+    /// - a deletion timestamp with a sentinel is a `NOT NULL` timestamp, the sentinel by default;
+    /// - the foreign key of an inherited deletion and the primary key of a recorded one exist.
+    ///
+    /// SQLite columns may have any declared type: the type of timestamps is not checked on SQLite.
+    pub fn validate_soft_delete_schema<Conn>(&self, conn: &Conn) -> Result<(), Vec<SchemaError>>
+    where
+        Conn: Connection<Backend = DB>,
+    {
+        let mut errors = Vec::new();
+        for table in self.tables() {
+            if let Err(e) = validate_table(table, conn, &mut errors) {
+                errors.push(SchemaError::Query(e));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn validate_table<DB, Conn>(
+    table: &SoftDeleteTable<DB>,
+    conn: &Conn,
+    errors: &mut Vec<SchemaError>,
+) -> QueryResult<()>
+where
+    DB: MigrationBackend,
+    Conn: Connection<Backend = DB>,
+{
+    let column = table.column();
+    let columns = DB::column_definitions(table.name(), conn)?;
+    if columns.is_empty() {
+        errors.push(SchemaError::MissingTable { table: table.name() });
+        return Ok(());
+    }
+    let find = |column: &str| columns.iter().find(|definition| definition.name == column);
+    let definition = match find(column) {
+        Some(definition) => definition,
+        None => {
+            let column = column.to_owned();
+            errors.push(SchemaError::MissingColumn { table: table.name(), column });
+            return Ok(());
+        }
+    };
+
+    let mut mismatch = |column: &str, expected, found: String| {
+        let column = column.to_owned();
+        errors.push(SchemaError::Mismatch { table: table.name(), column, expected, found });
+    };
+    let default = || definition.default.clone().unwrap_or_else(|| "no default".to_owned());
+    let kind = table.kind();
+    let (is_type, expected_type) = match kind {
+        DeletionKind::Flag | DeletionKind::Expiring { .. } => {
+            (DB::is_flag_type(&definition.sql_type), "a boolean")
+        }
+        DeletionKind::Timestamp | DeletionKind::Sentinel | DeletionKind::Scheduled => {
+            (DB::is_timestamp_type(&definition.sql_type), "a timestamp")
+        }
+        DeletionKind::Inherited | DeletionKind::Recorded => return Ok(()),
+    };
+    if !is_type {
+        mismatch(column, expected_type, definition.sql_type.clone());
+    }
+    match kind {
+        DeletionKind::Flag | DeletionKind::Expiring { .. } | DeletionKind::Sentinel
+            if definition.nullable =>
+        {
+            mismatch(column, "NOT NULL", "nullable".to_owned())
+        }
+        DeletionKind::Timestamp | DeletionKind::Scheduled if !definition.nullable => {
+            mismatch(column, "nullable", "NOT NULL".to_owned())
+        }
+        _ => {}
+    }
+    match kind {
+        DeletionKind::Flag | DeletionKind::Expiring { .. }
+            if !definition.default.as_deref().is_some_and(DB::is_false) =>
+        {
+            mismatch(column, "false by default", default())
+        }
+        DeletionKind::Sentinel
+            if !definition.default.as_deref().is_some_and(|d| d.contains(ALIVE_SENTINEL)) =>
+        {
+            mismatch(column, "the alive sentinel by default", default())
+        }
+        _ => {}
+    }
+    if let DeletionKind::Expiring { expires_at } = kind {
+        match find(expires_at) {
+            Some(definition) if !DB::is_timestamp_type(&definition.sql_type) => {
+                mismatch(expires_at, "a timestamp", definition.sql_type.clone())
+            }
+            Some(_) => {}
+            None => {
+                let column = expires_at.to_owned();
+                errors.push(SchemaError::MissingColumn { table: table.name(), column });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A unique index on the alive rows of a soft-deletable table, built by [`unique_alive_index`].
+#[cfg(feature = "postgres")]
+#[derive(Debug, Clone)]
+pub struct UniqueAliveIndex<T> {
+    name: String,
+    table: T,
+    columns: Vec<&'static str>,
+}
+
+/// Start a unique index named `name` on the alive rows of `table`.
+#[cfg(feature = "postgres")]
+pub fn unique_alive_index<T>(name: impl Into<String>, table: T) -> UniqueAliveIndex<T>
+where
+    T: SoftDeleteColumn + Table,
+{
+    UniqueAliveIndex { name: name.into(), table, columns: Vec::new() }
+}
+
+#[cfg(feature = "postgres")]
+impl<T> UniqueAliveIndex<T>
+where
+    T: SoftDeleteColumn + Table,
+    T::FromClause: QueryFragment<Pg>,
+    not<T::Deleted>: QueryFragment<Pg>,
+{
+    /// Add a column to the unique key, after the previous ones.
+    pub fn column<C: Column<Table = T>>(mut self, _column: C) -> Self {
+        self.columns.push(C::NAME);
+        self
+    }
+
+    /// The `CREATE UNIQUE INDEX` statement, for the `up.sql` of a migration.
+    ///
+    /// This fails if no column was added, or if the alive condition of the table reads the clock
+    /// or another table, which an index predicate cannot: on the tables whose deletions are
+    /// scheduled, expire, are inherited or recorded.
+    pub fn create_sql(&self) -> QueryResult<String> {
+        let mut out = PgQueryBuilder::default();
+        out.push_sql("CREATE UNIQUE INDEX ");
+        out.push_identifier(&self.name)?;
+        out.push_sql(" ON ");
+        self.table.from_clause().to_sql(&mut out)?;
+        out.push_sql(" ");
+        self.push_key(&mut out)?;
+        Ok(out.finish())
+    }
+
+    /// The `ON CONFLICT` target of the upserts checked against the index, e.g. `("email") WHERE
+    /// NOT ("user"."deleted")`, to pass to `on_conflict`.
+    ///
+    /// This fails as [`create_sql`](Self::create_sql) does.
+    pub fn conflict_target(&self) -> QueryResult<SqlLiteral<Bool>> {
+        let mut out = PgQueryBuilder::default();
+        self.push_key(&mut out)?;
+        Ok(sql(&out.finish()))
+    }
+
+    /// Push the columns and the condition of the index, shared by its definition and the
+    /// conflict targets.
+    fn push_key(&self, out: &mut PgQueryBuilder) -> QueryResult<()> {
+        if self.columns.is_empty() {
+            return Err(Error::QueryBuilderError(
+                format!("the unique index {:?} has no column", self.name).into(),
+            ));
+        }
+        if !matches!(T::KIND, DeletionKind::Flag | DeletionKind::Timestamp | DeletionKind::Sentinel)
+        {
+            return Err(Error::QueryBuilderError(
+                format!(
+                    "the alive rows of the unique index {:?} cannot be told by its predicate",
+                    self.name
+                )
+                .into(),
+            ));
+        }
+
+        out.push_sql("(");
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            out.push_identifier(column)?;
+        }
+        out.push_sql(") WHERE ");
+        self.table.not_deleted().to_sql(out)
+    }
+
+    /// The `DROP INDEX` statement, for the `down.sql` of a migration.
+    pub fn drop_sql(&self) -> QueryResult<String> {
+        let mut out = PgQueryBuilder::default();
+        out.push_sql("DROP INDEX ");
+        out.push_identifier(&self.name)?;
+        Ok(out.finish())
+    }
+}
+
+/// A unique key on the alive rows of a MySQL table, built by [`unique_alive_key`].
+///
+/// MySQL has no partial indexes: the key is a unique index on its columns and a generated column,
+/// `key_if_alive` by default, holding `1` on the alive rows and `NULL` on the soft-deleted ones.
+/// As `NULL`s never conflict, only the alive rows sharing the columns do.
+#[derive(Debug, Clone)]
+pub struct UniqueAliveKey {
+    name: String,
+    table: &'static str,
+    alive: Option<String>,
+    columns: Vec<String>,
+    generated: String,
+}
+
+/// Start the unique key `name` on the alive rows of `table`, for MySQL.
+pub fn unique_alive_key<DB: Backend>(
+    name: impl Into<String>,
+    table: &SoftDeleteTable<DB>,
+) -> UniqueAliveKey {
+    let column = mysql_identifier(table.column());
+    let alive = match table.kind() {
+        DeletionKind::Flag => Some(format!("NOT {}", column)),
+        DeletionKind::Timestamp => Some(format!("{} IS NULL", column)),
+        DeletionKind::Sentinel => Some(format!("{} = '{}'", column, ALIVE_SENTINEL)),
+        _ => None,
+    };
+    UniqueAliveKey {
+        name: name.into(),
+        table: table.name(),
+        alive,
+        columns: Vec::new(),
+        generated: "key_if_alive".to_owned(),
+    }
+}
+
+impl UniqueAliveKey {
+    /// Add the `columns` to the unique key, after the previous ones.
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns.extend(columns.iter().map(|&c| c.to_owned()));
+        self
+    }
+
+    /// Name the generated column `name` instead of `key_if_alive`, e.g. for a second unique key
+    /// of the table.
+    pub fn generated_column(mut self, name: impl Into<String>) -> Self {
+        self.generated = name.into();
+        self
+    }
+
+    /// The statement adding the generated column and the unique index, for the `up.sql` of a
+    /// migration.
+    ///
+    /// This fails if no column was added, or if the alive condition of the table reads the clock
+    /// or another table, which a generated column cannot: on the tables whose deletions are
+    /// scheduled, expire, are inherited or recorded.
+    pub fn up_sql(&self) -> QueryResult<String> {
+        if self.columns.is_empty() {
+            return Err(Error::QueryBuilderError(
+                format!("the unique key {:?} has no column", self.name).into(),
+            ));
+        }
+        let alive = self.alive.as_ref().ok_or_else(|| {
+            Error::QueryBuilderError(
+                format!("the alive rows of {:?} cannot be told by a generated column", self.table)
+                    .into(),
+            )
+        })?;
+        let columns = self.columns.iter().chain(Some(&self.generated));
+        Ok(format!(
+            "ALTER TABLE {} ADD COLUMN {} TINYINT GENERATED ALWAYS AS (IF({}, 1, NULL)) STORED, \
+             ADD UNIQUE INDEX {} ({});\n",
+            mysql_identifier(self.table),
+            mysql_identifier(&self.generated),
+            alive,
+            mysql_identifier(&self.name),
+            columns.map(|c| mysql_identifier(c)).collect::<Vec<_>>().join(", "),
+        ))
+    }
+
+    /// The statement dropping the unique index and the generated column, for the `down.sql` of a
+    /// migration.
+    pub fn down_sql(&self) -> String {
+        format!(
+            "ALTER TABLE {} DROP INDEX {}, DROP COLUMN {};\n",
+            mysql_identifier(self.table),
+            mysql_identifier(&self.name),
+            mysql_identifier(&self.generated),
+        )
+    }
+}
+
+/// Quote a MySQL identifier, with backticks.
+fn mysql_identifier(identifier: &str) -> String {
+    format!("`{}`", identifier.replace('`', "``"))
+}
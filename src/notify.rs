@@ -0,0 +1,100 @@
+//! Notifications of the soft deletions, restorations and purges with `NOTIFY` (PostgreSQL).
+//!
+//! An installed [`SoftNotifier`] is a [hook](crate::hooks): each write of this crate executed
+//! with a [`PgConnection`] notifies a channel of the rows it affected, for other services and
+//! caches to react without polling:
+//!
+//! ```rust,ignore
+//! let _notifier = SoftNotifier::new("soft_delete").install();
+//! post::table.filter(post::user_id.eq(spammer)).soft_delete_rows(&conn)?;
+//! // SELECT pg_notify('soft_delete', payload) FROM unnest($1) AS payload
+//! // with the payloads '{"table":"post","pk":"42","action":"soft_delete"}', ...
+//! ```
+//!
+//! The listeners run `LISTEN soft_delete` with a client receiving the notifications, which Diesel
+//! does not. The payload is the JSON object built by [`notification_payload`], the primary key
+//! being a string whatever its type.
+//!
+//! The notifications are sent in the transaction of the write: they are only delivered once it
+//! commits, and never if it rolls back.
+
+use std::fmt::Display;
+
+use diesel::{
+    pg::PgConnection,
+    sql_types::{Array, Text},
+    QueryResult, RunQueryDsl,
+};
+
+use crate::hooks::{self, HookGuard, WriteEvent};
+
+/// The channel notified of the soft-delete writes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoftNotifier {
+    channel: String,
+}
+
+impl SoftNotifier {
+    /// Notify the channel `channel`.
+    pub fn new(channel: impl Into<String>) -> Self {
+        Self { channel: channel.into() }
+    }
+
+    /// Notify the channel of the rows affected by each write of this crate executed on the current
+    /// thread, until the returned guard is dropped.
+    pub fn install(self) -> HookGuard {
+        hooks::install(move |event: &WriteEvent, conn: &PgConnection| {
+            self.notify(event.table, event.keys, event.action.as_str(), conn)
+        })
+    }
+
+    /// Notify the channel of the `action`, e.g. `soft_delete`, on the rows `pks` of `table`, with
+    /// a single statement.
+    pub fn notify<PK: Display>(
+        &self,
+        table: &str,
+        pks: &[PK],
+        action: &str,
+        conn: &PgConnection,
+    ) -> QueryResult<()> {
+        if pks.is_empty() {
+            return Ok(());
+        }
+        let payloads = pks.iter().map(|pk| notification_payload(table, pk, action));
+        diesel::sql_query("SELECT pg_notify($1, payload) FROM unnest($2) AS payload")
+            .bind::<Text, _>(&self.channel)
+            .bind::<Array<Text>, _>(payloads.collect::<Vec<_>>())
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+/// The JSON payload notifying the `action` on the row `pk` of `table`, e.g.
+/// `{"table":"post","pk":"42","action":"soft_delete"}`.
+pub fn notification_payload<PK: Display>(table: &str, pk: &PK, action: &str) -> String {
+    format!(
+        "{{\"table\":{},\"pk\":{},\"action\":{}}}",
+        json_string(table),
+        json_string(&pk.to_string()),
+        json_string(action),
+    )
+}
+
+/// Quote `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
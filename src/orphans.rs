@@ -0,0 +1,403 @@
+//! Consistency checks of the soft-delete cascades: the alive children of soft-deleted parents,
+//! e.g. the alive posts of trashed users, left behind by writes that did not follow the cascades.
+//!
+//! The orphans of a single relationship, declared with `joinable!`, are found with
+//! [`find_orphans`], and those of all the children of a table, declared with
+//! [`soft_delete_cascade!`](crate::soft_delete_cascade), with [`cascade_orphans`]:
+//!
+//! ```rust,ignore
+//! let orphans = find_orphans(post::table, user::table, &conn)?;
+//! for orphans in cascade_orphans(user::table, &conn)? {
+//!     println!("{} alive {} rows of trashed {}", orphans.ids.len(), orphans.child, orphans.parent);
+//! }
+//! ```
+//!
+//! The orphans left behind are then repaired with an [`OrphanRepair`], which soft-deletes or
+//! reassigns them in batches, and reports what it did.
+//!
+//! The children are joined to their parent with the `ON` clause of their `joinable!`, which must
+//! not check that the parent is alive, as those of [`soft_joinable!`](crate::soft_joinable) do
+//! with the `implicit-soft-joins` feature.
+
+use std::fmt;
+
+use diesel::{
+    associations::HasTable,
+    backend::Backend,
+    deserialize::Queryable,
+    dsl::{Filter, Update},
+    expression::{AppearsOnTable, NonAggregate, SelectableExpression},
+    query_builder::{
+        nodes::Identifier, AsChangeset, AstPass, IntoUpdateTarget, QueryBuilder, QueryFragment,
+        QueryId,
+    },
+    query_dsl::methods::{ExecuteDsl, FilterDsl},
+    sql_types::{BigInt, Bool, HasSqlType, Text},
+    Column, Connection, Expression, JoinTo, QueryResult, RunQueryDsl, Table,
+};
+
+use crate::{
+    cascade::SoftCascade,
+    hooks::{Action, HookConnection},
+    trace::{self, Target},
+    write::SoftDeleteTarget,
+    SoftDelete,
+};
+
+/// The alive rows of a child table whose parent row is soft-deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Orphans {
+    pub parent: &'static str,
+    pub child: &'static str,
+    /// The primary keys of the orphans, as text, in ascending order.
+    pub ids: Vec<String>,
+}
+
+/// A relationship from a child table to its `Parent` table whose orphans can be found on the
+/// backend `DB`.
+pub trait OrphanRelation<Parent, DB: Backend> {
+    fn child_name(&self) -> &'static str;
+
+    /// The condition of the orphans, whose parent table is joined in a `FROM` clause built with
+    /// [`orphans_from`](OrphanRelation::orphans_from).
+    fn orphans_condition(&self, parent: &Parent, out: &mut DB::QueryBuilder) -> QueryResult<()>;
+
+    /// The child table joined to its parent table.
+    fn orphans_from(&self, parent: &Parent, out: &mut DB::QueryBuilder) -> QueryResult<()>;
+
+    /// The primary key of the child table.
+    fn orphans_key(&self, out: &mut DB::QueryBuilder) -> QueryResult<()>;
+}
+
+impl<C, P, DB> OrphanRelation<P, DB> for C
+where
+    C: Table<FromClause = Identifier<'static>> + SoftDelete + JoinTo<P> + Copy,
+    C::PrimaryKey: QueryFragment<DB>,
+    C::Deleted: QueryFragment<DB>,
+    C::OnClause: QueryFragment<DB>,
+    P: Table + SoftDelete + Copy,
+    P::FromClause: QueryFragment<DB>,
+    P::Deleted: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn child_name(&self) -> &'static str {
+        self.from_clause().0
+    }
+
+    fn orphans_condition(&self, parent: &P, out: &mut DB::QueryBuilder) -> QueryResult<()> {
+        out.push_sql("NOT (");
+        self.deleted_col().to_sql(out)?;
+        out.push_sql(") AND ");
+        parent.deleted_col().to_sql(out)
+    }
+
+    fn orphans_from(&self, parent: &P, out: &mut DB::QueryBuilder) -> QueryResult<()> {
+        out.push_identifier(self.from_clause().0)?;
+        out.push_sql(" INNER JOIN ");
+        parent.from_clause().to_sql(out)?;
+        out.push_sql(" ON ");
+        C::join_target(*parent).1.to_sql(out)
+    }
+
+    fn orphans_key(&self, out: &mut DB::QueryBuilder) -> QueryResult<()> {
+        self.primary_key().to_sql(out)
+    }
+}
+
+/// Find the alive rows of `child` whose row of `parent` is soft-deleted.
+pub fn find_orphans<C, P, Conn>(child: C, parent: P, conn: &Conn) -> QueryResult<Orphans>
+where
+    C: OrphanRelation<P, Conn::Backend>,
+    P: Table<FromClause = Identifier<'static>>,
+    Conn: Connection,
+    Conn::Backend: HasSqlType<Text>,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+    String: Queryable<Text, Conn::Backend>,
+{
+    let mut sql = <Conn::Backend as Backend>::QueryBuilder::default();
+    sql.push_sql("SELECT CAST(");
+    child.orphans_key(&mut sql)?;
+    sql.push_sql(" AS TEXT) FROM ");
+    child.orphans_from(&parent, &mut sql)?;
+    sql.push_sql(" WHERE ");
+    child.orphans_condition(&parent, &mut sql)?;
+    sql.push_sql(" ORDER BY ");
+    child.orphans_key(&mut sql)?;
+    let ids = diesel::dsl::sql::<Text>(&sql.finish()).load(conn)?;
+    Ok(Orphans { parent: parent.from_clause().0, child: child.child_name(), ids })
+}
+
+/// Count the alive rows of `child` whose row of `parent` is soft-deleted.
+fn count_orphans<C, P, Conn>(child: C, parent: P, conn: &Conn) -> QueryResult<usize>
+where
+    C: OrphanRelation<P, Conn::Backend>,
+    Conn: Connection,
+    Conn::Backend: HasSqlType<BigInt>,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+    i64: Queryable<BigInt, Conn::Backend>,
+{
+    let mut sql = <Conn::Backend as Backend>::QueryBuilder::default();
+    sql.push_sql("SELECT COUNT(*) FROM ");
+    child.orphans_from(&parent, &mut sql)?;
+    sql.push_sql(" WHERE ");
+    child.orphans_condition(&parent, &mut sql)?;
+    let count = diesel::dsl::sql::<BigInt>(&sql.finish()).get_result::<i64>(conn)?;
+    Ok(count as usize)
+}
+
+/// The children of a cascade whose orphans can be found, as a tuple.
+pub trait CascadeOrphans<Parent, Conn> {
+    fn find_orphans(self, parent: Parent, conn: &Conn) -> QueryResult<Vec<Orphans>>;
+}
+
+macro_rules! impl_cascade_orphans {
+    ($($child:ident),+) => {
+        impl<Parent, Conn, $($child),+> CascadeOrphans<Parent, Conn> for ($($child,)+)
+        where
+            Parent: Table<FromClause = Identifier<'static>> + Copy,
+            Conn: Connection,
+            Conn::Backend: HasSqlType<Text>,
+            <Conn::Backend as Backend>::QueryBuilder: Default,
+            String: Queryable<Text, Conn::Backend>,
+            $($child: OrphanRelation<Parent, Conn::Backend>),+
+        {
+            #[allow(non_snake_case)]
+            fn find_orphans(self, parent: Parent, conn: &Conn) -> QueryResult<Vec<Orphans>> {
+                let ($($child,)+) = self;
+                Ok(vec![$(find_orphans($child, parent, conn)?),+])
+            }
+        }
+    };
+}
+
+impl_cascade_orphans!(A);
+impl_cascade_orphans!(A, B);
+impl_cascade_orphans!(A, B, C);
+impl_cascade_orphans!(A, B, C, D);
+impl_cascade_orphans!(A, B, C, D, E);
+impl_cascade_orphans!(A, B, C, D, E, F);
+impl_cascade_orphans!(A, B, C, D, E, F, G);
+impl_cascade_orphans!(A, B, C, D, E, F, G, H);
+
+/// Find the orphans of each child of the cascades of `parent`, in declaration order.
+pub fn cascade_orphans<P, Conn>(parent: P, conn: &Conn) -> QueryResult<Vec<Orphans>>
+where
+    P: SoftCascade,
+    P::Children: CascadeOrphans<P, Conn>,
+{
+    parent.children().find_orphans(parent, conn)
+}
+
+/// The condition of a batch of orphans of a child table, as in
+/// `child.id IN (SELECT child.id FROM child INNER JOIN parent ... LIMIT 100)`.
+#[derive(Debug, Clone, Copy)]
+pub struct OrphanBatch<C, P> {
+    child: C,
+    parent: P,
+    size: usize,
+}
+
+impl<C, P> Expression for OrphanBatch<C, P> {
+    type SqlType = Bool;
+}
+
+impl<C, P> NonAggregate for OrphanBatch<C, P> {}
+
+impl<C, P, QS> AppearsOnTable<QS> for OrphanBatch<C, P>
+where
+    C: Table,
+    C::PrimaryKey: AppearsOnTable<QS>,
+{
+}
+
+impl<C, P, QS> SelectableExpression<QS> for OrphanBatch<C, P>
+where
+    C: Table,
+    C::PrimaryKey: SelectableExpression<QS>,
+{
+}
+
+impl<C, P> QueryId for OrphanBatch<C, P> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<C, P, DB> QueryFragment<DB> for OrphanBatch<C, P>
+where
+    C: OrphanRelation<P, DB>,
+    DB: Backend,
+    DB::QueryBuilder: Default,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        let mut sql = DB::QueryBuilder::default();
+        self.child.orphans_key(&mut sql)?;
+        sql.push_sql(" IN (SELECT ");
+        self.child.orphans_key(&mut sql)?;
+        sql.push_sql(" FROM ");
+        self.child.orphans_from(&self.parent, &mut sql)?;
+        sql.push_sql(" WHERE ");
+        self.child.orphans_condition(&self.parent, &mut sql)?;
+        sql.push_sql(" ORDER BY ");
+        self.child.orphans_key(&mut sql)?;
+        sql.push_sql(&format!(" LIMIT {})", self.size));
+        out.push_sql(&sql.finish());
+        Ok(())
+    }
+}
+
+/// The report of an [`OrphanRepair`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    pub parent: &'static str,
+    pub child: &'static str,
+    /// The number of orphans found before the repair.
+    pub found: usize,
+    /// The number of orphans repaired, none on a dry run.
+    pub repaired: usize,
+    /// The number of batches the orphans were repaired in.
+    pub batches: usize,
+    pub dry_run: bool,
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}: {} orphans", self.child, self.parent, self.found)?;
+        if self.dry_run {
+            write!(f, ", dry run")
+        } else {
+            write!(f, ", {} repaired in {} batches", self.repaired, self.batches)
+        }
+    }
+}
+
+/// The repair of the orphans of a child table, bringing them in line with the cascades adopted
+/// after they were left behind, in batches of 1000 rows by default. The repair stops at the first
+/// batch leaving as many orphans, e.g. reassigning them to a trashed parent.
+///
+/// ```rust,ignore
+/// let report = OrphanRepair::new(post::table, user::table).batch_size(500).soft_delete(&conn)?;
+/// let report = OrphanRepair::new(comment::table, post::table)
+///     .dry_run(true)
+///     .reassign(comment::post_id.eq(archive_post), &conn)?;
+/// println!("{}", report);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct OrphanRepair<C, P> {
+    child: C,
+    parent: P,
+    batch_size: usize,
+    dry_run: bool,
+}
+
+impl<C, P> OrphanRepair<C, P> {
+    pub fn new(child: C, parent: P) -> Self {
+        Self { child, parent, batch_size: 1000, dry_run: false }
+    }
+
+    /// Repair the orphans `batch_size` rows at a time.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "the batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Only report the orphans, without repairing them.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+impl<C, P> OrphanRepair<C, P>
+where
+    C: Table + Copy,
+    P: Table<FromClause = Identifier<'static>> + Copy,
+{
+    /// Soft-delete the orphans, following the cascade.
+    pub fn soft_delete<Conn>(&self, conn: &Conn) -> QueryResult<RepairReport>
+    where
+        C: Table<FromClause = Identifier<'static>>,
+        C::PrimaryKey: Column,
+        C: FilterDsl<OrphanBatch<C, P>> + OrphanRelation<P, Conn::Backend>,
+        Filter<C, OrphanBatch<C, P>>: SoftDeleteTarget,
+        <Filter<C, OrphanBatch<C, P>> as SoftDeleteTarget>::SoftDelete:
+            QueryFragment<Conn::Backend> + QueryId,
+        Conn: HookConnection,
+        Conn::Backend: HasSqlType<BigInt>,
+        <Conn::Backend as Backend>::QueryBuilder: Default,
+        i64: Queryable<BigInt, Conn::Backend>,
+    {
+        self.repair(conn, |batch| {
+            let stmt = self.child.filter(batch).soft_delete();
+            trace::write(Action::SoftDelete, Target::of(self.child), stmt, conn)
+        })
+    }
+
+    /// Reassign the orphans with `changeset`, e.g. to an alive parent.
+    pub fn reassign<V, Conn>(&self, changeset: V, conn: &Conn) -> QueryResult<RepairReport>
+    where
+        C: FilterDsl<OrphanBatch<C, P>> + OrphanRelation<P, Conn::Backend>,
+        Filter<C, OrphanBatch<C, P>>: IntoUpdateTarget,
+        V: AsChangeset<Target = <Filter<C, OrphanBatch<C, P>> as HasTable>::Table> + Clone,
+        Update<Filter<C, OrphanBatch<C, P>>, V>: ExecuteDsl<Conn>,
+        Conn: Connection,
+        Conn::Backend: HasSqlType<BigInt>,
+        <Conn::Backend as Backend>::QueryBuilder: Default,
+        i64: Queryable<BigInt, Conn::Backend>,
+    {
+        self.repair(conn, |batch| {
+            let statement = diesel::update(self.child.filter(batch)).set(changeset.clone());
+            ExecuteDsl::execute(statement, conn)
+        })
+    }
+
+    /// Count the orphans, and repair them batch by batch with `repair` unless on a dry run, until
+    /// a batch leaves no fewer orphans.
+    fn repair<Conn>(
+        &self,
+        conn: &Conn,
+        mut repair: impl FnMut(OrphanBatch<C, P>) -> QueryResult<usize>,
+    ) -> QueryResult<RepairReport>
+    where
+        C: OrphanRelation<P, Conn::Backend>,
+        Conn: Connection,
+        Conn::Backend: HasSqlType<BigInt>,
+        <Conn::Backend as Backend>::QueryBuilder: Default,
+        i64: Queryable<BigInt, Conn::Backend>,
+    {
+        let found = count_orphans(self.child, self.parent, conn)?;
+        let mut report = RepairReport {
+            parent: self.parent.from_clause().0,
+            child: self.child.child_name(),
+            found,
+            repaired: 0,
+            batches: 0,
+            dry_run: self.dry_run,
+        };
+        if self.dry_run {
+            return Ok(report);
+        }
+        let mut left = found;
+        while left > 0 {
+            let batch =
+                OrphanBatch { child: self.child, parent: self.parent, size: self.batch_size };
+            let updated = conn.transaction(|| repair(batch))?;
+            if updated == 0 {
+                return Ok(report);
+            }
+            // A batch whose rows are still orphans, e.g. reassigned to a trashed parent, would be
+            // picked again by the next one.
+            let still = count_orphans(self.child, self.parent, conn)?;
+            if still >= left {
+                return Ok(report);
+            }
+            report.repaired += left - still;
+            report.batches += 1;
+            left = still;
+            if updated < self.batch_size {
+                return Ok(report);
+            }
+        }
+        Ok(report)
+    }
+}
@@ -1,9 +1,109 @@
 //! Methods to use on the query builder
 
-use crate::query_source::SoftJoin;
-use diesel::query_source::joins::{Inner, LeftOuter};
+pub use crate::query_source::{SoftJoin, SoftJoinTarget, SoftJoinTo, SoftOnClause};
+use crate::{methods::SoftDeleteDsl, query_source::SoftScope, scope::ScopeDsl, SoftDelete};
+use diesel::{
+    backend::Backend,
+    dsl::{exists, not, Filter, IntoBoxed, IsNotNull, LeftJoin, Select, SqlTypeOf},
+    expression::{exists::Exists, nullable::Nullable, AsExpression},
+    helper_types::not as Not,
+    query_builder::SelectStatement,
+    query_dsl::{
+        methods::{BoxedDsl, FilterDsl, SelectDsl},
+        InternalJoinDsl, JoinWithImplicitOnClause,
+    },
+    query_source::joins::{Inner, LeftOuter},
+    sql_types::{Bool, NotNull, SingleValue},
+    Expression, ExpressionMethods, JoinTo, NullableExpressionMethods, Table,
+};
+#[cfg(feature = "implicit-soft-joins")]
+use diesel::{
+    expression::{AppearsOnTable, NonAggregate},
+    query_builder::{AstPass, QueryFragment, QueryId},
+    QueryResult, SelectableExpression,
+};
 
-/// The `soft_left_join` and `soft_inner_join` methods.
+pub(crate) type Through<Lhs, Pivot, Far, Kind> =
+    <Lhs as SoftJoin<<Pivot as SoftJoin<Far, Inner>>::Output, Kind>>::Output;
+pub(crate) type ThroughWith<Lhs, Pivot, Far, Kind, Policy> =
+    <Policy as PivotDeletion<Lhs, <Pivot as SoftJoin<Far, Inner>>::Output, Kind>>::Output;
+type SemiJoinSubquery<Lhs, Rhs> = Filter<
+    <<Lhs as JoinTo<Rhs>>::FromClause as SoftDeleteDsl>::Output,
+    <Lhs as JoinTo<Rhs>>::OnClause,
+>;
+/// The type returned by [`soft_semi_join`](SoftJoinDsl::soft_semi_join).
+pub type SoftSemiJoin<Lhs, Rhs> = Filter<Lhs, Exists<SemiJoinSubquery<Lhs, Rhs>>>;
+/// The type returned by [`soft_anti_join`](SoftJoinDsl::soft_anti_join).
+pub type SoftAntiJoin<Lhs, Rhs> = Filter<Lhs, Not<Exists<SemiJoinSubquery<Lhs, Rhs>>>>;
+
+type FlaggedSelection<Lhs, Rhs> = (
+    <Lhs as Table>::AllColumns,
+    Nullable<<Rhs as Table>::AllColumns>,
+    Nullable<<Rhs as SoftDelete>::Deleted>,
+);
+/// The type returned by [`soft_left_join_flagged`](SoftJoinDsl::soft_left_join_flagged).
+pub type FlaggedLeftJoin<Lhs, Rhs> = Select<LeftJoin<Lhs, Rhs>, FlaggedSelection<Lhs, Rhs>>;
+/// The type returned by [`soft_join_dyn`](SoftJoinDsl::soft_join_dyn).
+pub type SoftJoinDyn<'a, Lhs, Rhs, DB> =
+    IntoBoxed<'a, <Lhs as SoftJoin<Rhs, LeftOuter>>::Output, DB>;
+
+/// How a through-join treats soft-deleted pivot rows.
+///
+/// This is implemented by [`HideLink`] and [`KeepLink`], to be passed to
+/// [`soft_through_join_with`](SoftJoinDsl::soft_through_join_with).
+pub trait PivotDeletion<Lhs, Rhs, Kind> {
+    /// The type of the join of the left-hand side to the pivot.
+    type Output;
+    fn join_pivot(self, lhs: Lhs, rhs: Rhs, kind: Kind) -> Self::Output;
+}
+
+/// A soft-deleted pivot row hides the association, as with
+/// [`soft_through_join`](SoftJoinDsl::soft_through_join). This fits e.g. memberships.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HideLink;
+
+/// A soft-deleted pivot row keeps the association visible, and only its own columns are to be
+/// disregarded. This fits e.g. tags whose pivot carries metadata of its own.
+///
+/// The pivot's deleted flag can be selected to tell whether its columns are relevant.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeepLink;
+
+impl<Lhs, Rhs, Kind> PivotDeletion<Lhs, Rhs, Kind> for HideLink
+where
+    Lhs: SoftJoin<Rhs, Kind>,
+{
+    type Output = Lhs::Output;
+
+    fn join_pivot(self, lhs: Lhs, rhs: Rhs, kind: Kind) -> Self::Output {
+        lhs.soft_join(rhs, kind)
+    }
+}
+
+impl<Lhs, Rhs, Kind> PivotDeletion<Lhs, Rhs, Kind> for KeepLink
+where
+    Lhs: JoinTo<Rhs> + InternalJoinDsl<Lhs::FromClause, Kind, Lhs::OnClause>,
+{
+    type Output = <Lhs as InternalJoinDsl<Lhs::FromClause, Kind, Lhs::OnClause>>::Output;
+
+    fn join_pivot(self, lhs: Lhs, rhs: Rhs, kind: Kind) -> Self::Output {
+        let (from, on) = Lhs::join_target(rhs);
+        lhs.join(from, kind, on)
+    }
+}
+
+/// The `soft_left_join` and `soft_inner_join` methods, and their variants.
+///
+/// The `_boxed` variants box the resulting query, which trades a little runtime cost for much
+/// smaller types when deeply chaining joins, and thus faster builds.
+///
+/// The soft-delete condition in the `ON` clause is equivalent to joining on a subquery of the
+/// alive rows, e.g. `LEFT JOIN (SELECT * FROM post WHERE NOT deleted) post ON ...`, without the
+/// subquery. Diesel does not support joining on filtered subqueries, so pre-filtered right-hand
+/// sides are not supported either: filter them in the `WHERE` clause of the outer query instead.
+///
+/// Only inner and left joins are available, as they are the only join kinds of Diesel. A right
+/// join is a left join with its sides swapped.
 pub trait SoftJoinDsl: Sized {
     fn soft_inner_join<Rhs>(self, rhs: Rhs) -> Self::Output
     where
@@ -18,6 +118,357 @@ pub trait SoftJoinDsl: Sized {
     {
         self.soft_join(rhs, LeftOuter)
     }
+
+    /// Left join all the rows of `rhs`, related through `joinable!`, including the soft-deleted
+    /// ones, and select them along with their deleted flag, to mark the trashed ones rather than
+    /// hiding them.
+    ///
+    /// The rows are loaded as `(Lhs, Option<Rhs>, Option<bool>)`. When no row of `rhs` is
+    /// joined, the flag is `None` on flag tables, but `Some(false)` on timestamp tables, whose
+    /// `IS NOT NULL` is false on the `NULL` columns: check the `rhs` row rather than the flag.
+    /// Restrict the left-hand side with `soft_filter` afterwards:
+    ///
+    /// ```rust,ignore
+    /// let posts = user::table
+    ///     .soft_left_join_flagged(post::table)
+    ///     .soft_filter(user::id.eq(id))
+    ///     .load::<(User, Option<Post>, Option<bool>)>(&conn)?;
+    /// ```
+    fn soft_left_join_flagged<Rhs>(self, rhs: Rhs) -> FlaggedLeftJoin<Self, Rhs>
+    where
+        Self: Table + JoinWithImplicitOnClause<Rhs, LeftOuter>,
+        Rhs: Table + SoftDelete,
+        SqlTypeOf<Rhs::AllColumns>: NotNull,
+        LeftJoin<Self, Rhs>: SelectDsl<FlaggedSelection<Self, Rhs>>,
+    {
+        let selection =
+            (Self::all_columns(), Rhs::all_columns().nullable(), rhs.is_deleted().nullable());
+        SelectDsl::select(diesel::QueryDsl::left_join(self, rhs), selection)
+    }
+
+    /// Like `soft_inner_join`, but also keeps only the alive rows of the left-hand side, for
+    /// queries starting from a table that was not already soft-scoped.
+    fn soft_inner_join_both<Rhs>(self, rhs: Rhs) -> <Self::Output as SoftDeleteDsl>::Output
+    where
+        Self: SoftJoin<Rhs, Inner>,
+        Self::Output: SoftDeleteDsl,
+    {
+        self.soft_join(rhs, Inner).soft_deleted()
+    }
+
+    /// Like `soft_left_join`, but also keeps only the alive rows of the left-hand side, for
+    /// queries starting from a table that was not already soft-scoped.
+    ///
+    /// The left-hand side condition goes in the `WHERE` clause, as it would not filter anything in
+    /// the `ON` clause of a left join.
+    fn soft_left_join_both<Rhs>(self, rhs: Rhs) -> <Self::Output as SoftDeleteDsl>::Output
+    where
+        Self: SoftJoin<Rhs, LeftOuter>,
+        Self::Output: SoftDeleteDsl,
+    {
+        self.soft_join(rhs, LeftOuter).soft_deleted()
+    }
+
+    /// Join a many-to-many relationship through its `pivot` table, as in
+    /// `self.soft_inner_join(pivot.soft_inner_join(far))`.
+    ///
+    /// A soft-deleted pivot row unlinks both sides, and soft-deleted rows of the `far` table are
+    /// not joined.
+    fn soft_through_join<Pivot, Far>(
+        self,
+        pivot: Pivot,
+        far: Far,
+    ) -> Through<Self, Pivot, Far, Inner>
+    where
+        Pivot: SoftJoin<Far, Inner>,
+        Self: SoftJoin<<Pivot as SoftJoin<Far, Inner>>::Output, Inner>,
+    {
+        self.soft_join(pivot.soft_join(far, Inner), Inner)
+    }
+
+    /// Like `soft_through_join`, but with a left join, so rows without any alive link are kept, as
+    /// in `self.soft_left_join(pivot.soft_inner_join(far))`.
+    ///
+    /// The pivot and the `far` table are inner-joined in the nested `ON` clause: a pivot row to a
+    /// soft-deleted `far` row is no link, rather than a link to `NULL`.
+    fn soft_left_through_join<Pivot, Far>(
+        self,
+        pivot: Pivot,
+        far: Far,
+    ) -> Through<Self, Pivot, Far, LeftOuter>
+    where
+        Pivot: SoftJoin<Far, Inner>,
+        Self: SoftJoin<<Pivot as SoftJoin<Far, Inner>>::Output, LeftOuter>,
+    {
+        self.soft_join(pivot.soft_join(far, Inner), LeftOuter)
+    }
+
+    /// Like `soft_through_join`, with the given [`PivotDeletion`] policy for soft-deleted pivot
+    /// rows. Soft-deleted rows of the `far` table are never joined.
+    fn soft_through_join_with<Pivot, Far, Policy>(
+        self,
+        pivot: Pivot,
+        far: Far,
+        policy: Policy,
+    ) -> ThroughWith<Self, Pivot, Far, Inner, Policy>
+    where
+        Pivot: SoftJoin<Far, Inner>,
+        Policy: PivotDeletion<Self, <Pivot as SoftJoin<Far, Inner>>::Output, Inner>,
+    {
+        policy.join_pivot(self, pivot.soft_join(far, Inner), Inner)
+    }
+
+    /// Like `soft_left_through_join`, with the given [`PivotDeletion`] policy for soft-deleted
+    /// pivot rows. Soft-deleted rows of the `far` table are never joined.
+    fn soft_left_through_join_with<Pivot, Far, Policy>(
+        self,
+        pivot: Pivot,
+        far: Far,
+        policy: Policy,
+    ) -> ThroughWith<Self, Pivot, Far, LeftOuter, Policy>
+    where
+        Pivot: SoftJoin<Far, Inner>,
+        Policy: PivotDeletion<Self, <Pivot as SoftJoin<Far, Inner>>::Output, LeftOuter>,
+    {
+        policy.join_pivot(self, pivot.soft_join(far, Inner), LeftOuter)
+    }
+
+    /// Keep the rows having at least one alive row of `rhs`, related through `joinable!`, as in
+    /// `WHERE EXISTS (SELECT ... FROM rhs WHERE NOT rhs.deleted AND <join condition>)`.
+    ///
+    /// Unlike an inner join, each row is kept once and the columns of `rhs` are not selected.
+    fn soft_semi_join<Rhs>(self, rhs: Rhs) -> SoftSemiJoin<Self, Rhs>
+    where
+        Self: JoinTo<Rhs> + FilterDsl<Exists<SemiJoinSubquery<Self, Rhs>>>,
+        <Self as JoinTo<Rhs>>::FromClause: SoftDeleteDsl,
+        <<Self as JoinTo<Rhs>>::FromClause as SoftDeleteDsl>::Output:
+            FilterDsl<<Self as JoinTo<Rhs>>::OnClause>,
+    {
+        let (from_clause, on_clause) = Self::join_target(rhs);
+        let subquery = FilterDsl::filter(from_clause.soft_deleted(), on_clause);
+        FilterDsl::filter(self, exists(subquery))
+    }
+
+    /// Keep the rows having no alive row of `rhs`, related through `joinable!`, as in
+    /// `WHERE NOT EXISTS (SELECT ... FROM rhs WHERE NOT rhs.deleted AND <join condition>)`.
+    ///
+    /// Rows only related to soft-deleted rows of `rhs` are kept.
+    fn soft_anti_join<Rhs>(self, rhs: Rhs) -> SoftAntiJoin<Self, Rhs>
+    where
+        Self: JoinTo<Rhs> + FilterDsl<Not<Exists<SemiJoinSubquery<Self, Rhs>>>>,
+        <Self as JoinTo<Rhs>>::FromClause: SoftDeleteDsl,
+        <<Self as JoinTo<Rhs>>::FromClause as SoftDeleteDsl>::Output:
+            FilterDsl<<Self as JoinTo<Rhs>>::OnClause>,
+        Exists<SemiJoinSubquery<Self, Rhs>>: Expression<SqlType = Bool>,
+    {
+        let (from_clause, on_clause) = Self::join_target(rhs);
+        let subquery = FilterDsl::filter(from_clause.soft_deleted(), on_clause);
+        FilterDsl::filter(self, not(exists(subquery)))
+    }
+
+    fn soft_inner_join_boxed<'a, Rhs, DB>(
+        self,
+        rhs: Rhs,
+    ) -> IntoBoxed<'a, <Self as SoftJoin<Rhs, Inner>>::Output, DB>
+    where
+        Self: SoftJoin<Rhs, Inner>,
+        <Self as SoftJoin<Rhs, Inner>>::Output: BoxedDsl<'a, DB>,
+        DB: Backend,
+    {
+        self.soft_join(rhs, Inner).internal_into_boxed()
+    }
+
+    /// Soft-join `rhs` with a join kind chosen at runtime, e.g. from an "include users without
+    /// posts" toggle, boxing the query.
+    ///
+    /// Both kinds return the same type, a left join loading as `(Lhs, Option<Rhs>)`: the inner join
+    /// is the left join keeping only the rows joined to an alive row of `rhs`, whose primary key is
+    /// then not null.
+    fn soft_join_dyn<'a, Rhs, DB>(self, rhs: Rhs, kind: JoinKind) -> SoftJoinDyn<'a, Self, Rhs, DB>
+    where
+        Rhs: Table,
+        SqlTypeOf<Rhs::PrimaryKey>: SingleValue,
+        Self: SoftJoin<Rhs, LeftOuter>,
+        <Self as SoftJoin<Rhs, LeftOuter>>::Output: BoxedDsl<'a, DB>,
+        SoftJoinDyn<'a, Self, Rhs, DB>:
+            FilterDsl<IsNotNull<Rhs::PrimaryKey>, Output = SoftJoinDyn<'a, Self, Rhs, DB>>,
+        DB: Backend,
+    {
+        let primary_key = rhs.primary_key();
+        let query = self.soft_join(rhs, LeftOuter).internal_into_boxed();
+        match kind {
+            JoinKind::Inner => query.filter(primary_key.is_not_null()),
+            JoinKind::Left => query,
+        }
+    }
+
+    fn soft_left_join_boxed<'a, Rhs, DB>(
+        self,
+        rhs: Rhs,
+    ) -> IntoBoxed<'a, <Self as SoftJoin<Rhs, LeftOuter>>::Output, DB>
+    where
+        Self: SoftJoin<Rhs, LeftOuter>,
+        <Self as SoftJoin<Rhs, LeftOuter>>::Output: BoxedDsl<'a, DB>,
+        DB: Backend,
+    {
+        self.soft_join(rhs, LeftOuter).internal_into_boxed()
+    }
 }
 
 impl<Lhs> SoftJoinDsl for Lhs where Lhs: Sized {}
+
+/// The kind of a join chosen at runtime, for [`soft_join_dyn`](SoftJoinDsl::soft_join_dyn).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+}
+
+/// The `soft_scope_all` method.
+pub trait SoftScopeAllDsl {
+    /// The type returned by `.soft_scope_all`.
+    type Output;
+
+    /// Keep only the alive rows of the table the query starts from and of all its inner-joined
+    /// tables, in a single `WHERE` condition.
+    ///
+    /// # Limitations
+    ///
+    /// Outer-joined tables are **not** scoped: their soft-deleted rows are still joined. Their
+    /// condition belongs to the `ON` clause of their join, which Diesel does not allow changing
+    /// once the join is built, and checking it in the `WHERE` clause would drop the rows only
+    /// related to soft-deleted rows instead of joining them to `NULL`. Join them with
+    /// [`soft_left_join`](SoftJoinDsl::soft_left_join) instead, which `soft_scope_all` leaves as
+    /// is:
+    ///
+    /// ```rust,ignore
+    /// user::table.inner_join(post::table).soft_left_join(comment::table).soft_scope_all()
+    /// // WHERE NOT ("user"."deleted") AND NOT ("post"."deleted"), with the comment condition in
+    /// // the ON clause of its join
+    /// ```
+    ///
+    /// Nested joins on the right-hand side of a join are not supported either.
+    fn soft_scope_all(self) -> Self::Output;
+
+    /// Keep only the alive rows, as [`soft_scope_all`](SoftScopeAllDsl::soft_scope_all) does,
+    /// within the `scope` of the table the query starts from, e.g. a tenant.
+    fn soft_scope_all_with<S>(self, scope: S) -> <Self::Output as ScopeDsl<S>>::Output
+    where
+        Self: Sized,
+        Self::Output: ScopeDsl<S>,
+    {
+        self.soft_scope_all().scoped(scope)
+    }
+}
+
+impl<F, S, D, W, O, L, Of, G, LC> SoftScopeAllDsl for SelectStatement<F, S, D, W, O, L, Of, G, LC>
+where
+    F: SoftScope,
+    Self: FilterDsl<F::Predicate>,
+{
+    type Output = Filter<Self, F::Predicate>;
+
+    fn soft_scope_all(self) -> Self::Output {
+        self.filter(F::scope_predicate())
+    }
+}
+
+/// The implicit `ON` clause of the joins declared with [`soft_joinable`](crate::soft_joinable),
+/// implemented by the joined table.
+///
+/// With the `implicit-soft-joins` feature, it also checks that the joined row is alive, so that
+/// the plain `inner_join` and `left_join` behave like their soft counterparts. Otherwise, it is
+/// the same as the one of Diesel's `joinable`.
+pub trait ImplicitOnClause<FK, PK>: SoftDelete {
+    type OnClause;
+    fn implicit_on_clause(&self, foreign_key: FK, primary_key: PK) -> Self::OnClause;
+}
+
+impl<T, FK, PK> ImplicitOnClause<FK, PK> for T
+where
+    T: SoftDelete,
+    FK: Expression,
+    PK: Expression,
+    Nullable<FK>: ExpressionMethods,
+    Nullable<PK>: AsExpression<SqlTypeOf<Nullable<FK>>>,
+    diesel::dsl::Eq<Nullable<FK>, Nullable<PK>>: Expression<SqlType = Bool>,
+{
+    #[cfg(feature = "implicit-soft-joins")]
+    type OnClause = ImplicitOn<diesel::dsl::Eq<Nullable<FK>, Nullable<PK>>, T::Deleted>;
+    #[cfg(not(feature = "implicit-soft-joins"))]
+    type OnClause = diesel::dsl::Eq<Nullable<FK>, Nullable<PK>>;
+
+    fn implicit_on_clause(&self, foreign_key: FK, primary_key: PK) -> Self::OnClause {
+        let on_clause = foreign_key.nullable().eq(primary_key.nullable());
+        #[cfg(feature = "implicit-soft-joins")]
+        let on_clause = ImplicitOn { on_clause, deleted: self.deleted_col() };
+        on_clause
+    }
+}
+
+/// The implicit `ON` clause of a [`soft_joinable`](crate::soft_joinable) join checking that the
+/// joined row is alive, as in `post.user_id = user.id AND NOT (user.deleted)`.
+///
+/// A soft join leaves it as it is, so that enabling the `implicit-soft-joins` feature does not
+/// change the SQL of the soft joins.
+#[cfg(feature = "implicit-soft-joins")]
+#[derive(Debug, Clone, Copy)]
+pub struct ImplicitOn<E, D> {
+    on_clause: E,
+    deleted: D,
+}
+
+#[cfg(feature = "implicit-soft-joins")]
+impl<E, D> Expression for ImplicitOn<E, D> {
+    type SqlType = Bool;
+}
+
+#[cfg(feature = "implicit-soft-joins")]
+impl<E: NonAggregate, D: NonAggregate> NonAggregate for ImplicitOn<E, D> {}
+
+#[cfg(feature = "implicit-soft-joins")]
+impl<E, D, QS> AppearsOnTable<QS> for ImplicitOn<E, D>
+where
+    E: AppearsOnTable<QS>,
+    D: AppearsOnTable<QS>,
+{
+}
+
+#[cfg(feature = "implicit-soft-joins")]
+impl<E, D, QS> SelectableExpression<QS> for ImplicitOn<E, D>
+where
+    E: SelectableExpression<QS>,
+    D: SelectableExpression<QS>,
+{
+}
+
+#[cfg(feature = "implicit-soft-joins")]
+impl<E, D> QueryId for ImplicitOn<E, D> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+#[cfg(feature = "implicit-soft-joins")]
+impl<E, D, DB> QueryFragment<DB> for ImplicitOn<E, D>
+where
+    E: QueryFragment<DB>,
+    D: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        self.on_clause.walk_ast(out.reborrow())?;
+        out.push_sql(" AND NOT (");
+        self.deleted.walk_ast(out.reborrow())?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+#[cfg(feature = "implicit-soft-joins")]
+impl<E, D> SoftOnClause<D> for ImplicitOn<E, D> {
+    type Output = Self;
+
+    fn soft_on_clause(self, _deleted: D) -> Self {
+        self
+    }
+}
@@ -1,31 +1,267 @@
 use super::SoftDelete;
 use diesel::{
-    dsl::{not, And},
+    associations::HasTable,
+    dsl::{not, And, ForUpdate},
+    expression::{grouped::Grouped, operators, AppearsOnTable},
     helper_types::not as Not,
-    query_builder::AsQuery,
-    query_dsl::InternalJoinDsl,
-    BoolExpressionMethods, Expression, JoinTo,
+    query_builder::{AsQuery, BoxedSelectStatement, SelectStatement},
+    query_dsl::{InternalJoinDsl, LoadQuery},
+    query_source::joins::{Inner, Join, JoinOn, LeftOuter},
+    sql_types::Bool,
+    BoolExpressionMethods, Expression, JoinTo, OptionalExtension, QueryDsl, QueryResult,
+    RunQueryDsl, SelectableExpression,
 };
 
+/// A query source whose leftmost table is soft-deletable.
+///
+/// This is the table whose deleted flag is checked when soft-scoping a query built from this
+/// source, including when it is itself the right-hand side of a soft join. A table is only a
+/// source when its flag is selectable from it, and the queries of a source are only soft-deletable
+/// when the flag appears in their `FROM` clause.
+pub trait SoftSource {
+    type Table: SoftDelete;
+    fn soft_table() -> Self::Table;
+}
+
+impl<T> SoftSource for T
+where
+    T: SoftDelete + HasTable<Table = T>,
+    T::Deleted: SelectableExpression<T>,
+{
+    type Table = T;
+
+    fn soft_table() -> Self::Table {
+        T::table()
+    }
+}
+
+impl<Left, Right, Kind> SoftSource for Join<Left, Right, Kind>
+where
+    Left: SoftSource,
+{
+    type Table = Left::Table;
+
+    fn soft_table() -> Self::Table {
+        Left::soft_table()
+    }
+}
+
+impl<J, On> SoftSource for JoinOn<J, On>
+where
+    J: SoftSource,
+{
+    type Table = J::Table;
+
+    fn soft_table() -> Self::Table {
+        J::soft_table()
+    }
+}
+
+/// The `WHERE` clause of a query.
+pub trait WhereClauseOf {
+    type WhereClause;
+}
+
+impl<F, S, D, W, O, L, Of, G, LC> WhereClauseOf for SelectStatement<F, S, D, W, O, L, Of, G, LC> {
+    type WhereClause = W;
+}
+
+/// The query source of the `FROM` clause of a query.
+pub trait FromClauseOf {
+    type FromClause;
+}
+
+impl<F, S, D, W, O, L, Of, G, LC> FromClauseOf for SelectStatement<F, S, D, W, O, L, Of, G, LC> {
+    type FromClause = F;
+}
+
+/// The `DISTINCT` clause of a query.
+pub trait DistinctClauseOf {
+    type DistinctClause;
+}
+
+impl<F, S, D, W, O, L, Of, G, LC> DistinctClauseOf
+    for SelectStatement<F, S, D, W, O, L, Of, G, LC>
+{
+    type DistinctClause = D;
+}
+
+/// Diesel's `NoDistinctClause`, which is not exported.
+type NoDistinctClause = <SelectStatement<()> as DistinctClauseOf>::DistinctClause;
+
+/// A query that can be loaded with a `FOR UPDATE` clause.
+///
+/// Diesel does not export the locking types, so generic code cannot require `.for_update()` to
+/// be available: this is implemented for the queries it is available on.
+pub trait LoadForUpdate<Conn, U> {
+    fn load_for_update(self, conn: &Conn) -> QueryResult<Option<U>>;
+}
+
+impl<F, S, W, O, L, Of, Conn, U> LoadForUpdate<Conn, U>
+    for SelectStatement<F, S, NoDistinctClause, W, O, L, Of>
+where
+    ForUpdate<Self>: LoadQuery<Conn, U> + RunQueryDsl<Conn>,
+{
+    fn load_for_update(self, conn: &Conn) -> QueryResult<Option<U>> {
+        self.for_update().get_result(conn).optional()
+    }
+}
+
+/// Type equality, as a bound.
+pub trait Same<T> {}
+
+impl<T> Same<T> for T {}
+
+/// A query source whose soft-delete conditions can all be checked in the `WHERE` clause.
+///
+/// This covers its leftmost table and its inner-joined tables. The conditions of outer-joined
+/// tables belong to the `ON` clause of their join, and are thus not part of the predicate. Every
+/// table checked this way must be soft-deletable.
+pub trait SoftScope {
+    type Predicate: Expression<SqlType = Bool>;
+    fn scope_predicate() -> Self::Predicate;
+}
+
+impl<T> SoftScope for T
+where
+    T: SoftDelete + HasTable<Table = T>,
+{
+    type Predicate = Not<T::Deleted>;
+
+    fn scope_predicate() -> Self::Predicate {
+        not(T::table().deleted_col())
+    }
+}
+
+impl<Left, Right> SoftScope for Join<Left, Right, Inner>
+where
+    Left: SoftScope,
+    Right: SoftScope,
+{
+    type Predicate = And<Left::Predicate, Right::Predicate>;
+
+    fn scope_predicate() -> Self::Predicate {
+        Left::scope_predicate().and(Right::scope_predicate())
+    }
+}
+
+impl<Left, Right> SoftScope for Join<Left, Right, LeftOuter>
+where
+    Left: SoftScope,
+{
+    type Predicate = Left::Predicate;
+
+    fn scope_predicate() -> Self::Predicate {
+        Left::scope_predicate()
+    }
+}
+
+impl<J, On> SoftScope for JoinOn<J, On>
+where
+    J: SoftScope,
+{
+    type Predicate = J::Predicate;
+
+    fn scope_predicate() -> Self::Predicate {
+        J::scope_predicate()
+    }
+}
+
 /// Indicates that two tables can be joined without an explicit `ON` clause while respecting
 /// soft-delete.
+///
+/// Either side may already contain joins, so queries can be built incrementally with a soft join
+/// at each step. When the right-hand side is a joined query, the deleted flag of its leftmost
+/// table is checked, the others being checked by its own `ON` clauses.
+///
+/// It is implemented for the right-hand sides implementing [`SoftJoinTarget`], by adding their
+/// alive condition to the `ON` clause of their `JoinTo`. Custom query sources, e.g. views or
+/// table-valued functions, implement `SoftDelete` without `SoftJoinTarget` to implement it
+/// themselves instead.
 pub trait SoftJoinTo<T>: JoinTo<T> {
     type SoftOnClause;
     fn soft_join_target(rhs: T) -> (<Self as JoinTo<T>>::FromClause, Self::SoftOnClause);
 }
 
+/// A soft-deletable query source joined by the default implementation of [`SoftJoinTo`].
+///
+/// It is implemented by [`soft_delete!`](crate::soft_delete) on the tables, and on the queries of
+/// such tables.
+pub trait SoftJoinTarget: SoftDelete {}
+
+impl<F, S, D, W, O, L, Of, G, LC> SoftJoinTarget for SelectStatement<F, S, D, W, O, L, Of, G, LC>
+where
+    F: SoftSource,
+    <F::Table as SoftDelete>::Deleted: AppearsOnTable<F>,
+{
+}
+
+impl<'a, ST, QS, DB> SoftJoinTarget for BoxedSelectStatement<'a, ST, QS, DB>
+where
+    QS: SoftSource,
+    <QS::Table as SoftDelete>::Deleted: AppearsOnTable<QS>,
+{
+}
+
 impl<Lhs, Rhs> SoftJoinTo<Rhs> for Lhs
 where
     Lhs: JoinTo<Rhs>,
-    Rhs: SoftDelete,
-    <Lhs as JoinTo<Rhs>>::OnClause: Expression + BoolExpressionMethods,
+    Rhs: SoftJoinTarget,
+    <Lhs as JoinTo<Rhs>>::OnClause: SoftOnClause<Rhs::Deleted>,
 {
-    type SoftOnClause = And<Lhs::OnClause, Not<Rhs::Deleted>>;
+    type SoftOnClause = <Lhs::OnClause as SoftOnClause<Rhs::Deleted>>::Output;
 
     fn soft_join_target(rhs: Rhs) -> (Self::FromClause, Self::SoftOnClause) {
         let deleted = Rhs::deleted_col(&rhs);
         let (from_clause, on_clause) = Self::join_target(rhs);
-        (from_clause, on_clause.and(not(deleted)))
+        (from_clause, on_clause.soft_on_clause(deleted))
+    }
+}
+
+/// The `ON` clause of a join, to which a soft join adds the alive condition of the joined table.
+///
+/// It is implemented for the clauses of Diesel's `joinable`, of the `AND` of conditions and of the
+/// grouped conditions, e.g. an `OR` built with `or`, and for the
+/// [`ImplicitOn`](crate::query_dsl::ImplicitOn) clauses of the `implicit-soft-joins` feature,
+/// which already check the joined row and are left as they are.
+pub trait SoftOnClause<D> {
+    type Output;
+    fn soft_on_clause(self, deleted: D) -> Self::Output;
+}
+
+impl<L, R, D> SoftOnClause<D> for operators::Eq<L, R>
+where
+    Self: Expression<SqlType = Bool>,
+    D: Expression<SqlType = Bool>,
+{
+    type Output = And<Self, Not<D>>;
+
+    fn soft_on_clause(self, deleted: D) -> Self::Output {
+        self.and(not(deleted))
+    }
+}
+
+impl<L, R, D> SoftOnClause<D> for operators::And<L, R>
+where
+    Self: Expression<SqlType = Bool>,
+    D: Expression<SqlType = Bool>,
+{
+    type Output = And<Self, Not<D>>;
+
+    fn soft_on_clause(self, deleted: D) -> Self::Output {
+        self.and(not(deleted))
+    }
+}
+
+impl<T, D> SoftOnClause<D> for Grouped<T>
+where
+    Self: Expression<SqlType = Bool>,
+    D: Expression<SqlType = Bool>,
+{
+    type Output = And<Self, Not<D>>;
+
+    fn soft_on_clause(self, deleted: D) -> Self::Output {
+        self.and(not(deleted))
     }
 }
 
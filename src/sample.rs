@@ -0,0 +1,185 @@
+//! Random samples of the alive rows with `TABLESAMPLE` (PostgreSQL).
+//!
+//! Analytics and data-quality spot checks rarely need every row of a large table. A
+//! [`TableSample`] reads a percentage of its pages with `SYSTEM`, or of its rows with `BERNOULLI`,
+//! and [`soft_sample`](SoftSampleDsl::soft_sample) keeps the alive rows of the sample:
+//!
+//! ```rust,ignore
+//! let posts = post::table
+//!     .soft_sample(TableSample::bernoulli(1.0).repeatable(42.0))
+//!     .filter(post::user_id.eq(id))
+//!     .select(sampled((post::id, post::title)))
+//!     .load::<(i32, String)>(&conn)?;
+//! // SELECT ... FROM "post" TABLESAMPLE BERNOULLI ($1) REPEATABLE ($2)
+//! //     WHERE NOT ("post"."deleted") AND "post"."user_id" = $3
+//! ```
+//!
+//! The alive condition is checked on the sampled rows: the sample holds about the given
+//! percentage of the alive rows, not of the table.
+//!
+//! The columns of a table are only selectable from the table itself for Diesel: an explicit
+//! selection is wrapped in [`sampled`], e.g. `.select(sampled(count(post::id)))`. The filters and
+//! the ordering take the columns as they are.
+
+use diesel::{
+    backend::Backend,
+    expression::{AppearsOnTable, NonAggregate, SelectableExpression},
+    pg::Pg,
+    query_builder::{AstPass, QueryFragment, QueryId, SelectStatement},
+    query_source::{AppearsInFromClause, QuerySource},
+    sql_types::Double,
+    Expression, QueryResult, Table,
+};
+
+use crate::{dsl::SoftAll, methods::SoftDeleteDsl, query_source::SoftSource};
+
+/// How the rows of a table are sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SampleMethod {
+    System,
+    Bernoulli,
+}
+
+/// The `TABLESAMPLE` clause of a [`SampledTable`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableSample {
+    method: SampleMethod,
+    percent: f64,
+    seed: Option<f64>,
+}
+
+impl TableSample {
+    /// Sample `percent` percent of the pages of the table, keeping all their rows: fast, but
+    /// clustered.
+    pub fn system(percent: f64) -> Self {
+        Self { method: SampleMethod::System, percent, seed: None }
+    }
+
+    /// Sample `percent` percent of the rows of the table, each one independently: uniform, but
+    /// reading the whole table.
+    pub fn bernoulli(percent: f64) -> Self {
+        Self { method: SampleMethod::Bernoulli, percent, seed: None }
+    }
+
+    /// Draw the same sample for the same `seed`, as long as the table does not change.
+    pub fn repeatable(mut self, seed: f64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// A table read through a `TABLESAMPLE` clause, usable as the source of a query.
+#[derive(Debug, Clone, Copy)]
+pub struct SampledTable<T> {
+    table: T,
+    sample: TableSample,
+}
+
+impl<T> QuerySource for SampledTable<T>
+where
+    T: Table + Copy,
+    T::DefaultSelection: AppearsOnTable<Self>,
+{
+    type FromClause = Self;
+    type DefaultSelection = Sampled<T::DefaultSelection>;
+
+    fn from_clause(&self) -> Self::FromClause {
+        *self
+    }
+
+    fn default_selection(&self) -> Self::DefaultSelection {
+        sampled(self.table.default_selection())
+    }
+}
+
+/// The columns of the table appear in the sample, as in the table.
+impl<T, QS> AppearsInFromClause<QS> for SampledTable<T>
+where
+    T: AppearsInFromClause<QS>,
+{
+    type Count = T::Count;
+}
+
+impl<T: SoftSource> SoftSource for SampledTable<T> {
+    type Table = T::Table;
+
+    fn soft_table() -> Self::Table {
+        T::soft_table()
+    }
+}
+
+impl<T> QueryId for SampledTable<T> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<T> QueryFragment<Pg> for SampledTable<T>
+where
+    T: Table,
+    T::FromClause: QueryFragment<Pg>,
+{
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        self.table.from_clause().walk_ast(out.reborrow())?;
+        out.push_sql(match self.sample.method {
+            SampleMethod::System => " TABLESAMPLE SYSTEM (",
+            SampleMethod::Bernoulli => " TABLESAMPLE BERNOULLI (",
+        });
+        out.push_bind_param::<Double, _>(&self.sample.percent)?;
+        out.push_sql(")");
+        if let Some(seed) = &self.sample.seed {
+            out.push_sql(" REPEATABLE (");
+            out.push_bind_param::<Double, _>(seed)?;
+            out.push_sql(")");
+        }
+        Ok(())
+    }
+}
+
+/// A selection of the columns of a [`SampledTable`], built by [`sampled`].
+#[derive(Debug, Clone, Copy)]
+pub struct Sampled<S>(S);
+
+/// Select `selection` from a sampled table, e.g. a tuple of its columns or an aggregate of them.
+pub fn sampled<S: Expression>(selection: S) -> Sampled<S> {
+    Sampled(selection)
+}
+
+impl<S: Expression> Expression for Sampled<S> {
+    type SqlType = S::SqlType;
+}
+
+impl<S: NonAggregate> NonAggregate for Sampled<S> {}
+
+impl<S: AppearsOnTable<QS>, QS> AppearsOnTable<QS> for Sampled<S> {}
+
+impl<S, T> SelectableExpression<SampledTable<T>> for Sampled<S> where
+    S: AppearsOnTable<SampledTable<T>>
+{
+}
+
+impl<S: QueryId> QueryId for Sampled<S> {
+    type QueryId = Sampled<S::QueryId>;
+    const HAS_STATIC_QUERY_ID: bool = S::HAS_STATIC_QUERY_ID;
+}
+
+impl<S: QueryFragment<DB>, DB: Backend> QueryFragment<DB> for Sampled<S> {
+    fn walk_ast(&self, out: AstPass<DB>) -> QueryResult<()> {
+        self.0.walk_ast(out)
+    }
+}
+
+/// The type returned by [`soft_sample`](SoftSampleDsl::soft_sample).
+pub type SoftSample<T> = SoftAll<SelectStatement<SampledTable<T>>>;
+
+/// The `soft_sample` method.
+pub trait SoftSampleDsl: Table + Copy {
+    /// Keep the alive rows of a `sample` of the table.
+    fn soft_sample(self, sample: TableSample) -> SoftSample<Self>
+    where
+        SelectStatement<SampledTable<Self>>: SoftDeleteDsl,
+    {
+        SelectStatement::simple(SampledTable { table: self, sample }).soft_deleted()
+    }
+}
+
+impl<T: Table + Copy> SoftSampleDsl for T {}
@@ -0,0 +1,118 @@
+//! Scheduled soft deletions, effective at a given time unless cancelled.
+//!
+//! A table declared with the `scheduled` form of [`soft_delete!`](crate::soft_delete) has a
+//! nullable `delete_at` timestamp column: its rows are deleted for the soft methods once the
+//! column is set and has passed, without a job flipping them. A deletion is scheduled with
+//! [`schedule_soft_delete`], listed with [`scheduled_deletions`] until it happens, and cancelled
+//! with [`cancel_scheduled_delete`]:
+//!
+//! ```rust,ignore
+//! soft_delete!(account::table => scheduled(account::delete_at));
+//!
+//! let at = SystemTime::now() + Duration::from_secs(30 * 24 * 3600);
+//! schedule_soft_delete(account::table.find(id), at).execute(&conn)?;
+//! // The account is alive for 30 days, unless its owner changes their mind
+//! cancel_scheduled_delete(account::table.find(id)).execute(&conn)?;
+//! ```
+//!
+//! The time of the database decides when a scheduled row is deleted: it is compared to
+//! `CURRENT_TIMESTAMP`. On PostgreSQL, that is a `timestamptz`, to which a `timestamp` column is
+//! converted from the `TimeZone` of the session: as the times are written in UTC, a session ahead
+//! of UTC deletes the rows early by its offset, and a session behind it late. Use a `timestamptz`
+//! column, or sessions in UTC.
+//!
+//! As the alive rows change with the time, no index predicate can tell them, PostgreSQL rejecting
+//! `CURRENT_TIMESTAMP` in them: [`unique_alive_index`](crate::migration::unique_alive_index)
+//! rejects the scheduled tables, and
+//! [`missing_indexes`](crate::config::SoftDeleteConfig::missing_indexes) suggests a plain index
+//! on their `delete_at` column.
+
+use diesel::{
+    associations::HasTable,
+    dsl::{Filter, Gt, SqlTypeOf, Update},
+    expression::AsExpression,
+    helper_types::not as Not,
+    query_builder::{AsChangeset, IntoUpdateTarget},
+    query_dsl::methods::FilterDsl,
+    sql_types::SingleValue,
+    Column, ExpressionMethods,
+};
+
+use crate::{
+    write::{
+        current_time, timestamp_changeset, Deleted, DeletionTime, SoftDeleteWrite,
+        TimestampChangeset,
+    },
+    SoftDelete,
+};
+
+/// A soft-deletable table whose deletions are scheduled.
+///
+/// This is implemented by the `scheduled` form of [`soft_delete!`](crate::soft_delete).
+pub trait ScheduledSoftDelete: SoftDeleteWrite {
+    /// The nullable timestamp column holding the scheduled deletion time.
+    type DeleteAt: Column<Table = Self> + Copy;
+
+    fn delete_at_col(&self) -> Self::DeleteAt;
+}
+
+type DeleteAt<T> = <<T as HasTable>::Table as ScheduledSoftDelete>::DeleteAt;
+
+/// The condition of the rows whose deletion is scheduled but has not happened yet.
+pub type Pending<T> = Gt<DeleteAt<T>, DeletionTime<DeleteAt<T>>>;
+
+/// The type returned by [`schedule_soft_delete`].
+pub type ScheduleSoftDelete<T, At> =
+    Filter<Update<T, diesel::dsl::Eq<DeleteAt<T>, At>>, Not<Deleted<T>>>;
+/// The type returned by [`cancel_scheduled_delete`].
+pub type CancelScheduledDelete<T> = Filter<Update<T, TimestampChangeset<DeleteAt<T>>>, Pending<T>>;
+/// The type returned by [`scheduled_deletions`].
+pub type ScheduledDeletions<T> = Filter<T, Pending<T>>;
+
+/// Creates an `UPDATE` statement scheduling the soft deletion of the alive rows of `target` at
+/// `at`, replacing their previous schedule.
+pub fn schedule_soft_delete<T, At>(target: T, at: At) -> ScheduleSoftDelete<T, At>
+where
+    T: IntoUpdateTarget,
+    T::Table: ScheduledSoftDelete,
+    SqlTypeOf<DeleteAt<T>>: SingleValue,
+    At: AsExpression<SqlTypeOf<DeleteAt<T>>>,
+    diesel::dsl::Eq<DeleteAt<T>, At>: AsChangeset<Target = T::Table>,
+    Update<T, diesel::dsl::Eq<DeleteAt<T>, At>>: FilterDsl<Not<Deleted<T>>>,
+{
+    let table = T::table();
+    diesel::update(target)
+        .set(table.delete_at_col().eq(at))
+        .filter(diesel::dsl::not(table.deleted_col()))
+}
+
+/// Creates an `UPDATE` statement cancelling the scheduled deletions of the rows of `target` that
+/// have not happened yet.
+pub fn cancel_scheduled_delete<T>(target: T) -> CancelScheduledDelete<T>
+where
+    T: IntoUpdateTarget,
+    T::Table: ScheduledSoftDelete,
+    SqlTypeOf<DeleteAt<T>>: SingleValue,
+    Update<T, TimestampChangeset<DeleteAt<T>>>: FilterDsl<Pending<T>>,
+{
+    let delete_at = T::table().delete_at_col();
+    diesel::update(target).set(timestamp_changeset(delete_at, false)).filter(pending(delete_at))
+}
+
+/// The rows of `source` whose deletion is scheduled but has not happened yet, alive until then.
+pub fn scheduled_deletions<T>(source: T) -> ScheduledDeletions<T>
+where
+    T: HasTable + FilterDsl<Pending<T>>,
+    T::Table: ScheduledSoftDelete,
+    SqlTypeOf<DeleteAt<T>>: SingleValue,
+{
+    source.filter(pending(T::table().delete_at_col()))
+}
+
+fn pending<C>(delete_at: C) -> Gt<C, DeletionTime<C>>
+where
+    C: Column,
+    C::SqlType: SingleValue,
+{
+    delete_at.gt(current_time())
+}
@@ -0,0 +1,243 @@
+//! Named default scopes, generalizing the alive condition of soft-deletable tables.
+//!
+//! A scope is a condition that the queries on a table can be restricted to, such as the rows of
+//! a tenant or the published rows. It is a value implementing [`Scope`] for the table, built with
+//! the parameters of the condition if any, e.g. `Tenant(42)`. The alive condition is itself the
+//! [`NotDeleted`] scope of the soft-deletable tables.
+//!
+//! Tables declare their scopes with the [`scope`](crate::scope!) macro, then queries are
+//! restricted to them with [`scoped`](ScopeDsl::scoped), and joins with
+//! [`scoped_inner_join`](ScopedJoinDsl::scoped_inner_join) /
+//! [`scoped_left_join`](ScopedJoinDsl::scoped_left_join) which check the scope in the `ON`
+//! clause, as the soft joins do:
+//!
+//! ```rust,ignore
+//! pub struct Published;
+//! pub struct Tenant(pub i32);
+//!
+//! scope!(post::table => { Published = post::published, Tenant(i32) = post::tenant_id });
+//!
+//! let posts = post::table.scoped(Tenant(42)).scoped(Published).scoped(NotDeleted);
+//! let authors = user::table.scoped_inner_join(post::table, Published);
+//! ```
+//!
+//! Tuples of scopes are scopes themselves, requiring all of them, so that scopes applied together,
+//! such as a tenant and the alive rows, are stacked once and applied by a single call:
+//!
+//! ```rust,ignore
+//! let tenant = (Tenant(42), NotDeleted);
+//! let posts = post::table.scoped(tenant);
+//! let posts = post::table.inner_join(user::table).soft_scope_all_with(Tenant(42));
+//! ```
+
+use diesel::{
+    associations::HasTable,
+    dsl::{And, Filter},
+    expression::{grouped::Grouped, NonAggregate},
+    query_builder::{
+        nodes::{Identifier, InfixNode},
+        AsQuery,
+    },
+    query_dsl::{methods::FilterDsl, InternalJoinDsl},
+    query_source::joins::{Inner, Join, JoinOn, LeftOuter},
+    sql_types::Bool,
+    BoolExpressionMethods, Expression, JoinTo, QuerySource, Table,
+};
+
+use crate::{methods::AliveCondition, query_source::FromClauseOf, SoftDelete};
+
+/// A condition restricting the rows of the table `T`.
+pub trait Scope<T> {
+    type Predicate: Expression<SqlType = Bool> + NonAggregate;
+
+    fn predicate(self, table: &T) -> Self::Predicate;
+}
+
+/// The scope of the alive rows of a soft-deletable table, i.e. its alive condition.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NotDeleted;
+
+impl<T: SoftDelete> Scope<T> for NotDeleted {
+    type Predicate = AliveCondition<T::Deleted>;
+
+    fn predicate(self, table: &T) -> Self::Predicate {
+        crate::methods::alive_condition(table.deleted_col())
+    }
+}
+
+impl<T, A, B> Scope<T> for (A, B)
+where
+    A: Scope<T>,
+    B: Scope<T>,
+    And<A::Predicate, B::Predicate>: Expression<SqlType = Bool> + NonAggregate,
+{
+    type Predicate = And<A::Predicate, B::Predicate>;
+
+    fn predicate(self, table: &T) -> Self::Predicate {
+        let (a, b) = self;
+        a.predicate(table).and(b.predicate(table))
+    }
+}
+
+impl<T, A, B, C> Scope<T> for (A, B, C)
+where
+    ((A, B), C): Scope<T>,
+{
+    type Predicate = <((A, B), C) as Scope<T>>::Predicate;
+
+    fn predicate(self, table: &T) -> Self::Predicate {
+        let (a, b, c) = self;
+        ((a, b), c).predicate(table)
+    }
+}
+
+impl<T, A, B, C, D> Scope<T> for (A, B, C, D)
+where
+    ((A, B, C), D): Scope<T>,
+{
+    type Predicate = <((A, B, C), D) as Scope<T>>::Predicate;
+
+    fn predicate(self, table: &T) -> Self::Predicate {
+        let (a, b, c, d) = self;
+        ((a, b, c), d).predicate(table)
+    }
+}
+
+/// A query source whose scopes are the ones of its leftmost table.
+///
+/// It is implemented for every table, and for the joins starting from one.
+pub trait ScopeSource {
+    type Table;
+
+    fn scope_table() -> Self::Table;
+}
+
+impl<T> ScopeSource for T
+where
+    T: QuerySource,
+    T::FromClause: LeftmostTable<T>,
+{
+    type Table = <T::FromClause as LeftmostTable<T>>::Table;
+
+    fn scope_table() -> Self::Table {
+        <T::FromClause as LeftmostTable<T>>::leftmost_table()
+    }
+}
+
+/// The leftmost table of the query source `T`, implemented by its `FROM` clause.
+///
+/// Diesel's tables and joins are told apart by their `FROM` clauses: they implement the same
+/// traits, which Diesel could add to either.
+pub trait LeftmostTable<T> {
+    type Table;
+
+    fn leftmost_table() -> Self::Table;
+}
+
+impl<T> LeftmostTable<T> for Identifier<'static>
+where
+    T: Table + HasTable<Table = T>,
+{
+    type Table = T;
+
+    fn leftmost_table() -> Self::Table {
+        T::table()
+    }
+}
+
+/// The tables of a schema.
+impl<T> LeftmostTable<T> for InfixNode<'static, Identifier<'static>, Identifier<'static>>
+where
+    T: Table + HasTable<Table = T>,
+{
+    type Table = T;
+
+    fn leftmost_table() -> Self::Table {
+        T::table()
+    }
+}
+
+impl<Left, Right, Kind> LeftmostTable<Join<Left, Right, Kind>> for Join<Left, Right, Kind>
+where
+    Left: ScopeSource,
+{
+    type Table = Left::Table;
+
+    fn leftmost_table() -> Self::Table {
+        Left::scope_table()
+    }
+}
+
+impl<J, On> LeftmostTable<JoinOn<J, On>> for Grouped<InfixNode<'static, J::FromClause, On>>
+where
+    J: QuerySource + ScopeSource,
+{
+    type Table = J::Table;
+
+    fn leftmost_table() -> Self::Table {
+        J::scope_table()
+    }
+}
+
+/// Restricting a query to a scope of its leftmost table.
+pub trait ScopeDsl<S> {
+    /// The type returned by `.scoped`.
+    type Output;
+
+    fn scoped(self, scope: S) -> Self::Output;
+}
+
+type ScopeTable<T> = <<<T as AsQuery>::Query as FromClauseOf>::FromClause as ScopeSource>::Table;
+type ScopePredicate<T, S> = <S as Scope<ScopeTable<T>>>::Predicate;
+
+impl<T, S> ScopeDsl<S> for T
+where
+    T: AsQuery + FilterDsl<ScopePredicate<T, S>>,
+    T::Query: FromClauseOf,
+    <T::Query as FromClauseOf>::FromClause: ScopeSource,
+    S: Scope<ScopeTable<T>>,
+{
+    type Output = Filter<T, ScopePredicate<T, S>>;
+
+    fn scoped(self, scope: S) -> Self::Output {
+        let table = <<T::Query as FromClauseOf>::FromClause as ScopeSource>::scope_table();
+        self.filter(scope.predicate(&table))
+    }
+}
+
+type ScopedOnClause<Lhs, Rhs, S> =
+    And<<Lhs as JoinTo<Rhs>>::OnClause, <S as Scope<Rhs>>::Predicate>;
+
+pub(crate) type ScopedJoin<Lhs, Rhs, Kind, S> = <Lhs as InternalJoinDsl<
+    <Lhs as JoinTo<Rhs>>::FromClause,
+    Kind,
+    ScopedOnClause<Lhs, Rhs, S>,
+>>::Output;
+
+/// Joins restricted to a scope of the joined table, checked in the `ON` clause.
+pub trait ScopedJoinDsl: Sized {
+    fn scoped_inner_join<Rhs, S>(self, rhs: Rhs, scope: S) -> ScopedJoin<Self, Rhs, Inner, S>
+    where
+        Self: JoinTo<Rhs> + InternalJoinDsl<Self::FromClause, Inner, ScopedOnClause<Self, Rhs, S>>,
+        Self::OnClause: Expression<SqlType = Bool>,
+        S: Scope<Rhs>,
+    {
+        let predicate = scope.predicate(&rhs);
+        let (from, on) = Self::join_target(rhs);
+        self.join(from, Inner, on.and(predicate))
+    }
+
+    fn scoped_left_join<Rhs, S>(self, rhs: Rhs, scope: S) -> ScopedJoin<Self, Rhs, LeftOuter, S>
+    where
+        Self: JoinTo<Rhs>
+            + InternalJoinDsl<Self::FromClause, LeftOuter, ScopedOnClause<Self, Rhs, S>>,
+        Self::OnClause: Expression<SqlType = Bool>,
+        S: Scope<Rhs>,
+    {
+        let predicate = scope.predicate(&rhs);
+        let (from, on) = Self::join_target(rhs);
+        self.join(from, LeftOuter, on.and(predicate))
+    }
+}
+
+impl<Lhs> ScopedJoinDsl for Lhs where Lhs: Sized {}
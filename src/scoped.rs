@@ -0,0 +1,435 @@
+//! Wrappers around soft-deletable tables enforcing a decision on deleted rows.
+
+use std::marker::PhantomData;
+
+use diesel::{
+    associations::HasTable,
+    backend::Backend,
+    dsl::{Distinct, Filter, Find, IntoBoxed, Limit, Offset, Order, Select},
+    expression::operators::And,
+    helper_types::not as Not,
+    query_builder::{AsQuery, SelectStatement},
+    query_dsl::{
+        methods::{
+            BoxedDsl, DistinctDsl, FilterDsl, FindDsl, LimitDsl, LockingDsl, ModifyLockDsl,
+            OffsetDsl, OrderDsl, SelectDsl,
+        },
+        QueryDsl, RunQueryDsl,
+    },
+    query_source::joins::{Join, JoinOn},
+    sql_types::Bool,
+    Expression,
+};
+
+use crate::{
+    methods::{SoftDeleteDsl, SoftFilterDsl, SoftFindDsl},
+    query_source::{Same, WhereClauseOf},
+    SoftDelete,
+};
+
+/// A table whose queries always keep only the alive rows.
+///
+/// It can be used in place of the table with the usual query builder methods, and as a query on
+/// its own: all of them start from [`soft_deleted`](SoftDeleteDsl::soft_deleted). Handing it to
+/// application code rather than the table makes it impossible to accidentally query trashed rows.
+///
+/// As the wrapper records that the query is soft-scoped, the soft methods called on it do not add
+/// the alive condition a second time, e.g. `.soft_filter(p)` is the same as `.filter(p)`: helpers
+/// composing soft methods can be handed either the table or its `Scoped` wrapper.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Scoped<T>(T);
+
+impl<T: SoftDeleteDsl> Scoped<T> {
+    pub fn new(table: T) -> Self {
+        Self(table)
+    }
+
+    /// The underlying soft-scoped query, e.g. to join other tables onto it.
+    pub fn query(self) -> T::Output {
+        self.0.soft_deleted()
+    }
+}
+
+impl<T> AsQuery for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: AsQuery,
+{
+    type SqlType = <T::Output as AsQuery>::SqlType;
+    type Query = <T::Output as AsQuery>::Query;
+
+    fn as_query(self) -> Self::Query {
+        self.query().as_query()
+    }
+}
+
+impl<T> QueryDsl for Scoped<T> {}
+
+impl<T, Conn> RunQueryDsl<Conn> for Scoped<T> {}
+
+impl<T, Predicate> FilterDsl<Predicate> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: FilterDsl<Predicate>,
+{
+    type Output = Filter<T::Output, Predicate>;
+
+    fn filter(self, predicate: Predicate) -> Self::Output {
+        self.query().filter(predicate)
+    }
+}
+
+impl<T, PK> FindDsl<PK> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: FindDsl<PK>,
+{
+    type Output = Find<T::Output, PK>;
+
+    fn find(self, id: PK) -> Self::Output {
+        self.query().find(id)
+    }
+}
+
+impl<T, Selection> SelectDsl<Selection> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: SelectDsl<Selection>,
+    Selection: Expression,
+{
+    type Output = Select<T::Output, Selection>;
+
+    fn select(self, selection: Selection) -> Self::Output {
+        self.query().select(selection)
+    }
+}
+
+impl<T, Expr> OrderDsl<Expr> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: OrderDsl<Expr>,
+    Expr: Expression,
+{
+    type Output = Order<T::Output, Expr>;
+
+    fn order(self, expr: Expr) -> Self::Output {
+        self.query().order(expr)
+    }
+}
+
+impl<T> LimitDsl for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: LimitDsl,
+{
+    type Output = Limit<T::Output>;
+
+    fn limit(self, limit: i64) -> Self::Output {
+        self.query().limit(limit)
+    }
+}
+
+impl<T> OffsetDsl for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: OffsetDsl,
+{
+    type Output = Offset<T::Output>;
+
+    fn offset(self, offset: i64) -> Self::Output {
+        self.query().offset(offset)
+    }
+}
+
+impl<T> DistinctDsl for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: DistinctDsl,
+{
+    type Output = Distinct<T::Output>;
+
+    fn distinct(self) -> Self::Output {
+        self.query().distinct()
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl<T, Selection> diesel::query_dsl::methods::DistinctOnDsl<Selection> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: diesel::query_dsl::methods::DistinctOnDsl<Selection>,
+{
+    type Output = diesel::dsl::DistinctOn<T::Output, Selection>;
+
+    fn distinct_on(self, selection: Selection) -> Self::Output {
+        self.query().distinct_on(selection)
+    }
+}
+
+impl<T, Lock> LockingDsl<Lock> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: LockingDsl<Lock>,
+{
+    type Output = <T::Output as LockingDsl<Lock>>::Output;
+
+    fn with_lock(self, lock: Lock) -> Self::Output {
+        self.query().with_lock(lock)
+    }
+}
+
+impl<T, Modifier> ModifyLockDsl<Modifier> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: ModifyLockDsl<Modifier>,
+{
+    type Output = <T::Output as ModifyLockDsl<Modifier>>::Output;
+
+    fn modify_lock(self, modifier: Modifier) -> Self::Output {
+        self.query().modify_lock(modifier)
+    }
+}
+
+impl<'a, T, DB> BoxedDsl<'a, DB> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: BoxedDsl<'a, DB>,
+    DB: Backend,
+{
+    type Output = IntoBoxed<'a, T::Output, DB>;
+
+    fn internal_into_boxed(self) -> Self::Output {
+        self.query().internal_into_boxed()
+    }
+}
+
+impl<T: SoftDeleteDsl> SoftDeleteDsl for Scoped<T> {
+    type Output = T::Output;
+
+    fn soft_deleted(self) -> Self::Output {
+        self.query()
+    }
+}
+
+impl<T, PK> SoftFindDsl<PK> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: FindDsl<PK>,
+{
+    type Output = Find<T::Output, PK>;
+
+    fn soft_find(self, id: PK) -> Self::Output {
+        self.query().find(id)
+    }
+}
+
+impl<T, Predicate> SoftFilterDsl<Predicate> for Scoped<T>
+where
+    T: SoftDeleteDsl,
+    T::Output: FilterDsl<Predicate>,
+{
+    type Output = Filter<T::Output, Predicate>;
+
+    fn soft_filter(self, predicate: Predicate) -> Self::Output {
+        self.query().filter(predicate)
+    }
+}
+
+/// A query on a soft-deletable table that cannot be run until it is decided which rows it sees.
+///
+/// This is created by [`soft_query`], and has no query builder methods of its own: one of
+/// [`alive`](Self::alive), [`trashed`](Self::trashed) or
+/// [`including_deleted`](Self::including_deleted) must be called first.
+#[derive(Debug, Clone, Copy)]
+#[must_use = "an unscoped query does nothing until its deleted rows visibility is decided"]
+pub struct Unscoped<T>(T);
+
+/// Start a query on `table` that requires an explicit decision on its deleted rows.
+pub fn soft_query<T: SoftDelete>(table: T) -> Unscoped<T> {
+    Unscoped(table)
+}
+
+impl<T: SoftDelete> Unscoped<T> {
+    /// Only see the alive rows.
+    pub fn alive(self) -> T::Output
+    where
+        T: SoftDeleteDsl,
+    {
+        self.0.soft_deleted()
+    }
+
+    /// Only see the soft-deleted rows.
+    pub fn trashed(self) -> Filter<T, T::Deleted>
+    where
+        T: FilterDsl<T::Deleted>,
+    {
+        let deleted = self.0.deleted_col();
+        self.0.filter(deleted)
+    }
+
+    /// See all rows, whether they are soft-deleted or not.
+    pub fn including_deleted(self) -> T {
+        self.0
+    }
+}
+
+/// A query whose type proves that it only sees alive rows.
+///
+/// This covers:
+///
+/// - the queries built with the soft methods (e.g. `soft_deleted`, `soft_find`, `soft_filter`),
+///   including when other conditions are added with `filter` or `soft_filter`: the alive rows of
+///   the table they start from are seen;
+/// - the joins whose every table is scoped, either in the `WHERE` clause as by
+///   [`soft_inner_join_both`](crate::query_dsl::SoftJoinDsl::soft_inner_join_both) and
+///   [`soft_scope_all`](crate::query_dsl::SoftScopeAllDsl::soft_scope_all), or in the `ON` clause
+///   of its join as by the soft joins. A soft join from an unscoped table is not covered;
+/// - the [`Scoped`] tables.
+///
+/// Boxed queries erase their conditions from their type, and thus cannot implement it.
+///
+/// The `Proof` parameter tells where the alive conditions were found. It is inferred, so that
+/// application functions can refuse unscoped queries at compile time with a generic parameter:
+///
+/// ```rust,ignore
+/// fn list_users<Q, P>(query: Q, conn: &PgConnection) -> QueryResult<Vec<User>>
+/// where
+///     Q: SoftScoped<P> + LoadQuery<PgConnection, User>,
+/// {
+///     query.load(conn)
+/// }
+/// ```
+pub trait SoftScoped<Proof> {}
+
+/// A predicate that implies the alive condition on the `Deleted` column.
+pub trait AlivePredicate<Deleted, Proof> {}
+
+/// The predicate is the alive condition itself.
+#[derive(Debug)]
+pub struct Here;
+
+/// The alive condition is found on the left-hand side of an `AND`.
+#[derive(Debug)]
+pub struct InLeft<P>(PhantomData<P>);
+
+/// The alive condition is found on the right-hand side of an `AND`.
+#[derive(Debug)]
+pub struct InRight<P>(PhantomData<P>);
+
+/// The alive condition of a table is found in the `WHERE` clause.
+#[derive(Debug)]
+pub struct InWhere<P>(PhantomData<P>);
+
+/// The left-hand side of a join is scoped as told by `L`, and its right-hand side as told by `R`.
+#[derive(Debug)]
+pub struct InJoin<L, R>(PhantomData<(L, R)>);
+
+/// The alive condition of the table is found in the `ON` clause of its join.
+#[derive(Debug)]
+pub struct InOn<P>(PhantomData<P>);
+
+/// The joined query is scoped as told by `P`.
+#[derive(Debug)]
+pub struct InQuery<P>(PhantomData<P>);
+
+impl<D> AlivePredicate<D, Here> for Not<D> where D: Expression<SqlType = Bool> {}
+
+#[cfg(feature = "sql-comments")]
+impl<D> AlivePredicate<D, Here> for crate::comment::Tagged<Not<D>> where
+    D: Expression<SqlType = Bool>
+{
+}
+
+#[cfg(feature = "implicit-soft-joins")]
+impl<E, D> AlivePredicate<D, Here> for crate::query_dsl::ImplicitOn<E, D> {}
+
+impl<L, R, D, P> AlivePredicate<D, InLeft<P>> for And<L, R> where L: AlivePredicate<D, P> {}
+
+impl<L, R, D, P> AlivePredicate<D, InRight<P>> for And<L, R> where R: AlivePredicate<D, P> {}
+
+type WhereClause<F, Predicate> =
+    <Filter<SelectStatement<F>, Predicate> as WhereClauseOf>::WhereClause;
+
+/// A query source whose rows are all alive in a query with the `Predicate` `WHERE` clause.
+pub trait AliveSource<Predicate, Proof> {}
+
+impl<T, Predicate, P> AliveSource<Predicate, InWhere<P>> for T
+where
+    T: SoftDelete + HasTable<Table = T>,
+    Predicate: AlivePredicate<T::Deleted, P>,
+{
+}
+
+impl<Left, Right, Kind, On, Predicate, LP, P> AliveSource<Predicate, InJoin<LP, InWhere<P>>>
+    for JoinOn<Join<Left, Right, Kind>, On>
+where
+    Left: AliveSource<Predicate, LP>,
+    Right: AliveSource<Predicate, InWhere<P>>,
+{
+}
+
+impl<Left, Right, Kind, On, Predicate, LP, P> AliveSource<Predicate, InJoin<LP, InOn<P>>>
+    for JoinOn<Join<Left, Right, Kind>, On>
+where
+    Left: AliveSource<Predicate, LP>,
+    Right: JoinedSource<On, InOn<P>>,
+{
+}
+
+impl<Left, Right, Kind, On, Predicate, LP, RL, RR>
+    AliveSource<Predicate, InJoin<LP, InJoin<RL, RR>>> for JoinOn<Join<Left, Right, Kind>, On>
+where
+    Left: AliveSource<Predicate, LP>,
+    Right: JoinedSource<On, InJoin<RL, RR>>,
+{
+}
+
+impl<Left, Right, Kind, On, Predicate, LP, P> AliveSource<Predicate, InJoin<LP, InQuery<P>>>
+    for JoinOn<Join<Left, Right, Kind>, On>
+where
+    Left: AliveSource<Predicate, LP>,
+    Right: JoinedSource<On, InQuery<P>>,
+{
+}
+
+/// A query source joined with the `On` clause, whose rows are all alive.
+pub trait JoinedSource<On, Proof> {}
+
+impl<T, On, P> JoinedSource<On, InOn<P>> for T
+where
+    T: SoftDelete + HasTable<Table = T>,
+    On: AlivePredicate<T::Deleted, P>,
+{
+}
+
+impl<Left, Right, Kind, InnerOn, On, LP, RP> JoinedSource<On, InJoin<LP, RP>>
+    for JoinOn<Join<Left, Right, Kind>, InnerOn>
+where
+    Left: JoinedSource<On, LP>,
+    Right: JoinedSource<InnerOn, RP>,
+{
+}
+
+impl<F, S, D, W, O, L, Of, G, LC, On, P> JoinedSource<On, InQuery<P>>
+    for SelectStatement<F, S, D, W, O, L, Of, G, LC>
+where
+    F: JoinedSource<On, P>,
+{
+}
+
+impl<F, S, D, W, O, L, Of, G, LC, Predicate, P> SoftScoped<(Predicate, P)>
+    for SelectStatement<F, S, D, W, O, L, Of, G, LC>
+where
+    SelectStatement<F>: FilterDsl<Predicate>,
+    Filter<SelectStatement<F>, Predicate>: WhereClauseOf,
+    W: Same<WhereClause<F, Predicate>>,
+    F: AliveSource<Predicate, P>,
+{
+}
+
+impl<T: SoftDelete> SoftScoped<Here> for Scoped<T> {}
+
+#[doc(hidden)]
+pub fn assert_soft_scoped<Q: SoftScoped<P>, P>(query: Q) -> Q {
+    query
+}
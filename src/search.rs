@@ -0,0 +1,87 @@
+//! Full-text search on soft-scoped queries (PostgreSQL).
+//!
+//! Search endpoints are a common place for trashed rows to leak out. The `@@` match of a
+//! `tsvector` against a `tsquery` is an ordinary predicate, and combines with the alive condition
+//! like any other: [`soft_search`](SoftSearchDsl::soft_search) is a shortcut for
+//! `.soft_filter(ts_matches(vector, query))`.
+//!
+//! Both sides can be any expression, e.g. the columns and functions of `diesel_full_text_search`,
+//! or SQL literals.
+
+use diesel::{
+    expression::{AppearsOnTable, NonAggregate, SelectableExpression},
+    pg::Pg,
+    query_builder::{AstPass, QueryFragment, QueryId},
+    sql_types::Bool,
+    Expression, QueryResult,
+};
+
+use crate::methods::SoftFilterDsl;
+
+/// The `vector @@ query` full-text match.
+#[derive(Debug, Clone, Copy)]
+pub struct TsMatches<V, Q> {
+    vector: V,
+    query: Q,
+}
+
+/// Match the `tsvector` expression `vector` against the `tsquery` expression `query`.
+pub fn ts_matches<V, Q>(vector: V, query: Q) -> TsMatches<V, Q>
+where
+    V: Expression,
+    Q: Expression,
+{
+    TsMatches { vector, query }
+}
+
+impl<V, Q> Expression for TsMatches<V, Q> {
+    type SqlType = Bool;
+}
+
+impl<V: NonAggregate, Q: NonAggregate> NonAggregate for TsMatches<V, Q> {}
+
+impl<V, Q, QS> AppearsOnTable<QS> for TsMatches<V, Q>
+where
+    V: AppearsOnTable<QS>,
+    Q: AppearsOnTable<QS>,
+{
+}
+
+impl<V, Q, QS> SelectableExpression<QS> for TsMatches<V, Q>
+where
+    V: SelectableExpression<QS>,
+    Q: SelectableExpression<QS>,
+{
+}
+
+impl<V: QueryId, Q: QueryId> QueryId for TsMatches<V, Q> {
+    type QueryId = TsMatches<V::QueryId, Q::QueryId>;
+    const HAS_STATIC_QUERY_ID: bool = V::HAS_STATIC_QUERY_ID && Q::HAS_STATIC_QUERY_ID;
+}
+
+impl<V, Q> QueryFragment<Pg> for TsMatches<V, Q>
+where
+    V: QueryFragment<Pg>,
+    Q: QueryFragment<Pg>,
+{
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        self.vector.walk_ast(out.reborrow())?;
+        out.push_sql(" @@ ");
+        self.query.walk_ast(out.reborrow())?;
+        Ok(())
+    }
+}
+
+/// The `soft_search` method.
+pub trait SoftSearchDsl<V, Q>: SoftFilterDsl<TsMatches<V, Q>> + Sized {
+    /// Keep the alive rows whose `vector` matches `query`.
+    fn soft_search(self, vector: V, query: Q) -> Self::Output
+    where
+        V: Expression,
+        Q: Expression,
+    {
+        self.soft_filter(ts_matches(vector, query))
+    }
+}
+
+impl<T, V, Q> SoftSearchDsl<V, Q> for T where T: SoftFilterDsl<TsMatches<V, Q>> {}
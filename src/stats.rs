@@ -0,0 +1,329 @@
+//! Trash statistics of soft-deletable tables, for housekeeping dashboards and monitoring.
+//!
+//! The statistics can also be checked against [`TrashThresholds`], e.g. in a health check, for the
+//! tables whose trash grows out of hand to surface:
+//!
+//! ```rust,ignore
+//! let thresholds = TrashThresholds::new().max_ratio(0.3).max_age(Duration::from_secs(30 * 86400));
+//! for violation in registry.trash_violations(&thresholds, &conn)? {
+//!     log::warn!("{}", violation);
+//! }
+//! ```
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, QueryableByName},
+    dsl::{count_star, CountStar, Filter, IsNotNull, IsNull, Select, SqlTypeOf},
+    helper_types::min as Min,
+    query_builder::nodes::Identifier,
+    query_dsl::{
+        methods::{FilterDsl, SelectDsl},
+        LoadQuery,
+    },
+    row::NamedRow,
+    sql_types::{BigInt, IntoNullable, SingleValue, SqlOrd},
+    Connection, ExpressionMethods, QueryResult, QuerySource, RunQueryDsl,
+};
+
+use crate::{
+    config::{SoftDeleteConfig, SoftDeleteTable},
+    methods::{OldestTrashDsl, OnlyDeletedDsl, SoftDeleteDsl},
+    SoftDeleteHistory,
+};
+
+/// The trash statistics of a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableStats<Ts = SystemTime> {
+    pub table: &'static str,
+    pub alive: i64,
+    pub trashed: i64,
+    /// The deletion time of the oldest trashed row. It is only known for the tables registered
+    /// with their deletion history.
+    pub oldest_trashed: Option<Ts>,
+}
+
+impl<Ts> TableStats<Ts> {
+    /// The share of trashed rows, between 0 and 1. It is 0 for an empty table.
+    pub fn trash_ratio(&self) -> f64 {
+        match self.alive + self.trashed {
+            0 => 0.,
+            total => self.trashed as f64 / total as f64,
+        }
+    }
+}
+
+type Collector<Conn, Ts> = Box<dyn Fn(&Conn) -> QueryResult<TableStats<Ts>> + Send + Sync>;
+type DeletedAt<T> = <T as SoftDeleteHistory>::DeletedAt;
+
+/// The set of tables whose trash statistics are reported.
+///
+/// The `Ts` type parameter is the type the `deleted_at` columns of the tables registered with
+/// [`history_table`](Self::history_table) are loaded as.
+pub struct TrashRegistry<Conn, Ts = SystemTime> {
+    tables: Vec<Collector<Conn, Ts>>,
+}
+
+impl<Conn, Ts> Default for TrashRegistry<Conn, Ts> {
+    fn default() -> Self {
+        Self { tables: Vec::new() }
+    }
+}
+
+impl<Conn, Ts> TrashRegistry<Conn, Ts> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a table soft-deleted with a flag.
+    pub fn table<T>(mut self, table: T) -> Self
+    where
+        T: QuerySource<FromClause = Identifier<'static>> + SoftDeleteDsl + OnlyDeletedDsl,
+        T: Copy + Send + Sync + 'static,
+        <T as SoftDeleteDsl>::Output: SelectDsl<CountStar>,
+        <T as OnlyDeletedDsl>::Output: SelectDsl<CountStar>,
+        Select<<T as SoftDeleteDsl>::Output, CountStar>: LoadQuery<Conn, i64> + RunQueryDsl<Conn>,
+        Select<<T as OnlyDeletedDsl>::Output, CountStar>: LoadQuery<Conn, i64> + RunQueryDsl<Conn>,
+    {
+        self.tables.push(Box::new(move |conn| {
+            Ok(TableStats {
+                table: table.from_clause().0,
+                alive: table.soft_deleted().select(count_star()).get_result(conn)?,
+                trashed: table.only_deleted().select(count_star()).get_result(conn)?,
+                oldest_trashed: None,
+            })
+        }));
+        self
+    }
+
+    /// Register a table soft-deleted with a `deleted_at` timestamp.
+    pub fn history_table<T, Oldest>(mut self, table: T) -> Self
+    where
+        T: QuerySource<FromClause = Identifier<'static>> + SoftDeleteHistory + OldestTrashDsl,
+        T: FilterDsl<IsNull<DeletedAt<T>>> + FilterDsl<IsNotNull<DeletedAt<T>>>,
+        T: Copy + Send + Sync + 'static,
+        SqlTypeOf<DeletedAt<T>>: SingleValue + SqlOrd + IntoNullable,
+        T: SelectDsl<Min<DeletedAt<T>>, Output = Oldest>,
+        Filter<T, IsNull<DeletedAt<T>>>: SelectDsl<CountStar>,
+        Filter<T, IsNotNull<DeletedAt<T>>>: SelectDsl<CountStar>,
+        Select<Filter<T, IsNull<DeletedAt<T>>>, CountStar>:
+            LoadQuery<Conn, i64> + RunQueryDsl<Conn>,
+        Select<Filter<T, IsNotNull<DeletedAt<T>>>, CountStar>:
+            LoadQuery<Conn, i64> + RunQueryDsl<Conn>,
+        Oldest: LoadQuery<Conn, Option<Ts>> + RunQueryDsl<Conn>,
+    {
+        self.tables.push(Box::new(move |conn| {
+            let (alive, trashed) =
+                (table.deleted_at_col().is_null(), table.deleted_at_col().is_not_null());
+            Ok(TableStats {
+                table: table.from_clause().0,
+                alive: table.filter(alive).select(count_star()).get_result(conn)?,
+                trashed: table.filter(trashed).select(count_star()).get_result(conn)?,
+                oldest_trashed: table.oldest_trash().get_result(conn)?,
+            })
+        }));
+        self
+    }
+
+    /// Register the tables of `config`. Their rows are counted with their rendered conditions, and
+    /// the deletion time of their oldest trashed row is not reported.
+    pub fn config(mut self, config: &SoftDeleteConfig<Conn::Backend>) -> Self
+    where
+        Conn: Connection + 'static,
+        <Conn::Backend as Backend>::QueryBuilder: Default,
+        i64: FromSql<BigInt, Conn::Backend>,
+    {
+        for table in config.tables() {
+            let table = table.clone();
+            self.tables.push(Box::new(move |conn| {
+                Ok(TableStats {
+                    table: table.name(),
+                    alive: count_where(&table, &table.not_deleted_sql()?, conn)?,
+                    trashed: count_where(&table, &table.deleted_sql()?, conn)?,
+                    oldest_trashed: None,
+                })
+            }));
+        }
+        self
+    }
+
+    /// The statistics of each registered table, in registration order.
+    pub fn soft_delete_stats(&self, conn: &Conn) -> QueryResult<Vec<TableStats<Ts>>> {
+        self.tables.iter().map(|stats| stats(conn)).collect()
+    }
+
+    /// The violations of `thresholds` by the registered tables, in registration order, e.g. to
+    /// fail a health check.
+    pub fn trash_violations(
+        &self,
+        thresholds: &TrashThresholds<Ts>,
+        conn: &Conn,
+    ) -> QueryResult<Vec<TrashViolation<Ts>>>
+    where
+        Ts: PartialOrd + Copy,
+    {
+        Ok(thresholds.violations(&self.soft_delete_stats(conn)?))
+    }
+}
+
+/// The limits on the trash of the tables of a [`TrashRegistry`], past which they are reported as
+/// [`TrashViolation`]s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrashThresholds<Ts = SystemTime> {
+    max_ratio: Option<f64>,
+    trashed_before: Option<Cutoff<Ts>>,
+}
+
+/// The time before which the rows must not have been trashed.
+#[derive(Debug, Clone, Copy)]
+enum Cutoff<Ts> {
+    At(Ts),
+    /// A maximum age, and the cutoff it gives as of now, if any.
+    Age(Duration, fn(Duration) -> Option<Ts>),
+}
+
+impl<Ts: PartialEq> PartialEq for Cutoff<Ts> {
+    fn eq(&self, other: &Self) -> bool {
+        // The cutoff of an age is only computed by `max_age`.
+        match (self, other) {
+            (Self::At(at), Self::At(other)) => at == other,
+            (Self::Age(age, _), Self::Age(other, _)) => age == other,
+            _ => false,
+        }
+    }
+}
+
+impl<Ts> Default for TrashThresholds<Ts> {
+    fn default() -> Self {
+        Self { max_ratio: None, trashed_before: None }
+    }
+}
+
+impl<Ts> TrashThresholds<Ts> {
+    /// No limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the tables whose [share of trashed rows](TableStats::trash_ratio) exceeds
+    /// `max_ratio`, between 0 and 1.
+    pub fn max_ratio(mut self, max_ratio: f64) -> Self {
+        self.max_ratio = Some(max_ratio);
+        self
+    }
+
+    /// Report the tables holding a row trashed before `trashed_before`. Only the tables registered
+    /// with their deletion history know when their rows were trashed.
+    pub fn trashed_before(mut self, trashed_before: Ts) -> Self {
+        self.trashed_before = Some(Cutoff::At(trashed_before));
+        self
+    }
+
+    /// The violations of these thresholds by the tables of `stats`, in order.
+    pub fn violations(&self, stats: &[TableStats<Ts>]) -> Vec<TrashViolation<Ts>>
+    where
+        Ts: PartialOrd + Copy,
+    {
+        let trashed_before = self.trashed_before.and_then(|cutoff| match cutoff {
+            Cutoff::At(trashed_before) => Some(trashed_before),
+            Cutoff::Age(max_age, cutoff) => cutoff(max_age),
+        });
+        let mut violations = Vec::new();
+        for stats in stats {
+            let ratio = stats.trash_ratio();
+            match self.max_ratio {
+                Some(max_ratio) if ratio > max_ratio => {
+                    violations.push(TrashViolation::Ratio { table: stats.table, ratio, max_ratio })
+                }
+                _ => {}
+            }
+            match (stats.oldest_trashed, trashed_before) {
+                (Some(oldest_trashed), Some(trashed_before)) if oldest_trashed < trashed_before => {
+                    violations.push(TrashViolation::Age {
+                        table: stats.table,
+                        oldest_trashed,
+                        trashed_before,
+                    })
+                }
+                _ => {}
+            }
+        }
+        violations
+    }
+}
+
+impl TrashThresholds<SystemTime> {
+    /// Report the tables holding a row trashed for longer than `max_age`, as of each check. An age
+    /// reaching before the epoch is never exceeded.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        let cutoff = |max_age| SystemTime::now().checked_sub(max_age);
+        self.trashed_before = Some(Cutoff::Age(max_age, cutoff));
+        self
+    }
+}
+
+/// A table whose trash exceeds its [`TrashThresholds`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrashViolation<Ts = SystemTime> {
+    /// The share of trashed rows exceeds `max_ratio`.
+    Ratio { table: &'static str, ratio: f64, max_ratio: f64 },
+    /// The oldest trashed row was trashed before `trashed_before`.
+    Age { table: &'static str, oldest_trashed: Ts, trashed_before: Ts },
+}
+
+impl<Ts> TrashViolation<Ts> {
+    /// The table exceeding its thresholds.
+    pub fn table(&self) -> &'static str {
+        match self {
+            Self::Ratio { table, .. } | Self::Age { table, .. } => table,
+        }
+    }
+}
+
+impl<Ts> fmt::Display for TrashViolation<Ts> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ratio { table, ratio, max_ratio } => write!(
+                f,
+                "{:.0}% of the rows of `{}` are trashed, more than {:.0}%",
+                ratio * 100.,
+                table,
+                max_ratio * 100.,
+            ),
+            Self::Age { table, .. } => {
+                write!(f, "`{}` holds rows trashed for longer than allowed", table)
+            }
+        }
+    }
+}
+
+/// The number of rows of a table.
+pub(crate) struct Count(pub(crate) i64);
+
+impl<DB: Backend> QueryableByName<DB> for Count
+where
+    i64: FromSql<BigInt, DB>,
+{
+    fn build<R: NamedRow<DB>>(row: &R) -> deserialize::Result<Self> {
+        row.get::<BigInt, i64>("count").map(Self)
+    }
+}
+
+/// Count the rows of `table` matching the SQL `condition`.
+fn count_where<Conn>(
+    table: &SoftDeleteTable<Conn::Backend>,
+    condition: &str,
+    conn: &Conn,
+) -> QueryResult<i64>
+where
+    Conn: Connection,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+    i64: FromSql<BigInt, Conn::Backend>,
+{
+    let sql = format!("SELECT COUNT(*) AS count FROM {} WHERE {}", table.quoted_name()?, condition);
+    diesel::sql_query(sql).get_result::<Count>(conn).map(|Count(count)| count)
+}
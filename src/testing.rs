@@ -0,0 +1,84 @@
+//! Test utilities for the queries built with the soft methods.
+//!
+//! [`debug_soft_sql`] renders the SQL of a query in a normalized form, so that downstream crates
+//! can snapshot-test that their queries carry the alive condition:
+//!
+//! ```rust,ignore
+//! assert_eq!(
+//!     debug_soft_sql::<Pg, _>(&user::table.soft_find(1)),
+//!     r#"SELECT "user"."id", "user"."name", "user"."deleted" FROM "user" WHERE NOT ("user"."deleted") AND "user"."id" = $1 -- binds: [1]"#,
+//! );
+//! ```
+//!
+//! The [`assert_sql_eq!`](crate::assert_sql_eq) macro compares the SQL of a query with an expected
+//! string regardless of whitespace and bind placeholders.
+
+use diesel::{backend::Backend, query_builder::QueryFragment};
+
+#[cfg(any(test, feature = "testing"))]
+pub mod fixtures;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+
+/// The SQL of `query` as rendered by [`debug_query`](diesel::debug_query), followed by its binds.
+///
+/// The SQL is normalized: the comment tagging the alive condition with the `sql-comments` feature
+/// is removed, so snapshots do not depend on it, and runs of whitespace are collapsed into a single
+/// space.
+pub fn debug_soft_sql<DB, Q>(query: &Q) -> String
+where
+    DB: Backend,
+    DB::QueryBuilder: Default,
+    Q: QueryFragment<DB>,
+{
+    let sql = diesel::debug_query::<DB, _>(query).to_string();
+    #[cfg(feature = "sql-comments")]
+    let sql = sql.replace(crate::comment::ALIVE_TAG, "");
+    collapse_whitespace(&sql)
+}
+
+/// The SQL of `query`, without its binds, normalized with [`normalize_sql`].
+///
+/// The comment tagging the alive condition with the `sql-comments` feature is removed.
+///
+/// # Panics
+///
+/// If the query cannot be rendered, e.g. an `IN` on an empty list.
+pub fn normalized_sql<DB, Q>(query: &Q) -> String
+where
+    DB: Backend,
+    DB::QueryBuilder: Default,
+    Q: QueryFragment<DB>,
+{
+    use diesel::query_builder::QueryBuilder;
+
+    let mut builder = DB::QueryBuilder::default();
+    query.to_sql(&mut builder).expect("failed to render the query");
+    let sql = builder.finish();
+    #[cfg(feature = "sql-comments")]
+    let sql = sql.replace(crate::comment::ALIVE_TAG, "");
+    normalize_sql(&sql)
+}
+
+/// Normalize a SQL string so that it compares equal across backends and formatting: runs of
+/// whitespace are collapsed into a single space, and numbered bind placeholders such as
+/// PostgreSQL's `$1` are replaced with `?`.
+pub fn normalize_sql(sql: &str) -> String {
+    let mut normalized = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '$' && chars.peek().is_some_and(char::is_ascii_digit) {
+            while chars.peek().is_some_and(char::is_ascii_digit) {
+                chars.next();
+            }
+            normalized.push('?');
+        } else {
+            normalized.push(c);
+        }
+    }
+    collapse_whitespace(&normalized)
+}
+
+fn collapse_whitespace(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
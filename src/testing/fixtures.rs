@@ -0,0 +1,283 @@
+//! An in-memory SQLite harness with `user`, `post` and `comment` fixtures.
+//!
+//! This is available with the `testing` feature, to test soft-delete logic against a ready-made
+//! schema:
+//!
+//! ```rust,ignore
+//! use diesel_softdelete::testing::fixtures::{self, schema::post};
+//!
+//! let conn = fixtures::conn();
+//! let joe = fixtures::insert_user(&conn, "Joe");
+//! fixtures::insert_post(&conn, &joe, "Alive");
+//! fixtures::insert_trashed_post(&conn, &joe, "Trashed");
+//! assert_eq!(post::table.soft_deleted().count().get_result::<i64>(&conn)?, 1);
+//! ```
+//!
+//! Larger datasets are built with [`Seed`], which always inserts the same rows for the same
+//! settings.
+
+#![allow(non_local_definitions)]
+
+use diesel::{connection::SimpleConnection, prelude::*, sqlite::SqliteConnection};
+
+use crate::SoftIdentifiable;
+
+use self::schema::{comment, post, user};
+
+/// The tables of the fixtures, whose deleted flag is `deleted`.
+pub mod schema {
+    use crate::{soft_delete, soft_delete_cascade};
+
+    table! {
+        user (id) {
+            id -> Integer,
+            name -> Text,
+            deleted -> Bool,
+        }
+    }
+
+    table! {
+        post (id) {
+            id -> Integer,
+            user_id -> Integer,
+            title -> Text,
+            deleted -> Bool,
+        }
+    }
+
+    table! {
+        comment (id) {
+            id -> Integer,
+            user_id -> Integer,
+            post_id -> Integer,
+            content -> Text,
+            deleted -> Bool,
+        }
+    }
+
+    joinable!(post -> user (user_id));
+    joinable!(comment -> user (user_id));
+    joinable!(comment -> post (post_id));
+    allow_tables_to_appear_in_same_query!(user, post, comment);
+    soft_delete!(user, post, comment);
+    soft_delete_cascade!(user => [post, comment], post => [comment]);
+}
+
+#[derive(Identifiable, Queryable, Debug, PartialEq)]
+#[table_name = "user"]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    pub deleted: bool,
+}
+
+#[derive(Insertable)]
+#[table_name = "user"]
+pub struct NewUser<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Identifiable, Queryable, Associations, AsChangeset, Debug, PartialEq)]
+#[belongs_to(User)]
+#[table_name = "post"]
+pub struct Post {
+    pub id: i32,
+    pub user_id: i32,
+    pub title: String,
+    pub deleted: bool,
+}
+
+impl SoftIdentifiable for Post {
+    fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+}
+
+#[derive(Insertable, Default)]
+#[table_name = "post"]
+pub struct NewPost<'a> {
+    pub user_id: i32,
+    pub title: &'a str,
+    pub deleted: Option<bool>,
+}
+
+#[derive(Identifiable, Queryable, Associations, Debug, PartialEq)]
+#[belongs_to(User)]
+#[belongs_to(Post)]
+#[table_name = "comment"]
+pub struct Comment {
+    pub id: i32,
+    pub user_id: i32,
+    pub post_id: i32,
+    pub content: String,
+    pub deleted: bool,
+}
+
+#[derive(Insertable, Default)]
+#[table_name = "comment"]
+pub struct NewComment<'a> {
+    pub user_id: i32,
+    pub post_id: i32,
+    pub content: &'a str,
+    pub deleted: Option<bool>,
+}
+
+/// A connection to a new in-memory SQLite database with the fixture tables.
+pub fn conn() -> SqliteConnection {
+    let conn = SqliteConnection::establish(":memory:").expect("Failed to open `:memory:` database");
+    conn.batch_execute(
+        "
+        create table user(
+            id integer primary key,
+            name text not null,
+            deleted bool not null default false
+        );
+        create table post(
+            id integer primary key,
+            user_id integer not null,
+            title text not null,
+            deleted bool not null default false,
+            foreign key (user_id) references user(id)
+        );
+        create table comment(
+            id integer primary key,
+            user_id integer not null,
+            post_id integer not null,
+            content text not null,
+            deleted bool not null default false,
+            foreign key (user_id) references user(id),
+            foreign key (post_id) references post(id)
+        );
+    ",
+    )
+    .expect("Failed to create fixture tables");
+    conn
+}
+
+/// Insert an alive user.
+pub fn insert_user(conn: &SqliteConnection, name: &str) -> User {
+    diesel::insert_into(user::table).values(NewUser { name }).execute(conn).unwrap();
+    user::table.order(user::id.desc()).first(conn).unwrap()
+}
+
+/// Insert a soft-deleted user.
+pub fn insert_trashed_user(conn: &SqliteConnection, name: &str) -> User {
+    let user = insert_user(conn, name);
+    diesel::update(&user).set(user::deleted.eq(true)).execute(conn).unwrap();
+    User { deleted: true, ..user }
+}
+
+/// Insert an alive post of `user`.
+pub fn insert_post(conn: &SqliteConnection, user: &User, title: &str) -> Post {
+    insert_post_with(conn, user, title, false)
+}
+
+/// Insert a soft-deleted post of `user`.
+pub fn insert_trashed_post(conn: &SqliteConnection, user: &User, title: &str) -> Post {
+    insert_post_with(conn, user, title, true)
+}
+
+fn insert_post_with(conn: &SqliteConnection, user: &User, title: &str, deleted: bool) -> Post {
+    let new = NewPost { user_id: user.id, title, deleted: Some(deleted) };
+    diesel::insert_into(post::table).values(new).execute(conn).unwrap();
+    post::table.order(post::id.desc()).first(conn).unwrap()
+}
+
+/// Insert an alive comment on `post`, by the author of the post.
+pub fn insert_comment(conn: &SqliteConnection, post: &Post, content: &str) -> Comment {
+    insert_comment_with(conn, post, content, false)
+}
+
+/// Insert a soft-deleted comment on `post`, by the author of the post.
+pub fn insert_trashed_comment(conn: &SqliteConnection, post: &Post, content: &str) -> Comment {
+    insert_comment_with(conn, post, content, true)
+}
+
+fn insert_comment_with(
+    conn: &SqliteConnection,
+    post: &Post,
+    content: &str,
+    deleted: bool,
+) -> Comment {
+    let new =
+        NewComment { user_id: post.user_id, post_id: post.id, content, deleted: Some(deleted) };
+    diesel::insert_into(comment::table).values(new).execute(conn).unwrap();
+    comment::table.order(comment::id.desc()).first(conn).unwrap()
+}
+
+/// The rows inserted for a dataset, in insertion order.
+#[derive(Debug, Default)]
+pub struct Inserted {
+    pub users: Vec<User>,
+    pub posts: Vec<Post>,
+    pub comments: Vec<Comment>,
+}
+
+/// A builder of reproducible datasets of users, posts and comments, some of them trashed.
+///
+/// Every user gets the same posts, and every post the same comments, by the author of the post:
+///
+/// ```rust,ignore
+/// // 3 alive users and 2 trashed ones, each with 2 alive posts and 2 trashed ones
+/// let rows = Seed::new().users(3, 2).posts_per_user(2, 2).insert(&conn);
+/// assert_eq!(rows.posts.len(), 20);
+/// ```
+///
+/// The rows are inserted alive ones first, and named after their position: users `user<n>`,
+/// posts `post<n>` and comments `comment<n>`, numbered from 0.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Seed {
+    users: (usize, usize),
+    posts: (usize, usize),
+    comments: (usize, usize),
+}
+
+impl Seed {
+    /// An empty dataset.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert `alive` users, then `trashed` soft-deleted ones.
+    pub fn users(mut self, alive: usize, trashed: usize) -> Self {
+        self.users = (alive, trashed);
+        self
+    }
+
+    /// Give each user `alive` posts, then `trashed` soft-deleted ones.
+    pub fn posts_per_user(mut self, alive: usize, trashed: usize) -> Self {
+        self.posts = (alive, trashed);
+        self
+    }
+
+    /// Give each post `alive` comments, then `trashed` soft-deleted ones.
+    pub fn comments_per_post(mut self, alive: usize, trashed: usize) -> Self {
+        self.comments = (alive, trashed);
+        self
+    }
+
+    /// Insert the dataset in a database created by [`conn`].
+    pub fn insert(self, conn: &SqliteConnection) -> Inserted {
+        fn flags((alive, trashed): (usize, usize)) -> impl Iterator<Item = bool> {
+            std::iter::repeat_n(false, alive).chain(std::iter::repeat_n(true, trashed))
+        }
+
+        let mut inserted = Inserted::default();
+        for deleted in flags(self.users) {
+            let name = format!("user{}", inserted.users.len());
+            let user =
+                if deleted { insert_trashed_user(conn, &name) } else { insert_user(conn, &name) };
+            for deleted in flags(self.posts) {
+                let title = format!("post{}", inserted.posts.len());
+                let post = insert_post_with(conn, &user, &title, deleted);
+                for deleted in flags(self.comments) {
+                    let content = format!("comment{}", inserted.comments.len());
+                    inserted.comments.push(insert_comment_with(conn, &post, &content, deleted));
+                }
+                inserted.posts.push(post);
+            }
+            inserted.users.push(user);
+        }
+        inserted
+    }
+}
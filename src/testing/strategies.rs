@@ -0,0 +1,113 @@
+//! Proptest strategies generating soft-deletable datasets for the fixtures.
+//!
+//! This is available with the `proptest` feature, to property-test invariants that must hold for
+//! any mix of alive and trashed rows, e.g. that a query never returns a trashed row:
+//!
+//! ```rust,ignore
+//! proptest! {
+//!     #[test]
+//!     fn never_returns_trashed_posts(dataset in strategies::dataset()) {
+//!         let conn = fixtures::conn();
+//!         dataset.insert(&conn);
+//!         let posts: Vec<Post> = list_posts(&conn)?;
+//!         prop_assert!(posts.iter().all(|post| !post.deleted));
+//!     }
+//! }
+//! ```
+
+use diesel::{prelude::*, sqlite::SqliteConnection};
+use proptest::{collection::vec, prelude::*, sample::Index};
+
+use super::fixtures::{self, schema::comment, Inserted, NewComment};
+
+/// A generated set of users, with their posts and the comments on those.
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    pub users: Vec<UserSpec>,
+}
+
+/// A generated user.
+#[derive(Debug, Clone)]
+pub struct UserSpec {
+    pub deleted: bool,
+    pub posts: Vec<PostSpec>,
+}
+
+/// A generated post, of the user it belongs to.
+#[derive(Debug, Clone)]
+pub struct PostSpec {
+    pub deleted: bool,
+    pub comments: Vec<CommentSpec>,
+}
+
+/// A generated comment on the post it belongs to.
+#[derive(Debug, Clone)]
+pub struct CommentSpec {
+    pub deleted: bool,
+    /// The author of the comment, among all the users of the dataset.
+    pub author: Index,
+}
+
+impl Dataset {
+    /// Insert the dataset in a database created by [`fixtures::conn`].
+    ///
+    /// Users are named `user<n>`, posts `post<n>` and comments `comment<n>`, numbered from 0 in
+    /// insertion order.
+    pub fn insert(&self, conn: &SqliteConnection) -> Inserted {
+        let mut inserted = Inserted::default();
+        for (i, spec) in self.users.iter().enumerate() {
+            let name = format!("user{}", i);
+            inserted.users.push(if spec.deleted {
+                fixtures::insert_trashed_user(conn, &name)
+            } else {
+                fixtures::insert_user(conn, &name)
+            });
+        }
+        for (spec, user) in self.users.iter().zip(&inserted.users) {
+            for spec in &spec.posts {
+                let title = format!("post{}", inserted.posts.len());
+                let post = if spec.deleted {
+                    fixtures::insert_trashed_post(conn, user, &title)
+                } else {
+                    fixtures::insert_post(conn, user, &title)
+                };
+                for spec in &spec.comments {
+                    let author = spec.author.get(&inserted.users);
+                    let content = format!("comment{}", inserted.comments.len());
+                    let new = NewComment {
+                        user_id: author.id,
+                        post_id: post.id,
+                        content: &content,
+                        deleted: Some(spec.deleted),
+                    };
+                    diesel::insert_into(comment::table).values(new).execute(conn).unwrap();
+                    let comment = comment::table.order(comment::id.desc()).first(conn).unwrap();
+                    inserted.comments.push(comment);
+                }
+                inserted.posts.push(post);
+            }
+        }
+        inserted
+    }
+}
+
+/// Up to 8 users, each with up to 4 posts having up to 4 comments, any of them trashed.
+pub fn dataset() -> impl Strategy<Value = Dataset> {
+    vec(user(), 0..8).prop_map(|users| Dataset { users })
+}
+
+/// A user, alive or trashed, with up to 4 posts.
+pub fn user() -> impl Strategy<Value = UserSpec> {
+    (any::<bool>(), vec(post(), 0..4)).prop_map(|(deleted, posts)| UserSpec { deleted, posts })
+}
+
+/// A post, alive or trashed, with up to 4 comments.
+pub fn post() -> impl Strategy<Value = PostSpec> {
+    (any::<bool>(), vec(comment(), 0..4))
+        .prop_map(|(deleted, comments)| PostSpec { deleted, comments })
+}
+
+/// A comment, alive or trashed, by any user.
+pub fn comment() -> impl Strategy<Value = CommentSpec> {
+    (any::<bool>(), any::<Index>()).prop_map(|(deleted, author)| CommentSpec { deleted, author })
+}
@@ -1,122 +1,200 @@
+#![allow(non_local_definitions)]
+
 use crate::prelude::*;
+use crate::testing::fixtures::{
+    self,
+    schema::{comment, post, user},
+    Comment, NewComment, NewPost, NewUser, Post, User,
+};
 use diesel::{connection::SimpleConnection, prelude::*, sqlite::SqliteConnection};
 
 table! {
-    user (id) {
+    event (id) {
         id -> Integer,
         name -> Text,
-        deleted -> Bool,
+        created_at -> Integer,
+        deleted_at -> Nullable<Integer>,
     }
 }
 
 table! {
-    post (id) {
+    document (id) {
         id -> Integer,
-        user_id -> Integer,
         title -> Text,
         deleted -> Bool,
+        lock_version -> Integer,
     }
 }
 
 table! {
-    comment (id) {
+    team (id) {
         id -> Integer,
-        user_id -> Integer,
-        post_id -> Integer,
-        content -> Text,
+        name -> Text,
         deleted -> Bool,
     }
 }
 
-joinable!(post -> user (user_id));
-joinable!(comment -> user (user_id));
-joinable!(comment -> post (post_id));
-allow_tables_to_appear_in_same_query!(user, post, comment);
-soft_delete!(user);
-soft_delete!(post);
-soft_delete!(comment);
-
-#[derive(Identifiable, Queryable, Debug, PartialEq)]
-#[table_name = "user"]
-struct User {
-    id: i32,
-    name: String,
-    deleted: bool,
+table! {
+    membership (id) {
+        id -> Integer,
+        user_id -> Integer,
+        team_id -> Integer,
+        role -> Text,
+        deleted -> Bool,
+    }
 }
 
-#[derive(Insertable)]
-#[table_name = "user"]
-struct NewUser<'a> {
-    name: &'a str,
+table! {
+    note (id) {
+        id -> Integer,
+        content -> Text,
+        deleted_at -> Nullable<Timestamp>,
+    }
 }
 
-#[derive(Identifiable, Queryable, Debug, PartialEq)]
-#[table_name = "post"]
-struct Post {
-    id: i32,
-    user_id: i32,
-    title: String,
-    deleted: bool,
+table! {
+    session (id) {
+        id -> Integer,
+        token -> Text,
+        deleted_at -> Timestamp,
+    }
 }
 
-#[derive(Insertable, Default)]
-#[table_name = "post"]
-struct NewPost<'a> {
-    user_id: i32,
-    title: &'a str,
-    deleted: Option<bool>,
+#[cfg(feature = "derive")]
+table! {
+    draft (id) {
+        id -> Integer,
+        removed -> Bool,
+        version -> Integer,
+    }
 }
 
-#[derive(Identifiable, Queryable, Debug, PartialEq)]
-#[table_name = "comment"]
-struct Comment {
-    id: i32,
-    user_id: i32,
-    post_id: i32,
-    content: String,
-    deleted: bool,
+table! {
+    article (id) {
+        id -> Integer,
+        user_id -> Integer,
+        tenant_id -> Integer,
+        published -> Bool,
+        deleted -> Bool,
+    }
 }
 
-#[derive(Insertable, Default)]
-#[table_name = "comment"]
-struct NewComment<'a> {
-    user_id: i32,
-    post_id: i32,
-    content: &'a str,
-    deleted: Option<bool>,
+mod schema {
+    pub mod billing {
+        table! {
+            billing.invoice (id) {
+                id -> Integer,
+                deleted -> Bool,
+                is_void -> Bool,
+            }
+        }
+    }
 }
 
+joinable!(membership -> user (user_id));
+joinable!(article -> user (user_id));
+soft_joinable!(membership -> team (team_id));
+allow_tables_to_appear_in_same_query!(user, team, membership);
+allow_tables_to_appear_in_same_query!(user, article);
+allow_tables_to_appear_in_same_query!(post, team);
+allow_tables_to_appear_in_same_query!(post, membership);
+allow_tables_to_appear_in_same_query!(comment, team);
+allow_tables_to_appear_in_same_query!(comment, membership);
+soft_delete!(team, membership, article, crate::tests::schema::billing::invoice);
+soft_delete_history!(event);
+soft_delete!(
+    document::table => (document::deleted, revision = document::lock_version),
+    note::table => timestamp(note::deleted_at),
+    session::table => sentinel(session::deleted_at),
+);
+
 fn conn() -> SqliteConnection {
-    let conn = SqliteConnection::establish(":memory:").expect("Failed to open `:memory:` database");
+    let conn = fixtures::conn();
     conn.batch_execute(
         "
-        create table user(
+        create table team(
             id integer primary key,
             name text not null,
             deleted bool not null default false
         );
-        create table post(
+        create table membership(
             id integer primary key,
             user_id integer not null,
+            team_id integer not null,
+            role text not null,
+            deleted bool not null default false,
+            foreign key (user_id) references user(id),
+            foreign key (team_id) references team(id)
+        );
+        create table event(
+            id integer primary key,
+            name text not null,
+            created_at integer not null,
+            deleted_at integer
+        );
+        create table document(
+            id integer primary key,
             title text not null,
             deleted bool not null default false,
-            foreign key (user_id) references user(id)
+            lock_version integer not null default 0
         );
-        create table comment(
+        create table article(
             id integer primary key,
             user_id integer not null,
-            post_id integer not null,
-            content text not null,
+            tenant_id integer not null,
+            published bool not null,
             deleted bool not null default false,
-            foreign key (user_id) references user(id),
-            foreign key (post_id) references post(id)
+            foreign key (user_id) references user(id)
+        );
+        create table note(
+            id integer primary key,
+            content text not null,
+            deleted_at timestamp
+        );
+        create table session(
+            id integer primary key,
+            token text not null,
+            deleted_at timestamp not null default '1970-01-01 00:00:01',
+            unique (token, deleted_at)
         );
     ",
     )
-    .expect("Failed to create `user`, `post` or `comment` table");
+    .expect("Failed to create test tables");
     conn
 }
 
+/// The SQL of a query, without the comment tagging the alive condition with `sql-comments`.
+fn sql_of<T: diesel::query_builder::QueryFragment<diesel::sqlite::Sqlite>>(query: &T) -> String {
+    crate::testing::debug_soft_sql::<diesel::sqlite::Sqlite, _>(query)
+}
+
+#[cfg(feature = "mysql")]
+mod mysql;
+#[cfg(feature = "postgres")]
+mod postgres;
+
+#[cfg(feature = "postgres")]
+fn pg_sql_of<T: diesel::query_builder::QueryFragment<diesel::pg::Pg>>(query: &T) -> String {
+    crate::testing::debug_soft_sql::<diesel::pg::Pg, _>(query)
+}
+
+#[cfg(feature = "sql-comments")]
+#[test]
+fn test_sql_comments_ok() {
+    assert_eq!(
+        diesel::debug_query::<diesel::sqlite::Sqlite, _>(
+            &user::table.soft_find(1).select(user::id)
+        )
+        .to_string(),
+        "SELECT `user`.`id` FROM `user` \
+         WHERE NOT (`user`.`deleted`) /* softdelete:alive */ AND `user`.`id` = ? -- binds: [1]",
+    );
+    let conn = conn();
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    let names = user::table.soft_deleted().select(user::name).load::<String>(&conn).unwrap();
+    assert_eq!(names, ["Joe"]);
+}
+
 #[test]
 fn test_find_ok() {
     let conn = conn();
@@ -182,12 +260,88 @@ fn test_soft_filter_ok() {
 }
 
 #[test]
-fn test_join_ok() {
+fn test_soft_filter_on_select_statement_ok() {
+    let conn = conn();
+
+    let users =
+        vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "William" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    let joe_query = user::table.filter(user::name.eq("Joe"));
+    diesel::update(joe_query).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let users: Vec<String> = user::table
+        .filter(user::id.gt(0))
+        .soft_filter(user::name.like("J%"))
+        .select(user::name)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(users, vec!["Jack".to_owned()]);
+}
+
+#[test]
+fn test_boxed_soft_filter_ok() {
+    let conn = conn();
+
+    let users =
+        vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "William" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    let joe_query = user::table.filter(user::name.eq("Joe"));
+    diesel::update(joe_query).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let users: Vec<String> = user::table
+        .select(user::name)
+        .into_boxed()
+        .soft_filter(user::name.like("J%"))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(users, vec!["Jack".to_owned()]);
+
+    let users: Vec<String> =
+        user::table.select(user::name).into_boxed().soft_deleted().load(&conn).unwrap();
+    assert_eq!(users, vec!["Jack".to_owned(), "William".to_owned()]);
+}
+
+#[test]
+fn test_soft_filter_boxed_predicate_ok() {
+    use crate::methods::BoxedPredicate;
+    use diesel::sqlite::Sqlite;
+
+    let conn = conn();
+
+    let users =
+        vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "William" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    let joe_query = user::table.filter(user::name.eq("Joe"));
+    diesel::update(joe_query).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let search = |prefix: Option<&str>, min_id: Option<i32>| -> Vec<String> {
+        let mut predicate: BoxedPredicate<user::table, Sqlite> = Box::new(user::id.gt(0));
+        if let Some(prefix) = prefix {
+            predicate = Box::new(predicate.and(user::name.like(format!("{}%", prefix))));
+        }
+        if let Some(min_id) = min_id {
+            predicate = Box::new(predicate.and(user::id.ge(min_id)));
+        }
+        user::table.soft_filter(predicate).select(user::name).load(&conn).unwrap()
+    };
+    assert_eq!(search(None, None), vec!["Jack".to_owned(), "William".to_owned()]);
+    assert_eq!(search(Some("J"), None), vec!["Jack".to_owned()]);
+    assert_eq!(search(None, Some(3)), vec!["William".to_owned()]);
+
+    let predicate: BoxedPredicate<user::table, Sqlite> = Box::new(user::name.like("J%"));
+    let users: Vec<String> =
+        user::table.select(user::name).into_boxed().soft_filter(predicate).load(&conn).unwrap();
+    assert_eq!(users, vec!["Jack".to_owned()]);
+}
+
+#[test]
+fn test_soft_boxed_variants_ok() {
+    use diesel::sqlite::Sqlite;
+
     let conn = conn();
 
     diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
     let joe_id: i32 = user::table.select(user::id).first(&conn).unwrap();
-
     diesel::insert_into(post::table)
         .values(vec![
             NewPost { user_id: joe_id, title: "My first post", ..Default::default() },
@@ -196,200 +350,4048 @@ fn test_join_ok() {
         .execute(&conn)
         .unwrap();
 
-    let user_posts = user::table
-        .soft_find(joe_id)
-        .left_join(post::table)
-        .load::<(User, Option<Post>)>(&conn)
+    let posts: Vec<String> =
+        post::table.soft_deleted_boxed::<Sqlite>().select(post::title).load(&conn).unwrap();
+    assert_eq!(posts, vec!["My first post".to_owned()]);
+
+    let posts: Vec<Post> =
+        post::table.soft_filter_boxed::<_, Sqlite>(post::user_id.eq(joe_id)).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+
+    let joe: Option<User> =
+        user::table.soft_find_boxed::<_, Sqlite>(joe_id).first(&conn).optional().unwrap();
+    assert!(joe.is_some());
+
+    let user_posts: Vec<(User, Option<Post>)> =
+        user::table.soft_left_join_boxed::<_, Sqlite>(post::table).load(&conn).unwrap();
+    assert_eq!(user_posts.len(), 1);
+
+    let user_posts: Vec<(User, Post)> = user::table
+        .soft_inner_join_boxed::<_, Sqlite>(post::table)
+        .filter(post::title.like("My%"))
+        .load(&conn)
         .unwrap();
+    assert_eq!(user_posts.len(), 1);
 
-    assert_eq!(user_posts.len(), 2);
+    let titles = |include_deleted| {
+        post::table
+            .soft_scoped_boxed::<Sqlite>(include_deleted)
+            .select(post::title)
+            .order(post::id)
+            .load::<String>(&conn)
+            .unwrap()
+    };
+    assert_eq!(titles(false), ["My first post"]);
+    assert_eq!(titles(true), ["My first post", "Failed post"]);
 }
 
 #[test]
-fn test_soft_left_join_ok() {
+fn test_soft_children_for_ok() {
     let conn = conn();
 
-    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
-    let joe_id: i32 = user::table.select(user::id).first(&conn).unwrap();
-
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Jim" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
     diesel::insert_into(post::table)
-        .values(NewPost { user_id: joe_id, title: "Some post", ..Default::default() })
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 2, title: "Trashed", deleted: Some(true) },
+            NewPost { user_id: 1, title: "Second", ..Default::default() },
+            NewPost { user_id: 3, title: "Other", ..Default::default() },
+        ])
         .execute(&conn)
         .unwrap();
 
-    let (_, post) = user::table
-        .soft_find(joe_id)
-        .soft_left_join(post::table)
-        .first::<(User, Option<Post>)>(&conn)
-        .unwrap();
-    assert!(post.is_some());
-    let post = post.unwrap();
-    assert!(!post.deleted);
-    assert_eq!(post.title, "Some post");
+    let users = user::table.order(user::id).load::<User>(&conn).unwrap();
+    let posts = soft_children_for::<Post, _, _>(&users, &conn).unwrap();
+    let titles = posts
+        .iter()
+        .map(|posts| posts.iter().map(|p| p.title.as_str()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, vec![vec!["First", "Second"], vec![], vec!["Other"]]);
+}
 
-    diesel::update(&post).set(post::deleted.eq(true)).execute(&conn).unwrap();
+#[test]
+fn test_soft_grouped_by_ok() {
+    let conn = conn();
 
-    let (_, post) = user::table
-        .soft_find(joe_id)
-        .soft_left_join(post::table)
-        .first::<(User, Option<Post>)>(&conn)
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 2, title: "Other", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 2, title: "Other trashed", deleted: Some(true) },
+        ])
+        .execute(&conn)
         .unwrap();
-    assert!(post.is_none());
+
+    let users = user::table.order(user::id).load::<User>(&conn).unwrap();
+    let posts = post::table.load::<Post>(&conn).unwrap().soft_grouped_by(&users);
+    let titles = posts
+        .iter()
+        .map(|posts| posts.iter().map(|p| p.title.as_str()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, vec![vec!["First"], vec!["Other"]]);
 }
 
 #[test]
-fn test_soft_inner_join_ok() {
-    let conn = conn();
+fn test_grouped_soft_scopes_ok() {
+    use diesel::{dsl::sql, sql_types::BigInt};
 
-    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
-    let joe_id: i32 = user::table.select(user::id).first(&conn).unwrap();
+    let conn = conn();
 
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
     diesel::insert_into(post::table)
-        .values(NewPost { user_id: joe_id, title: "Some post", ..Default::default() })
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Second", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+            NewPost { user_id: 2, title: "Other", ..Default::default() },
+            NewPost { user_id: 2, title: "Other trashed", deleted: Some(true) },
+        ])
         .execute(&conn)
         .unwrap();
 
-    let user_and_post = user::table
-        .soft_find(joe_id)
-        .soft_inner_join(post::table)
-        .first::<(User, Post)>(&conn)
-        .optional()
+    let counts: Vec<(i32, i64)> = post::table
+        .group_by(post::user_id)
+        .select((post::user_id, sql::<BigInt>("COUNT(*)")))
+        .soft_deleted()
+        .order(post::user_id)
+        .load(&conn)
         .unwrap();
-    assert!(user_and_post.is_some());
-    let (_, post) = user_and_post.unwrap();
-    assert!(!post.deleted);
-    assert_eq!(post.title, "Some post");
-
-    diesel::update(&post).set(post::deleted.eq(true)).execute(&conn).unwrap();
+    assert_eq!(counts, vec![(1, 2), (2, 1)]);
 
-    let user_and_post = user::table
-        .soft_find(joe_id)
-        .soft_inner_join(post::table)
-        .first::<(User, Post)>(&conn)
-        .optional()
+    let counts: Vec<(i32, i64)> = post::table
+        .group_by(post::user_id)
+        .select((post::user_id, sql::<BigInt>("COUNT(*)")))
+        .soft_filter(post::title.like("O%"))
+        .load(&conn)
         .unwrap();
-    assert!(user_and_post.is_none());
+    assert_eq!(counts, vec![(2, 1)]);
+
+    let query = post::table.group_by(post::user_id).select(post::user_id).soft_deleted();
+    let sql = sql_of(&query);
+    assert!(sql.contains("WHERE NOT (`post`.`deleted`) GROUP BY `post`.`user_id`"), "{}", sql);
 }
 
 #[test]
-fn test_nested_join_ok() {
+fn test_scoped_ok() {
     let conn = conn();
 
     diesel::insert_into(user::table)
-        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Jim" }])
         .execute(&conn)
         .unwrap();
-    let joe: User = user::table.filter(user::name.eq("Joe")).first(&conn).unwrap();
-    let jack: User = user::table.filter(user::name.eq("Jack")).first(&conn).unwrap();
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
 
-    diesel::insert_into(post::table)
-        .values(NewPost { user_id: joe.id, title: "Some post", ..Default::default() })
-        .execute(&conn)
+    let users = Scoped::new(user::table);
+    assert_eq!(users.count().get_result::<i64>(&conn).unwrap(), 2);
+    assert_eq!(users.load::<User>(&conn).unwrap().len(), 2);
+    assert!(users.find(2).first::<User>(&conn).optional().unwrap().is_none());
+    let names = users
+        .filter(user::name.like("J%"))
+        .select(user::name)
+        .order(user::id.desc())
+        .limit(1)
+        .load::<String>(&conn)
         .unwrap();
-    let post_id: i32 = post::table.select(post::id).first(&conn).unwrap();
+    assert_eq!(names, vec!["Jim"]);
+    let boxed = users.into_boxed::<diesel::sqlite::Sqlite>();
+    assert_eq!(boxed.load::<User>(&conn).unwrap().len(), 2);
+}
 
-    diesel::insert_into(comment::table)
-        .values(NewComment {
-            user_id: jack.id,
-            post_id,
-            content: "Some comment",
-            ..Default::default()
-        })
+#[test]
+fn test_soft_query_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Jim" }])
         .execute(&conn)
         .unwrap();
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
 
-    // Comments made by Jack on Joe's posts
-    let (_, post_and_comment) = user::table
-        .soft_find(joe.id)
-        .left_join(post::table.left_join(comment::table))
-        .first::<(User, Option<(Post, Option<Comment>)>)>(&conn)
-        .unwrap();
-    assert!(post_and_comment.is_some());
-    let (_, comment) = post_and_comment.unwrap();
-    assert!(comment.is_some());
+    let ids = |users: Vec<User>| users.into_iter().map(|u| u.id).collect::<Vec<_>>();
+    assert_eq!(ids(soft_query(user::table).alive().load(&conn).unwrap()), vec![1, 3]);
+    assert_eq!(ids(soft_query(user::table).trashed().load(&conn).unwrap()), vec![2]);
+    let all = soft_query(user::table).including_deleted().load(&conn).unwrap();
+    assert_eq!(ids(all), vec![1, 2, 3]);
+}
 
-    let user_post_comment = user::table
-        .soft_find(joe.id)
-        .inner_join(post::table.inner_join(comment::table))
-        .first::<(User, (Post, Comment))>(&conn)
-        .optional()
+#[test]
+fn test_assert_soft_scoped_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .execute(&conn)
         .unwrap();
-    assert!(user_post_comment.is_some());
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let users = assert_soft_scoped!(user::table.soft_deleted()).load::<User>(&conn).unwrap();
+    assert_eq!(users.len(), 1);
+    let _ = assert_soft_scoped!(user::table.soft_find(1));
+    let _ = assert_soft_scoped!(user::table.soft_filter(user::name.eq("Joe")).select(user::id));
+    let _ = assert_soft_scoped!(user::table.filter(user::name.eq("Joe")).soft_deleted());
+    let _ = assert_soft_scoped!(user::table
+        .soft_deleted()
+        .filter(user::name.eq("Joe"))
+        .filter(user::id.eq(1))
+        .order(user::id));
+    let _ = assert_soft_scoped!(user::table.soft_inner_join_both(post::table));
+    let _ = assert_soft_scoped!(user::table.inner_join(post::table).soft_scope_all());
+    let _ = assert_soft_scoped!(user::table.soft_deleted().soft_left_join(membership::table));
+    let _ = assert_soft_scoped!(user::table
+        .soft_deleted()
+        .soft_left_join(membership::table.soft_inner_join(team::table)));
 }
 
 #[test]
-fn test_nested_join_inner_soft_ok() {
+fn test_soft_scoped_bound_ok() {
+    fn count<Q, P>(query: Q, conn: &SqliteConnection) -> usize
+    where
+        Q: SoftScoped<P> + diesel::query_dsl::LoadQuery<SqliteConnection, User>,
+    {
+        query.load::<User>(conn).unwrap().len()
+    }
+
     let conn = conn();
 
     diesel::insert_into(user::table)
         .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
         .execute(&conn)
         .unwrap();
-    let joe: User = user::table.filter(user::name.eq("Joe")).first(&conn).unwrap();
-    let jack: User = user::table.filter(user::name.eq("Jack")).first(&conn).unwrap();
-
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
     diesel::insert_into(post::table)
-        .values(NewPost { user_id: joe.id, title: "Some post", ..Default::default() })
+        .values(NewPost { user_id: 1, title: "Some post", ..Default::default() })
         .execute(&conn)
         .unwrap();
-    let post_id: i32 = post::table.select(post::id).first(&conn).unwrap();
 
-    diesel::insert_into(comment::table)
-        .values(NewComment {
-            user_id: jack.id,
-            post_id,
-            content: "Some comment",
-            ..Default::default()
-        })
+    assert_eq!(count(user::table.soft_deleted(), &conn), 1);
+    assert_eq!(count(Scoped::new(user::table), &conn), 1);
+    let users = post::table.soft_inner_join_both(user::table).select(user::all_columns);
+    assert_eq!(count(users, &conn), 1);
+}
+
+#[test]
+fn test_guarded_connection_ok() {
+    use crate::guard::{GuardedConnection, UnscopedQuery};
+    use std::sync::{Arc, Mutex};
+
+    let reported = Arc::new(Mutex::new(Vec::<UnscopedQuery>::new()));
+    let hook = reported.clone();
+    let conn = GuardedConnection::new(conn())
+        .table(user::table)
+        .on_unscoped(move |query| hook.lock().unwrap().push(query.clone()));
+
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    user::table.soft_deleted().load::<User>(&conn).unwrap();
+    post::table.load::<Post>(&conn).unwrap();
+    assert!(reported.lock().unwrap().is_empty());
+
+    user::table.load::<User>(&conn).unwrap();
+    let reported = reported.lock().unwrap();
+    assert_eq!(reported.len(), 1);
+    assert_eq!(reported[0].table, "user");
+
+    let conn = GuardedConnection::new(self::conn()).table(user::table).strict();
+    assert!(user::table.load::<User>(&conn).is_err());
+    assert!(user::table.soft_find(1).first::<User>(&conn).optional().is_ok());
+    assert!(user::table.soft_filter(user::id.eq(1).or(user::id.eq(2))).load::<User>(&conn).is_ok());
+    let posts = user::table.soft_deleted().soft_left_join(post::table);
+    assert!(posts.load::<(User, Option<Post>)>(&conn).is_ok());
+
+    // The flag has to be checked for every row, in a condition
+    let bypass = user::table.filter(diesel::dsl::not(user::deleted).or(user::id.eq(1)));
+    assert!(bypass.load::<User>(&conn).is_err());
+    assert!(user::table.filter(user::name.eq("`user`.`deleted`")).load::<User>(&conn).is_err());
+    assert!(user::table.order(user::deleted).load::<User>(&conn).is_err());
+}
+
+#[test]
+fn test_soft_exists_in_ok() {
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Jim" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 2, title: "Trashed", deleted: Some(true) },
+        ])
         .execute(&conn)
         .unwrap();
 
-    // Comments made by Jack on Joe's posts
-    let (_, post_and_comment) = user::table
-        .soft_find(joe.id)
-        .left_join(post::table.soft_left_join(comment::table))
-        .first::<(User, Option<(Post, Option<Comment>)>)>(&conn)
+    let posts = post::table.filter(post::user_id.eq(user::id));
+    let names = user::table
+        .soft_filter(soft_exists_in(posts))
+        .select(user::name)
+        .load::<String>(&conn)
         .unwrap();
-    assert!(post_and_comment.is_some());
-    let (_, comment) = post_and_comment.unwrap();
-    assert!(comment.is_some());
+    assert_eq!(names, ["Joe"]);
 }
 
-// does not work at the moment
-//#[test]
-//fn test_nested_join_outer_soft_ok() {
-//    let conn = conn();
-//
-//    diesel::insert_into(user::table)
-//        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
-//        .execute(&conn)
-//        .unwrap();
-//    let joe: User = user::table.filter(user::name.eq("Joe")).first(&conn).unwrap();
-//    let jack: User = user::table.filter(user::name.eq("Jack")).first(&conn).unwrap();
-//
-//    diesel::insert_into(post::table)
-//        .values(NewPost { user_id: joe.id, title: "Some post", ..Default::default() })
-//        .execute(&conn)
-//        .unwrap();
-//    let post_id: i32 = post::table.select(post::id).first(&conn).unwrap();
-//
-//    diesel::insert_into(comment::table)
-//        .values(NewComment {
-//            user_id: jack.id,
-//            post_id,
-//            content: "Some comment",
-//            ..Default::default()
-//        })
-//        .execute(&conn)
-//        .unwrap();
-//
-//    // Comments made by Jack on Joe's posts
-//    let (_, post_and_comment) = user::table
-//        .soft_find(joe.id)
-//        .soft_left_join(post::table.soft_left_join(comment::table))
-//        .first::<(User, Option<(Post, Option<Comment>)>)>(&conn)
-//        .unwrap();
-//    assert!(post_and_comment.is_some());
-//    let (_, comment) = post_and_comment.unwrap();
-//    assert!(comment.is_some());
-//}
+#[test]
+fn test_soft_semi_anti_join_ok() {
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Jim" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Second", ..Default::default() },
+            NewPost { user_id: 2, title: "Trashed", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let names = user::table.soft_semi_join(post::table).select(user::name).load::<String>(&conn);
+    assert_eq!(names.unwrap(), ["Joe"]);
+    let names = user::table.soft_anti_join(post::table).select(user::name).load::<String>(&conn);
+    assert_eq!(names.unwrap(), ["Jack", "Jim"]);
+}
+
+#[test]
+fn test_soft_update_ok() {
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Jim" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let count = diesel::update(user::table.soft_filter(user::name.like("J%")))
+        .set(user::name.eq("Someone"))
+        .execute(&conn)
+        .unwrap();
+    assert_eq!(count, 2);
+    let count =
+        diesel::update(user::table).set(user::name.eq("No one")).soft_deleted().execute(&conn);
+    assert_eq!(count.unwrap(), 2);
+
+    let names = user::table.order(user::id).select(user::name).load::<String>(&conn).unwrap();
+    assert_eq!(names, ["No one", "Jack", "No one"]);
+}
+
+#[test]
+fn test_only_deleted_purge_ok() {
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Jim" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::update(user::table.filter(user::id.gt(1)))
+        .set(user::deleted.eq(true))
+        .execute(&conn)
+        .unwrap();
+
+    let count = diesel::delete(user::table.only_deleted().filter(user::name.eq("Joe")))
+        .execute(&conn)
+        .unwrap();
+    assert_eq!(count, 0);
+    let count = diesel::delete(user::table.find(2)).only_deleted().execute(&conn).unwrap();
+    assert_eq!(count, 1);
+    let count = crate::write::purge(user::table).execute(&conn).unwrap();
+    assert_eq!(count, 1);
+
+    let names = user::table.select(user::name).load::<String>(&conn).unwrap();
+    assert_eq!(names, ["Joe"]);
+}
+
+#[test]
+fn test_deleted_expressions_ok() {
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 2, title: "Trashed", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let on = post::user_id.eq(user::id).and(post::table.not_deleted());
+    let titles = user::table
+        .left_join(post::table.on(on))
+        .order(user::id)
+        .select(post::title.nullable())
+        .load::<Option<String>>(&conn)
+        .unwrap();
+    assert_eq!(titles, [Some("First".to_owned()), None]);
+
+    let trashed = post::table.order(post::id).select(post::table.is_deleted()).load::<bool>(&conn);
+    assert_eq!(trashed.unwrap(), [false, true]);
+}
+
+#[test]
+fn test_alive_sql_ok() {
+    use diesel::{dsl::sql, sql_types::Bool, sqlite::Sqlite};
+
+    let conn = conn();
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let alive = alive_sql::<Sqlite, _>(&user::table).unwrap();
+    assert!(alive.starts_with("NOT (`user`.`deleted`)"), "{}", alive);
+    let names = user::table.filter(sql::<Bool>(&alive)).select(user::name).load::<String>(&conn);
+    assert_eq!(names.unwrap(), ["Joe"]);
+}
+
+#[test]
+fn test_soft_or_filter_ok() {
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Jim" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let names = user::table
+        .soft_filter(user::name.eq("Joe"))
+        .soft_or_filter(user::name.eq("Jack"))
+        .soft_or_filter(user::name.eq("Jim"))
+        .order(user::id)
+        .select(user::name)
+        .load::<String>(&conn)
+        .unwrap();
+    assert_eq!(names, ["Joe", "Jim"]);
+}
+
+#[test]
+fn test_scoped_idempotent_ok() {
+    fn named<Q: SoftFilterDsl<diesel::dsl::Eq<user::name, &'static str>>>(query: Q) -> Q::Output {
+        query.soft_filter(user::name.eq("Joe"))
+    }
+
+    let scoped = Scoped::new(user::table);
+    assert_eq!(sql_of(&scoped.soft_deleted()), sql_of(&user::table.soft_deleted()));
+    assert_eq!(sql_of(&scoped.soft_find(1)), sql_of(&user::table.soft_find(1)));
+    assert_eq!(sql_of(&named(scoped)), sql_of(&named(user::table)));
+    assert_eq!(sql_of(&named(scoped)).matches("deleted").count(), 2, "{}", sql_of(&named(scoped)));
+}
+
+#[test]
+fn test_soft_load_partitioned_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+            NewPost { user_id: 1, title: "Second", ..Default::default() },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let (alive, trashed) =
+        post::table.order(post::id).soft_load_partitioned::<Post>(&conn).unwrap();
+    let titles = |posts: Vec<Post>| posts.into_iter().map(|post| post.title).collect::<Vec<_>>();
+    assert_eq!(titles(alive), ["First", "Second"]);
+    assert_eq!(titles(trashed), ["Trashed"]);
+}
+
+#[test]
+fn test_row_ok() {
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let rows = user::table
+        .order(user::id)
+        .select((user::name, user::deleted))
+        .load::<Row<String>>(&conn)
+        .unwrap();
+    assert_eq!(rows, [Row::Alive("Joe".to_owned()), Row::Trashed("Jack".to_owned())]);
+    assert!(rows[1].is_deleted());
+    assert_eq!(rows[1].clone().into_inner(), "Jack");
+    assert_eq!(rows.into_iter().filter_map(Row::alive).collect::<Vec<_>>(), ["Joe"]);
+
+    let posts = post::table.select((post::all_columns, post::deleted)).load::<Row<Post>>(&conn);
+    assert!(posts.unwrap().is_empty());
+}
+
+#[test]
+fn test_optional_alive_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let post = post::table.find(1).first::<Post>(&conn).unwrap();
+    assert_eq!(post.optional_alive().map(|post| post.title), Some("First".to_owned()));
+    let post = post::table.find(2).first::<Post>(&conn).unwrap();
+    assert!(post.optional_alive().is_none());
+    let post = post::table.find(2).first::<Post>(&conn).optional().unwrap();
+    assert!(post.is_some());
+    assert!(post.optional_alive().is_none());
+    let post = post::table.find(3).first::<Post>(&conn).optional().unwrap();
+    assert!(post.optional_alive().is_none());
+}
+
+#[test]
+fn test_alive_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    fn title(post: Alive<Post>) -> String {
+        post.title.clone()
+    }
+
+    let post = post::table.find(1).first::<Post>(&conn).unwrap();
+    assert_eq!(Alive::new(post).map(title).unwrap(), "First");
+    let post = post::table.find(2).first::<Post>(&conn).unwrap();
+    let TrashedRow(post) = Alive::new(post).unwrap_err();
+    assert_eq!(post.title, "Trashed");
+
+    let rows = post::table
+        .order(post::id)
+        .select((post::title, post::deleted))
+        .load::<Row<String>>(&conn)
+        .unwrap();
+    let titles = rows.into_iter().map(|row| Alive::try_from(row).map(Alive::into_inner));
+    assert_eq!(
+        titles.collect::<Vec<_>>(),
+        [Ok("First".to_owned()), Err(TrashedRow("Trashed".to_owned()))]
+    );
+}
+
+#[test]
+fn test_soft_identifiable_ok() {
+    use std::time::{Duration, SystemTime};
+
+    #[derive(Queryable)]
+    struct Event {
+        name: String,
+        deleted_at: Option<i32>,
+    }
+
+    impl SoftIdentifiable for Event {
+        fn is_deleted(&self) -> bool {
+            self.deleted_at.is_some()
+        }
+
+        fn deleted_at(&self) -> Option<SystemTime> {
+            let secs = self.deleted_at?;
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs as u64))
+        }
+
+        fn deleted_by(&self) -> Option<&str> {
+            self.is_deleted().then_some("admin")
+        }
+    }
+
+    fn describe<T: SoftIdentifiable>(row: T) -> Option<(SystemTime, String)> {
+        Some((row.deleted_at()?, row.deleted_by()?.to_owned()))
+    }
+
+    let conn = conn();
+    diesel::insert_into(event::table)
+        .values(vec![
+            (event::name.eq("launch"), event::created_at.eq(10), event::deleted_at.eq(None)),
+            (event::name.eq("beta"), event::created_at.eq(20), event::deleted_at.eq(Some(30))),
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let events = event::table
+        .order(event::id)
+        .select((event::name, event::deleted_at))
+        .load::<Event>(&conn)
+        .unwrap();
+    assert_eq!(events[0].name, "launch");
+    assert_eq!(describe(&events[0]), None);
+    let at = SystemTime::UNIX_EPOCH + Duration::from_secs(30);
+    assert_eq!(describe(&events[1]), Some((at, "admin".to_owned())));
+
+    let post = Post { id: 1, user_id: 1, title: "Post".to_owned(), deleted: true };
+    assert_eq!((post.deleted_at(), post.deleted_by()), (None, None));
+}
+
+#[test]
+fn test_soft_delete_timestamp_ok() {
+    let conn = conn();
+    conn.batch_execute("insert into note(id, content) values (1, 'First'), (2, 'Second')").unwrap();
+    let alive = || note::table.soft_deleted().select(note::id).load::<i32>(&conn).unwrap();
+
+    assert_eq!(crate::write::soft_delete(note::table.find(1)).execute(&conn).unwrap(), 1);
+    assert_eq!(crate::write::soft_delete(note::table.find(1)).execute(&conn).unwrap(), 0);
+    assert_eq!(alive(), [2]);
+    assert_eq!(
+        sql_of(&note::table.soft_deleted().select(note::id)),
+        "SELECT `note`.`id` FROM `note` WHERE NOT (`note`.`deleted_at` IS NOT NULL) -- binds: []",
+    );
+    assert_eq!(crate::write::restore(note::table.find(1)).execute(&conn).unwrap(), 1);
+    assert_eq!(alive(), [1, 2]);
+}
+
+#[test]
+fn test_soft_delete_sentinel_ok() {
+    let conn = conn();
+    let insert =
+        |token| diesel::insert_into(session::table).values(session::token.eq(token)).execute(&conn);
+    let alive = || session::table.soft_deleted().select(session::id).load::<i32>(&conn).unwrap();
+
+    insert("abc").unwrap();
+    assert!(insert("abc").is_err());
+    assert_eq!(crate::write::soft_delete(session::table.find(1)).execute(&conn).unwrap(), 1);
+    assert_eq!(crate::write::soft_delete(session::table.find(1)).execute(&conn).unwrap(), 0);
+    insert("abc").unwrap();
+    assert_eq!(alive(), [2]);
+    assert_eq!(
+        sql_of(&session::table.soft_deleted().select(session::id)),
+        "SELECT `session`.`id` FROM `session` \
+         WHERE NOT (`session`.`deleted_at` != '1970-01-01 00:00:01') -- binds: []",
+    );
+    assert!(crate::write::restore(session::table.find(1)).execute(&conn).is_err());
+    diesel::delete(session::table.find(2)).execute(&conn).unwrap();
+    assert_eq!(crate::write::restore(session::table.find(1)).execute(&conn).unwrap(), 1);
+    assert_eq!(alive(), [1]);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_derive_soft_delete_ok() {
+    #[derive(crate::SoftDelete)]
+    #[soft_delete(table = "draft", column = "removed", revision = "version")]
+    #[allow(dead_code)]
+    struct DraftSoftDelete;
+
+    assert_eq!(
+        sql_of(&crate::write::soft_delete(draft::table.find(1))),
+        "UPDATE `draft` SET `removed` = ?, `version` = `draft`.`version` + 1 \
+         WHERE `draft`.`id` = ? AND NOT (`draft`.`removed`) -- binds: [true, 1]",
+    );
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_soft_delete_schema_ok() {
+    mod schema {
+        crate::soft_delete_schema! {
+            #![soft_delete(column = "archived", column = "removed_at")]
+
+            table! {
+                tag (id) {
+                    id -> Integer,
+                    archived -> Bool,
+                }
+            }
+
+            table! {
+                /// Removed labels are kept for a while
+                label (id) {
+                    id -> Integer,
+                    #[sql_name = "removed"]
+                    removed_at -> Nullable<Timestamp>,
+                }
+            }
+
+            table! {
+                setting (id) {
+                    id -> Integer,
+                }
+            }
+        }
+    }
+    #[crate::soft_deletable(column = "archived_at")]
+    mod archive {
+        table! {
+            folder (id) {
+                id -> Integer,
+                archived_at -> Nullable<Timestamp>,
+            }
+        }
+    }
+    use archive::folder;
+    use schema::{label, tag};
+
+    assert_eq!(
+        sql_of(&tag::table.soft_deleted().select(tag::id)),
+        "SELECT `tag`.`id` FROM `tag` WHERE NOT (`tag`.`archived`) -- binds: []",
+    );
+    assert_eq!(
+        sql_of(&label::table.soft_deleted().select(label::id)),
+        "SELECT `label`.`id` FROM `label` WHERE NOT (`label`.`removed` IS NOT NULL) -- binds: []",
+    );
+    assert_eq!(
+        sql_of(&folder::table.soft_deleted().select(folder::id)),
+        "SELECT `folder`.`id` FROM `folder` WHERE NOT (`folder`.`archived_at` IS NOT NULL) \
+         -- binds: []",
+    );
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_derive_soft_identifiable_ok() {
+    use std::time::{Duration, SystemTime};
+
+    #[derive(Queryable, SoftIdentifiable)]
+    struct User {
+        name: String,
+        deleted: bool,
+    }
+
+    #[derive(SoftIdentifiable)]
+    #[soft_delete(deleted_at = "removed_at", deleted_by = "removed_by")]
+    struct Removable {
+        removed_at: Option<SystemTime>,
+        removed_by: Option<String>,
+    }
+
+    #[derive(SoftIdentifiable)]
+    #[soft_delete(column = "trashed")]
+    struct Flagged {
+        trashed: bool,
+    }
+
+    let conn = conn();
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+    let users = user::table.order(user::id).select((user::name, user::deleted)).load::<User>(&conn);
+    let users = users.unwrap();
+    assert_eq!(users[0].name, "Joe");
+    assert_eq!((users[0].is_deleted(), users[1].is_deleted()), (false, true));
+
+    let at = SystemTime::UNIX_EPOCH + Duration::from_secs(30);
+    let row = Removable { removed_at: Some(at), removed_by: Some("admin".to_owned()) };
+    assert!(row.is_deleted());
+    assert_eq!((row.deleted_at(), row.deleted_by()), (Some(at), Some("admin")));
+    let row = Removable { removed_at: None, removed_by: None };
+    assert!(!row.is_deleted());
+    assert!(Flagged { trashed: true }.is_deleted());
+}
+
+#[test]
+fn test_soft_save_changes_ok() {
+    use crate::write::{soft_save_changes, SaveError};
+
+    let conn = conn();
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let mut post = post::table.find(1).first::<Post>(&conn).unwrap();
+    post.title = "Edited".to_owned();
+    let post: Post = soft_save_changes(&post, &conn).unwrap();
+    assert_eq!(post.title, "Edited");
+
+    let mut trashed = post::table.find(2).first::<Post>(&conn).unwrap();
+    trashed.title = "Resurrected".to_owned();
+    assert!(matches!(
+        soft_save_changes::<_, _, _, Post, _>(&trashed, &conn),
+        Err(SaveError::Deleted)
+    ));
+
+    // Soft-deleted since it was loaded
+    let mut post = post;
+    crate::write::soft_delete(post::table.find(1)).execute(&conn).unwrap();
+    post.title = "Too late".to_owned();
+    assert!(matches!(soft_save_changes::<_, _, _, Post, _>(&post, &conn), Err(SaveError::Deleted)));
+
+    let titles = post::table.order(post::id).select(post::title).load::<String>(&conn).unwrap();
+    assert_eq!(titles, ["Edited", "Trashed"]);
+}
+
+#[test]
+fn test_soft_filter_single_predicate() {
+    let query = user::table.soft_filter(user::name.like("J%").or(user::name.like("W%")));
+    assert_eq!(
+        sql_of(&query),
+        "SELECT `user`.`id`, `user`.`name`, `user`.`deleted` FROM `user` \
+         WHERE NOT (`user`.`deleted`) AND (`user`.`name` LIKE ? OR `user`.`name` LIKE ?) \
+         -- binds: [\"J%\", \"W%\"]",
+    );
+
+    let query = user::table.soft_find(1);
+    assert_eq!(
+        sql_of(&query),
+        "SELECT `user`.`id`, `user`.`name`, `user`.`deleted` FROM `user` \
+         WHERE NOT (`user`.`deleted`) AND `user`.`id` = ? -- binds: [1]",
+    );
+}
+
+#[test]
+fn test_soft_sql_stable_across_chaining() {
+    let predicate = || user::name.like("J%");
+    assert_eq!(
+        sql_of(&user::table.soft_filter(predicate())),
+        sql_of(&user::table.soft_deleted().filter(predicate())),
+    );
+    assert_eq!(sql_of(&user::table.soft_find(1)), sql_of(&user::table.soft_deleted().find(1)),);
+}
+
+#[test]
+fn test_soft_deleted_sql_shape() {
+    let query = user::table.soft_deleted().select(user::id);
+    assert_eq!(
+        sql_of(&query),
+        "SELECT `user`.`id` FROM `user` WHERE NOT (`user`.`deleted`) -- binds: []",
+    );
+}
+
+#[test]
+fn test_soft_methods_after_distinct_ok() {
+    let query = user::table.select(user::name).distinct().soft_filter(user::id.gt(1));
+    assert_eq!(
+        sql_of(&query),
+        "SELECT DISTINCT `user`.`name` FROM `user` \
+         WHERE NOT (`user`.`deleted`) AND `user`.`id` > ? -- binds: [1]",
+    );
+
+    let query = post::table.select(post::user_id).group_by(post::user_id).soft_deleted();
+    assert_eq!(
+        sql_of(&query),
+        "SELECT `post`.`user_id` FROM `post` WHERE NOT (`post`.`deleted`) \
+         GROUP BY `post`.`user_id` -- binds: []",
+    );
+
+    let query = event::table.select(event::name).distinct().as_of(25);
+    assert_eq!(
+        sql_of(&query),
+        "SELECT DISTINCT `event`.`name` FROM `event` WHERE `event`.`created_at` <= ? \
+         AND (`event`.`deleted_at` IS NULL OR `event`.`deleted_at` > ?) -- binds: [25, 25]",
+    );
+    assert_eq!(
+        sql_of(&event::table.into_boxed::<diesel::sqlite::Sqlite>().as_of(25)),
+        sql_of(&event::table.as_of(25)),
+    );
+}
+
+#[test]
+fn test_as_of_ok() {
+    let conn = conn();
+
+    diesel::insert_into(event::table)
+        .values(vec![
+            (event::name.eq("launch"), event::created_at.eq(10), event::deleted_at.eq(None)),
+            (event::name.eq("beta"), event::created_at.eq(20), event::deleted_at.eq(Some(30))),
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let names = |at: i32| -> Vec<String> {
+        event::table.as_of(at).select(event::name).order(event::id).load(&conn).unwrap()
+    };
+    assert_eq!(names(5), Vec::<String>::new());
+    assert_eq!(names(15), vec!["launch".to_owned()]);
+    assert_eq!(names(25), vec!["launch".to_owned(), "beta".to_owned()]);
+    assert_eq!(names(30), vec!["launch".to_owned()]);
+
+    let names: Vec<String> = event::table
+        .filter(event::name.eq("beta"))
+        .as_of(25)
+        .select(event::name)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(names, vec!["beta".to_owned()]);
+}
+
+#[test]
+fn test_soft_paginate_ok() {
+    let conn = conn();
+
+    let users = vec![
+        NewUser { name: "Joe" },
+        NewUser { name: "Jack" },
+        NewUser { name: "William" },
+        NewUser { name: "Averell" },
+    ];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    let joe_query = user::table.filter(user::name.eq("Joe"));
+    diesel::update(joe_query).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let (users, total) = user::table.soft_paginate::<_, User, _>(user::id, 1, 2, &conn).unwrap();
+    assert_eq!(total, 3);
+    assert_eq!(users.iter().map(|u| u.name.as_str()).collect::<Vec<_>>(), vec!["Jack", "William"]);
+
+    let (users, total) = user::table.soft_paginate::<_, User, _>(user::id, 2, 2, &conn).unwrap();
+    assert_eq!(total, 3);
+    assert_eq!(users.iter().map(|u| u.name.as_str()).collect::<Vec<_>>(), vec!["Averell"]);
+
+    let (users, _) = user::table.soft_paginate::<_, User, _>(user::name, 1, 2, &conn).unwrap();
+    assert_eq!(users.iter().map(|u| u.name.as_str()).collect::<Vec<_>>(), vec!["Averell", "Jack"]);
+}
+
+#[test]
+fn test_join_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    let joe_id: i32 = user::table.select(user::id).first(&conn).unwrap();
+
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: joe_id, title: "My first post", ..Default::default() },
+            NewPost { user_id: joe_id, title: "Failed post", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let user_posts = user::table
+        .soft_find(joe_id)
+        .left_join(post::table)
+        .load::<(User, Option<Post>)>(&conn)
+        .unwrap();
+
+    assert_eq!(user_posts.len(), 2);
+}
+
+#[test]
+fn test_soft_left_join_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    let joe_id: i32 = user::table.select(user::id).first(&conn).unwrap();
+
+    diesel::insert_into(post::table)
+        .values(NewPost { user_id: joe_id, title: "Some post", ..Default::default() })
+        .execute(&conn)
+        .unwrap();
+
+    let (_, post) = user::table
+        .soft_find(joe_id)
+        .soft_left_join(post::table)
+        .first::<(User, Option<Post>)>(&conn)
+        .unwrap();
+    assert!(post.is_some());
+    let post = post.unwrap();
+    assert!(!post.deleted);
+    assert_eq!(post.title, "Some post");
+
+    diesel::update(&post).set(post::deleted.eq(true)).execute(&conn).unwrap();
+
+    let (_, post) = user::table
+        .soft_find(joe_id)
+        .soft_left_join(post::table)
+        .first::<(User, Option<Post>)>(&conn)
+        .unwrap();
+    assert!(post.is_none());
+}
+
+#[test]
+fn test_soft_left_join_flagged() {
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let jane = fixtures::insert_user(&conn, "Jane");
+    fixtures::insert_user(&conn, "Jack");
+    fixtures::insert_trashed_post(&conn, &joe, "Trashed");
+    fixtures::insert_post(&conn, &jane, "Alive");
+
+    let rows = user::table
+        .soft_left_join_flagged(post::table)
+        .order(user::id)
+        .load::<(User, Option<Post>, Option<bool>)>(&conn)
+        .unwrap();
+    let rows = rows
+        .into_iter()
+        .map(|(user, post, deleted)| (user.name, post.map(|post| post.title), deleted));
+    assert_eq!(
+        rows.collect::<Vec<_>>(),
+        [
+            ("Joe".to_owned(), Some("Trashed".to_owned()), Some(true)),
+            ("Jane".to_owned(), Some("Alive".to_owned()), Some(false)),
+            ("Jack".to_owned(), None, None),
+        ],
+    );
+}
+
+#[test]
+fn test_soft_join_dyn_ok() {
+    use diesel::sqlite::Sqlite;
+
+    use crate::query_dsl::JoinKind;
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let jack = fixtures::insert_user(&conn, "Jack");
+    let zoe = fixtures::insert_user(&conn, "Zoe");
+    fixtures::insert_post(&conn, &joe, "Alive");
+    fixtures::insert_trashed_post(&conn, &jack, "Trashed");
+
+    let authors = |kind| {
+        user::table
+            .soft_join_dyn::<_, Sqlite>(post::table, kind)
+            .soft_filter(user::id.ne(zoe.id))
+            .order(user::id)
+            .load::<(User, Option<Post>)>(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|(user, post)| (user.name, post.map(|post| post.title)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(authors(JoinKind::Inner), [("Joe".to_owned(), Some("Alive".to_owned()))]);
+    assert_eq!(
+        authors(JoinKind::Left),
+        [("Joe".to_owned(), Some("Alive".to_owned())), ("Jack".to_owned(), None)],
+    );
+    assert_eq!(
+        sql_of(
+            &user::table.soft_join_dyn::<_, Sqlite>(post::table, JoinKind::Inner).select(user::id)
+        ),
+        "SELECT `user`.`id` FROM (`user` LEFT OUTER JOIN `post` ON `post`.`user_id` = `user`.`id` \
+         AND NOT (`post`.`deleted`)) WHERE `post`.`id` IS NOT NULL -- binds: []",
+    );
+}
+
+#[test]
+fn test_soft_inner_join_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    let joe_id: i32 = user::table.select(user::id).first(&conn).unwrap();
+
+    diesel::insert_into(post::table)
+        .values(NewPost { user_id: joe_id, title: "Some post", ..Default::default() })
+        .execute(&conn)
+        .unwrap();
+
+    let user_and_post = user::table
+        .soft_find(joe_id)
+        .soft_inner_join(post::table)
+        .first::<(User, Post)>(&conn)
+        .optional()
+        .unwrap();
+    assert!(user_and_post.is_some());
+    let (_, post) = user_and_post.unwrap();
+    assert!(!post.deleted);
+    assert_eq!(post.title, "Some post");
+
+    diesel::update(&post).set(post::deleted.eq(true)).execute(&conn).unwrap();
+
+    let user_and_post = user::table
+        .soft_find(joe_id)
+        .soft_inner_join(post::table)
+        .first::<(User, Post)>(&conn)
+        .optional()
+        .unwrap();
+    assert!(user_and_post.is_none());
+}
+
+#[test]
+fn test_soft_join_both_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .execute(&conn)
+        .unwrap();
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "Joe's post", ..Default::default() },
+            NewPost { user_id: 2, title: "Jack's post", ..Default::default() },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let titles = post::table
+        .soft_inner_join_both(user::table)
+        .select(post::title)
+        .load::<String>(&conn)
+        .unwrap();
+    assert_eq!(titles, vec!["Joe's post"]);
+
+    diesel::update(post::table.find(1)).set(post::deleted.eq(true)).execute(&conn).unwrap();
+    let users = user::table
+        .soft_left_join_both(post::table)
+        .select((user::name, post::title.nullable()))
+        .load::<(String, Option<String>)>(&conn)
+        .unwrap();
+    assert_eq!(users, vec![("Joe".to_owned(), None)]);
+
+    let query = user::table.soft_left_join_both(post::table);
+    let sql = sql_of(&query);
+    assert!(
+        sql.ends_with("AND NOT (`post`.`deleted`)) WHERE NOT (`user`.`deleted`) -- binds: []"),
+        "{}",
+        sql
+    );
+}
+
+#[test]
+fn test_soft_through_join_ok() {
+    let conn = conn();
+
+    conn.batch_execute(
+        "
+        insert into user(id, name) values (1, 'Joe'), (2, 'Jack');
+        insert into team(id, name, deleted) values
+            (1, 'Core', false),
+            (2, 'Gone', true),
+            (3, 'Ops', false);
+        insert into membership(user_id, team_id, role, deleted) values
+            (1, 1, 'owner', false),
+            (1, 2, 'member', false),
+            (1, 3, 'member', true),
+            (2, 3, 'member', false);
+        ",
+    )
+    .unwrap();
+
+    let teams = user::table
+        .soft_through_join(membership::table, team::table)
+        .filter(user::id.eq(1))
+        .select(team::name)
+        .load::<String>(&conn)
+        .unwrap();
+    assert_eq!(teams, vec!["Core"]);
+
+    let teams = user::table
+        .soft_left_through_join(membership::table, team::table)
+        .select((user::name, team::name.nullable()))
+        .order((user::id, team::id))
+        .load::<(String, Option<String>)>(&conn)
+        .unwrap();
+    assert_eq!(
+        teams,
+        vec![
+            ("Joe".to_owned(), Some("Core".to_owned())),
+            ("Jack".to_owned(), Some("Ops".to_owned())),
+        ]
+    );
+
+    diesel::update(membership::table).set(membership::deleted.eq(true)).execute(&conn).unwrap();
+    let teams = user::table
+        .soft_left_through_join(membership::table, team::table)
+        .select((user::name, team::name.nullable()))
+        .order(user::id)
+        .load::<(String, Option<String>)>(&conn)
+        .unwrap();
+    assert_eq!(teams, vec![("Joe".to_owned(), None), ("Jack".to_owned(), None)]);
+}
+
+#[test]
+fn test_soft_through_join_pivot_deletion_ok() {
+    let conn = conn();
+
+    conn.batch_execute(
+        "
+        insert into user(id, name) values (1, 'Joe');
+        insert into team(id, name, deleted) values
+            (1, 'Core', false),
+            (2, 'Gone', true),
+            (3, 'Ops', false);
+        insert into membership(user_id, team_id, role, deleted) values
+            (1, 1, 'owner', false),
+            (1, 2, 'member', false),
+            (1, 3, 'member', true);
+        ",
+    )
+    .unwrap();
+
+    let teams = user::table
+        .soft_through_join_with(membership::table, team::table, HideLink)
+        .select(team::name)
+        .load::<String>(&conn)
+        .unwrap();
+    assert_eq!(teams, vec!["Core"]);
+
+    let teams = user::table
+        .soft_through_join_with(membership::table, team::table, KeepLink)
+        .select((team::name, membership::deleted))
+        .order(team::id)
+        .load::<(String, bool)>(&conn)
+        .unwrap();
+    assert_eq!(teams, vec![("Core".to_owned(), false), ("Ops".to_owned(), true)]);
+}
+
+#[test]
+fn test_soft_scope_all_ok() {
+    let conn = conn();
+
+    conn.batch_execute(
+        "
+        insert into user(id, name, deleted) values (1, 'Joe', false), (2, 'Jack', true);
+        insert into post(id, user_id, title, deleted) values
+            (1, 1, 'Alive', false),
+            (2, 1, 'Trashed', true),
+            (3, 2, 'By trashed', false);
+        insert into comment(user_id, post_id, content, deleted) values
+            (1, 1, 'Alive', false),
+            (1, 1, 'Trashed', true),
+            (2, 3, 'By trashed', false);
+        ",
+    )
+    .unwrap();
+
+    let contents = comment::table
+        .inner_join(post::table)
+        .inner_join(user::table)
+        .soft_scope_all()
+        .select(comment::content)
+        .load::<String>(&conn)
+        .unwrap();
+    assert_eq!(contents, vec!["Alive"]);
+
+    let query = user::table.inner_join(post::table).soft_left_join(comment::table).soft_scope_all();
+    let sql = sql_of(&query);
+    assert!(sql.contains("WHERE NOT (`user`.`deleted`) AND NOT (`post`.`deleted`) --"), "{}", sql);
+
+    let contents = user::table
+        .left_join(comment::table)
+        .soft_scope_all()
+        .select(comment::content.nullable())
+        .order(comment::id)
+        .load::<Option<String>>(&conn)
+        .unwrap();
+    assert_eq!(contents, vec![Some("Alive".to_owned()), Some("Trashed".to_owned())]);
+}
+
+#[test]
+fn test_soft_joinable_ok() {
+    let conn = conn();
+
+    conn.batch_execute(
+        "
+        insert into user(id, name) values (1, 'Joe');
+        insert into team(id, name, deleted) values (1, 'Core', false), (2, 'Gone', true);
+        insert into membership(user_id, team_id, role, deleted) values
+            (1, 1, 'owner', false),
+            (1, 2, 'member', false);
+        ",
+    )
+    .unwrap();
+
+    let teams = membership::table
+        .inner_join(team::table)
+        .select(team::name)
+        .order(team::id)
+        .load::<String>(&conn)
+        .unwrap();
+    if cfg!(feature = "implicit-soft-joins") {
+        assert_eq!(teams, vec!["Core"]);
+    } else {
+        assert_eq!(teams, vec!["Core", "Gone"]);
+    }
+
+    let teams = membership::table
+        .soft_inner_join(team::table)
+        .select(team::name)
+        .load::<String>(&conn)
+        .unwrap();
+    assert_eq!(teams, vec!["Core"]);
+
+    let sql = sql_of(&membership::table.soft_inner_join(team::table));
+    assert_eq!(sql.matches("NOT (`team`.`deleted`)").count(), 1, "{}", sql);
+}
+
+#[test]
+fn test_nested_join_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .execute(&conn)
+        .unwrap();
+    let joe: User = user::table.filter(user::name.eq("Joe")).first(&conn).unwrap();
+    let jack: User = user::table.filter(user::name.eq("Jack")).first(&conn).unwrap();
+
+    diesel::insert_into(post::table)
+        .values(NewPost { user_id: joe.id, title: "Some post", ..Default::default() })
+        .execute(&conn)
+        .unwrap();
+    let post_id: i32 = post::table.select(post::id).first(&conn).unwrap();
+
+    diesel::insert_into(comment::table)
+        .values(NewComment {
+            user_id: jack.id,
+            post_id,
+            content: "Some comment",
+            ..Default::default()
+        })
+        .execute(&conn)
+        .unwrap();
+
+    // Comments made by Jack on Joe's posts
+    let (_, post_and_comment) = user::table
+        .soft_find(joe.id)
+        .left_join(post::table.left_join(comment::table))
+        .first::<(User, Option<(Post, Option<Comment>)>)>(&conn)
+        .unwrap();
+    assert!(post_and_comment.is_some());
+    let (_, comment) = post_and_comment.unwrap();
+    assert!(comment.is_some());
+
+    let user_post_comment = user::table
+        .soft_find(joe.id)
+        .inner_join(post::table.inner_join(comment::table))
+        .first::<(User, (Post, Comment))>(&conn)
+        .optional()
+        .unwrap();
+    assert!(user_post_comment.is_some());
+}
+
+#[test]
+fn test_nested_join_inner_soft_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .execute(&conn)
+        .unwrap();
+    let joe: User = user::table.filter(user::name.eq("Joe")).first(&conn).unwrap();
+    let jack: User = user::table.filter(user::name.eq("Jack")).first(&conn).unwrap();
+
+    diesel::insert_into(post::table)
+        .values(NewPost { user_id: joe.id, title: "Some post", ..Default::default() })
+        .execute(&conn)
+        .unwrap();
+    let post_id: i32 = post::table.select(post::id).first(&conn).unwrap();
+
+    diesel::insert_into(comment::table)
+        .values(NewComment {
+            user_id: jack.id,
+            post_id,
+            content: "Some comment",
+            ..Default::default()
+        })
+        .execute(&conn)
+        .unwrap();
+
+    // Comments made by Jack on Joe's posts
+    let (_, post_and_comment) = user::table
+        .soft_find(joe.id)
+        .left_join(post::table.soft_left_join(comment::table))
+        .first::<(User, Option<(Post, Option<Comment>)>)>(&conn)
+        .unwrap();
+    assert!(post_and_comment.is_some());
+    let (_, comment) = post_and_comment.unwrap();
+    assert!(comment.is_some());
+}
+
+#[test]
+fn test_nested_join_outer_soft_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .execute(&conn)
+        .unwrap();
+    let joe: User = user::table.filter(user::name.eq("Joe")).first(&conn).unwrap();
+    let jack: User = user::table.filter(user::name.eq("Jack")).first(&conn).unwrap();
+
+    diesel::insert_into(post::table)
+        .values(NewPost { user_id: joe.id, title: "Some post", ..Default::default() })
+        .execute(&conn)
+        .unwrap();
+    let post_id: i32 = post::table.select(post::id).first(&conn).unwrap();
+
+    diesel::insert_into(comment::table)
+        .values(NewComment {
+            user_id: jack.id,
+            post_id,
+            content: "Some comment",
+            ..Default::default()
+        })
+        .execute(&conn)
+        .unwrap();
+
+    // Comments made by Jack on Joe's posts
+    let (_, post_and_comment) = user::table
+        .soft_find(joe.id)
+        .soft_left_join(post::table.soft_left_join(comment::table))
+        .first::<(User, Option<(Post, Option<Comment>)>)>(&conn)
+        .unwrap();
+    assert!(post_and_comment.is_some());
+    let (_, comment) = post_and_comment.unwrap();
+    assert!(comment.is_some());
+
+    let (_, post_and_comment) = soft_join_path!(user => post => comment, LeftOuter)
+        .filter(user::id.eq(joe.id))
+        .first::<(User, Option<(Post, Option<Comment>)>)>(&conn)
+        .unwrap();
+    assert!(post_and_comment.unwrap().1.is_some());
+    let user_post_comment = soft_join_path!(user => post => comment)
+        .first::<(User, (Post, Comment))>(&conn)
+        .optional()
+        .unwrap();
+    assert!(user_post_comment.is_some());
+
+    // The nested right-hand side's own table is checked in the outer `ON` clause
+    diesel::update(post::table).set(post::deleted.eq(true)).execute(&conn).unwrap();
+    let (_, post_and_comment) = user::table
+        .soft_find(joe.id)
+        .soft_left_join(post::table.soft_left_join(comment::table))
+        .first::<(User, Option<(Post, Option<Comment>)>)>(&conn)
+        .unwrap();
+    assert!(post_and_comment.is_none());
+}
+
+#[test]
+fn test_incremental_soft_join_ok() {
+    let conn = conn();
+
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .execute(&conn)
+        .unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "Some post", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed post", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+    diesel::insert_into(comment::table)
+        .values(vec![
+            NewComment { user_id: 1, post_id: 1, content: "Alive", ..Default::default() },
+            NewComment { user_id: 1, post_id: 2, content: "On trashed", ..Default::default() },
+            NewComment { user_id: 1, post_id: 1, content: "Trashed", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    // Soft joins chained onto an already-joined query
+    let contents = comment::table
+        .soft_inner_join(post::table)
+        .soft_inner_join(user::table)
+        .select(comment::content)
+        .soft_deleted()
+        .load::<String>(&conn)
+        .unwrap();
+    assert_eq!(contents, vec!["Alive"]);
+
+    // A soft join whose right-hand side is itself a soft-joined query
+    let contents = user::table
+        .soft_inner_join(post::table.soft_inner_join(comment::table))
+        .select(comment::content)
+        .soft_deleted()
+        .load::<String>(&conn)
+        .unwrap();
+    assert_eq!(contents, vec!["Alive"]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_export_tombstones_ok() {
+    use crate::export::{export_tombstones, Format, TombstoneWriter};
+
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    let joe_query = user::table.filter(user::name.eq("Joe"));
+    diesel::update(joe_query).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let mut out = TombstoneWriter::new(Vec::new(), Format::JsonLines);
+    let count = export_tombstones::<_, i32, _, _>(user::table, &conn, &mut out).unwrap();
+    assert_eq!(count, 1);
+    let out = String::from_utf8(out.into_inner().unwrap()).unwrap();
+    assert_eq!(out, "{\"table\":\"user\",\"id\":1}\n");
+
+    let mut out = TombstoneWriter::new(Vec::new(), Format::Csv);
+    export_tombstones::<_, i32, _, _>(user::table, &conn, &mut out).unwrap();
+    let out = String::from_utf8(out.into_inner().unwrap()).unwrap();
+    assert_eq!(out, "table,id\nuser,1\n");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_export_trashed_ok() {
+    use crate::export::{export_trashed, Format, TombstoneWriter, BATCH_SIZE};
+
+    let conn = conn();
+    let count = BATCH_SIZE as usize + 2;
+    let names = (0..count).map(|i| format!("User {}", i)).collect::<Vec<_>>();
+    let users = names.iter().map(|name| NewUser { name }).collect::<Vec<_>>();
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+    diesel::update(user::table.filter(user::id.ne(2)))
+        .set(user::deleted.eq(true))
+        .execute(&conn)
+        .unwrap();
+
+    let mut out = TombstoneWriter::new(Vec::new(), Format::JsonLines);
+    let source = user::table.select((user::id, user::name));
+    let exported = export_trashed::<_, i32, (i32, String), _, _>(source, &conn, &mut out);
+    assert_eq!(exported.unwrap(), count - 1);
+    let out = String::from_utf8(out.into_inner().unwrap()).unwrap();
+    let lines = out.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), count - 1);
+    assert_eq!(lines[..2], ["[1,\"User 0\"]", "[3,\"User 2\"]"]);
+    assert_eq!(lines[count - 2], format!("[{0},\"User {1}\"]", count, count - 1));
+
+    let mut out = TombstoneWriter::new(Vec::new(), Format::Csv);
+    let source = user::table.filter(user::name.eq("User 0")).select((user::id, user::name));
+    let exported = export_trashed::<_, i32, (i32, String), _, _>(source, &conn, &mut out);
+    assert_eq!(exported.unwrap(), 1);
+    assert_eq!(String::from_utf8(out.into_inner().unwrap()).unwrap(), "1,User 0\n");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_export_import_trash() {
+    use serde::{Deserialize, Serialize};
+
+    use crate::export::{export_trash, import_trash, Error, TrashExport};
+
+    #[derive(Queryable, Insertable, Serialize, Deserialize, Debug, PartialEq)]
+    #[table_name = "post"]
+    struct TrashedPost {
+        id: i32,
+        user_id: i32,
+        title: String,
+        deleted: bool,
+    }
+
+    let production = conn();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "Alive", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+        ])
+        .execute(&production)
+        .unwrap();
+    let export = export_trash::<_, TrashedPost, _>(post::table, "42", &production).unwrap();
+    let json = serde_json::to_string(&export).unwrap();
+
+    let staging = conn();
+    let import = || serde_json::from_str::<TrashExport<TrashedPost>>(&json).unwrap();
+    assert!(matches!(
+        import_trash::<_, TrashedPost, _>(post::table, import(), "43", &staging),
+        Err(Error::Version { .. })
+    ));
+    let comments = TrashExport { table: "comment".to_owned(), ..import() };
+    assert!(matches!(
+        import_trash::<_, TrashedPost, _>(post::table, comments, "42", &staging),
+        Err(Error::Table { expected: "post", .. })
+    ));
+    assert_eq!(
+        import_trash::<_, TrashedPost, _>(post::table, import(), "42", &staging).unwrap(),
+        1
+    );
+    assert_eq!(
+        post::table.load::<Post>(&staging),
+        Ok(vec![Post { id: 2, user_id: 1, title: "Trashed".to_owned(), deleted: true }]),
+    );
+}
+
+#[test]
+fn test_soft_delete_restore_ok() {
+    use crate::write::{restore, soft_delete};
+
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+
+    let count = soft_delete(user::table.filter(user::name.eq("Joe"))).execute(&conn).unwrap();
+    assert_eq!(count, 1);
+    let count = soft_delete(user::table.filter(user::name.eq("Joe"))).execute(&conn).unwrap();
+    assert_eq!(count, 0);
+
+    let users: Vec<String> = user::table.soft_deleted().select(user::name).load(&conn).unwrap();
+    assert_eq!(users, vec!["Jack".to_owned()]);
+
+    let count = restore(user::table).execute(&conn).unwrap();
+    assert_eq!(count, 1);
+
+    let users: Vec<String> = user::table.soft_deleted().select(user::name).load(&conn).unwrap();
+    assert_eq!(users, vec!["Joe".to_owned(), "Jack".to_owned()]);
+}
+
+#[test]
+fn test_chunked_soft_delete() {
+    use crate::chunked::{ChunkProgress, ChunkedSoftDelete};
+
+    let conn = conn();
+    let (joe, jack) = (fixtures::insert_user(&conn, "Joe"), fixtures::insert_user(&conn, "Jack"));
+    let posts = ["A", "B", "C", "D", "E"]
+        .iter()
+        .map(|&title| NewPost { user_id: joe.id, title, ..Default::default() })
+        .chain([NewPost { user_id: joe.id, title: "Trashed", deleted: Some(true) }])
+        .chain([NewPost { user_id: jack.id, title: "Other", ..Default::default() }])
+        .collect::<Vec<_>>();
+    diesel::insert_into(post::table).values(posts).execute(&conn).unwrap();
+
+    let mut chunks = Vec::new();
+    let progress = ChunkedSoftDelete::new(post::table, post::user_id.eq(joe.id))
+        .chunk_size(2)
+        .run_with_progress(&conn, |progress| chunks.push(progress.deleted))
+        .unwrap();
+    assert_eq!(progress, ChunkProgress { table: "post", deleted: 5, chunks: 3 });
+    assert_eq!(progress.to_string(), "post: 5 rows soft-deleted in 3 chunks");
+    assert_eq!(chunks, [2, 4, 5]);
+    let alive = post::table.soft_deleted().select(post::title).load::<String>(&conn);
+    assert_eq!(alive, Ok(vec!["Other".to_owned()]));
+
+    let progress = ChunkedSoftDelete::new(post::table, post::user_id.eq(joe.id)).run(&conn);
+    assert_eq!(progress, Ok(ChunkProgress { table: "post", deleted: 0, chunks: 0 }));
+}
+
+#[test]
+fn test_write_hooks() {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        chunked::ChunkedSoftDelete,
+        hooks::{self, Action, WriteEvent},
+        write::{empty_trash, ConfirmEmptyTrash, RestoreByIdDsl, SoftWriteDsl},
+    };
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let posts = ["A", "B", "C"].iter().map(|&title| NewPost {
+        user_id: joe.id,
+        title,
+        ..Default::default()
+    });
+    diesel::insert_into(post::table).values(posts.collect::<Vec<_>>()).execute(&conn).unwrap();
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let hook = {
+        let events = events.clone();
+        hooks::install(move |event: &WriteEvent, _: &SqliteConnection| {
+            events.borrow_mut().push((event.table, event.action, event.keys.to_vec()));
+            Ok(())
+        })
+    };
+    assert_eq!(post::table.find(1).soft_delete_rows(&conn), Ok(1));
+    assert_eq!(post::table.find(1).soft_delete_rows(&conn), Ok(0));
+    assert!(post::table.restore_by_id::<Post, _>(1, &conn).is_ok());
+    let chunked = ChunkedSoftDelete::new(post::table, post::user_id.eq(joe.id)).chunk_size(2);
+    assert_eq!(chunked.run(&conn).map(|progress| progress.deleted), Ok(3));
+    let confirm = ConfirmEmptyTrash::i_really_want_to_destroy_trashed_rows();
+    assert_eq!(empty_trash(post::table, confirm, &conn).unwrap(), 3);
+    let keys = |keys: &[&str]| keys.iter().map(|&key| key.to_owned()).collect::<Vec<_>>();
+    assert_eq!(
+        *events.borrow(),
+        [
+            ("post", Action::SoftDelete, keys(&["1"])),
+            ("post", Action::SoftDelete, keys(&[])),
+            ("post", Action::Restore, keys(&["1"])),
+            ("post", Action::SoftDelete, keys(&["1", "2"])),
+            ("post", Action::SoftDelete, keys(&["3"])),
+            ("post", Action::Purge, keys(&["1", "2", "3"])),
+        ],
+    );
+
+    drop(hook);
+    assert_eq!(user::table.find(joe.id).soft_delete_rows(&conn), Ok(1));
+    assert_eq!(events.borrow().len(), 6);
+    let failing = hooks::install(|_: &WriteEvent, _: &SqliteConnection| {
+        Err(diesel::result::Error::RollbackTransaction)
+    });
+    assert!(user::table.find(joe.id).restore_rows(&conn).is_err());
+    drop(failing);
+    assert_eq!(user::table.soft_deleted().count().get_result(&conn), Ok(0));
+}
+
+#[test]
+fn test_hard_delete_ok() {
+    use crate::write::{hard_delete, AllowHardDelete};
+
+    let conn = conn();
+
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .execute(&conn)
+        .unwrap();
+
+    let token = AllowHardDelete::i_really_want_to_destroy_rows();
+    assert_eq!(hard_delete(user::table.find(1), token).execute(&conn).unwrap(), 1);
+    let ids = user::table.select(user::id).load::<i32>(&conn).unwrap();
+    assert_eq!(ids, vec![2]);
+}
+
+#[test]
+fn test_soft_delete_revision_ok() {
+    use crate::write::{restore, soft_delete};
+
+    let conn = conn();
+
+    diesel::insert_into(document::table)
+        .values(document::title.eq("Draft"))
+        .execute(&conn)
+        .unwrap();
+    let version = || document::table.select(document::lock_version).first::<i32>(&conn).unwrap();
+
+    soft_delete(document::table).execute(&conn).unwrap();
+    assert_eq!(version(), 1);
+    soft_delete(document::table).execute(&conn).unwrap();
+    assert_eq!(version(), 1);
+    restore(document::table).execute(&conn).unwrap();
+    assert_eq!(version(), 2);
+}
+
+#[cfg(feature = "cdc")]
+#[test]
+fn test_cdc_operations_ok() {
+    use crate::cdc::{restore_tracked, soft_delete_tracked, Action, Context};
+
+    let conn = conn();
+
+    let users = vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }];
+    diesel::insert_into(user::table).values(users).execute(&conn).unwrap();
+
+    let ctx = Context::new().actor("admin").reason("spam");
+    let ops = soft_delete_tracked::<_, _, i32, _>(user::table, user::name.eq("Joe"), &ctx, &conn)
+        .unwrap();
+    assert_eq!(ops.len(), 1);
+    assert_eq!((ops[0].table, ops[0].pk, ops[0].action), ("user", 1, Action::SoftDelete));
+    assert_eq!(ops[0].actor.as_deref(), Some("admin"));
+    assert_eq!(ops[0].reason.as_deref(), Some("spam"));
+
+    let ops = soft_delete_tracked::<_, _, i32, _>(user::table, user::name.eq("Joe"), &ctx, &conn)
+        .unwrap();
+    assert!(ops.is_empty());
+
+    let ops = restore_tracked::<_, _, i32, _>(user::table, user::id.gt(0), &Context::new(), &conn)
+        .unwrap();
+    assert_eq!(ops.len(), 1);
+    assert_eq!((ops[0].pk, ops[0].action), (1, Action::Restore));
+}
+
+#[cfg(feature = "audit")]
+#[test]
+fn test_audit_log() {
+    use diesel::sql_types::{Nullable, Text};
+    use serde::Serialize;
+
+    use diesel::sqlite::Sqlite;
+
+    use crate::{
+        audit::AuditLog,
+        cdc::{Action, Context},
+        config::{SoftDeleteConfig, TableConfig},
+        write::{empty_trash, ConfirmEmptyTrash, SoftWriteDsl},
+    };
+
+    #[derive(Queryable, Serialize)]
+    struct PostSnapshot {
+        id: i32,
+        user_id: i32,
+        title: String,
+        deleted: bool,
+    }
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table soft_delete_audit(
+            table_name text not null,
+            row_pk text not null,
+            action text not null,
+            actor text,
+            reason text,
+            snapshot text,
+            performed_at timestamp not null
+        );",
+    )
+    .unwrap();
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Second", ..Default::default() },
+        ])
+        .execute(&conn)
+        .unwrap();
+    let ctx = Context::new().actor("admin").reason("spam");
+    {
+        let _audit = AuditLog::new().install::<SqliteConnection>(ctx.clone());
+        assert_eq!(post::table.filter(post::user_id.eq(1)).soft_delete_rows(&conn), Ok(2));
+    }
+    {
+        let _audit = AuditLog::new().install::<SqliteConnection>(Context::new());
+        assert_eq!(post::table.find(2).restore_rows(&conn), Ok(1));
+    }
+    let log = AuditLog::new();
+    let op = log.purge_by_id::<_, _, PostSnapshot, _, _>(post::table, 1, &ctx, &conn).unwrap();
+    assert_eq!(op.action, Action::Purge);
+    assert!(log.purge_by_id::<_, _, PostSnapshot, _, _>(post::table, 2, &ctx, &conn).is_err());
+    assert_eq!(post::table.find(2).soft_delete_rows(&conn), Ok(1));
+
+    let audit = diesel::dsl::sql::<(Text, Text, Text, Nullable<Text>, Nullable<Text>)>(
+        "select table_name, row_pk, action, actor, snapshot from soft_delete_audit order by rowid",
+    )
+    .load::<(String, String, String, Option<String>, Option<String>)>(&conn)
+    .unwrap();
+    let admin = || Some("admin".to_owned());
+    assert_eq!(
+        audit,
+        [
+            ("post".to_owned(), "1".to_owned(), "soft_delete".to_owned(), admin(), None),
+            ("post".to_owned(), "2".to_owned(), "soft_delete".to_owned(), admin(), None),
+            ("post".to_owned(), "2".to_owned(), "restore".to_owned(), None, None),
+            (
+                "post".to_owned(),
+                "1".to_owned(),
+                "purge".to_owned(),
+                admin(),
+                Some(r#"{"id":1,"user_id":1,"title":"First","deleted":true}"#.to_owned()),
+            ),
+        ],
+    );
+
+    let config = SoftDeleteConfig::<Sqlite>::new()
+        .table(TableConfig::new(user::table).audit())
+        .table(TableConfig::new(post::table));
+    let _audit = AuditLog::new().config(&config).install::<SqliteConnection>(ctx);
+    let confirm = ConfirmEmptyTrash::i_really_want_to_destroy_trashed_rows();
+    assert_eq!(empty_trash(post::table, confirm, &conn).unwrap(), 1);
+    assert_eq!(user::table.find(1).soft_delete_rows(&conn), Ok(1));
+    let audited = diesel::dsl::sql::<Text>("select table_name from soft_delete_audit");
+    assert_eq!(audited.load::<String>(&conn).unwrap().len(), 5);
+}
+
+#[test]
+fn test_protect_deleted() {
+    use crate::write::{Assigns, ProtectDeleted};
+
+    let conn = conn();
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(NewPost { user_id: 1, title: "First", ..Default::default() })
+        .execute(&conn)
+        .unwrap();
+
+    let update = post::table.find(1);
+    let edit = (post::title.eq("Edited"), Some(post::user_id.eq(1)));
+    diesel::update(update).set(ProtectDeleted(edit)).execute(&conn).unwrap();
+    let post = post::table.find(1).first::<Post>(&conn).unwrap();
+    assert_eq!((post.title.as_str(), post.deleted), ("Edited", false));
+
+    fn trashes<C: Assigns<post::deleted>>() -> bool {
+        C::ASSIGNS
+    }
+    type Edit = diesel::dsl::Eq<post::title, &'static str>;
+    type Trash = diesel::dsl::Eq<post::deleted, bool>;
+    assert!(!trashes::<(Edit, Option<Edit>)>());
+    assert!(trashes::<(Edit, Option<Trash>)>());
+    assert!(trashes::<&Trash>());
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_derive_assigns_ok() {
+    use crate::write::Assigns;
+
+    #[derive(AsChangeset, crate::Assigns)]
+    #[table_name = "post"]
+    #[allow(dead_code)]
+    struct PostForm {
+        #[column_name = "title"]
+        name: String,
+        user_id: i32,
+    }
+
+    #[derive(AsChangeset, crate::Assigns)]
+    #[table_name = "post"]
+    #[allow(dead_code)]
+    struct TrashForm<'a> {
+        title: &'a str,
+        #[column_name = "deleted"]
+        trashed: bool,
+    }
+
+    fn assigns<C: Assigns<Col>, Col>() -> bool {
+        C::ASSIGNS
+    }
+    assert!(!assigns::<PostForm, post::deleted>());
+    assert!(assigns::<PostForm, post::title>());
+    assert!(assigns::<&TrashForm, post::deleted>());
+}
+
+#[cfg(feature = "postgres")]
+#[test]
+fn test_distinct_on() {
+    let expected = "SELECT DISTINCT ON (\"post\".\"user_id\")\"post\".\"id\", \"post\".\"user_id\", \
+                    \"post\".\"title\", \"post\".\"deleted\" FROM \"post\" \
+                    WHERE NOT (\"post\".\"deleted\") ORDER BY \"post\".\"user_id\", \"post\".\"id\" DESC \
+                    -- binds: []";
+
+    let query = assert_soft_scoped!(post::table.soft_deleted().distinct_on(post::user_id))
+        .order((post::user_id, post::id.desc()));
+    assert_eq!(pg_sql_of(&query), expected);
+
+    let query =
+        Scoped::new(post::table).distinct_on(post::user_id).order((post::user_id, post::id.desc()));
+    assert_eq!(pg_sql_of(&query), expected);
+
+    let query = Scoped::new(post::table).select(post::user_id).distinct();
+    assert_eq!(
+        pg_sql_of(&query),
+        "SELECT DISTINCT \"post\".\"user_id\" FROM \"post\" WHERE NOT (\"post\".\"deleted\") \
+         -- binds: []",
+    );
+}
+
+#[cfg(feature = "postgres")]
+#[test]
+fn test_locking() {
+    let expected = "SELECT \"post\".\"id\", \"post\".\"user_id\", \"post\".\"title\", \
+                    \"post\".\"deleted\" FROM \"post\" WHERE NOT (\"post\".\"deleted\") \
+                    AND \"post\".\"id\" = $1 FOR UPDATE SKIP LOCKED -- binds: [1]";
+
+    let query = assert_soft_scoped!(post::table.soft_find(1).for_update().skip_locked());
+    assert_eq!(pg_sql_of(&query), expected);
+
+    let query = assert_soft_scoped!(post::table.for_update().skip_locked().soft_find(1));
+    assert_eq!(pg_sql_of(&query), expected);
+
+    let query = Scoped::new(post::table).for_update().skip_locked().find(1);
+    assert_eq!(pg_sql_of(&query), expected);
+
+    let query = Scoped::new(post::table).for_share().no_wait();
+    assert_eq!(
+        pg_sql_of(&query),
+        "SELECT \"post\".\"id\", \"post\".\"user_id\", \"post\".\"title\", \"post\".\"deleted\" \
+         FROM \"post\" WHERE NOT (\"post\".\"deleted\") FOR SHARE NOWAIT -- binds: []",
+    );
+}
+
+#[test]
+fn test_row_number_over() {
+    use crate::window::row_number_over;
+
+    let conn = conn();
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .execute(&conn)
+        .unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+            NewPost { user_id: 2, title: "Other", ..Default::default() },
+            NewPost { user_id: 1, title: "Last", ..Default::default() },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let query = post::table
+        .soft_deleted()
+        .select((post::id, row_number_over(post::user_id, post::id.desc())))
+        .order(post::id);
+    assert_eq!(
+        sql_of(&query),
+        "SELECT `post`.`id`, ROW_NUMBER() OVER (PARTITION BY `post`.`user_id` ORDER BY \
+         `post`.`id` DESC) FROM `post` WHERE NOT (`post`.`deleted`) ORDER BY `post`.`id` \
+         -- binds: []",
+    );
+    let numbers = query.load::<(i32, i64)>(&conn).unwrap();
+    assert_eq!(numbers, [(1, 2), (3, 1), (4, 1)]);
+}
+
+#[test]
+fn test_soft_aggregates() {
+    let conn = conn();
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Zoe" }])
+        .execute(&conn)
+        .unwrap();
+    diesel::update(user::table.find(3)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let query = user::table.soft_max(user::id);
+    assert_eq!(
+        sql_of(&query),
+        "SELECT max(`user`.`id`) FROM `user` WHERE NOT (`user`.`deleted`) -- binds: []",
+    );
+    assert_eq!(query.get_result::<Option<i32>>(&conn), Ok(Some(2)));
+    assert_eq!(user::table.soft_min(user::id).get_result::<Option<i32>>(&conn), Ok(Some(1)));
+    assert_eq!(user::table.soft_sum(user::id).get_result::<Option<i64>>(&conn), Ok(Some(3)));
+    assert_eq!(
+        sql_of(&user::table.soft_avg(user::id)),
+        "SELECT avg(`user`.`id`) FROM `user` WHERE NOT (`user`.`deleted`) -- binds: []",
+    );
+    let names = user::table.soft_max(user::name).get_result::<Option<String>>(&conn);
+    assert_eq!(names, Ok(Some("Joe".to_owned())));
+}
+
+#[cfg(feature = "postgres")]
+#[test]
+fn test_soft_search() {
+    use crate::search::{ts_matches, SoftSearchDsl};
+    use diesel::{dsl::sql, sql_types::Text};
+
+    let vector = || sql::<Text>("to_tsvector('english', \"post\".\"title\")");
+    let query =
+        || sql::<Text>("plainto_tsquery('english', ").bind::<Text, _>("soft delete").sql(")");
+    let expected = "SELECT \"post\".\"id\", \"post\".\"user_id\", \"post\".\"title\", \
+                    \"post\".\"deleted\" FROM \"post\" WHERE NOT (\"post\".\"deleted\") AND \
+                    to_tsvector('english', \"post\".\"title\") @@ plainto_tsquery('english', $1) \
+                    -- binds: [\"soft delete\"]";
+
+    let search = assert_soft_scoped!(post::table.soft_search(vector(), query()));
+    assert_eq!(pg_sql_of(&search), expected);
+    let search = post::table.soft_filter(ts_matches(vector(), query()));
+    assert_eq!(pg_sql_of(&search), expected);
+}
+
+#[cfg(feature = "postgres")]
+#[test]
+fn test_soft_sample() {
+    use crate::sample::{sampled, SoftSample, SoftSampleDsl, TableSample};
+
+    let sample: SoftSample<post::table> = post::table.soft_sample(TableSample::system(10.0));
+    assert_eq!(
+        pg_sql_of(&sample.select(sampled(post::id))),
+        "SELECT \"post\".\"id\" FROM \"post\" TABLESAMPLE SYSTEM ($1) \
+         WHERE NOT (\"post\".\"deleted\") -- binds: [10.0]",
+    );
+    let sample = post::table
+        .soft_sample(TableSample::bernoulli(5.0).repeatable(42.0))
+        .filter(post::user_id.eq(1))
+        .select(sampled(post::title));
+    assert_eq!(
+        pg_sql_of(&sample),
+        "SELECT \"post\".\"title\" FROM \"post\" TABLESAMPLE BERNOULLI ($1) REPEATABLE ($2) \
+         WHERE NOT (\"post\".\"deleted\") AND \"post\".\"user_id\" = $3 \
+         -- binds: [5.0, 42.0, 1]",
+    );
+}
+
+#[cfg(feature = "postgres")]
+#[test]
+fn test_notification_payload() {
+    use crate::notify::notification_payload;
+
+    assert_eq!(
+        notification_payload("post", &42, "soft_delete"),
+        r#"{"table":"post","pk":"42","action":"soft_delete"}"#,
+    );
+    assert_eq!(
+        notification_payload("user", &"a\"b\\c\n", "restore"),
+        r#"{"table":"user","pk":"a\"b\\c\n","action":"restore"}"#,
+    );
+}
+
+#[test]
+fn test_restore_by_id() {
+    use crate::write::{RestoreByIdDsl, RestoreError};
+
+    let conn = conn();
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "Alive", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let post = post::table.restore_by_id::<Post, _>(2, &conn).unwrap();
+    assert_eq!((post.id, post.deleted), (2, false));
+    assert!(matches!(
+        post::table.restore_by_id::<Post, _>(1, &conn),
+        Err(RestoreError::AlreadyAlive)
+    ));
+    assert!(matches!(post::table.restore_by_id::<Post, _>(3, &conn), Err(RestoreError::NotFound)));
+}
+
+#[test]
+fn test_purge_by_id() {
+    use crate::write::{PurgeByIdDsl, PurgeError};
+
+    let conn = conn();
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "Alive", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    post::table.purge_by_id(2, &conn).unwrap();
+    assert!(matches!(post::table.purge_by_id(1, &conn), Err(PurgeError::Alive)));
+    assert!(matches!(post::table.purge_by_id(2, &conn), Err(PurgeError::NotFound)));
+
+    let ids = post::table.select(post::id).load::<i32>(&conn).unwrap();
+    assert_eq!(ids, [1]);
+}
+
+#[test]
+fn test_trash_bin() {
+    use crate::{
+        trash::TrashBin,
+        write::{PurgeError, RestoreError},
+    };
+
+    let conn = conn();
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "Alive", ..Default::default() },
+            NewPost { user_id: 1, title: "Bravo", deleted: Some(true) },
+            NewPost { user_id: 1, title: "Charlie", deleted: Some(true) },
+            NewPost { user_id: 1, title: "Alpha", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+    let bin = TrashBin::new(post::table);
+    let titles = |page: &[Post]| page.iter().map(|p| p.title.clone()).collect::<Vec<_>>();
+
+    let page = bin.list::<Post, _, _>(post::title.desc(), 1, 2, &conn).unwrap();
+    assert_eq!((page.total, page.pages()), (3, 2));
+    assert_eq!(titles(&page.rows), ["Charlie", "Bravo"]);
+    let page = bin.list::<Post, _, _>(post::title.desc(), 2, 2, &conn).unwrap();
+    assert_eq!(titles(&page.rows), ["Alpha"]);
+
+    let post = bin.restore::<_, Post, _>(2, &conn).unwrap();
+    assert_eq!((post.title.as_str(), post.deleted), ("Bravo", false));
+    assert!(matches!(bin.restore::<_, Post, _>(1, &conn), Err(RestoreError::AlreadyAlive)));
+    bin.purge(3, &conn).unwrap();
+    assert!(matches!(bin.purge(1, &conn), Err(PurgeError::Alive)));
+
+    let page = bin.list::<Post, _, _>(post::id.asc(), 1, 10, &conn).unwrap();
+    assert_eq!((page.total, titles(&page.rows)), (1, vec!["Alpha".to_owned()]));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_recently_deleted() {
+    use diesel::sqlite::Sqlite;
+
+    use crate::{
+        config::{SoftDeleteConfig, TableConfig},
+        trash::TrashEntry,
+    };
+
+    let config = SoftDeleteConfig::<Sqlite>::new()
+        .table(TableConfig::new(user::table).label(user::name))
+        .table(TableConfig::new(post::table))
+        .table(TableConfig::new(note::table).label(note::content));
+    let conn = conn();
+    fixtures::insert_trashed_user(&conn, "Ghost");
+    conn.batch_execute(
+        "insert into post(id, user_id, title, deleted) values (1, 1, 'Trashed', true);
+        insert into note(id, content, deleted_at) values
+            (1, 'Alive', null),
+            (2, 'Older', '2024-01-01 10:00:00'),
+            (3, 'Newer', '2024-02-01 10:00:00');",
+    )
+    .unwrap();
+    let entry = |table, id: &str, label: Option<&str>, deleted_at: Option<&str>| TrashEntry {
+        table,
+        id: id.to_owned(),
+        label: label.map(str::to_owned),
+        deleted_at: deleted_at.map(str::to_owned),
+    };
+
+    let page = config.recently_deleted::<String, _>(1, 3, &conn).unwrap();
+    assert_eq!((page.total, page.pages()), (4, 2));
+    assert_eq!(
+        page.rows,
+        [
+            entry("note", "3", Some("Newer"), Some("2024-02-01 10:00:00")),
+            entry("note", "2", Some("Older"), Some("2024-01-01 10:00:00")),
+            entry("post", "1", None, None),
+        ],
+    );
+    let page = config.recently_deleted::<String, _>(2, 3, &conn).unwrap();
+    assert_eq!(page.rows, [entry("user", "1", Some("Ghost"), None)]);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_archive() {
+    use std::time::Duration;
+
+    use diesel::sqlite::Sqlite;
+
+    use crate::{
+        archive::{archive_expired, unarchive, UnarchiveError},
+        config::{SoftDeleteConfig, TableConfig},
+    };
+
+    table! {
+        note_archive (id) {
+            id -> Integer,
+            content -> Text,
+            deleted_at -> Nullable<Timestamp>,
+        }
+    }
+
+    let day = Duration::from_secs(24 * 3600);
+    let config = SoftDeleteConfig::<Sqlite>::new()
+        .table(TableConfig::new(post::table).archive("post_archive", day))
+        .table(TableConfig::new(note::table).archive("note_archive", day));
+    let conn = conn();
+    conn.batch_execute(
+        "create table note_archive as select * from note where 0;
+        insert into note(id, content, deleted_at) values
+            (1, 'Alive', null),
+            (2, 'Recent', datetime('now', '-1 hours')),
+            (3, 'Expired', datetime('now', '-2 days'));",
+    )
+    .unwrap();
+
+    assert_eq!(archive_expired(&config, &conn).unwrap(), [("note", 1)]);
+    assert_eq!(note::table.select(note::id).order(note::id).load(&conn), Ok(vec![1, 2]));
+    let archived =
+        note_archive::table.select((note_archive::id, note_archive::content)).load(&conn);
+    assert_eq!(archived, Ok(vec![(3, "Expired".to_owned())]));
+    assert_eq!(archive_expired(&config, &conn).unwrap(), [("note", 0)]);
+
+    // A key reused after its archive is only archived once expired
+    conn.batch_execute(
+        "insert into note(id, content, deleted_at) values (3, 'Reused', datetime('now'))",
+    )
+    .unwrap();
+    assert_eq!(archive_expired(&config, &conn).unwrap(), [("note", 0)]);
+    assert_eq!(note::table.select(note::id).order(note::id).load(&conn), Ok(vec![1, 2, 3]));
+    conn.batch_execute("delete from note where id = 3").unwrap();
+
+    unarchive(&config, note::table, 3, &conn).unwrap();
+    let restored = note::table.soft_find(3).select(note::content).get_result(&conn);
+    assert_eq!(restored, Ok("Expired".to_owned()));
+    assert_eq!(note_archive::table.count().get_result(&conn), Ok(0));
+    assert!(matches!(unarchive(&config, note::table, 3, &conn), Err(UnarchiveError::NotFound)));
+    assert!(matches!(unarchive(&config, user::table, 1, &conn), Err(UnarchiveError::NotArchived)));
+
+    conn.batch_execute("insert into note_archive(id, content) values (1, 'Duplicate')").unwrap();
+    let conflict = unarchive(&config, note::table, 1, &conn);
+    assert!(matches!(conflict, Err(UnarchiveError::Conflict(_))), "{:?}", conflict);
+    assert_eq!(note_archive::table.count().get_result(&conn), Ok(1));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_with_archive() {
+    use crate::archive::with_archive;
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table note_archive as select * from note where 0;
+        insert into note(id, content, deleted_at) values
+            (1, 'Alive', null),
+            (2, 'Trashed', '2024-01-01 10:00:00');
+        insert into note_archive(id, content, deleted_at) values
+            (3, 'Archived', '2023-01-01 10:00:00'),
+            (4, 'Alive archived', null);",
+    )
+    .unwrap();
+    let ids = |mut ids: Vec<i32>| {
+        ids.sort_unstable();
+        ids
+    };
+
+    let union = with_archive(note::table, "note_archive").select(note::id);
+    assert_eq!(union.load(&conn).map(ids), Ok(vec![1, 2, 3, 4]));
+    assert_eq!(union.alive().load(&conn).map(ids), Ok(vec![1, 4]));
+    let search = union.only_deleted().filter(note::content.like("%ed")).filter(note::id.gt(2));
+    assert_eq!(
+        sql_of(&search),
+        "SELECT `note`.`id` FROM `note` WHERE `note`.`deleted_at` IS NOT NULL \
+         AND `note`.`content` LIKE ? AND `note`.`id` > ? UNION ALL \
+         SELECT `note`.`id` FROM `note_archive` AS `note` WHERE `note`.`deleted_at` IS NOT NULL \
+         AND `note`.`content` LIKE ? AND `note`.`id` > ? -- binds: [\"%ed\", 2, \"%ed\", 2]",
+    );
+    assert_eq!(search.load(&conn), Ok(vec![3]));
+}
+
+#[test]
+fn test_soft_combination() {
+    use diesel::dsl::Select;
+
+    use crate::{combination::SoftCombineDsl, dsl::SoftCombined};
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let jane = fixtures::insert_trashed_user(&conn, "Jane");
+    fixtures::insert_post(&conn, &joe, "Jane");
+    fixtures::insert_trashed_post(&conn, &joe, "Joe");
+    fixtures::insert_post(&conn, &joe, "Hello");
+    let names = |mut names: Vec<String>| {
+        names.sort_unstable();
+        names
+    };
+
+    let union: SoftCombined<Select<user::table, user::name>, Select<post::table, post::title>> =
+        user::table.select(user::name).soft_union(post::table.select(post::title));
+    assert_eq!(
+        sql_of(&union),
+        "SELECT `user`.`name` FROM `user` WHERE NOT (`user`.`deleted`) UNION \
+         SELECT `post`.`title` FROM `post` WHERE NOT (`post`.`deleted`) -- binds: []",
+    );
+    assert_eq!(union.load(&conn).map(names), Ok(vec!["Hello".into(), "Jane".into(), "Joe".into()]));
+    let union_all = user::table.select(user::name).soft_union_all(post::table.select(post::title));
+    assert_eq!(union_all.load::<String>(&conn).map(|names| names.len()), Ok(3));
+
+    let intersect = post::table.select(post::title).soft_intersect(user::table.select(user::name));
+    assert_eq!(intersect.load::<String>(&conn), Ok(vec![]));
+    let except = post::table
+        .select(post::title)
+        .soft_except(user::table.filter(user::id.eq(jane.id)).select(user::name))
+        .union(comment::table.select(comment::content));
+    assert_eq!(except.load(&conn).map(names), Ok(vec!["Hello".into(), "Jane".into()]));
+
+    let intersect = user::table
+        .select(user::name)
+        .soft_union(post::table.select(post::title))
+        .intersect(user::table.select(user::name));
+    let sql = sql_of(&intersect);
+    assert!(sql.starts_with("SELECT * FROM (SELECT `user`.`name` FROM `user`"), "{}", sql);
+    assert!(sql.contains(") AS `combined` INTERSECT SELECT `user`.`name`"), "{}", sql);
+    assert_eq!(intersect.load(&conn), Ok(vec!["Joe".to_owned()]));
+}
+
+#[test]
+fn test_trashed_between() {
+    let conn = conn();
+    diesel::insert_into(event::table)
+        .values(vec![
+            (event::name.eq("launch"), event::created_at.eq(10), event::deleted_at.eq(None)),
+            (event::name.eq("alpha"), event::created_at.eq(10), event::deleted_at.eq(Some(20))),
+            (event::name.eq("beta"), event::created_at.eq(10), event::deleted_at.eq(Some(30))),
+            (event::name.eq("gamma"), event::created_at.eq(10), event::deleted_at.eq(Some(40))),
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let query = event::table.trashed_between(20..40).order(event::deleted_at.desc());
+    assert_eq!(
+        sql_of(&query.select(event::name)),
+        "SELECT `event`.`name` FROM `event` WHERE `event`.`deleted_at` >= ? AND \
+         `event`.`deleted_at` < ? ORDER BY `event`.`deleted_at` DESC -- binds: [20, 40]",
+    );
+    let names = query.select(event::name).limit(1).offset(1).load::<String>(&conn).unwrap();
+    assert_eq!(names, ["alpha"]);
+    let names = query.select(event::name).load::<String>(&conn).unwrap();
+    assert_eq!(names, ["beta", "alpha"]);
+
+    let names = |query: diesel::dsl::Filter<event::table, _>| {
+        query.select(event::name).order(event::id).load::<String>(&conn).unwrap()
+    };
+    assert_eq!(names(event::table.trashed_between(30..)), ["beta", "gamma"]);
+    assert_eq!(names(event::table.trashed_between(..=30)), ["alpha", "beta"]);
+    assert_eq!(names(event::table.trashed_between(20..=20)), ["alpha"]);
+    assert_eq!(names(TrashedBetweenDsl::<i32>::trashed_between(event::table, ..)).len(), 3);
+}
+
+#[test]
+fn test_empty_trash() {
+    use crate::write::{empty_trash, ConfirmEmptyTrash, EmptyTrashError};
+
+    let conn = conn();
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "Alive", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+            NewPost { user_id: 1, title: "Trashed too", deleted: Some(true) },
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let confirm = ConfirmEmptyTrash::i_really_want_to_destroy_trashed_rows().max_rows(1);
+    assert!(matches!(
+        empty_trash(post::table, confirm, &conn),
+        Err(EmptyTrashError::TooManyRows { count: 2, max_rows: 1 })
+    ));
+    assert_eq!(post::table.count().get_result::<i64>(&conn), Ok(3));
+
+    let confirm = ConfirmEmptyTrash::i_really_want_to_destroy_trashed_rows().max_rows(2);
+    assert_eq!(empty_trash(post::table, confirm, &conn).unwrap(), 2);
+    let confirm = ConfirmEmptyTrash::i_really_want_to_destroy_trashed_rows();
+    assert_eq!(empty_trash(post::table, confirm, &conn).unwrap(), 0);
+    let titles = post::table.select(post::title).load::<String>(&conn).unwrap();
+    assert_eq!(titles, ["Alive"]);
+}
+
+#[test]
+fn test_oldest_trash() {
+    let conn = conn();
+    let query = event::table.oldest_trash();
+    assert_eq!(sql_of(&query), "SELECT min(`event`.`deleted_at`) FROM `event` -- binds: []",);
+    assert_eq!(query.get_result::<Option<i32>>(&conn), Ok(None));
+
+    diesel::insert_into(event::table)
+        .values(vec![
+            (event::name.eq("launch"), event::created_at.eq(10), event::deleted_at.eq(None)),
+            (event::name.eq("alpha"), event::created_at.eq(10), event::deleted_at.eq(Some(30))),
+            (event::name.eq("beta"), event::created_at.eq(10), event::deleted_at.eq(Some(20))),
+        ])
+        .execute(&conn)
+        .unwrap();
+    assert_eq!(query.get_result::<Option<i32>>(&conn), Ok(Some(20)));
+}
+
+#[test]
+fn test_soft_delete_stats() {
+    use std::time::{Duration, SystemTime};
+
+    use crate::stats::{TableStats, TrashRegistry, TrashThresholds, TrashViolation};
+
+    let conn = conn();
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }, NewUser { name: "Zoe" }])
+        .execute(&conn)
+        .unwrap();
+    diesel::update(user::table.find(3)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+    diesel::insert_into(event::table)
+        .values(vec![
+            (event::name.eq("launch"), event::created_at.eq(10), event::deleted_at.eq(None)),
+            (event::name.eq("alpha"), event::created_at.eq(10), event::deleted_at.eq(Some(30))),
+            (event::name.eq("beta"), event::created_at.eq(10), event::deleted_at.eq(Some(20))),
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let registry = TrashRegistry::<SqliteConnection, i32>::new()
+        .table(user::table)
+        .table(post::table)
+        .history_table(event::table);
+    let stats = registry.soft_delete_stats(&conn).unwrap();
+    assert_eq!(
+        stats,
+        [
+            TableStats { table: "user", alive: 2, trashed: 1, oldest_trashed: None },
+            TableStats { table: "post", alive: 0, trashed: 0, oldest_trashed: None },
+            TableStats { table: "event", alive: 1, trashed: 2, oldest_trashed: Some(20) },
+        ]
+    );
+    assert_eq!(stats[1].trash_ratio(), 0.);
+    assert!((stats[2].trash_ratio() - 2. / 3.).abs() < f64::EPSILON);
+
+    let thresholds = TrashThresholds::new().max_ratio(0.5).trashed_before(25);
+    let violations = registry.trash_violations(&thresholds, &conn).unwrap();
+    assert_eq!(
+        violations,
+        [
+            TrashViolation::Ratio { table: "event", ratio: 2. / 3., max_ratio: 0.5 },
+            TrashViolation::Age { table: "event", oldest_trashed: 20, trashed_before: 25 },
+        ]
+    );
+    assert_eq!(violations[0].to_string(), "67% of the rows of `event` are trashed, more than 50%");
+    let thresholds = TrashThresholds::new().max_ratio(0.3).trashed_before(20);
+    assert_eq!(registry.trash_violations(&thresholds, &conn).unwrap().len(), 2);
+    assert_eq!(registry.trash_violations(&TrashThresholds::new(), &conn), Ok(vec![]));
+
+    // The cutoff of a maximum age is computed at each check, not when building the thresholds.
+    let max_age = Duration::from_secs(3600);
+    let thresholds = TrashThresholds::new().max_age(max_age);
+    let oldest_trashed = SystemTime::now() - max_age + Duration::from_millis(20);
+    let stats =
+        [TableStats { table: "event", alive: 1, trashed: 1, oldest_trashed: Some(oldest_trashed) }];
+    assert_eq!(thresholds.violations(&stats), []);
+    std::thread::sleep(Duration::from_millis(40));
+    assert_eq!(thresholds.violations(&stats).len(), 1);
+    let unlimited = TrashThresholds::new().max_age(Duration::MAX);
+    assert_eq!(unlimited.violations(&stats), []);
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn test_trash_collector() {
+    use crate::{metrics::TrashCollector, stats::TrashRegistry};
+
+    let conn = conn();
+    diesel::insert_into(user::table)
+        .values(vec![NewUser { name: "Joe" }, NewUser { name: "Jack" }])
+        .execute(&conn)
+        .unwrap();
+    diesel::update(user::table.find(2)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let registry = TrashRegistry::new().table(user::table);
+    let collector = TrashCollector::new(registry, conn).unwrap();
+    let prometheus = prometheus::Registry::new();
+    prometheus.register(Box::new(collector)).unwrap();
+
+    let families = prometheus.gather();
+    let gauges: Vec<_> = families
+        .iter()
+        .flat_map(|family| {
+            family.get_metric().iter().map(move |metric| {
+                let label = metric.get_label()[0].get_value().to_owned();
+                (family.get_name(), label, metric.get_gauge().get_value())
+            })
+        })
+        .collect();
+    assert_eq!(
+        gauges,
+        [
+            ("softdelete_alive_rows", "user".to_owned(), 1.),
+            ("softdelete_trash_ratio", "user".to_owned(), 0.5),
+            ("softdelete_trashed_rows", "user".to_owned(), 1.),
+        ]
+    );
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing() {
+    use crate::write::PurgeByIdDsl;
+    use std::sync::{Arc, Mutex};
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    /// Records the fields of every event.
+    #[derive(Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for Recorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() != "duration" {
+                self.0.lock().unwrap().push(format!("{}={:?}", field.name(), value));
+            }
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+        fn new_span(&self, span: &span::Attributes) -> span::Id {
+            span.record(&mut Recorder(self.0.clone()));
+            span::Id::from_u64(1)
+        }
+        fn record(&self, _: &span::Id, _: &span::Record) {}
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+        fn event(&self, event: &Event) {
+            event.record(&mut Recorder(self.0.clone()));
+        }
+        fn enter(&self, _: &span::Id) {}
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    let conn = conn();
+    diesel::insert_into(user::table).values(NewUser { name: "Joe" }).execute(&conn).unwrap();
+    diesel::insert_into(post::table)
+        .values(NewPost { user_id: 1, title: "Trashed", deleted: Some(true) })
+        .execute(&conn)
+        .unwrap();
+
+    let recorder = Recorder::default();
+    let fields = recorder.0.clone();
+    tracing::subscriber::with_default(recorder, || {
+        user::table.soft_deleted().load::<User>(&conn).unwrap();
+        post::table.purge_by_id(1, &conn).unwrap();
+    });
+    assert_eq!(
+        *fields.lock().unwrap(),
+        [
+            "message=soft scope applied",
+            "operation=\"purge\"",
+            "table=\"post\"",
+            "message=purge executed",
+            "rows=1",
+        ]
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_explain_soft_ok() {
+    use crate::{explain::explain_soft, scoped::Scoped};
+
+    let conn = conn();
+    conn.batch_execute("create index post_alive_user on post(user_id) where not deleted").unwrap();
+
+    // The wording of the plans changes between SQLite versions, e.g. `SCAN TABLE post` before
+    // 3.36, and so do the indexes they pick for a full scan: only their keywords are checked.
+    let plan = explain_soft(post::table.soft_filter(post::user_id.eq(1)), false, &conn).unwrap();
+    assert!(plan.contains("post_alive_user"), "{}", plan);
+    let plan = explain_soft(Scoped::new(post::table), true, &conn).unwrap();
+    assert!(plan.contains("SCAN"), "{}", plan);
+}
+
+#[test]
+fn test_debug_soft_sql_ok() {
+    use crate::testing::debug_soft_sql;
+    use diesel::sqlite::Sqlite;
+
+    assert_eq!(
+        debug_soft_sql::<Sqlite, _>(&user::table.soft_find(1).select(user::name)),
+        "SELECT `user`.`name` FROM `user` WHERE NOT (`user`.`deleted`) AND `user`.`id` = ? -- binds: [1]",
+    );
+}
+
+#[test]
+fn test_assert_sql_eq_ok() {
+    use diesel::sqlite::Sqlite;
+
+    assert_sql_eq!(
+        Sqlite,
+        post::table.soft_filter(post::user_id.eq(1)).select(post::id),
+        "SELECT `post`.`id` FROM `post`
+         WHERE NOT (`post`.`deleted`) AND `post`.`user_id` = ?",
+    );
+    #[cfg(feature = "postgres")]
+    assert_sql_eq!(
+        diesel::pg::Pg,
+        post::table.soft_find(1).select(post::id),
+        r#"SELECT "post"."id" FROM "post" WHERE NOT ("post"."deleted") AND "post"."id" = ?"#,
+    );
+    assert_eq!(crate::testing::normalize_sql("a = $1 AND\n  b = $12"), "a = ? AND b = ?");
+}
+
+#[test]
+fn test_fixtures_ok() {
+    let conn = fixtures::conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let ghost = fixtures::insert_trashed_user(&conn, "Ghost");
+    let alive = fixtures::insert_post(&conn, &joe, "Alive");
+    fixtures::insert_trashed_post(&conn, &joe, "Trashed");
+    fixtures::insert_comment(&conn, &alive, "First");
+    let trashed = fixtures::insert_trashed_comment(&conn, &alive, "Second");
+
+    assert!(ghost.deleted && trashed.deleted);
+    assert_eq!((trashed.user_id, trashed.post_id), (joe.id, alive.id));
+    let users: Vec<User> = user::table.soft_deleted().load(&conn).unwrap();
+    assert_eq!(users, [joe]);
+    let titles: Vec<String> = post::table.soft_deleted().select(post::title).load(&conn).unwrap();
+    assert_eq!(titles, ["Alive"]);
+    let comments: Vec<Comment> = Comment::belonging_to(&alive).soft_deleted().load(&conn).unwrap();
+    assert_eq!(comments.len(), 1);
+}
+
+#[cfg(feature = "proptest")]
+proptest::proptest! {
+    #![proptest_config(proptest::prelude::ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn test_strategies_ok(dataset in crate::testing::strategies::dataset()) {
+        let conn = fixtures::conn();
+        let inserted = dataset.insert(&conn);
+
+        let posts: Vec<Post> = post::table.soft_inner_join(user::table)
+            .select(post::all_columns)
+            .soft_deleted()
+            .order(post::id)
+            .load(&conn)
+            .unwrap();
+        let expected: Vec<_> = inserted.posts.iter()
+            .filter(|p| !p.deleted && !inserted.users.iter().any(|u| u.id == p.user_id && u.deleted))
+            .map(|p| p.id)
+            .collect();
+        proptest::prop_assert_eq!(posts.iter().map(|p| p.id).collect::<Vec<_>>(), expected);
+        proptest::prop_assert!(posts.iter().all(|p| !p.deleted));
+    }
+}
+
+#[test]
+fn test_seed_ok() {
+    let conn = fixtures::conn();
+    let rows = fixtures::Seed::new()
+        .users(3, 2)
+        .posts_per_user(1, 1)
+        .comments_per_post(1, 1)
+        .insert(&conn);
+    assert_eq!((rows.users.len(), rows.posts.len(), rows.comments.len()), (5, 10, 20));
+    assert_eq!(
+        rows.users.iter().map(|u| (u.name.as_str(), u.deleted)).collect::<Vec<_>>(),
+        [("user0", false), ("user1", false), ("user2", false), ("user3", true), ("user4", true)],
+    );
+
+    let titles: Vec<String> = post::table
+        .soft_inner_join(user::table)
+        .select(post::title)
+        .soft_deleted()
+        .load(&conn)
+        .unwrap();
+    assert_eq!(titles, ["post0", "post2", "post4"]);
+    assert_eq!(comment::table.soft_deleted().count().get_result::<i64>(&conn).unwrap(), 10);
+}
+
+#[test]
+fn test_dsl_ok() {
+    use crate::dsl::{alive, trashed, SoftAll, SoftFind, Trashed};
+
+    fn named(id: i32) -> SoftFind<user::table, i32> {
+        user::table.soft_find(id)
+    }
+    fn alive_posts() -> SoftAll<post::table> {
+        alive(post::table)
+    }
+    fn trashed_posts() -> Trashed<post::table> {
+        trashed(post::table)
+    }
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    fixtures::insert_post(&conn, &joe, "Alive");
+    fixtures::insert_trashed_post(&conn, &joe, "Trashed");
+
+    assert_eq!(named(joe.id).first::<User>(&conn).unwrap(), joe);
+    let titles: Vec<String> = alive_posts().select(post::title).load(&conn).unwrap();
+    assert_eq!(titles, ["Alive"]);
+    let titles: Vec<String> = trashed_posts().select(post::title).load(&conn).unwrap();
+    assert_eq!(titles, ["Trashed"]);
+}
+
+#[test]
+fn test_helper_types_ok() {
+    use diesel::{dsl::Eq, sqlite::Sqlite};
+
+    use crate::helper_types::{
+        AsOf, OnlyDeleted, SoftFilterBoxed, SoftInnerJoinBoth, SoftLeftJoin, SoftSemiJoin, SoftSum,
+    };
+
+    struct Feed {
+        query: SoftLeftJoin<user::table, post::table>,
+    }
+    fn authors() -> SoftInnerJoinBoth<user::table, post::table> {
+        user::table.soft_inner_join_both(post::table)
+    }
+    fn named(name: &str) -> SoftFilterBoxed<'static, user::table, Eq<user::name, String>, Sqlite> {
+        user::table.soft_filter_boxed(user::name.eq(name.to_owned()))
+    }
+
+    let feed = Feed { query: user::table.soft_left_join(post::table) };
+    assert_eq!(sql_of(&feed.query), sql_of(&user::table.soft_left_join(post::table)));
+    assert_eq!(sql_of(&authors()), sql_of(&user::table.soft_inner_join_both(post::table)));
+    assert_eq!(
+        sql_of(&named("Joe")),
+        sql_of(&user::table.soft_filter(user::name.eq("Joe".to_owned()))),
+    );
+    let _: OnlyDeleted<post::table> = post::table.only_deleted();
+    let _: SoftSemiJoin<user::table, post::table> = user::table.soft_semi_join(post::table);
+    let _: SoftSum<post::table, post::id> = post::table.soft_sum(post::id);
+    let _: AsOf<event::table, i32> = event::table.as_of(10);
+}
+
+#[test]
+fn test_schema_qualified_ok() {
+    use schema::billing::invoice;
+
+    assert_eq!(
+        sql_of(&invoice::table.soft_find(1).select(invoice::id)),
+        "SELECT `billing`.`invoice`.`id` FROM `billing`.`invoice` \
+         WHERE NOT (`billing`.`invoice`.`deleted`) AND `billing`.`invoice`.`id` = ? -- binds: [1]",
+    );
+}
+
+#[test]
+fn test_soft_delete_cascade_ok() {
+    use crate::cascade::SoftCascade;
+
+    assert_eq!(<user::table as SoftCascade>::CHILDREN, ["post", "comment"]);
+    assert_eq!(<post::table as SoftCascade>::CHILDREN, ["comment"]);
+    let (_, comments) = user::table.children();
+    assert_eq!(
+        sql_of(&comments.soft_deleted().select(comment::id)),
+        "SELECT `comment`.`id` FROM `comment` WHERE NOT (`comment`.`deleted`) -- binds: []",
+    );
+}
+
+#[test]
+fn test_scope_ok() {
+    struct Published;
+    struct Tenant(i32);
+    scope!(article::table => { Published = article::published, Tenant(i32) = article::tenant_id });
+
+    let conn = conn();
+    fixtures::insert_user(&conn, "Joe");
+    conn.batch_execute(
+        "insert into article(id, user_id, tenant_id, published, deleted) values
+            (1, 1, 1, true, false), (2, 1, 1, false, false), (3, 1, 2, true, false),
+            (4, 1, 1, true, true)",
+    )
+    .unwrap();
+
+    let ids: Vec<i32> = article::table.scoped(Tenant(1)).select(article::id).load(&conn).unwrap();
+    assert_eq!(ids, [1, 2, 4]);
+    let ids: Vec<i32> = article::table
+        .scoped(Tenant(1))
+        .scoped(Published)
+        .scoped(NotDeleted)
+        .select(article::id)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(ids, [1]);
+    assert_eq!(
+        sql_of(&article::table.scoped(NotDeleted).scoped(Tenant(1)).select(article::id)),
+        "SELECT `article`.`id` FROM `article` \
+         WHERE NOT (`article`.`deleted`) AND `article`.`tenant_id` = ? -- binds: [1]",
+    );
+
+    let joined: Vec<i32> = user::table
+        .scoped_inner_join(article::table, Tenant(2))
+        .select(article::id)
+        .scoped(NotDeleted)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(joined, [3]);
+
+    let tenant = |id| (Tenant(id), NotDeleted);
+    let ids: Vec<i32> = article::table.scoped(tenant(1)).select(article::id).load(&conn).unwrap();
+    assert_eq!(ids, [1, 2]);
+    let ids: Vec<i32> = article::table
+        .scoped((Tenant(1), Published, NotDeleted))
+        .select(article::id)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(ids, [1]);
+    assert_eq!(
+        sql_of(&article::table.scoped(tenant(1)).select(article::id)),
+        "SELECT `article`.`id` FROM `article` \
+         WHERE `article`.`tenant_id` = ? AND NOT (`article`.`deleted`) -- binds: [1]",
+    );
+    let query = article::table.inner_join(user::table).select(article::id);
+    let ids: Vec<i32> = query.soft_scope_all_with(Tenant(1)).load(&conn).unwrap();
+    assert_eq!(ids, [1, 2]);
+    let query = user::table.inner_join(post::table).select(post::id);
+    assert_eq!(
+        sql_of(&query.soft_scope_all_with(NotDeleted)),
+        "SELECT `post`.`id` FROM (`user` INNER JOIN `post` ON `post`.`user_id` = `user`.`id`) \
+         WHERE NOT (`user`.`deleted`) AND NOT (`post`.`deleted`) AND NOT (`user`.`deleted`) \
+         -- binds: []",
+    );
+}
+
+#[test]
+fn test_soft_model_ok() {
+    soft_model!(User => user);
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    fixtures::insert_trashed_user(&conn, "Ghost");
+
+    assert_eq!(User::soft_all(&conn).unwrap(), [User::soft_find(&conn, joe.id).unwrap().unwrap()]);
+    assert_eq!(User::soft_find(&conn, 2).unwrap(), None);
+    assert_eq!(joe.soft_delete(&conn).unwrap(), 1);
+    assert_eq!(joe.soft_delete(&conn).unwrap(), 0);
+    assert_eq!(User::soft_find(&conn, joe.id).unwrap(), None);
+    assert!(!joe.is_deleted());
+    assert_eq!(joe.restore(&conn).unwrap(), 1);
+    assert_eq!(User::soft_find(&conn, joe.id).unwrap(), Some(joe));
+}
+
+#[cfg(feature = "postgres")]
+#[test]
+fn test_unique_alive_index() {
+    use crate::migration::unique_alive_index;
+    use schema::billing::invoice;
+
+    let index = unique_alive_index("post_user_title_alive_key", post::table)
+        .column(post::user_id)
+        .column(post::title);
+    assert_eq!(
+        index.create_sql().unwrap(),
+        "CREATE UNIQUE INDEX \"post_user_title_alive_key\" ON \"post\" (\"user_id\", \"title\") \
+         WHERE NOT (\"post\".\"deleted\")",
+    );
+    assert_eq!(index.drop_sql().unwrap(), "DROP INDEX \"post_user_title_alive_key\"");
+    assert_eq!(
+        pg_sql_of(&index.conflict_target().unwrap()),
+        "(\"user_id\", \"title\") WHERE NOT (\"post\".\"deleted\") -- binds: []",
+    );
+
+    assert_eq!(
+        unique_alive_index("note_content_alive_key", note::table)
+            .column(note::content)
+            .create_sql()
+            .unwrap(),
+        "CREATE UNIQUE INDEX \"note_content_alive_key\" ON \"note\" (\"content\") \
+         WHERE NOT (\"note\".\"deleted_at\" IS NOT NULL)",
+    );
+    assert_eq!(
+        unique_alive_index("invoice_void_alive_key", invoice::table)
+            .column(invoice::is_void)
+            .create_sql()
+            .unwrap(),
+        "CREATE UNIQUE INDEX \"invoice_void_alive_key\" ON \"billing\".\"invoice\" (\"is_void\") \
+         WHERE NOT (\"billing\".\"invoice\".\"deleted\")",
+    );
+    assert!(unique_alive_index("empty", post::table).create_sql().is_err());
+
+    table! {
+        account (id) {
+            id -> Integer,
+            delete_at -> Nullable<Timestamp>,
+        }
+    }
+
+    soft_delete!(account::table => scheduled(account::delete_at));
+
+    let index = unique_alive_index("account_id_alive_key", account::table).column(account::id);
+    assert!(index.create_sql().is_err());
+    assert!(index.conflict_target().is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_adopt_soft_delete() {
+    use diesel::{migration::Migration, sqlite::Sqlite};
+
+    use crate::{explain::explain_soft, migration::adopt_soft_delete};
+
+    table! {
+        legacy (id) {
+            id -> Integer,
+            title -> Text,
+            deleted -> Bool,
+            deleted_at -> Nullable<Timestamp>,
+        }
+    }
+    soft_delete!(legacy::table => (legacy::deleted));
+
+    let adoption = adopt_soft_delete("legacy")
+        .deleted_at("deleted_at")
+        .backfill("title = 'Archived'")
+        .index("legacy_title_alive_idx", &["title"]);
+    assert_eq!(
+        adoption.up_sql::<Sqlite>().unwrap(),
+        "ALTER TABLE `legacy` ADD COLUMN `deleted` BOOLEAN NOT NULL DEFAULT 0;\n\
+         ALTER TABLE `legacy` ADD COLUMN `deleted_at` TIMESTAMP;\n\
+         UPDATE `legacy` SET `deleted` = 1, `deleted_at` = CURRENT_TIMESTAMP \
+         WHERE title = 'Archived';\n\
+         CREATE INDEX `legacy_title_alive_idx` ON `legacy` (`title`) \
+         WHERE NOT (`legacy`.`deleted`);\n",
+    );
+    #[cfg(feature = "postgres")]
+    assert_eq!(
+        adopt_soft_delete("legacy")
+            .without_flag()
+            .deleted_at("deleted_at")
+            .deleted_by("deleted_by", "INTEGER")
+            .index("legacy_title_alive_idx", &["title"])
+            .up_sql::<diesel::pg::Pg>()
+            .unwrap(),
+        "ALTER TABLE \"legacy\" ADD COLUMN \"deleted_at\" TIMESTAMPTZ;\n\
+         ALTER TABLE \"legacy\" ADD COLUMN \"deleted_by\" INTEGER;\n\
+         CREATE INDEX \"legacy_title_alive_idx\" ON \"legacy\" (\"title\") \
+         WHERE NOT (\"legacy\".\"deleted_at\" IS NOT NULL);\n",
+    );
+    assert!(adopt_soft_delete("legacy").without_flag().up_sql::<Sqlite>().is_err());
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table legacy(id integer primary key, title text not null);
+        insert into legacy(id, title) values (1, 'Kept'), (2, 'Archived');",
+    )
+    .unwrap();
+    let migration = adoption.migration::<Sqlite>("20240101000000");
+    migration.run(&conn).unwrap();
+    let alive = legacy::table.soft_deleted().select(legacy::id).load::<i32>(&conn);
+    assert_eq!(alive, Ok(vec![1]));
+    let deleted_at =
+        legacy::table.find(2).select(legacy::deleted_at.is_not_null()).get_result(&conn);
+    assert_eq!(deleted_at, Ok(true));
+    let plan = explain_soft(legacy::table.soft_filter(legacy::title.eq("Kept")), false, &conn);
+    let plan = plan.unwrap();
+    assert!(plan.contains("legacy_title_alive_idx"), "{}", plan);
+
+    migration.revert(&conn).unwrap();
+    let columns = conn.batch_execute("select deleted from legacy");
+    assert!(columns.is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_index_linter() {
+    use diesel::sqlite::Sqlite;
+
+    use crate::migration::{IndexLinter, MissingIndex};
+
+    let conn = conn();
+    conn.batch_execute("create index post_alive_user on post(user_id) where not deleted").unwrap();
+    let linter =
+        IndexLinter::<Sqlite>::new().table(post::table).table(note::table).table(session::table);
+
+    let missing = linter.check(&conn).unwrap();
+    assert_eq!(
+        missing,
+        [MissingIndex {
+            table: "note",
+            column: "deleted_at".to_owned(),
+            suggestion: "CREATE INDEX `note_alive_idx` ON `note` (`id`) \
+                         WHERE NOT (`note`.`deleted_at` IS NOT NULL)"
+                .to_owned(),
+        }],
+    );
+    conn.batch_execute(&missing[0].suggestion).unwrap();
+    assert_eq!(linter.check(&conn), Ok(vec![]));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_validate_soft_delete_schema() {
+    use diesel::sqlite::Sqlite;
+
+    use crate::migration::{SchemaError, SchemaValidator};
+
+    table! {
+        drifted (id) {
+            id -> Integer,
+            deleted -> Bool,
+        }
+    }
+    table! {
+        renamed (id) {
+            id -> Integer,
+            deleted -> Bool,
+        }
+    }
+    table! {
+        ghost (id) {
+            id -> Integer,
+            deleted -> Bool,
+        }
+    }
+    soft_delete!(
+        drifted::table => (drifted::deleted),
+        renamed::table => (renamed::deleted),
+        ghost::table => (ghost::deleted),
+    );
+
+    let conn = conn();
+    let validator = SchemaValidator::<Sqlite>::new()
+        .table(post::table)
+        .table(note::table)
+        .table(session::table);
+    assert!(validator.validate_soft_delete_schema(&conn).is_ok());
+
+    conn.batch_execute(
+        "create table drifted(id integer primary key, deleted integer);
+        create table renamed(id integer primary key, removed bool not null default false);",
+    )
+    .unwrap();
+    let errors = validator
+        .table(drifted::table)
+        .table(renamed::table)
+        .table(ghost::table)
+        .validate_soft_delete_schema(&conn)
+        .unwrap_err();
+    assert_eq!(
+        errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        [
+            "soft-delete column `deleted` of table `drifted` should be a boolean, found INTEGER",
+            "soft-delete column `deleted` of table `drifted` should be NOT NULL, found nullable",
+            "soft-delete column `deleted` of table `drifted` should be false by default, found no \
+             default",
+            "soft-delete column `deleted` of table `renamed` does not exist",
+            "table `ghost` does not exist",
+        ],
+    );
+    assert!(matches!(errors[4], SchemaError::MissingTable { table: "ghost" }));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_soft_delete_config() {
+    use std::time::Duration;
+
+    use diesel::sqlite::Sqlite;
+
+    use crate::{
+        config::{DeletionKind, SoftDeleteConfig, TableConfig},
+        guard::GuardedConnection,
+        stats::{TableStats, TrashRegistry},
+        write::{purge_expired, AllowHardDelete},
+    };
+
+    let day = Duration::from_secs(24 * 3600);
+    let config = SoftDeleteConfig::<Sqlite>::new()
+        .table(TableConfig::new(user::table).cascades().hold())
+        .table(TableConfig::new(post::table).retention(day))
+        .table(TableConfig::new(note::table).retention(day))
+        .table(TableConfig::new(session::table));
+    let note_config = config.get("note").unwrap();
+    assert_eq!(note_config.kind(), DeletionKind::Timestamp);
+    assert_eq!(note_config.deleted_at(), Some("deleted_at"));
+    assert_eq!(config.get("session").unwrap().kind(), DeletionKind::Sentinel);
+    assert_eq!(config.get("user").unwrap().children(), ["post", "comment"]);
+    assert_eq!(config.get("post").unwrap().deleted_at(), None);
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    fixtures::insert_trashed_user(&conn, "Ghost");
+    conn.batch_execute(
+        "insert into note(id, content, deleted_at) values
+            (1, 'Alive', null),
+            (2, 'Recent', datetime('now', '-1 hours')),
+            (3, 'Expired', datetime('now', '-2 days'));",
+    )
+    .unwrap();
+
+    let stats = TrashRegistry::<SqliteConnection>::new().config(&config);
+    assert_eq!(
+        stats.soft_delete_stats(&conn).unwrap(),
+        [
+            TableStats { table: "user", alive: 1, trashed: 1, oldest_trashed: None },
+            TableStats { table: "post", alive: 0, trashed: 0, oldest_trashed: None },
+            TableStats { table: "note", alive: 1, trashed: 2, oldest_trashed: None },
+            TableStats { table: "session", alive: 0, trashed: 0, oldest_trashed: None },
+        ],
+    );
+
+    let purged = purge_expired(&config, AllowHardDelete::i_really_want_to_destroy_rows(), &conn);
+    assert_eq!(purged, Ok(vec![("note", 1)]));
+    let notes = note::table.select(note::id).order(note::id).load::<i32>(&conn);
+    assert_eq!(notes, Ok(vec![1, 2]));
+
+    let conn = GuardedConnection::new(conn).config(&config).strict();
+    assert!(note::table.load::<(i32, String, Option<String>)>(&conn).is_err());
+    assert!(user::table.soft_find(joe.id).first::<User>(&conn).is_ok());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_soft_delete_config_kinds() {
+    use diesel::sqlite::Sqlite;
+
+    use crate::{
+        config::{DeletionKind, SoftDeleteConfig, TableConfig},
+        guard::GuardedConnection,
+    };
+
+    table! {
+        account (id) {
+            id -> Integer,
+            delete_at -> Nullable<Timestamp>,
+        }
+    }
+    table! {
+        invitation (id) {
+            id -> Integer,
+            deleted -> Bool,
+            expires_at -> Nullable<Timestamp>,
+        }
+    }
+    table! {
+        user_profile (id) {
+            id -> Integer,
+            user_id -> Integer,
+        }
+    }
+    table! {
+        deletions (table_name, row_id) {
+            table_name -> Text,
+            row_id -> Integer,
+        }
+    }
+    table! {
+        ticket (id) {
+            id -> Integer,
+        }
+    }
+    soft_delete!(
+        account::table => scheduled(account::delete_at),
+        invitation::table => (invitation::deleted, expires = invitation::expires_at),
+        user_profile::table => inherit(user::table, user_profile::user_id),
+        ticket::table => recorded(deletions::table),
+    );
+
+    let config = SoftDeleteConfig::<Sqlite>::new()
+        .table(TableConfig::new(account::table))
+        .table(TableConfig::new(invitation::table))
+        .table(TableConfig::new(user_profile::table))
+        .table(TableConfig::new(ticket::table));
+    let kinds = config.tables().iter().map(|table| (table.kind(), table.column()));
+    assert_eq!(
+        kinds.collect::<Vec<_>>(),
+        [
+            (DeletionKind::Scheduled, "delete_at"),
+            (DeletionKind::Expiring { expires_at: "expires_at" }, "deleted"),
+            (DeletionKind::Inherited, "user_id"),
+            (DeletionKind::Recorded, "id"),
+        ],
+    );
+    assert_eq!(config.get("account").unwrap().deleted_at(), Some("delete_at"));
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table account(id integer primary key, delete_at timestamp);
+        create table invitation(
+            id integer primary key,
+            deleted bool not null default false,
+            expires_at timestamp
+        );
+        create table user_profile(id integer primary key, user_id integer not null);
+        create table deletions(table_name text not null, row_id integer not null);
+        create table ticket(id integer primary key);",
+    )
+    .unwrap();
+    assert!(config.validate_soft_delete_schema(&conn).is_ok());
+    let missing = config.missing_indexes(&conn).unwrap();
+    assert_eq!(
+        missing.iter().map(|missing| missing.suggestion.as_str()).collect::<Vec<_>>(),
+        [
+            "CREATE INDEX `account_alive_idx` ON `account` (`delete_at`)",
+            "CREATE INDEX `invitation_alive_idx` ON `invitation` (`deleted`)",
+            "CREATE INDEX `user_profile_alive_idx` ON `user_profile` (`user_id`)",
+        ],
+    );
+
+    // The key columns of the inherited and recorded tables do not scope their queries.
+    let conn = GuardedConnection::new(conn).config(&config).strict();
+    assert!(ticket::table.find(1).load::<(i32,)>(&conn).is_err());
+    assert_eq!(ticket::table.soft_find(1).load::<(i32,)>(&conn), Ok(vec![]));
+    assert!(user_profile::table.find(1).load::<(i32, i32)>(&conn).is_err());
+    assert_eq!(user_profile::table.soft_find(1).load::<(i32, i32)>(&conn), Ok(vec![]));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_expired_purge() {
+    use std::time::Duration;
+
+    use diesel::sqlite::Sqlite;
+
+    use crate::{
+        config::{SoftDeleteConfig, TableConfig},
+        write::{AllowHardDelete, ExpiredPurge},
+    };
+
+    let config = SoftDeleteConfig::<Sqlite>::new()
+        .table(TableConfig::new(note::table).retention(Duration::from_secs(24 * 3600)));
+    let conn = conn();
+    conn.batch_execute(
+        "insert into note(id, content, deleted_at) values
+            (1, 'Alive', null),
+            (2, 'Recent', datetime('now', '-1 hours')),
+            (3, 'Expired', datetime('now', '-2 days')),
+            (4, 'Expired', datetime('now', '-3 days')),
+            (5, 'Expired', datetime('now', '-4 days'));",
+    )
+    .unwrap();
+
+    let purged = ExpiredPurge::new(&config, AllowHardDelete::i_really_want_to_destroy_rows())
+        .batch_size(2)
+        .statement_timeout(Duration::from_secs(1))
+        .run(&conn)
+        .unwrap();
+    assert_eq!(purged, vec![("note", 3)]);
+    let notes = note::table.select(note::id).order(note::id).load::<i32>(&conn);
+    assert_eq!(notes, Ok(vec![1, 2]));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_erasure() {
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    use diesel::sqlite::Sqlite;
+
+    use crate::{
+        config::{SoftDeleteConfig, TableConfig},
+        erasure::{Erasure, ErasureTable},
+        hooks::{self, Action, WriteEvent},
+    };
+
+    table! {
+        customer (id) {
+            id -> Integer,
+            name -> Text,
+            email -> Nullable<Text>,
+            deleted -> Bool,
+            deleted_at -> Nullable<Timestamp>,
+        }
+    }
+
+    table! {
+        address (id) {
+            id -> Integer,
+            customer_id -> Integer,
+            line -> Text,
+            deleted_at -> Nullable<Timestamp>,
+        }
+    }
+
+    soft_delete!(customer::table => (customer::deleted), address::table => timestamp(address::deleted_at));
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table customer(
+            id integer primary key,
+            name text not null,
+            email text,
+            deleted boolean not null default false,
+            deleted_at timestamp
+        );
+        create table address(
+            id integer primary key,
+            customer_id integer not null,
+            line text not null,
+            deleted_at timestamp
+        );
+        insert into customer(id, name, email) values (1, 'Joe', 'joe@example.com'), (2, 'Jane', null);
+        insert into address(id, customer_id, line, deleted_at) values
+            (1, 1, '1 Main St', null),
+            (2, 1, '2 Old St', '2024-01-01 10:00:00'),
+            (3, 2, '3 Other St', null);",
+    )
+    .unwrap();
+    let window = Duration::from_secs(30 * 24 * 3600);
+    let config = SoftDeleteConfig::<Sqlite>::new()
+        .table(TableConfig::new(customer::table).deleted_at(customer::deleted_at).retention(window))
+        .table(TableConfig::new(address::table).retention(window));
+    let erasure = Erasure::new(&config)
+        .table(ErasureTable::new(customer::table, customer::id).scrub(customer::email))
+        .table(
+            ErasureTable::new(address::table, address::customer_id).scrub_with(address::line, "-"),
+        );
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let _hook = {
+        let events = events.clone();
+        hooks::install(move |event: &WriteEvent, _: &SqliteConnection| {
+            events.borrow_mut().push((event.table, event.action, event.keys.to_vec()));
+            Ok(())
+        })
+    };
+    assert_eq!(erasure.erase(1, &conn), Ok(vec![("customer", 1), ("address", 1)]));
+    let customers = customer::table
+        .select((
+            customer::id,
+            customer::email,
+            customer::deleted,
+            customer::deleted_at.is_not_null(),
+        ))
+        .order(customer::id)
+        .load::<(i32, Option<String>, bool, bool)>(&conn);
+    assert_eq!(customers, Ok(vec![(1, None, true, true), (2, None, false, false)]),);
+    let addresses = address::table
+        .select((address::line, address::deleted_at))
+        .order(address::id)
+        .load::<(String, Option<String>)>(&conn)
+        .unwrap();
+    assert_eq!(addresses[0].0, "-");
+    assert!(addresses[0].1.is_some());
+    assert_eq!(addresses[1], ("-".to_owned(), Some("2024-01-01 10:00:00".to_owned())));
+    assert_eq!(addresses[2], ("3 Other St".to_owned(), None));
+
+    assert_eq!(
+        *events.borrow(),
+        vec![
+            ("customer", Action::SoftDelete, vec!["1".to_owned()]),
+            ("address", Action::SoftDelete, vec!["1".to_owned()]),
+        ],
+    );
+}
+
+#[test]
+fn test_recorded_deletions() {
+    use std::{cell::RefCell, rc::Rc};
+
+    use diesel::sqlite::Sqlite;
+
+    use crate::{
+        deletions::{restore_recorded, soft_delete_recorded},
+        hooks::{self, Action, WriteEvent},
+    };
+
+    table! {
+        deletions (table_name, row_id) {
+            table_name -> Text,
+            row_id -> Integer,
+        }
+    }
+
+    table! {
+        ticket (id) {
+            id -> Integer,
+            title -> Text,
+        }
+    }
+
+    soft_delete!(ticket::table => recorded(deletions::table));
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table deletions(table_name text not null, row_id integer not null);
+        create table ticket(id integer primary key, title text not null);
+        insert into ticket(id, title) values (1, 'Bug'), (2, 'Feature');",
+    )
+    .unwrap();
+    assert_sql_eq!(
+        Sqlite,
+        ticket::table.soft_find(1).select(ticket::id),
+        "SELECT `ticket`.`id` FROM `ticket`
+         WHERE NOT (EXISTS (SELECT 1 FROM `deletions` WHERE `deletions`.`table_name` = 'ticket'
+         AND `deletions`.`row_id` = `ticket`.`id`)) AND `ticket`.`id` = ?",
+    );
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let _hook = {
+        let events = events.clone();
+        hooks::install(move |event: &WriteEvent, _: &SqliteConnection| {
+            events.borrow_mut().push((event.table, event.action, event.keys.to_vec()));
+            Ok(())
+        })
+    };
+    assert_eq!(soft_delete_recorded(ticket::table, 1, &conn), Ok(1));
+    assert_eq!(soft_delete_recorded(ticket::table, 1, &conn), Ok(0));
+    let alive = || ticket::table.soft_deleted().select(ticket::id).load::<i32>(&conn);
+    assert_eq!(alive(), Ok(vec![2]));
+    assert_eq!(ticket::table.select(ticket::id).only_deleted().load::<i32>(&conn), Ok(vec![1]));
+
+    assert_eq!(restore_recorded(ticket::table, 1, &conn), Ok(1));
+    assert_eq!(alive(), Ok(vec![1, 2]));
+    assert_eq!(
+        *events.borrow(),
+        vec![
+            ("ticket", Action::SoftDelete, vec!["1".to_owned()]),
+            ("ticket", Action::SoftDelete, vec![]),
+            ("ticket", Action::Restore, vec!["1".to_owned()]),
+        ],
+    );
+}
+
+#[test]
+fn test_two_phase_restore() {
+    use crate::approval::{
+        apply_pending_restores, pending_restores, reject_restore, request_restore,
+    };
+
+    table! {
+        ticket (id) {
+            id -> Integer,
+            deleted -> Bool,
+            restore_requested -> Bool,
+        }
+    }
+
+    soft_delete!(ticket);
+    restore_requests!(ticket);
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table ticket(
+            id integer primary key,
+            deleted bool not null,
+            restore_requested bool not null default false
+        );
+        insert into ticket(id, deleted) values (1, false), (2, true), (3, true), (4, true);",
+    )
+    .unwrap();
+
+    assert_eq!(request_restore(ticket::table.find(1)).execute(&conn), Ok(0));
+    assert_eq!(request_restore(ticket::table.filter(ticket::id.gt(1))).execute(&conn), Ok(3));
+    assert_eq!(reject_restore(ticket::table.find(4)).execute(&conn), Ok(1));
+    let pending = || pending_restores(ticket::table.select(ticket::id)).load::<i32>(&conn);
+    assert_eq!(pending(), Ok(vec![2, 3]));
+    assert_eq!(ticket::table.soft_deleted().select(ticket::id).load::<i32>(&conn), Ok(vec![1]));
+
+    assert_eq!(apply_pending_restores(ticket::table.find(2)).execute(&conn), Ok(1));
+    assert_eq!(pending(), Ok(vec![3]));
+    assert_eq!(apply_pending_restores(ticket::table).execute(&conn), Ok(1));
+    assert_eq!(pending(), Ok(vec![]));
+    let alive = ticket::table.soft_deleted().select(ticket::id).load::<i32>(&conn);
+    assert_eq!(alive, Ok(vec![1, 2, 3]));
+    let requested = ticket::table.filter(ticket::restore_requested).count().get_result(&conn);
+    assert_eq!(requested, Ok(0i64));
+}
+
+#[test]
+fn test_scheduled_soft_delete() {
+    use diesel::{
+        dsl::sql,
+        sql_types::{Nullable, Timestamp},
+    };
+
+    use crate::{
+        schedule::{cancel_scheduled_delete, schedule_soft_delete, scheduled_deletions},
+        write::{restore, soft_delete},
+    };
+
+    table! {
+        account (id) {
+            id -> Integer,
+            delete_at -> Nullable<Timestamp>,
+        }
+    }
+
+    soft_delete!(account::table => scheduled(account::delete_at));
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table account(id integer primary key, delete_at timestamp);
+        insert into account(id, delete_at) values
+            (1, null),
+            (2, datetime('now', '-1 day')),
+            (3, datetime('now', '+1 day')),
+            (4, null);",
+    )
+    .unwrap();
+    let alive = || account::table.soft_deleted().select(account::id).load::<i32>(&conn);
+    let scheduled = || scheduled_deletions(account::table.select(account::id)).load::<i32>(&conn);
+
+    assert_eq!(
+        sql_of(&account::table.soft_deleted().select(account::id)),
+        "SELECT `account`.`id` FROM `account` WHERE NOT (`account`.`delete_at` IS NOT NULL \
+         AND `account`.`delete_at` <= CURRENT_TIMESTAMP) -- binds: []",
+    );
+    assert_eq!(alive(), Ok(vec![1, 3, 4]));
+    assert_eq!(scheduled(), Ok(vec![3]));
+
+    let in_a_month = || sql::<Nullable<Timestamp>>("datetime('now', '+30 days')");
+    assert_eq!(schedule_soft_delete(account::table.find(1), in_a_month()).execute(&conn), Ok(1));
+    assert_eq!(schedule_soft_delete(account::table.find(2), in_a_month()).execute(&conn), Ok(0));
+    assert_eq!(scheduled(), Ok(vec![1, 3]));
+    assert_eq!(alive(), Ok(vec![1, 3, 4]));
+
+    assert_eq!(cancel_scheduled_delete(account::table).execute(&conn), Ok(2));
+    assert_eq!(scheduled(), Ok(vec![]));
+    assert_eq!(soft_delete(account::table.find(4)).execute(&conn), Ok(1));
+    assert_eq!(alive(), Ok(vec![1, 3]));
+    assert_eq!(restore(account::table).execute(&conn), Ok(2));
+    assert_eq!(alive(), Ok(vec![1, 2, 3, 4]));
+}
+
+#[test]
+fn test_expiring_soft_delete() {
+    use crate::{
+        approval::{apply_pending_restores, pending_restores, request_restore},
+        expiry::{expired, purge_past_expiry},
+        write::{restore, soft_delete, UpsertAliveDsl, Upserted},
+    };
+
+    table! {
+        invitation (id) {
+            id -> Integer,
+            code -> Text,
+            deleted -> Bool,
+            expires_at -> Nullable<Timestamp>,
+            restore_requested -> Bool,
+        }
+    }
+
+    soft_delete!(invitation::table => (invitation::deleted, expires = invitation::expires_at));
+    restore_requests!(invitation);
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table invitation(
+            id integer primary key,
+            code text not null default '',
+            deleted bool not null default false,
+            expires_at timestamp,
+            restore_requested bool not null default false
+        );
+        insert into invitation(id, deleted, expires_at) values
+            (1, false, null),
+            (2, false, datetime('now', '-1 hour')),
+            (3, false, datetime('now', '+1 hour')),
+            (4, true, datetime('now', '+1 hour')),
+            (5, true, datetime('now', '-1 hour'));",
+    )
+    .unwrap();
+    let alive = || invitation::table.soft_deleted().select(invitation::id).load::<i32>(&conn);
+
+    assert_eq!(
+        sql_of(&invitation::table.soft_deleted().select(invitation::id)),
+        "SELECT `invitation`.`id` FROM `invitation` WHERE NOT ((`invitation`.`deleted` \
+         OR `invitation`.`expires_at` IS NOT NULL \
+         AND `invitation`.`expires_at` <= CURRENT_TIMESTAMP)) -- binds: []",
+    );
+    assert_eq!(alive(), Ok(vec![1, 3]));
+    let expired_ids = expired(invitation::table.select(invitation::id)).load::<i32>(&conn);
+    assert_eq!(expired_ids, Ok(vec![2, 5]));
+
+    assert_eq!(soft_delete(invitation::table).execute(&conn), Ok(2));
+    let restore_sql = sql_of(&restore(invitation::table));
+    assert!(
+        restore_sql.starts_with(
+            "UPDATE `invitation` SET `deleted` = ? WHERE `invitation`.`deleted` \
+             AND NOT (`invitation`.`expires_at` IS NOT NULL \
+             AND `invitation`.`expires_at` <= CURRENT_TIMESTAMP)",
+        ),
+        "{}",
+        restore_sql,
+    );
+    assert_eq!(restore(invitation::table.filter(invitation::id.le(2))).execute(&conn), Ok(1));
+    assert_eq!(alive(), Ok(vec![1]));
+    assert_eq!(restore(invitation::table).execute(&conn), Ok(2));
+    assert_eq!(alive(), Ok(vec![1, 3, 4]));
+
+    assert_eq!(purge_past_expiry(invitation::table).execute(&conn), Ok(2));
+    let ids = invitation::table.select(invitation::id).load::<i32>(&conn);
+    assert_eq!(ids, Ok(vec![1, 3, 4]));
+
+    // An expired row is neither restored by an upsert nor by an approved restore request.
+    conn.batch_execute(
+        "insert into invitation(id, code, deleted, expires_at)
+            values (6, 'abc', true, datetime('now', '-1 hour'));",
+    )
+    .unwrap();
+    let code = invitation::code.eq("abc");
+    let upserted = invitation::table.upsert_alive::<i32, _, _, _, _>(code, code, code, &conn);
+    assert_eq!(upserted, Ok(Upserted::Inserted));
+    assert_eq!(request_restore(invitation::table.find(6)).execute(&conn), Ok(1));
+    assert_eq!(apply_pending_restores(invitation::table).execute(&conn), Ok(0));
+    let pending = pending_restores(invitation::table.select(invitation::id)).load::<i32>(&conn);
+    assert_eq!(pending, Ok(vec![6]));
+}
+
+#[test]
+fn test_inherited_soft_delete() {
+    use diesel::sqlite::Sqlite;
+
+    use crate::orphans::find_orphans;
+
+    table! {
+        user_profile (id) {
+            id -> Integer,
+            user_id -> Integer,
+            bio -> Text,
+        }
+    }
+
+    joinable!(user_profile -> user (user_id));
+    soft_delete!(user_profile::table => inherit(user::table, user_profile::user_id));
+
+    let conn = conn();
+    conn.batch_execute(
+        "create table user_profile(id integer primary key, user_id integer not null, bio text not null);",
+    )
+    .unwrap();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let jane = fixtures::insert_user(&conn, "Jane");
+    diesel::insert_into(user_profile::table)
+        .values(&vec![
+            (user_profile::user_id.eq(joe.id), user_profile::bio.eq("Hi")),
+            (user_profile::user_id.eq(jane.id), user_profile::bio.eq("Hello")),
+        ])
+        .execute(&conn)
+        .unwrap();
+    assert_sql_eq!(
+        Sqlite,
+        user_profile::table.soft_deleted().select(user_profile::bio),
+        "SELECT `user_profile`.`bio` FROM `user_profile`
+         WHERE NOT (EXISTS (SELECT 1 FROM `user` WHERE `user`.`id` = `user_profile`.`user_id`
+         AND `user`.`deleted`))",
+    );
+
+    diesel::update(user::table.find(joe.id)).set(user::deleted.eq(true)).execute(&conn).unwrap();
+    let bios = user_profile::table.soft_deleted().select(user_profile::bio).load::<String>(&conn);
+    assert_eq!(bios, Ok(vec!["Hello".to_owned()]));
+    let bios = user_profile::table.only_deleted().select(user_profile::bio).load::<String>(&conn);
+    assert_eq!(bios, Ok(vec!["Hi".to_owned()]));
+    // The profiles of the trashed users are trashed along with them, never orphaned.
+    let orphans = find_orphans(user_profile::table, user::table, &conn).unwrap();
+    assert!(orphans.ids.is_empty());
+}
+
+#[test]
+fn test_soft_delete_view() {
+    table! {
+        post_summary (id) {
+            id -> Integer,
+            author -> Text,
+            title -> Text,
+            deleted -> Bool,
+        }
+    }
+
+    soft_delete!(post_summary::table => view(post_summary::deleted));
+
+    let conn = conn();
+    conn.batch_execute(
+        "create view post_summary as
+            select post.id, user.name as author, post.title, post.deleted
+            from post inner join user on user.id = post.user_id",
+    )
+    .unwrap();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    fixtures::insert_post(&conn, &joe, "Hello");
+    fixtures::insert_trashed_post(&conn, &joe, "Bye");
+
+    let titles = post_summary::table
+        .soft_filter(post_summary::author.eq("Joe"))
+        .select(post_summary::title)
+        .load::<String>(&conn);
+    assert_eq!(titles, Ok(vec!["Hello".to_owned()]));
+    let titles = post_summary::table.only_deleted().select(post_summary::title).load(&conn);
+    assert_eq!(titles, Ok(vec!["Bye".to_owned()]));
+}
+
+#[test]
+fn test_soft_cte() {
+    use diesel::sql_types::Text;
+
+    use crate::cte::soft_cte;
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    fixtures::insert_post(&conn, &joe, "A");
+    fixtures::insert_post(&conn, &joe, "B");
+    fixtures::insert_trashed_post(&conn, &joe, "C");
+
+    let latest =
+        soft_cte("latest", post::table.select(post::title).order(post::id.desc()).limit(2))
+            .query::<Text>("SELECT title FROM latest ORDER BY title");
+    assert_eq!(
+        sql_of(&latest),
+        "WITH `latest` AS (SELECT `post`.`title` FROM `post` WHERE NOT (`post`.`deleted`) \
+         ORDER BY `post`.`id` DESC LIMIT ?) SELECT title FROM latest ORDER BY title -- binds: [2]",
+    );
+    assert_eq!(latest.load::<String>(&conn), Ok(vec!["A".to_owned(), "B".to_owned()]));
+}
+
+#[test]
+fn test_orphans() {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        hooks::{self, Action, WriteEvent},
+        orphans::{cascade_orphans, find_orphans, OrphanRepair, Orphans},
+    };
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let jane = fixtures::insert_user(&conn, "Jane");
+    let hello = fixtures::insert_post(&conn, &joe, "Hello");
+    fixtures::insert_trashed_post(&conn, &joe, "Bye");
+    let world = fixtures::insert_post(&conn, &jane, "World");
+    let nice = fixtures::insert_comment(&conn, &hello, "Nice");
+    fixtures::insert_comment(&conn, &world, "Great");
+    diesel::update(&joe).set(user::deleted.eq(true)).execute(&conn).unwrap();
+
+    let orphans = find_orphans(post::table, user::table, &conn);
+    let expected = |child, ids: Vec<i32>| Orphans {
+        parent: "user",
+        child,
+        ids: ids.iter().map(ToString::to_string).collect(),
+    };
+    assert_eq!(orphans, Ok(expected("post", vec![hello.id])));
+    assert_eq!(
+        cascade_orphans(user::table, &conn),
+        Ok(vec![expected("post", vec![hello.id]), expected("comment", vec![nice.id])]),
+    );
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let hook = {
+        let events = events.clone();
+        hooks::install(move |event: &WriteEvent, _: &SqliteConnection| {
+            events.borrow_mut().push((event.table, event.action, event.keys.to_vec()));
+            Ok(())
+        })
+    };
+    let repair = OrphanRepair::new(post::table, user::table).batch_size(1);
+    let report = repair.dry_run(true).soft_delete(&conn).unwrap();
+    assert_eq!(report.to_string(), "post -> user: 1 orphans, dry run");
+    assert_eq!(find_orphans(post::table, user::table, &conn), Ok(expected("post", vec![hello.id])));
+    let report = repair.soft_delete(&conn).unwrap();
+    assert_eq!(report.to_string(), "post -> user: 1 orphans, 1 repaired in 1 batches");
+    assert_eq!(find_orphans(post::table, user::table, &conn), Ok(expected("post", vec![])));
+    drop(hook);
+    assert_eq!(*events.borrow(), vec![("post", Action::SoftDelete, vec![hello.id.to_string()])]);
+
+    // Reassigned to a trashed user, the comment stays an orphan: the repair stops.
+    let report = OrphanRepair::new(comment::table, user::table)
+        .batch_size(1)
+        .reassign(comment::user_id.eq(joe.id), &conn)
+        .unwrap();
+    assert_eq!(report.to_string(), "comment -> user: 1 orphans, 0 repaired in 0 batches");
+    let report = OrphanRepair::new(comment::table, user::table)
+        .reassign(comment::user_id.eq(jane.id), &conn)
+        .unwrap();
+    assert_eq!((report.found, report.repaired), (1, 1));
+    let comment = comment::table.find(nice.id).first::<Comment>(&conn).unwrap();
+    assert_eq!((comment.user_id, comment.deleted), (jane.id, false));
+}
+
+#[test]
+fn test_alive_duplicates() {
+    use crate::uniqueness::{alive_duplicates, AliveDuplicate};
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let jane = fixtures::insert_user(&conn, "Jane");
+    fixtures::insert_post(&conn, &joe, "Hello");
+    fixtures::insert_post(&conn, &joe, "Hello");
+    fixtures::insert_post(&conn, &jane, "Hello");
+    fixtures::insert_post(&conn, &jane, "Bye");
+    fixtures::insert_trashed_post(&conn, &jane, "Bye");
+
+    let duplicates = alive_duplicates(post::table, post::title, &conn);
+    assert_eq!(duplicates, Ok(vec![AliveDuplicate { key: "Hello".to_owned(), count: 3 }]));
+    let duplicates = alive_duplicates(post::table, (post::user_id, post::title), &conn);
+    assert_eq!(
+        duplicates,
+        Ok(vec![AliveDuplicate { key: (joe.id, "Hello".to_owned()), count: 2 }])
+    );
+}
+
+#[test]
+fn test_newtype_keys() {
+    use diesel::{
+        deserialize::{self, FromSql},
+        serialize::{self, Output, ToSql},
+        sql_types::Integer,
+        sqlite::Sqlite,
+    };
+
+    #[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow)]
+    #[sql_type = "Integer"]
+    struct PostId(i32);
+
+    impl ToSql<Integer, Sqlite> for PostId {
+        fn to_sql<W: std::io::Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+            ToSql::<Integer, Sqlite>::to_sql(&self.0, out)
+        }
+    }
+
+    impl FromSql<Integer, Sqlite> for PostId {
+        fn from_sql(
+            bytes: Option<&<Sqlite as diesel::backend::Backend>::RawValue>,
+        ) -> deserialize::Result<Self> {
+            <i32 as FromSql<Integer, Sqlite>>::from_sql(bytes).map(PostId)
+        }
+    }
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let hello = PostId(fixtures::insert_post(&conn, &joe, "Hello").id);
+    let bye = PostId(fixtures::insert_trashed_post(&conn, &joe, "Bye").id);
+    let world = PostId(fixtures::insert_post(&conn, &joe, "World").id);
+
+    let id = post::table.soft_find(hello).select(post::id).first::<PostId>(&conn);
+    assert_eq!(id, Ok(hello));
+    assert_eq!(
+        post::table.soft_find(bye).select(post::id).first::<PostId>(&conn).optional(),
+        Ok(None)
+    );
+    let ids = post::table.soft_find_many(vec![hello, bye, world]).select(post::id).order(post::id);
+    assert_eq!(ids.load::<PostId>(&conn), Ok(vec![hello, world]));
+}
+
+#[test]
+fn test_custom_soft_join_to() {
+    use diesel::{sqlite::Sqlite, JoinTo};
+
+    use crate::query_dsl::SoftJoinTo;
+
+    table! {
+        alive_post (id) {
+            id -> Integer,
+            user_id -> Integer,
+            title -> Text,
+            deleted -> Bool,
+        }
+    }
+
+    joinable!(alive_post -> user (user_id));
+    allow_tables_to_appear_in_same_query!(alive_post, user);
+
+    /// A view of the alive posts, which needs no alive condition when joined.
+    impl SoftDelete for alive_post::table {
+        type Deleted = alive_post::deleted;
+        fn deleted_col(&self) -> Self::Deleted {
+            alive_post::deleted
+        }
+    }
+
+    impl SoftJoinTo<alive_post::table> for user::table {
+        type SoftOnClause = <Self as JoinTo<alive_post::table>>::OnClause;
+        fn soft_join_target(rhs: alive_post::table) -> (alive_post::table, Self::SoftOnClause) {
+            Self::join_target(rhs)
+        }
+    }
+
+    assert_sql_eq!(
+        Sqlite,
+        user::table.soft_inner_join(alive_post::table).select(alive_post::title),
+        "SELECT `alive_post`.`title` FROM (`user` INNER JOIN `alive_post`
+         ON `alive_post`.`user_id` = `user`.`id`)",
+    );
+}
+
+#[test]
+fn test_soft_join_grouped_on_clause() {
+    use diesel::{dsl, sqlite::Sqlite, JoinTo};
+
+    table! {
+        mentorship (id) {
+            id -> Integer,
+            mentor_id -> Integer,
+            mentee_id -> Integer,
+            deleted -> Bool,
+        }
+    }
+
+    allow_tables_to_appear_in_same_query!(mentorship, user);
+    soft_delete!(mentorship);
+
+    impl JoinTo<mentorship::table> for user::table {
+        type FromClause = mentorship::table;
+        type OnClause = dsl::Or<
+            dsl::Eq<mentorship::mentor_id, user::id>,
+            dsl::Eq<mentorship::mentee_id, user::id>,
+        >;
+        fn join_target(rhs: mentorship::table) -> (Self::FromClause, Self::OnClause) {
+            (rhs, mentorship::mentor_id.eq(user::id).or(mentorship::mentee_id.eq(user::id)))
+        }
+    }
+
+    assert_sql_eq!(
+        Sqlite,
+        user::table.soft_inner_join(mentorship::table).select(mentorship::id),
+        "SELECT `mentorship`.`id` FROM (`user` INNER JOIN `mentorship`
+         ON (`mentorship`.`mentor_id` = `user`.`id` OR `mentorship`.`mentee_id` = `user`.`id`)
+         AND NOT (`mentorship`.`deleted`))",
+    );
+}
+
+#[test]
+fn test_upsert_alive() {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use diesel::sql_types::Integer;
+
+    use crate::write::{UpsertAliveDsl, Upserted};
+
+    let conn = conn();
+    let joe = fixtures::insert_user(&conn, "Joe");
+    let jane = fixtures::insert_user(&conn, "Jane");
+    let upsert = |title: &str, user_id: i32| {
+        post::table.upsert_alive::<i32, _, _, _, _>(
+            post::title.eq(title),
+            NewPost { user_id, title, ..Default::default() },
+            post::user_id.eq(user_id),
+            &conn,
+        )
+    };
+    let posts = || {
+        post::table.select((post::id, post::user_id, post::title, post::deleted)).order(post::id)
+    };
+
+    assert_eq!(upsert("Hello", joe.id), Ok(Upserted::Inserted));
+    assert_eq!(upsert("Hello", jane.id), Ok(Upserted::Updated));
+    assert_eq!(posts().load(&conn), Ok(vec![(1, jane.id, "Hello".to_owned(), false)]));
+
+    fixtures::insert_trashed_post(&conn, &joe, "Bye");
+    fixtures::insert_trashed_post(&conn, &joe, "Bye");
+    assert_eq!(upsert("Bye", jane.id), Ok(Upserted::Restored));
+    assert_eq!(upsert("Bye", joe.id), Ok(Upserted::Updated));
+    assert_eq!(
+        posts().filter(post::title.eq("Bye")).load(&conn),
+        Ok(vec![(2, joe.id, "Bye".to_owned(), true), (3, joe.id, "Bye".to_owned(), false)]),
+    );
+
+    // A post inserted concurrently, after the lookups, violates the unique key on the alive rows:
+    // the functions below make the key match it only once the insert was attempted.
+    sql_function!(fn inserting(user_id: Integer) -> Integer);
+    sql_function!(fn inserted(user_id: Integer) -> Bool);
+    let attempted = Arc::new(AtomicBool::new(false));
+    let flag = attempted.clone();
+    inserting::register_nondeterministic_impl(&conn, move |user_id: i32| {
+        flag.store(true, Ordering::SeqCst);
+        user_id
+    })
+    .unwrap();
+    inserted::register_nondeterministic_impl(&conn, move |_: i32| attempted.load(Ordering::SeqCst))
+        .unwrap();
+    conn.batch_execute("create unique index post_title_alive on post(title) where not deleted")
+        .unwrap();
+    let upserted = post::table.upsert_alive::<i32, _, _, _, _>(
+        post::title.eq("Hello").and(inserted(0)),
+        (post::user_id.eq(inserting(jane.id)), post::title.eq("Hello")),
+        post::user_id.eq(joe.id),
+        &conn,
+    );
+    assert_eq!(upserted, Ok(Upserted::Updated));
+    assert_eq!(
+        posts().filter(post::title.eq("Hello")).load(&conn),
+        Ok(vec![(1, joe.id, "Hello".to_owned(), false)]),
+    );
+}
+
+#[cfg(feature = "codegen")]
+#[test]
+fn test_schema_scanner() {
+    use crate::codegen::{CodegenError, SchemaScanner};
+
+    let schema = r#"
+        table! {
+            user (id) {
+                id -> Integer,
+                name -> Text,
+                deleted -> Bool,
+            }
+        }
+
+        table! {
+            post (id) {
+                id -> Integer,
+                user_id -> Integer,
+                #[sql_name = "removed_at"]
+                deleted_at -> Nullable<Timestamp>,
+            }
+        }
+
+        table! {
+            tag (id) {
+                id -> Integer,
+            }
+        }
+
+        pub mod billing {
+            table! {
+                billing.invoice (id) {
+                    id -> Integer,
+                    user_id -> Integer,
+                    is_deleted -> diesel::sql_types::Bool,
+                }
+            }
+        }
+
+        joinable!(post -> user (user_id));
+        joinable!(invoice -> user (user_id));
+        joinable!(post -> tag (tag_id));
+    "#;
+
+    assert_eq!(
+        SchemaScanner::new().cascades(true).generate(schema).unwrap(),
+        "// @generated by diesel-softdelete, do not edit.\n\
+         \n\
+         use crate::schema::user;\n\
+         use crate::schema::post;\n\
+         use crate::schema::billing::invoice;\n\
+         \n\
+         diesel_softdelete::soft_delete!(\n    \
+             user::table => (user::deleted),\n    \
+             post::table => timestamp(post::deleted_at),\n    \
+             invoice::table => (invoice::is_deleted),\n\
+         );\n\
+         \n\
+         diesel_softdelete::soft_delete_cascade!(\n    \
+             user => [post, invoice],\n\
+         );\n",
+    );
+    assert_eq!(
+        SchemaScanner::new().column("deleted").schema_path("db").generate(schema).unwrap(),
+        "// @generated by diesel-softdelete, do not edit.\n\
+         \n\
+         use db::user;\n\
+         \n\
+         diesel_softdelete::soft_delete!(\n    \
+             user::table => (user::deleted),\n\
+         );\n",
+    );
+    assert!(matches!(
+        SchemaScanner::new().column("id").generate(schema),
+        Err(CodegenError::ColumnType { .. }),
+    ));
+    let flag = "table! { post (id) { id -> Integer, deleted_at -> Nullable<Bool>, } }";
+    assert!(matches!(SchemaScanner::new().generate(flag), Err(CodegenError::ColumnType { .. }),));
+    let timestamp = "table! { post (id) { id -> Integer, deleted_at -> \
+                     diesel::sql_types::Nullable<diesel::sql_types::Timestamptz>, } }";
+    assert!(SchemaScanner::new()
+        .generate(timestamp)
+        .unwrap()
+        .contains("timestamp(post::deleted_at)"));
+}
@@ -0,0 +1,271 @@
+//! Integration tests against the MySQL database at `MYSQL_DATABASE_URL`, covering what the SQLite
+//! tests cannot: the indexes without partial indexes, the unique keys on the alive rows, the
+//! `DATETIME` sentinel and the catalog of `information_schema`. Run them with
+//! `cargo test --features mysql --lib mysql -- --ignored --test-threads=1`.
+
+use diesel::{
+    connection::SimpleConnection,
+    debug_query,
+    deserialize::{self, FromSql},
+    migration::Migration,
+    mysql::{Mysql, MysqlConnection},
+    prelude::*,
+    result::{DatabaseErrorKind, Error},
+    sql_types::Timestamp,
+};
+
+use super::{note, session};
+use crate::{
+    config::{SoftDeleteConfig, TableConfig},
+    migration::{adopt_soft_delete, unique_alive_key},
+    prelude::*,
+    testing::fixtures::{schema::post, NewPost},
+    trash::TrashEntry,
+    write,
+};
+
+table! {
+    legacy (id) {
+        id -> Integer,
+        title -> Text,
+        deleted -> Bool,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+soft_delete!(legacy::table => (legacy::deleted));
+
+/// A connection to the MySQL database at `MYSQL_DATABASE_URL`, with the tables `post` and
+/// `session` created afresh. MySQL commits its DDL, even in a test transaction: the tests share
+/// the tables, and run one at a time.
+fn mysql_conn() -> MysqlConnection {
+    let url = std::env::var("MYSQL_DATABASE_URL").expect("MYSQL_DATABASE_URL must be set");
+    let conn = MysqlConnection::establish(&url).expect("Failed to connect to MySQL");
+    conn.batch_execute(
+        "
+        DROP TABLE IF EXISTS post, session, legacy;
+        CREATE TABLE post(
+            id INTEGER PRIMARY KEY AUTO_INCREMENT,
+            user_id INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            deleted BOOLEAN NOT NULL DEFAULT FALSE
+        );
+        CREATE TABLE session(
+            id INTEGER PRIMARY KEY AUTO_INCREMENT,
+            token VARCHAR(64) NOT NULL,
+            deleted_at DATETIME NOT NULL DEFAULT '1970-01-01 00:00:01',
+            UNIQUE (token, deleted_at)
+        );
+    ",
+    )
+    .expect("Failed to create test tables");
+    conn
+}
+
+/// Insert the posts `First`, `Trashed` (soft-deleted) and `Third` of the user 1.
+fn insert_posts(conn: &MysqlConnection) {
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+            NewPost { user_id: 1, title: "Third", ..Default::default() },
+        ])
+        .execute(conn)
+        .unwrap();
+}
+
+#[test]
+fn test_mysql_sql() {
+    assert_eq!(
+        debug_query::<Mysql, _>(&post::table.soft_find(1).select(post::title)).to_string(),
+        "SELECT `post`.`title` FROM `post` WHERE NOT (`post`.`deleted`) AND `post`.`id` = ? \
+         -- binds: [1]",
+    );
+    assert_eq!(
+        debug_query::<Mysql, _>(&write::soft_delete(post::table.find(1))).to_string(),
+        "UPDATE `post` SET `deleted` = ? WHERE `post`.`id` = ? AND NOT (`post`.`deleted`) \
+         -- binds: [true, 1]",
+    );
+    assert_eq!(
+        debug_query::<Mysql, _>(&session::table.soft_deleted().select(session::id)).to_string(),
+        "SELECT `session`.`id` FROM `session` \
+         WHERE NOT (`session`.`deleted_at` != '1970-01-01 00:00:01') -- binds: []",
+    );
+
+    let adoption = adopt_soft_delete("legacy")
+        .deleted_at("deleted_at")
+        .backfill("title = 'Archived'")
+        .index("legacy_title_alive_idx", &["title"]);
+    assert_eq!(
+        adoption.up_sql::<Mysql>().unwrap(),
+        "ALTER TABLE `legacy` ADD COLUMN `deleted` BOOLEAN NOT NULL DEFAULT FALSE;\n\
+         ALTER TABLE `legacy` ADD COLUMN `deleted_at` DATETIME(6);\n\
+         UPDATE `legacy` SET `deleted` = TRUE, `deleted_at` = CURRENT_TIMESTAMP \
+         WHERE title = 'Archived';\n\
+         CREATE INDEX `legacy_title_alive_idx` ON `legacy` (`title`, `deleted`);\n",
+    );
+    assert_eq!(
+        adoption.down_sql::<Mysql>().unwrap(),
+        "DROP INDEX `legacy_title_alive_idx` ON `legacy`;\n\
+         ALTER TABLE `legacy` DROP COLUMN `deleted_at`;\n\
+         ALTER TABLE `legacy` DROP COLUMN `deleted`;\n",
+    );
+
+    let config = SoftDeleteConfig::<Mysql>::new()
+        .table(TableConfig::new(post::table))
+        .table(TableConfig::new(note::table))
+        .table(TableConfig::new(session::table));
+    let key = unique_alive_key("post_title_alive_key", config.get("post").unwrap())
+        .columns(&["user_id", "title"]);
+    assert_eq!(
+        key.up_sql().unwrap(),
+        "ALTER TABLE `post` ADD COLUMN `key_if_alive` TINYINT \
+         GENERATED ALWAYS AS (IF(NOT `deleted`, 1, NULL)) STORED, \
+         ADD UNIQUE INDEX `post_title_alive_key` (`user_id`, `title`, `key_if_alive`);\n",
+    );
+    assert_eq!(
+        key.down_sql(),
+        "ALTER TABLE `post` DROP INDEX `post_title_alive_key`, DROP COLUMN `key_if_alive`;\n",
+    );
+    let key = unique_alive_key("note_content_alive_key", config.get("note").unwrap())
+        .columns(&["content"])
+        .generated_column("content_if_alive");
+    assert!(key.up_sql().unwrap().contains(
+        "AS (IF(`deleted_at` IS NULL, 1, NULL)) STORED, \
+         ADD UNIQUE INDEX `note_content_alive_key` (`content`, `content_if_alive`)"
+    ));
+    let key = unique_alive_key("session_token_alive_key", config.get("session").unwrap())
+        .columns(&["token"]);
+    assert!(key.up_sql().unwrap().contains("IF(`deleted_at` = '1970-01-01 00:00:01', 1, NULL)"));
+    assert!(unique_alive_key("empty", config.get("note").unwrap()).up_sql().is_err());
+}
+
+#[test]
+#[ignore = "needs a MySQL database at MYSQL_DATABASE_URL"]
+fn test_mysql_soft_writes() {
+    let conn = mysql_conn();
+    insert_posts(&conn);
+
+    let titles = || post::table.soft_deleted().select(post::title).load::<String>(&conn);
+    assert_eq!(titles(), Ok(vec!["First".to_owned(), "Third".to_owned()]));
+    assert_eq!(write::soft_delete(post::table.find(1)).execute(&conn), Ok(1));
+    assert_eq!(write::soft_delete(post::table.find(1)).execute(&conn), Ok(0));
+    assert_eq!(titles(), Ok(vec!["Third".to_owned()]));
+    assert_eq!(post::table.soft_find(1).select(post::id).first::<i32>(&conn).optional(), Ok(None));
+    assert_eq!(write::restore(post::table.find(2)).execute(&conn), Ok(1));
+    assert_eq!(titles(), Ok(vec!["Trashed".to_owned(), "Third".to_owned()]));
+}
+
+#[test]
+#[ignore = "needs a MySQL database at MYSQL_DATABASE_URL"]
+fn test_mysql_sentinel() {
+    let conn = mysql_conn();
+    let insert =
+        |token| diesel::insert_into(session::table).values(session::token.eq(token)).execute(&conn);
+    let alive = || session::table.soft_deleted().select(session::id).load::<i32>(&conn);
+
+    insert("abc").unwrap();
+    assert!(matches!(
+        insert("abc"),
+        Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)),
+    ));
+    assert_eq!(write::soft_delete(session::table.find(1)).execute(&conn), Ok(1));
+    insert("abc").unwrap();
+    assert_eq!(alive().unwrap().len(), 1);
+    assert!(write::restore(session::table.find(1)).execute(&conn).is_err());
+}
+
+#[test]
+#[ignore = "needs a MySQL database at MYSQL_DATABASE_URL"]
+fn test_mysql_unique_alive_key() {
+    let conn = mysql_conn();
+    let config = SoftDeleteConfig::<Mysql>::new().table(TableConfig::new(post::table));
+    let key =
+        unique_alive_key("post_user_alive_key", config.get("post").unwrap()).columns(&["user_id"]);
+    conn.batch_execute(&key.up_sql().unwrap()).unwrap();
+
+    let insert = |deleted| {
+        diesel::insert_into(post::table)
+            .values(NewPost { user_id: 1, title: "Post", deleted: Some(deleted) })
+            .execute(&conn)
+    };
+    insert(false).unwrap();
+    insert(true).unwrap();
+    insert(true).unwrap();
+    assert!(matches!(
+        insert(false),
+        Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)),
+    ));
+    assert_eq!(write::soft_delete(post::table.find(1)).execute(&conn), Ok(1));
+    insert(false).unwrap();
+    conn.batch_execute(&key.down_sql()).unwrap();
+}
+
+#[test]
+#[ignore = "needs a MySQL database at MYSQL_DATABASE_URL"]
+fn test_mysql_schema() {
+    let conn = mysql_conn();
+    conn.batch_execute(
+        "CREATE TABLE legacy(id INTEGER PRIMARY KEY, title VARCHAR(64) NOT NULL);
+        INSERT INTO legacy(id, title) VALUES (1, 'Kept'), (2, 'Archived');",
+    )
+    .unwrap();
+    let adoption = adopt_soft_delete("legacy")
+        .deleted_at("deleted_at")
+        .backfill("title = 'Archived'")
+        .index("legacy_title_alive_idx", &["title"])
+        .migration::<Mysql>("20240101000000");
+    adoption.run(&conn).unwrap();
+    let alive = legacy::table.soft_deleted().select(legacy::id).load::<i32>(&conn);
+    assert_eq!(alive, Ok(vec![1]));
+
+    let config = SoftDeleteConfig::<Mysql>::new()
+        .table(TableConfig::new(legacy::table))
+        .table(TableConfig::new(post::table));
+    assert!(config.validate_soft_delete_schema(&conn).is_ok());
+    let missing = config.missing_indexes(&conn).unwrap();
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0].table, "post");
+    assert_eq!(missing[0].suggestion, "CREATE INDEX `post_alive_idx` ON `post` (`deleted`)");
+    conn.batch_execute(&missing[0].suggestion).unwrap();
+    assert!(config.missing_indexes(&conn).unwrap().is_empty());
+
+    adoption.revert(&conn).unwrap();
+    let errors = config.validate_soft_delete_schema(&conn).unwrap_err();
+    assert_eq!(errors.len(), 1);
+}
+
+/// A timestamp, loaded without its value: Diesel reads the MySQL timestamps with `chrono` only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AnyTime;
+
+impl FromSql<Timestamp, Mysql> for AnyTime {
+    fn from_sql(_: Option<&[u8]>) -> deserialize::Result<Self> {
+        Ok(Self)
+    }
+}
+
+#[test]
+#[ignore = "needs a MySQL database at MYSQL_DATABASE_URL"]
+fn test_mysql_recently_deleted() {
+    let conn = mysql_conn();
+    insert_posts(&conn);
+    let config = SoftDeleteConfig::<Mysql>::new()
+        .table(TableConfig::new(post::table).label(post::title))
+        .table(TableConfig::new(session::table));
+    diesel::insert_into(session::table).values(session::token.eq("abc")).execute(&conn).unwrap();
+    write::soft_delete(session::table).execute(&conn).unwrap();
+
+    let page = config.recently_deleted::<AnyTime, _>(1, 10, &conn).unwrap();
+    let entry = |table, id: &str, label: Option<&str>, deleted_at| TrashEntry {
+        table,
+        id: id.to_owned(),
+        label: label.map(str::to_owned),
+        deleted_at,
+    };
+    assert_eq!(page.total, 2);
+    assert_eq!(
+        page.rows,
+        [entry("session", "1", None, Some(AnyTime)), entry("post", "2", Some("Trashed"), None)],
+    );
+}
@@ -0,0 +1,474 @@
+//! Integration tests against the PostgreSQL database at `DATABASE_URL`, covering what the SQLite
+//! tests cannot: `RETURNING`, partial indexes, `= ANY`, `COPY`, `TABLESAMPLE`, `NOTIFY` and
+//! `timestamptz` columns. Run them with `cargo test --features postgres --lib -- --ignored`.
+
+use diesel::{connection::SimpleConnection, pg::PgConnection, prelude::*};
+
+use crate::{
+    config::{SoftDeleteConfig, TableConfig},
+    explain::explain_soft,
+    load::FindForUpdate,
+    migration::{unique_alive_index, IndexLinter, SchemaValidator},
+    prelude::*,
+    testing::fixtures::{schema::post, NewPost, Post},
+    write,
+};
+
+table! {
+    log_entry (id) {
+        id -> Integer,
+        message -> Text,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+soft_delete!(log_entry::table => timestamp(log_entry::deleted_at));
+
+/// A connection to the PostgreSQL database at `DATABASE_URL`, in a test transaction. The test
+/// tables are temporary: `post` and `log_entry`.
+fn pg_conn() -> PgConnection {
+    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let conn = PgConnection::establish(&url).expect("Failed to connect to PostgreSQL");
+    conn.begin_test_transaction().unwrap();
+    conn.batch_execute(
+        "
+        create temporary table post(
+            id serial primary key,
+            user_id integer not null,
+            title text not null,
+            deleted bool not null default false
+        );
+        create temporary table log_entry(
+            id serial primary key,
+            message text not null,
+            deleted_at timestamptz
+        );
+    ",
+    )
+    .expect("Failed to create test tables");
+    conn
+}
+
+/// Insert the posts `First`, `Trashed` (soft-deleted) and `Third` of the user 1.
+fn insert_posts(conn: &PgConnection) {
+    diesel::insert_into(post::table)
+        .values(vec![
+            NewPost { user_id: 1, title: "First", ..Default::default() },
+            NewPost { user_id: 1, title: "Trashed", deleted: Some(true) },
+            NewPost { user_id: 1, title: "Third", ..Default::default() },
+        ])
+        .execute(conn)
+        .unwrap();
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_soft_find_for_update() {
+    let conn = pg_conn();
+    insert_posts(&conn);
+
+    let post = post::table.soft_find_for_update::<Post, _>(1, &conn).unwrap();
+    assert!(matches!(post, FindForUpdate::Locked(Post { id: 1, .. })));
+    let post = post::table.soft_find_for_update::<Post, _>(2, &conn).unwrap();
+    assert!(matches!(post, FindForUpdate::Deleted(Post { id: 2, .. })));
+    assert_eq!(post::table.soft_find_for_update::<Post, _>(4, &conn), Ok(FindForUpdate::Missing));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_returning() {
+    let conn = pg_conn();
+    insert_posts(&conn);
+
+    let deleted = write::soft_delete(post::table.filter(post::user_id.eq(1)))
+        .returning(post::id)
+        .get_results::<i32>(&conn)
+        .map(|mut ids| {
+            ids.sort_unstable();
+            ids
+        });
+    assert_eq!(deleted, Ok(vec![1, 3]));
+    let restored = write::restore(post::table.find(2)).returning(post::title).get_result(&conn);
+    assert_eq!(restored, Ok("Trashed".to_owned()));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_eq_any() {
+    let conn = pg_conn();
+    insert_posts(&conn);
+
+    let query = post::table.soft_filter(post::id.eq(diesel::dsl::any(vec![1, 2]))).select(post::id);
+    assert_eq!(
+        super::pg_sql_of(&query),
+        "SELECT \"post\".\"id\" FROM \"post\" \
+         WHERE NOT (\"post\".\"deleted\") AND \"post\".\"id\" = ANY($1) -- binds: [[1, 2]]",
+    );
+    assert_eq!(query.load::<i32>(&conn), Ok(vec![1]));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_partial_index() {
+    let conn = pg_conn();
+    insert_posts(&conn);
+    let index = unique_alive_index("post_title_alive_key", post::table).column(post::title);
+    conn.batch_execute(&index.create_sql().unwrap()).unwrap();
+
+    let insert = |title, deleted| {
+        diesel::insert_into(post::table)
+            .values(NewPost { user_id: 2, title, deleted: Some(deleted) })
+            .execute(&conn)
+    };
+    assert_eq!(insert("Trashed", false), Ok(1));
+    assert_eq!(insert("First", true), Ok(1));
+    assert!(conn.transaction(|| insert("First", false)).is_err());
+
+    conn.batch_execute("set local enable_seqscan = off").unwrap();
+    let plan = explain_soft(post::table.soft_filter(post::title.eq("First")), false, &conn);
+    let plan = plan.unwrap();
+    assert!(plan.contains("post_title_alive_key"), "{}", plan);
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_index_linter() {
+    let conn = pg_conn();
+    let linter = IndexLinter::new().table(post::table).table(log_entry::table);
+    let missing = linter.check(&conn).unwrap();
+    assert_eq!(
+        missing.iter().map(|missing| missing.table).collect::<Vec<_>>(),
+        ["post", "log_entry"]
+    );
+    assert_eq!(
+        missing[0].suggestion,
+        "CREATE INDEX \"post_alive_idx\" ON \"post\" (\"id\") WHERE NOT (\"post\".\"deleted\")",
+    );
+
+    conn.batch_execute(&missing[0].suggestion).unwrap();
+    conn.batch_execute("create index log_entry_deleted_at on log_entry (deleted_at)").unwrap();
+    assert_eq!(linter.check(&conn), Ok(vec![]));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_validate_soft_delete_schema() {
+    let conn = pg_conn();
+    let validator = SchemaValidator::new().table(post::table).table(log_entry::table);
+    assert!(validator.validate_soft_delete_schema(&conn).is_ok());
+
+    conn.batch_execute(
+        "alter table post alter column deleted drop default;
+        alter table log_entry alter column deleted_at type text;",
+    )
+    .unwrap();
+    let errors = validator.validate_soft_delete_schema(&conn).unwrap_err();
+    assert_eq!(
+        errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        [
+            "soft-delete column `deleted` of table `post` should be false by default, found no \
+             default",
+            "soft-delete column `deleted_at` of table `log_entry` should be a timestamp, found text",
+        ],
+    );
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_alive_upsert() {
+    use diesel::pg::upsert::excluded;
+
+    let conn = pg_conn();
+    let index = unique_alive_index("post_user_title_alive_key", post::table)
+        .column(post::user_id)
+        .column(post::title);
+    conn.batch_execute(&index.create_sql().unwrap()).unwrap();
+
+    let upsert = |user_id| {
+        diesel::insert_into(post::table)
+            .values(NewPost { user_id, title: "Title", ..Default::default() })
+            .on_conflict(index.conflict_target().unwrap())
+            .do_update()
+            .set(post::title.eq(excluded(post::title).concat(" (2)")))
+            .execute(&conn)
+    };
+    diesel::insert_into(post::table)
+        .values(NewPost { user_id: 1, title: "Title", deleted: Some(true) })
+        .execute(&conn)
+        .unwrap();
+    assert_eq!(upsert(1), Ok(1));
+    assert_eq!(upsert(1), Ok(1));
+    assert_eq!(
+        post::table.select((post::id, post::title, post::deleted)).order(post::id).load(&conn),
+        Ok(vec![(1, "Title".to_owned(), true), (2, "Title (2)".to_owned(), false)]),
+    );
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_timestamptz() {
+    let conn = pg_conn();
+    diesel::insert_into(log_entry::table)
+        .values(&vec![log_entry::message.eq("Started"), log_entry::message.eq("Stopped")])
+        .execute(&conn)
+        .unwrap();
+    let alive = || {
+        log_entry::table
+            .soft_deleted()
+            .select(log_entry::id)
+            .order(log_entry::id)
+            .load::<i32>(&conn)
+    };
+
+    assert_eq!(write::soft_delete(log_entry::table.find(1)).execute(&conn), Ok(1));
+    assert_eq!(write::soft_delete(log_entry::table.find(1)).execute(&conn), Ok(0));
+    assert_eq!(alive(), Ok(vec![2]));
+    let deleted_at =
+        log_entry::table.find(1).select(log_entry::deleted_at.is_not_null()).get_result(&conn);
+    assert_eq!(deleted_at, Ok(true));
+
+    assert_eq!(write::restore(log_entry::table.find(1)).execute(&conn), Ok(1));
+    assert_eq!(alive(), Ok(vec![1, 2]));
+}
+
+table! {
+    device (id) {
+        id -> Uuid,
+        name -> Text,
+        deleted -> Bool,
+    }
+}
+
+soft_delete!(device);
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_uuid_keys() {
+    use uuid::Uuid;
+
+    let conn = pg_conn();
+    conn.batch_execute(
+        "create temporary table device(
+            id uuid primary key,
+            name text not null,
+            deleted bool not null default false
+        )",
+    )
+    .unwrap();
+    let (phone, laptop, watch) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+    diesel::insert_into(device::table)
+        .values(&vec![
+            (device::id.eq(phone), device::name.eq("Phone"), device::deleted.eq(false)),
+            (device::id.eq(laptop), device::name.eq("Laptop"), device::deleted.eq(true)),
+            (device::id.eq(watch), device::name.eq("Watch"), device::deleted.eq(false)),
+        ])
+        .execute(&conn)
+        .unwrap();
+
+    let name = device::table.soft_find(phone).select(device::name).first::<String>(&conn);
+    assert_eq!(name, Ok("Phone".to_owned()));
+    assert_eq!(
+        device::table.soft_find(laptop).select(device::id).first::<Uuid>(&conn).optional(),
+        Ok(None)
+    );
+
+    let query = device::table
+        .soft_find_any(vec![phone, laptop, watch])
+        .select(device::name)
+        .order(device::name);
+    assert_eq!(
+        super::pg_sql_of(&query),
+        "SELECT \"device\".\"name\" FROM \"device\" \
+         WHERE NOT (\"device\".\"deleted\") AND \"device\".\"id\" = ANY($1) \
+         ORDER BY \"device\".\"name\" -- binds: [[00000000-0000-0000-0000-000000000001, \
+         00000000-0000-0000-0000-000000000002, 00000000-0000-0000-0000-000000000003]]",
+    );
+    let names = query.load::<String>(&conn);
+    assert_eq!(names, Ok(vec!["Phone".to_owned(), "Watch".to_owned()]));
+    let names = device::table.soft_find_many(vec![laptop, watch]).select(device::name).load(&conn);
+    assert_eq!(names, Ok(vec!["Watch".to_owned()]));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_copy() {
+    use crate::copy::{soft_copy, CopyFormat};
+
+    let conn = pg_conn();
+    insert_posts(&conn);
+
+    let copy =
+        soft_copy(post::table.select((post::id, post::title)), CopyFormat::Csv { header: true });
+    let sql = copy.sql().unwrap();
+    assert!(sql.starts_with(
+        "COPY (SELECT \"post\".\"id\", \"post\".\"title\" FROM \"post\" \
+         WHERE NOT (\"post\".\"deleted\")"
+    ));
+    assert!(sql.ends_with(") TO STDOUT WITH (FORMAT csv, HEADER true)"));
+    let path = std::env::temp_dir().join(format!("soft_copy_{}.csv", std::process::id()));
+    assert_eq!(copy.to_file(path.to_str().unwrap(), &conn), Ok(2));
+    let _ = std::fs::remove_file(&path);
+
+    let copy = soft_copy(post::table.filter(post::user_id.eq(1)), CopyFormat::Binary);
+    assert!(copy.sql().unwrap().contains("$1"));
+    assert!(copy.to_file(path.to_str().unwrap(), &conn).is_err());
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_expired_purge_timeouts() {
+    use std::time::Duration;
+
+    use crate::write::{is_timeout, AllowHardDelete, ExpiredPurge, ExpiredPurgeError};
+
+    let conn = pg_conn();
+    conn.batch_execute(
+        "insert into log_entry(message, deleted_at) values
+            ('Alive', null),
+            ('Recent', now() - interval '1 hour'),
+            ('Expired', now() - interval '2 days'),
+            ('Expired', now() - interval '3 days');",
+    )
+    .unwrap();
+
+    let config = SoftDeleteConfig::<diesel::pg::Pg>::new()
+        .table(TableConfig::new(log_entry::table).retention(Duration::from_secs(24 * 3600)));
+    let purged = ExpiredPurge::new(&config, AllowHardDelete::i_really_want_to_destroy_rows())
+        .batch_size(1)
+        .statement_timeout(Duration::from_secs(5))
+        .lock_timeout(Duration::from_millis(100))
+        .run(&conn)
+        .unwrap();
+    assert_eq!(purged, vec![("log_entry", 2)]);
+
+    conn.batch_execute(
+        "insert into log_entry(message, deleted_at) values ('Expired', now() - interval '2 days');
+        create function pg_temp.slow_delete() returns trigger language plpgsql as $$
+            begin perform pg_sleep(1); return old; end
+        $$;
+        create trigger slow_delete before delete on log_entry
+            for each row execute function pg_temp.slow_delete();",
+    )
+    .unwrap();
+    let cancelled = ExpiredPurge::new(&config, AllowHardDelete::i_really_want_to_destroy_rows())
+        .statement_timeout(Duration::from_millis(10))
+        .max_retries(1)
+        .run(&conn);
+    match cancelled {
+        Err(ExpiredPurgeError::Timeout { table, purged, error }) => {
+            assert_eq!(table, "log_entry");
+            assert_eq!(purged, vec![("log_entry", 0)]);
+            assert!(is_timeout(&error));
+        }
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+    let plain = conn.transaction(|| {
+        conn.execute("SET LOCAL statement_timeout = 1")?;
+        conn.execute("SELECT pg_sleep(1)")
+    });
+    assert!(!is_timeout(&plain.unwrap_err()));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_expired_purge_hooks() {
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    use crate::{
+        hooks::{self, WriteEvent},
+        write::{purge_expired, AllowHardDelete, ExpiredPurge},
+    };
+
+    let conn = pg_conn();
+    conn.batch_execute(
+        "insert into log_entry(message, deleted_at) values
+            ('Alive', null),
+            ('Expired', now() - interval '2 days'),
+            ('Expired', now() - interval '3 days'),
+            ('Expired', now() - interval '4 days');",
+    )
+    .unwrap();
+    let keys = Rc::new(RefCell::new(Vec::new()));
+    let _hook = {
+        let keys = keys.clone();
+        hooks::install(move |event: &WriteEvent, _: &PgConnection| {
+            keys.borrow_mut().push(event.keys.to_vec());
+            Ok(())
+        })
+    };
+
+    let config = SoftDeleteConfig::<diesel::pg::Pg>::new()
+        .table(TableConfig::new(log_entry::table).retention(Duration::from_secs(24 * 3600)));
+    let purged = ExpiredPurge::new(&config, AllowHardDelete::i_really_want_to_destroy_rows())
+        .batch_size(2)
+        .run(&conn)
+        .unwrap();
+    assert_eq!(purged, vec![("log_entry", 3)]);
+    let mut batches = keys.borrow().clone();
+    batches.iter_mut().for_each(|keys| keys.sort());
+    assert_eq!(batches, [vec!["2".to_owned(), "3".to_owned()], vec!["4".to_owned()]]);
+
+    let purged = purge_expired(&config, AllowHardDelete::i_really_want_to_destroy_rows(), &conn);
+    assert_eq!(purged, Ok(vec![("log_entry", 0)]));
+    assert_eq!(keys.borrow().last(), Some(&Vec::new()));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_soft_combination_order() {
+    use crate::combination::SoftCombineDsl;
+
+    let conn = pg_conn();
+    insert_posts(&conn);
+
+    // INTERSECT binds tighter than UNION: the union must be wrapped to be intersected as a whole.
+    let titles = post::table
+        .select(post::title)
+        .soft_union(post::table.filter(post::id.eq(3)).select(post::title))
+        .intersect(post::table.filter(post::title.eq("First")).select(post::title));
+    assert_eq!(titles.load::<String>(&conn), Ok(vec!["First".to_owned()]));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_soft_sample() {
+    use crate::sample::{sampled, SoftSampleDsl, TableSample};
+
+    let conn = pg_conn();
+    insert_posts(&conn);
+
+    let titles = post::table
+        .soft_sample(TableSample::bernoulli(100.0).repeatable(1.0))
+        .select(sampled(post::title))
+        .order(post::id)
+        .load::<String>(&conn);
+    assert_eq!(titles, Ok(vec!["First".to_owned(), "Third".to_owned()]));
+    let count = post::table
+        .soft_sample(TableSample::system(100.0))
+        .select(sampled(diesel::dsl::count(post::id)))
+        .get_result::<i64>(&conn);
+    assert_eq!(count, Ok(2));
+    let posts = post::table.soft_sample(TableSample::system(0.0)).load::<Post>(&conn);
+    assert_eq!(posts, Ok(vec![]));
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL database at DATABASE_URL"]
+fn test_soft_notifier() {
+    use crate::{notify::SoftNotifier, write::SoftWriteDsl};
+
+    let conn = pg_conn();
+    insert_posts(&conn);
+    let _notifier = SoftNotifier::new("soft_delete").install();
+
+    assert_eq!(post::table.filter(post::id.ge(2)).soft_delete_rows(&conn), Ok(1));
+    assert_eq!(post::table.find(3).restore_rows(&conn), Ok(1));
+    assert_eq!(post::table.find(3).restore_rows(&conn), Ok(0));
+    assert_eq!(SoftNotifier::new("soft_delete").notify("post", &[1, 2], "purge", &conn), Ok(()));
+
+    // An empty channel is rejected by pg_notify, failing the write.
+    let broken = SoftNotifier::new("").install();
+    assert!(conn.transaction(|| post::table.find(3).soft_delete_rows(&conn)).is_err());
+    drop(broken);
+    assert_eq!(post::table.soft_deleted().count().get_result(&conn), Ok(2i64));
+}
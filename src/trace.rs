@@ -0,0 +1,206 @@
+//! `tracing` instrumentation, with the `tracing` feature.
+//!
+//! An event is emitted every time the alive condition is added to a query, and the soft-delete,
+//! restore and purge statements executed by this crate run in a span recording their table, as
+//! named by its `FROM` clause, the rows they affected and their duration.
+//!
+//! With the `log` feature, those are also emitted as `log` records when no `tracing` subscriber is
+//! set, so that retention jobs using [`empty_trash`](crate::write::empty_trash),
+//! [`purge_by_id`](crate::write::PurgeByIdDsl::purge_by_id) and the purges of the expired trash
+//! are auditable with either.
+//!
+//! The soft-delete, restore and purge statements also run the [`hooks`](crate::hooks) installed
+//! for their connection.
+
+use diesel::{
+    query_builder::{nodes::Identifier, QueryFragment, QueryId},
+    query_dsl::methods::ExecuteDsl,
+    Column, Connection, QueryResult, Table,
+};
+
+use crate::hooks::{self, Action, HookConnection, WriteEvent};
+
+/// The table written by this crate, named by its `FROM` clause, and its primary key column, whose
+/// values are passed to the hooks.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Target {
+    pub(crate) name: &'static str,
+    pub(crate) key: &'static str,
+}
+
+impl Target {
+    pub(crate) fn of<T>(table: T) -> Self
+    where
+        T: Table<FromClause = Identifier<'static>>,
+        T::PrimaryKey: Column,
+    {
+        Self { name: table.from_clause().0, key: <T::PrimaryKey as Column>::NAME }
+    }
+}
+
+/// What a write of [`write_with`] returns: the number of rows it affected, or their keys when
+/// hooks are installed.
+pub(crate) enum Affected {
+    Count(usize),
+    Keys(Vec<String>),
+}
+
+/// Report that the alive condition is added to a query.
+#[inline]
+pub(crate) fn soft_scope() {
+    #[cfg(feature = "tracing")]
+    tracing::trace!("soft scope applied");
+}
+
+/// Execute the write `operation` on the `table`, named by its `FROM` clause.
+pub(crate) fn execute<S, Conn>(
+    operation: &'static str,
+    table: &'static str,
+    stmt: S,
+    conn: &Conn,
+) -> QueryResult<usize>
+where
+    S: ExecuteDsl<Conn>,
+    Conn: Connection,
+{
+    run(operation, table, || ExecuteDsl::execute(stmt, conn))
+}
+
+/// Execute the soft deletion, restoration or purge `stmt` of rows of `target`, running the hooks.
+pub(crate) fn write<S, Conn>(
+    action: Action,
+    target: Target,
+    stmt: S,
+    conn: &Conn,
+) -> QueryResult<usize>
+where
+    S: QueryFragment<Conn::Backend> + QueryId,
+    Conn: HookConnection,
+{
+    write_with(action, target, conn, |keyed| match keyed {
+        false => ExecuteDsl::execute(stmt, conn).map(Affected::Count),
+        true => conn.load_keys(stmt, target.key).map(Affected::Keys),
+    })
+}
+
+/// Run the write `action` on rows of `target`, executed by `f`, which returns their keys if
+/// passed `true`, and running the hooks.
+pub(crate) fn write_with<Conn, F>(
+    action: Action,
+    target: Target,
+    conn: &Conn,
+    f: F,
+) -> QueryResult<usize>
+where
+    Conn: HookConnection,
+    F: FnOnce(bool) -> QueryResult<Affected>,
+{
+    let hooked = hooks::installed::<Conn>();
+    let write = || {
+        let mut keys = None;
+        let count = run(action.as_str(), target.name, || match f(hooked)? {
+            Affected::Count(count) => Ok(count),
+            Affected::Keys(affected) => Ok(keys.insert(affected).len()),
+        })?;
+        if let Some(keys) = keys {
+            hooks::run(&WriteEvent { table: target.name, action, keys: &keys }, conn)?;
+        }
+        Ok(count)
+    };
+    // With hooks, the write is undone when one of them fails, even outside of a transaction.
+    match hooked {
+        true => conn.transaction(write),
+        false => write(),
+    }
+}
+
+/// Run the write `operation` on the `table`, executed by `f` returning the rows it affected.
+#[cfg(feature = "tracing")]
+pub(crate) fn run<F>(operation: &'static str, table: &'static str, f: F) -> QueryResult<usize>
+where
+    F: FnOnce() -> QueryResult<usize>,
+{
+    let span = tracing::debug_span!("softdelete", operation, table);
+    let _entered = span.enter();
+    let start = std::time::Instant::now();
+    let rows = f();
+    let duration = start.elapsed();
+    match &rows {
+        Ok(rows) => tracing::debug!(rows, ?duration, "{} executed", operation),
+        Err(error) => tracing::debug!(%error, ?duration, "{} failed", operation),
+    }
+    rows
+}
+
+/// Run the write `operation` on the `table`, executed by `f` returning the rows it affected.
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn run<F>(_operation: &str, _table: &str, f: F) -> QueryResult<usize>
+where
+    F: FnOnce() -> QueryResult<usize>,
+{
+    f()
+}
+
+/// Execute the write `action` on the `table`, loading the key of each affected row, and running the
+/// hooks.
+#[cfg(feature = "cdc")]
+pub(crate) fn load<S, PK, Conn>(
+    action: Action,
+    table: &'static str,
+    stmt: S,
+    conn: &Conn,
+) -> QueryResult<Vec<PK>>
+where
+    S: diesel::query_dsl::LoadQuery<Conn, PK>,
+    PK: std::fmt::Display,
+    Conn: HookConnection,
+{
+    if !hooks::installed::<Conn>() {
+        return load_traced(action.as_str(), table, stmt, conn);
+    }
+    conn.transaction(|| {
+        let keys = load_traced(action.as_str(), table, stmt, conn)?;
+        let text = keys.iter().map(ToString::to_string).collect::<Vec<_>>();
+        hooks::run(&WriteEvent { table, action, keys: &text }, conn)?;
+        Ok(keys)
+    })
+}
+
+/// Execute the write `operation` on the `table`, loading what it returns of each affected row.
+#[cfg(all(feature = "cdc", feature = "tracing"))]
+fn load_traced<S, U, Conn>(
+    operation: &'static str,
+    table: &'static str,
+    stmt: S,
+    conn: &Conn,
+) -> QueryResult<Vec<U>>
+where
+    S: diesel::query_dsl::LoadQuery<Conn, U>,
+    Conn: Connection,
+{
+    let span = tracing::debug_span!("softdelete", operation, table);
+    let _entered = span.enter();
+    let start = std::time::Instant::now();
+    let rows = stmt.internal_load(conn);
+    let duration = start.elapsed();
+    match &rows {
+        Ok(rows) => tracing::debug!(rows = rows.len(), ?duration, "{} executed", operation),
+        Err(error) => tracing::debug!(%error, ?duration, "{} failed", operation),
+    }
+    rows
+}
+
+/// Execute the write `operation` on the `table`, loading what it returns of each affected row.
+#[cfg(all(feature = "cdc", not(feature = "tracing")))]
+fn load_traced<S, U, Conn>(
+    _operation: &str,
+    _table: &str,
+    stmt: S,
+    conn: &Conn,
+) -> QueryResult<Vec<U>>
+where
+    S: diesel::query_dsl::LoadQuery<Conn, U>,
+    Conn: Connection,
+{
+    stmt.internal_load(conn)
+}
@@ -0,0 +1,266 @@
+//! The backend of the "recycle bin" admin screens: browsing the trashed rows of a table, and
+//! restoring or purging them one by one.
+//!
+//! ```rust,ignore
+//! let bin = TrashBin::new(post::table);
+//! let page = bin.list::<Post, _, _>(post::deleted_at.desc(), 1, 20, &conn)?;
+//! let post = bin.restore::<_, Post, _>(page.rows[0].id, &conn)?;
+//! bin.purge(page.rows[1].id, &conn)?;
+//! ```
+//!
+//! The rows are loaded with the default selection of the table, so their deletion metadata, e.g.
+//! `deleted_at` and `deleted_by` columns, is part of the model when the table has it.
+//!
+//! The "recently deleted" views spanning every table list the trashed rows of the tables of a
+//! [`SoftDeleteConfig`] together instead, with
+//! [`recently_deleted`](SoftDeleteConfig::recently_deleted).
+
+use std::time::SystemTime;
+
+use diesel::{
+    associations::HasTable,
+    dsl::{count_star, CountStar, Find, Limit, Offset, Order, Select},
+    query_builder::{nodes::Identifier, IntoUpdateTarget, QueryFragment, QueryId},
+    query_dsl::{
+        methods::{FilterDsl, FindDsl, LimitDsl, OffsetDsl, OrderDsl, SelectDsl},
+        LoadQuery,
+    },
+    Column, Expression, QueryResult, RunQueryDsl, Table,
+};
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, QueryableByName},
+    row::NamedRow,
+    sql_types::{BigInt, Nullable, Text, Timestamp},
+    Connection,
+};
+
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+use crate::{config::SoftDeleteConfig, migration::MigrationBackend, stats::Count};
+use crate::{
+    hooks::HookConnection,
+    methods::OnlyDeletedDsl,
+    write::{
+        Deleted, PurgeByIdDsl, PurgeError, PurgeStatement, RestoreByIdDsl, RestoreError,
+        SoftDeleteTarget,
+    },
+    SoftDelete,
+};
+
+type Trashed<T> = <T as OnlyDeletedDsl>::Output;
+type TrashedPage<T, O> = Offset<Limit<Order<Trashed<T>, O>>>;
+
+/// A page of trashed rows, listed by [`TrashBin::list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashPage<U> {
+    pub rows: Vec<U>,
+    /// The total number of trashed rows of the table.
+    pub total: i64,
+    /// The number of the page, from 1.
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl<U> TrashPage<U> {
+    /// The number of pages, 0 for an empty trash.
+    pub fn pages(&self) -> i64 {
+        match self.per_page {
+            0 => 0,
+            per_page => (self.total + per_page - 1) / per_page,
+        }
+    }
+}
+
+/// The trash of a soft-deletable table.
+#[derive(Debug, Clone, Copy)]
+pub struct TrashBin<T> {
+    table: T,
+}
+
+impl<T: SoftDelete + Copy> TrashBin<T> {
+    pub fn new(table: T) -> Self {
+        Self { table }
+    }
+
+    /// Load a page of trashed rows sorted by `order`, e.g. `post::deleted_at.desc()`, along with
+    /// the total number of trashed rows.
+    ///
+    /// Pages are numbered from 1.
+    pub fn list<U, O: Expression, Conn>(
+        &self,
+        order: O,
+        page: i64,
+        per_page: i64,
+        conn: &Conn,
+    ) -> QueryResult<TrashPage<U>>
+    where
+        T: OnlyDeletedDsl,
+        Trashed<T>: OrderDsl<O> + SelectDsl<CountStar>,
+        Order<Trashed<T>, O>: LimitDsl,
+        Limit<Order<Trashed<T>, O>>: OffsetDsl,
+        TrashedPage<T, O>: LoadQuery<Conn, U> + RunQueryDsl<Conn>,
+        Select<Trashed<T>, CountStar>: LoadQuery<Conn, i64> + RunQueryDsl<Conn>,
+    {
+        let page = page.max(1);
+        let total = self.table.only_deleted().select(count_star()).get_result(conn)?;
+        let rows = self
+            .table
+            .only_deleted()
+            .order(order)
+            .limit(per_page)
+            .offset((page - 1) * per_page)
+            .load(conn)?;
+        Ok(TrashPage { rows, total, page, per_page })
+    }
+
+    /// Restore the trashed row `id`, and load it back.
+    pub fn restore<PK, U, Conn>(&self, id: PK, conn: &Conn) -> Result<U, RestoreError>
+    where
+        T: FindDsl<PK>,
+        PK: Clone,
+        Find<T, PK>: SoftDeleteTarget + RunQueryDsl<Conn> + LoadQuery<Conn, U>,
+        <Find<T, PK> as HasTable>::Table: Table<FromClause = Identifier<'static>>,
+        <<Find<T, PK> as HasTable>::Table as Table>::PrimaryKey: Column,
+        <Find<T, PK> as SoftDeleteTarget>::Restore: QueryFragment<Conn::Backend> + QueryId,
+        Conn: HookConnection,
+    {
+        self.table.restore_by_id(id, conn)
+    }
+
+    /// Destroy the trashed row `id`. An alive row is left untouched.
+    pub fn purge<PK, Stmt, Conn>(&self, id: PK, conn: &Conn) -> Result<(), PurgeError>
+    where
+        T: FindDsl<PK>,
+        PK: Clone,
+        Find<T, PK>: IntoUpdateTarget + SelectDsl<CountStar>,
+        <Find<T, PK> as HasTable>::Table: SoftDelete + Table<FromClause = Identifier<'static>>,
+        <<Find<T, PK> as HasTable>::Table as Table>::PrimaryKey: Column,
+        PurgeStatement<Find<T, PK>>: FilterDsl<Deleted<Find<T, PK>>, Output = Stmt>,
+        Stmt: QueryFragment<Conn::Backend> + QueryId,
+        Select<Find<T, PK>, CountStar>: LoadQuery<Conn, i64> + RunQueryDsl<Conn>,
+        Conn: HookConnection,
+    {
+        self.table.purge_by_id(id, conn)
+    }
+}
+
+/// A trashed row of any of the tables of a [`SoftDeleteConfig`](crate::config::SoftDeleteConfig),
+/// listed by [`recently_deleted`](crate::config::SoftDeleteConfig::recently_deleted).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashEntry<Ts = SystemTime> {
+    pub table: &'static str,
+    /// The primary key of the row, as text.
+    pub id: String,
+    /// The value of the [`label`](crate::config::TableConfig::label) column of the table, if any.
+    pub label: Option<String>,
+    /// The deletion time of the row, if the table records it.
+    pub deleted_at: Option<Ts>,
+}
+
+/// A [`TrashEntry`] as loaded, before its table name is matched with the registered ones.
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+struct Entry<Ts> {
+    table: String,
+    id: String,
+    label: Option<String>,
+    deleted_at: Option<Ts>,
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+impl<DB, Ts> QueryableByName<DB> for Entry<Ts>
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+    Ts: FromSql<Timestamp, DB>,
+{
+    fn build<R: NamedRow<DB>>(row: &R) -> deserialize::Result<Self> {
+        Ok(Self {
+            table: row.get::<Text, _>("table_name")?,
+            id: row.get::<Text, _>("id")?,
+            label: row.get::<Nullable<Text>, _>("label")?,
+            deleted_at: row.get::<Nullable<Timestamp>, _>("deleted_at")?,
+        })
+    }
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+impl<DB> SoftDeleteConfig<DB>
+where
+    DB: MigrationBackend,
+    DB::QueryBuilder: Default,
+{
+    /// Load a page of the trashed rows of all the registered tables, the most recently deleted
+    /// first, along with their total number.
+    ///
+    /// The rows of the tables that do not record their deletion time come last. Pages are
+    /// numbered from 1.
+    pub fn recently_deleted<Ts, Conn>(
+        &self,
+        page: i64,
+        per_page: i64,
+        conn: &Conn,
+    ) -> QueryResult<TrashPage<TrashEntry<Ts>>>
+    where
+        Conn: Connection<Backend = DB>,
+        String: FromSql<Text, DB>,
+        Ts: FromSql<Timestamp, DB>,
+        i64: FromSql<BigInt, DB>,
+    {
+        let page = page.max(1);
+        let union = match self.trash_union()? {
+            Some(union) => union,
+            None => return Ok(TrashPage { rows: Vec::new(), total: 0, page, per_page }),
+        };
+        let count = format!("SELECT COUNT(*) AS count FROM ({}) AS trash", union);
+        let Count(total) = diesel::sql_query(count).get_result(conn)?;
+        let sql = format!(
+            "SELECT * FROM ({}) AS trash \
+             ORDER BY deleted_at IS NULL, deleted_at DESC, table_name, id LIMIT {} OFFSET {}",
+            union,
+            per_page,
+            (page - 1) * per_page,
+        );
+        let rows = diesel::sql_query(sql)
+            .load::<Entry<Ts>>(conn)?
+            .into_iter()
+            .filter_map(|entry| {
+                let table = self.get(&entry.table)?.name();
+                Some(TrashEntry {
+                    table,
+                    id: entry.id,
+                    label: entry.label,
+                    deleted_at: entry.deleted_at,
+                })
+            })
+            .collect();
+        Ok(TrashPage { rows, total, page, per_page })
+    }
+
+    /// The `UNION ALL` of the trashed rows of the registered tables, if any.
+    fn trash_union(&self) -> QueryResult<Option<String>> {
+        let mut arms = Vec::new();
+        for table in self.tables() {
+            let label = match table.label() {
+                Some(label) => format!("CAST({} AS {})", table.quoted_column(label)?, DB::TEXT),
+                None => format!("CAST(NULL AS {})", DB::TEXT),
+            };
+            let deleted_at = match table.deleted_at() {
+                Some(deleted_at) => table.quoted_column(deleted_at)?,
+                None => "NULL".to_owned(),
+            };
+            arms.push(format!(
+                "SELECT '{}' AS table_name, CAST({} AS {}) AS id, {} AS label, \
+                 {} AS deleted_at FROM {} WHERE {}",
+                table.name().replace('\'', "''"),
+                table.quoted_column(table.primary_key())?,
+                DB::TEXT,
+                label,
+                deleted_at,
+                table.quoted_name()?,
+                table.deleted_sql()?,
+            ));
+        }
+        Ok(if arms.is_empty() { None } else { Some(arms.join(" UNION ALL ")) })
+    }
+}
@@ -0,0 +1,67 @@
+//! Checks that a key is unique among the alive rows of a table, e.g. before adding a partial
+//! unique index to a long-lived table, or as a recurring integrity check.
+//!
+//! ```rust,ignore
+//! let duplicates: Vec<AliveDuplicate<(i32, String)>> =
+//!     alive_duplicates(user::table, (user::tenant_id, user::email), &conn)?;
+//! for duplicate in duplicates {
+//!     println!("{:?} is used by {} alive users", duplicate.key, duplicate.count);
+//! }
+//! ```
+
+use diesel::{
+    backend::Backend,
+    deserialize::Queryable,
+    dsl::SqlTypeOf,
+    query_builder::{QueryBuilder, QueryFragment},
+    sql_types::{BigInt, HasSqlType},
+    Connection, Expression, QueryResult, RunQueryDsl, Table,
+};
+
+use crate::SoftDelete;
+
+/// A key shared by several alive rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliveDuplicate<K> {
+    pub key: K,
+    /// The number of alive rows sharing the key, at least 2.
+    pub count: i64,
+}
+
+/// The keys shared by several alive rows of `table`, loaded as `U`, in ascending order of key.
+///
+/// The `key` is a column of the table or a tuple of them. The soft-deleted rows are ignored, as a
+/// partial unique index on the alive rows does.
+pub fn alive_duplicates<T, K, U, Conn>(
+    table: T,
+    key: K,
+    conn: &Conn,
+) -> QueryResult<Vec<AliveDuplicate<U>>>
+where
+    T: Table + SoftDelete,
+    T::FromClause: QueryFragment<Conn::Backend>,
+    T::Deleted: QueryFragment<Conn::Backend>,
+    K: Expression + QueryFragment<Conn::Backend>,
+    (U, i64): Queryable<(SqlTypeOf<K>, BigInt), Conn::Backend>,
+    Conn: Connection,
+    Conn::Backend: HasSqlType<(SqlTypeOf<K>, BigInt)>,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+{
+    let mut sql = <Conn::Backend as Backend>::QueryBuilder::default();
+    sql.push_sql("SELECT ");
+    key.to_sql(&mut sql)?;
+    sql.push_sql(", COUNT(*) FROM ");
+    table.from_clause().to_sql(&mut sql)?;
+    sql.push_sql(" WHERE NOT (");
+    table.deleted_col().to_sql(&mut sql)?;
+    sql.push_sql(") GROUP BY ");
+    key.to_sql(&mut sql)?;
+    sql.push_sql(" HAVING COUNT(*) > 1 ORDER BY ");
+    key.to_sql(&mut sql)?;
+    let duplicates = diesel::dsl::sql::<(SqlTypeOf<K>, BigInt)>(&sql.finish())
+        .load::<(U, i64)>(conn)?
+        .into_iter()
+        .map(|(key, count)| AliveDuplicate { key, count })
+        .collect();
+    Ok(duplicates)
+}
@@ -0,0 +1,73 @@
+//! Window functions over soft-scoped queries.
+//!
+//! The alive condition added by the soft methods belongs to the `WHERE` clause, which is evaluated
+//! before the window functions of the select clause: the windows only ever see alive rows, e.g.
+//! `post::table.soft_deleted().select((post::id, row_number_over(post::user_id, post::id)))`
+//! numbers the alive posts of each user without gaps left by the trashed ones.
+
+use diesel::{
+    backend::Backend,
+    expression::{AppearsOnTable, NonAggregate, SelectableExpression},
+    query_builder::{AstPass, QueryFragment, QueryId},
+    sql_types::BigInt,
+    Expression, QueryResult,
+};
+
+/// The `ROW_NUMBER() OVER (PARTITION BY .. ORDER BY ..)` window function.
+#[derive(Debug, Clone, Copy)]
+pub struct RowNumber<P, O> {
+    partition: P,
+    order: O,
+}
+
+/// Number the rows of each `partition`, in `order`, starting from 1.
+///
+/// Both can be a single expression or a tuple of them, and `order` can use `.desc()`.
+pub fn row_number_over<P, O>(partition: P, order: O) -> RowNumber<P, O>
+where
+    P: Expression,
+    O: Expression,
+{
+    RowNumber { partition, order }
+}
+
+impl<P, O> Expression for RowNumber<P, O> {
+    type SqlType = BigInt;
+}
+
+impl<P, O> NonAggregate for RowNumber<P, O> {}
+
+impl<P, O, QS> AppearsOnTable<QS> for RowNumber<P, O>
+where
+    P: AppearsOnTable<QS>,
+    O: AppearsOnTable<QS>,
+{
+}
+
+impl<P, O, QS> SelectableExpression<QS> for RowNumber<P, O>
+where
+    P: SelectableExpression<QS>,
+    O: SelectableExpression<QS>,
+{
+}
+
+impl<P: QueryId, O: QueryId> QueryId for RowNumber<P, O> {
+    type QueryId = RowNumber<P::QueryId, O::QueryId>;
+    const HAS_STATIC_QUERY_ID: bool = P::HAS_STATIC_QUERY_ID && O::HAS_STATIC_QUERY_ID;
+}
+
+impl<P, O, DB> QueryFragment<DB> for RowNumber<P, O>
+where
+    P: QueryFragment<DB>,
+    O: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.push_sql("ROW_NUMBER() OVER (PARTITION BY ");
+        self.partition.walk_ast(out.reborrow())?;
+        out.push_sql(" ORDER BY ");
+        self.order.walk_ast(out.reborrow())?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
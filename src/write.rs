@@ -0,0 +1,1103 @@
+//! Write operations: soft-deleting and restoring rows.
+//!
+//! Those are the soft counterparts to [`diesel::delete`], building an `UPDATE` statement that
+//! flips the deleted flag instead of removing rows. Only rows whose state actually changes are
+//! touched, so the number of affected rows is meaningful.
+
+use std::{error::Error as StdError, fmt, marker::PhantomData};
+
+use diesel::{
+    associations::{HasTable, Identifiable},
+    backend::Backend,
+    dsl::{count_star, not, CountStar, Desc, Filter, Find, Limit, Order, Select, Update},
+    expression::{operators, AppearsOnTable, AsExpression, NonAggregate},
+    helper_types::not as Not,
+    query_builder::{
+        nodes::Identifier, AsChangeset, AstPass, DeleteStatement, InsertStatement,
+        IntoUpdateTarget, Query, QueryFragment, QueryId,
+    },
+    query_dsl::{
+        methods::{ExecuteDsl, FilterDsl, FindDsl, LimitDsl, OrderDsl, SelectDsl},
+        LoadQuery,
+    },
+    result::{DatabaseErrorKind, Error},
+    sql_types::{Bool, SingleValue},
+    BoolExpressionMethods, Column, Connection, Expression, ExpressionMethods, Insertable,
+    OptionalExtension, QueryResult, RunQueryDsl, SelectableExpression, Table,
+};
+
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+use diesel::query_builder::QueryBuilder;
+
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+use crate::{config::SoftDeleteConfig, migration::MigrationBackend};
+use crate::{
+    hooks::{Action, HookConnection},
+    methods::{OnlyDeletedDsl, SoftFilterDsl},
+    trace::{self, Target},
+    SoftDelete, SoftIdentifiable,
+};
+
+/// A SQL database table whose rows can be soft-deleted and restored.
+///
+/// This is implemented by the [`soft_delete`](crate::soft_delete) macro.
+pub trait SoftDeleteWrite: SoftDelete + Table {
+    /// The changeset flagging a row as deleted.
+    type DeleteChangeset: AsChangeset<Target = Self>;
+    /// The changeset flagging a row as alive.
+    type RestoreChangeset: AsChangeset<Target = Self>;
+    /// The condition of the rows brought back by the restore changeset, usually the deleted rows.
+    type Restorable: Expression<SqlType = Bool>;
+
+    fn delete_changeset(&self) -> Self::DeleteChangeset;
+    fn restore_changeset(&self) -> Self::RestoreChangeset;
+    fn restorable(&self) -> Self::Restorable;
+}
+
+/// The changeset setting a boolean flag column.
+pub type FlagChangeset<C> = diesel::dsl::Eq<C, bool>;
+
+/// Build the changeset setting a boolean flag column to `value`.
+pub fn flag_changeset<C>(col: C, value: bool) -> FlagChangeset<C>
+where
+    C: Column<SqlType = Bool>,
+    bool: AsExpression<Bool>,
+{
+    col.eq(value)
+}
+
+/// The deletion timestamp of the alive rows of the tables declared with the `sentinel` form of
+/// [`soft_delete`](crate::soft_delete), the earliest value of a MySQL `TIMESTAMP` in UTC.
+///
+/// MySQL converts the `TIMESTAMP` literals from the `time_zone` of the session: in a session ahead
+/// of UTC, the sentinel is before the epoch and rejected. The sentinel columns are `DATETIME`, or
+/// are only written by sessions in UTC.
+pub const ALIVE_SENTINEL: &str = "1970-01-01 00:00:01";
+
+/// The value of a deletion timestamp column: `CURRENT_TIMESTAMP` once deleted, `NULL` or the
+/// [`ALIVE_SENTINEL`] otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct DeletionTime<C> {
+    value: TimeValue,
+    _col: std::marker::PhantomData<C>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TimeValue {
+    Now,
+    Null,
+    Sentinel,
+}
+
+impl<C> DeletionTime<C> {
+    fn new(value: TimeValue) -> Self {
+        Self { value, _col: std::marker::PhantomData }
+    }
+}
+
+impl<C: Expression> Expression for DeletionTime<C> {
+    type SqlType = C::SqlType;
+}
+
+impl<C> NonAggregate for DeletionTime<C> {}
+
+impl<C, QS> AppearsOnTable<QS> for DeletionTime<C> where Self: Expression {}
+
+impl<C, QS> SelectableExpression<QS> for DeletionTime<C> where Self: Expression {}
+
+impl<C> QueryId for DeletionTime<C> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<C, DB: Backend> QueryFragment<DB> for DeletionTime<C> {
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        match self.value {
+            TimeValue::Now => out.push_sql("CURRENT_TIMESTAMP"),
+            TimeValue::Null => out.push_sql("NULL"),
+            TimeValue::Sentinel => {
+                out.push_sql("'");
+                out.push_sql(ALIVE_SENTINEL);
+                out.push_sql("'");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The changeset setting a deletion timestamp column.
+pub type TimestampChangeset<C> = diesel::dsl::Eq<C, DeletionTime<C>>;
+
+/// Build the changeset setting a nullable deletion timestamp column to the current time if
+/// `deleted`, or to `NULL` otherwise.
+pub fn timestamp_changeset<C>(col: C, deleted: bool) -> TimestampChangeset<C>
+where
+    C: Column,
+    C::SqlType: SingleValue,
+{
+    col.eq(DeletionTime::new(if deleted { TimeValue::Now } else { TimeValue::Null }))
+}
+
+/// The deleted flag of a deletion timestamp column holding the [`ALIVE_SENTINEL`] on alive rows.
+pub type SentinelDeleted<C> = diesel::dsl::NotEq<C, DeletionTime<C>>;
+
+/// Build the deleted flag of a deletion timestamp column holding the [`ALIVE_SENTINEL`] on alive
+/// rows, i.e. `col != '1970-01-01 00:00:01'`.
+pub fn sentinel_deleted<C>(col: C) -> SentinelDeleted<C>
+where
+    C: Column,
+    C::SqlType: SingleValue,
+{
+    col.ne(DeletionTime::new(TimeValue::Sentinel))
+}
+
+/// Build the changeset setting a deletion timestamp column to the current time if `deleted`, or
+/// to the [`ALIVE_SENTINEL`] otherwise.
+pub fn sentinel_changeset<C>(col: C, deleted: bool) -> TimestampChangeset<C>
+where
+    C: Column,
+    C::SqlType: SingleValue,
+{
+    col.eq(DeletionTime::new(if deleted { TimeValue::Now } else { TimeValue::Sentinel }))
+}
+
+/// The deleted flag of a nullable deletion timestamp column scheduling the deletion of the rows:
+/// set and passed.
+pub type ScheduledDeleted<C> =
+    diesel::dsl::And<diesel::dsl::IsNotNull<C>, diesel::dsl::LtEq<C, DeletionTime<C>>>;
+
+/// Build the deleted flag of a nullable deletion timestamp column scheduling the deletion of the
+/// rows, i.e. `col IS NOT NULL AND col <= CURRENT_TIMESTAMP`.
+pub fn scheduled_deleted<C>(col: C) -> ScheduledDeleted<C>
+where
+    C: Column + Copy,
+    C::SqlType: SingleValue,
+{
+    col.is_not_null().and(col.le(current_time()))
+}
+
+/// The current time, `CURRENT_TIMESTAMP`, as a value of the deletion timestamp column `C`.
+pub(crate) fn current_time<C>() -> DeletionTime<C> {
+    DeletionTime::new(TimeValue::Now)
+}
+
+pub(crate) type Deleted<T> = <<T as HasTable>::Table as SoftDelete>::Deleted;
+type DeleteChangeset<T> = <<T as HasTable>::Table as SoftDeleteWrite>::DeleteChangeset;
+type RestoreChangeset<T> = <<T as HasTable>::Table as SoftDeleteWrite>::RestoreChangeset;
+type Restorable<T> = <<T as HasTable>::Table as SoftDeleteWrite>::Restorable;
+
+/// An update target whose rows can be soft-deleted and restored.
+///
+/// This is implemented for every update target of a [`SoftDeleteWrite`] table, and is what
+/// [`soft_delete`] and [`restore`] build upon.
+pub trait SoftDeleteTarget: IntoUpdateTarget {
+    /// The type returned by [`soft_delete`].
+    type SoftDelete;
+    /// The type returned by [`restore`].
+    type Restore;
+
+    fn soft_delete(self) -> Self::SoftDelete;
+    fn restore(self) -> Self::Restore;
+}
+
+impl<T> SoftDeleteTarget for T
+where
+    T: IntoUpdateTarget,
+    T::Table: SoftDeleteWrite,
+    Update<T, DeleteChangeset<T>>: FilterDsl<Not<Deleted<T>>>,
+    Update<T, RestoreChangeset<T>>: FilterDsl<Restorable<T>>,
+{
+    type SoftDelete = Filter<Update<T, DeleteChangeset<T>>, Not<Deleted<T>>>;
+    type Restore = Filter<Update<T, RestoreChangeset<T>>, Restorable<T>>;
+
+    fn soft_delete(self) -> Self::SoftDelete {
+        let table = T::table();
+        let (changeset, deleted) = (table.delete_changeset(), table.deleted_col());
+        diesel::update(self).set(changeset).filter(not(deleted))
+    }
+
+    fn restore(self) -> Self::Restore {
+        let table = T::table();
+        let (changeset, restorable) = (table.restore_changeset(), table.restorable());
+        diesel::update(self).set(changeset).filter(restorable)
+    }
+}
+
+/// Creates an `UPDATE` statement soft-deleting the alive rows of `target`.
+///
+/// Executed by Diesel, the statement runs none of the [`hooks`](crate::hooks), which
+/// [`soft_delete_rows`](SoftWriteDsl::soft_delete_rows) does.
+pub fn soft_delete<T: SoftDeleteTarget>(target: T) -> T::SoftDelete {
+    target.soft_delete()
+}
+
+/// Creates an `UPDATE` statement restoring the soft-deleted rows of `target`.
+///
+/// On the tables whose rows [expire](crate::expiry), only the flagged rows which have not expired
+/// are restored, as the expired ones would stay deleted.
+///
+/// Executed by Diesel, the statement runs none of the [`hooks`](crate::hooks), which
+/// [`restore_rows`](SoftWriteDsl::restore_rows) does.
+pub fn restore<T: SoftDeleteTarget>(target: T) -> T::Restore {
+    target.restore()
+}
+
+/// The `soft_delete_rows` and `restore_rows` methods, executing the statements of [`soft_delete`]
+/// and [`restore`] and running the [`hooks`](crate::hooks) installed for the connection.
+pub trait SoftWriteDsl: SoftDeleteTarget + Sized {
+    /// Soft-delete the alive rows of the target, returning their number.
+    fn soft_delete_rows<Conn>(self, conn: &Conn) -> QueryResult<usize>
+    where
+        Self::Table: Table<FromClause = Identifier<'static>>,
+        <Self::Table as Table>::PrimaryKey: Column,
+        Self::SoftDelete: QueryFragment<Conn::Backend> + QueryId,
+        Conn: HookConnection,
+    {
+        let target = Target::of(Self::table());
+        trace::write(Action::SoftDelete, target, self.soft_delete(), conn)
+    }
+
+    /// Restore the soft-deleted rows of the target, returning their number.
+    fn restore_rows<Conn>(self, conn: &Conn) -> QueryResult<usize>
+    where
+        Self::Table: Table<FromClause = Identifier<'static>>,
+        <Self::Table as Table>::PrimaryKey: Column,
+        Self::Restore: QueryFragment<Conn::Backend> + QueryId,
+        Conn: HookConnection,
+    {
+        let target = Target::of(Self::table());
+        trace::write(Action::Restore, target, self.restore(), conn)
+    }
+}
+
+impl<T: SoftDeleteTarget> SoftWriteDsl for T {}
+
+/// Creates a `DELETE` statement removing the soft-deleted rows of `target`, alive rows being
+/// left untouched.
+///
+/// This is the same as `diesel::delete(target).only_deleted()`, and does not need an
+/// [`AllowHardDelete`] token: the rows were already deleted from the application's point of view.
+pub fn purge<T>(target: T) -> Filter<DeleteStatement<T::Table, T::WhereClause>, Deleted<T>>
+where
+    T: IntoUpdateTarget,
+    T::Table: SoftDelete,
+    DeleteStatement<T::Table, T::WhereClause>: FilterDsl<Deleted<T>>,
+{
+    let deleted = T::table().deleted_col();
+    diesel::delete(target).filter(deleted)
+}
+
+/// A write statement returning an expression of each row it affects, built by [`returning`].
+#[derive(Debug, Clone, Copy)]
+pub struct Returning<S, E> {
+    stmt: S,
+    returning: E,
+}
+
+/// Return `returning` from each row affected by the `UPDATE` or `DELETE` statement `stmt`, as in
+/// `UPDATE post SET deleted = true WHERE ... RETURNING post.id`.
+///
+/// Unlike Diesel's own `returning`, this works with the statements of [`soft_delete`], [`restore`]
+/// and [`purge`] on both PostgreSQL and SQLite 3.35 or later. MySQL has no `RETURNING` clause.
+pub fn returning<S, E: Expression>(stmt: S, returning: E) -> Returning<S, E> {
+    Returning { stmt, returning }
+}
+
+impl<S, E: Expression> Query for Returning<S, E> {
+    type SqlType = E::SqlType;
+}
+
+impl<S, E> QueryId for Returning<S, E> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<S, E, DB> QueryFragment<DB> for Returning<S, E>
+where
+    S: QueryFragment<DB>,
+    E: QueryFragment<DB>,
+    DB: Backend,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        self.stmt.walk_ast(out.reborrow())?;
+        out.push_sql(" RETURNING ");
+        self.returning.walk_ast(out.reborrow())
+    }
+}
+
+impl<S, E, Conn> RunQueryDsl<Conn> for Returning<S, E> {}
+
+/// The error of [`soft_save_changes`].
+#[derive(Debug)]
+pub enum SaveError {
+    /// The row is soft-deleted, either in the model or concurrently in the database.
+    Deleted,
+    Query(Error),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Deleted => f.write_str("cannot save changes to a soft-deleted row"),
+            Self::Query(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for SaveError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Deleted => None,
+            Self::Query(e) => Some(e),
+        }
+    }
+}
+
+impl From<Error> for SaveError {
+    fn from(e: Error) -> Self {
+        Self::Query(e)
+    }
+}
+
+/// The soft counterpart to Diesel's `save_changes`, which refuses to save a soft-deleted model.
+///
+/// The `UPDATE` statement only touches the row if it is still alive, so a row soft-deleted since
+/// the model was loaded is not updated either: both cases fail with [`SaveError::Deleted`]. On
+/// success, the updated row is loaded back.
+pub fn soft_save_changes<T, Tab, Stmt, U, Conn>(model: T, conn: &Conn) -> Result<U, SaveError>
+where
+    T: SoftIdentifiable + Identifiable<Table = Tab> + AsChangeset<Target = Tab> + Copy,
+    Tab: Table<FromClause = Identifier<'static>> + SoftDelete + FindDsl<T::Id>,
+    Find<Tab, T::Id>: IntoUpdateTarget<Table = Tab> + RunQueryDsl<Conn> + LoadQuery<Conn, U>,
+    Update<Find<Tab, T::Id>, T>: FilterDsl<Not<Tab::Deleted>, Output = Stmt>,
+    Stmt: ExecuteDsl<Conn>,
+    Conn: Connection,
+{
+    if model.is_deleted() {
+        return Err(SaveError::Deleted);
+    }
+    let (target, deleted) = (T::table().find(model.id()), T::table().deleted_col());
+    let update = diesel::update(target).set(model).filter(not(deleted));
+    if trace::execute("save_changes", T::table().from_clause().0, update, conn)? == 0 {
+        return Err(SaveError::Deleted);
+    }
+    Ok(T::table().find(model.id()).get_result(conn)?)
+}
+
+/// The error of [`restore_by_id`](RestoreByIdDsl::restore_by_id).
+#[derive(Debug)]
+pub enum RestoreError {
+    /// There is no such row.
+    NotFound,
+    /// The row is not soft-deleted.
+    AlreadyAlive,
+    Query(Error),
+}
+
+impl fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound => f.write_str("no row to restore"),
+            Self::AlreadyAlive => f.write_str("the row is not soft-deleted"),
+            Self::Query(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for RestoreError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::NotFound | Self::AlreadyAlive => None,
+            Self::Query(e) => Some(e),
+        }
+    }
+}
+
+impl From<Error> for RestoreError {
+    fn from(e: Error) -> Self {
+        Self::Query(e)
+    }
+}
+
+/// The `restore_by_id` method.
+pub trait RestoreByIdDsl<PK>: FindDsl<PK> + Copy {
+    /// Restore a single soft-deleted row by primary key, and load it back.
+    fn restore_by_id<U, Conn>(self, id: PK, conn: &Conn) -> Result<U, RestoreError>
+    where
+        PK: Clone,
+        Find<Self, PK>: SoftDeleteTarget + RunQueryDsl<Conn> + LoadQuery<Conn, U>,
+        <Find<Self, PK> as HasTable>::Table: Table<FromClause = Identifier<'static>>,
+        <<Find<Self, PK> as HasTable>::Table as Table>::PrimaryKey: Column,
+        <Find<Self, PK> as SoftDeleteTarget>::Restore: QueryFragment<Conn::Backend> + QueryId,
+        Conn: HookConnection,
+    {
+        let restored = self.find(id.clone()).restore_rows(conn)?;
+        match (restored, self.find(id).get_result(conn).optional()?) {
+            (_, None) => Err(RestoreError::NotFound),
+            (0, Some(_)) => Err(RestoreError::AlreadyAlive),
+            (_, Some(row)) => Ok(row),
+        }
+    }
+}
+
+impl<T, PK> RestoreByIdDsl<PK> for T where T: FindDsl<PK> + Copy {}
+
+/// The error of [`purge_by_id`](PurgeByIdDsl::purge_by_id).
+#[derive(Debug)]
+pub enum PurgeError {
+    /// There is no such row.
+    NotFound,
+    /// The row is not soft-deleted, and was left untouched.
+    Alive,
+    Query(Error),
+}
+
+impl fmt::Display for PurgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound => f.write_str("no row to purge"),
+            Self::Alive => f.write_str("cannot purge a row that is not soft-deleted"),
+            Self::Query(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for PurgeError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::NotFound | Self::Alive => None,
+            Self::Query(e) => Some(e),
+        }
+    }
+}
+
+impl From<Error> for PurgeError {
+    fn from(e: Error) -> Self {
+        Self::Query(e)
+    }
+}
+
+pub(crate) type PurgeStatement<T> =
+    DeleteStatement<<T as HasTable>::Table, <T as IntoUpdateTarget>::WhereClause>;
+
+/// The `purge_by_id` method.
+pub trait PurgeByIdDsl<PK>: FindDsl<PK> + Copy {
+    /// Hard-delete a single row by primary key, only if it is already soft-deleted.
+    ///
+    /// This is the building block of "delete forever" actions: an alive row is never destroyed.
+    fn purge_by_id<Stmt, Conn>(self, id: PK, conn: &Conn) -> Result<(), PurgeError>
+    where
+        PK: Clone,
+        Find<Self, PK>: IntoUpdateTarget + SelectDsl<CountStar>,
+        <Find<Self, PK> as HasTable>::Table: SoftDelete + Table<FromClause = Identifier<'static>>,
+        <<Find<Self, PK> as HasTable>::Table as Table>::PrimaryKey: Column,
+        PurgeStatement<Find<Self, PK>>: FilterDsl<Deleted<Find<Self, PK>>, Output = Stmt>,
+        Stmt: QueryFragment<Conn::Backend> + QueryId,
+        Select<Find<Self, PK>, CountStar>: LoadQuery<Conn, i64> + RunQueryDsl<Conn>,
+        Conn: HookConnection,
+    {
+        let target = Target::of(<Find<Self, PK> as HasTable>::table());
+        if trace::write(Action::Purge, target, purge(self.find(id.clone())), conn)? > 0 {
+            return Ok(());
+        }
+        match self.find(id).select(count_star()).get_result::<i64>(conn)? {
+            0 => Err(PurgeError::NotFound),
+            _ => Err(PurgeError::Alive),
+        }
+    }
+}
+
+impl<T, PK> PurgeByIdDsl<PK> for T where T: FindDsl<PK> + Copy {}
+
+/// A changeset that is not allowed to set the deleted flag of its table.
+///
+/// Wrapping the changesets built from forms or other user-provided structs ensures that only the
+/// dedicated [`soft_delete`] and [`restore`] functions flip the flag: the `UPDATE` statements
+/// whose changeset sets it fail to build. The changeset must implement [`Assigns`], which tells
+/// from its type the columns it sets.
+#[derive(Debug, Clone, Copy)]
+pub struct ProtectDeleted<C>(pub C);
+
+impl<C> AsChangeset for ProtectDeleted<C>
+where
+    C: AsChangeset + Assigns<<C::Target as SoftDelete>::Deleted>,
+    C::Target: SoftDelete,
+{
+    type Target = C::Target;
+    type Changeset = C::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        #[allow(clippy::let_unit_value)]
+        let () = Unassigned::<C, <C::Target as SoftDelete>::Deleted>::CHECK;
+        self.0.as_changeset()
+    }
+}
+
+/// The check that the changeset `C` does not set the column `Col`, evaluated at build time.
+struct Unassigned<C, Col>(PhantomData<(C, Col)>);
+
+impl<C: Assigns<Col>, Col> Unassigned<C, Col> {
+    const CHECK: () =
+        assert!(!C::ASSIGNS, "changeset sets the deleted flag, use soft_delete or restore");
+}
+
+/// A changeset whose type tells whether it sets the `Col` column.
+///
+/// It is implemented for the `column.eq(value)` assignments, and the options and tuples of
+/// changesets. With the `derive` feature, `#[derive(Assigns)]` implements it on the structs
+/// deriving `AsChangeset`.
+pub trait Assigns<Col> {
+    const ASSIGNS: bool;
+}
+
+impl<L: Column, R, Col: Column> Assigns<Col> for operators::Eq<L, R> {
+    const ASSIGNS: bool = crate::__private::same_name(L::NAME, Col::NAME);
+}
+
+impl<C: Assigns<Col>, Col> Assigns<Col> for Option<C> {
+    const ASSIGNS: bool = C::ASSIGNS;
+}
+
+impl<C: Assigns<Col>, Col> Assigns<Col> for &C {
+    const ASSIGNS: bool = C::ASSIGNS;
+}
+
+macro_rules! impl_assigns {
+    ($($changeset:ident),+) => {
+        impl<Col, $($changeset),+> Assigns<Col> for ($($changeset,)+)
+        where
+            $($changeset: Assigns<Col>),+
+        {
+            const ASSIGNS: bool = $($changeset::ASSIGNS)||+;
+        }
+    };
+}
+
+impl_assigns!(A);
+impl_assigns!(A, B);
+impl_assigns!(A, B, C);
+impl_assigns!(A, B, C, D);
+impl_assigns!(A, B, C, D, E);
+impl_assigns!(A, B, C, D, E, F);
+impl_assigns!(A, B, C, D, E, F, G);
+impl_assigns!(A, B, C, D, E, F, G, H);
+impl_assigns!(A, B, C, D, E, F, G, H, I);
+impl_assigns!(A, B, C, D, E, F, G, H, I, J);
+impl_assigns!(A, B, C, D, E, F, G, H, I, J, K);
+impl_assigns!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_assigns!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_assigns!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_assigns!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_assigns!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
+
+/// The capability to hard-delete rows of soft-deletable tables, required by [`hard_delete`].
+///
+/// It can only be built with the loudly named [`AllowHardDelete::i_really_want_to_destroy_rows`],
+/// so that hard deletes stand out in code and in review. To make it mandatory, forbid the plain
+/// [`diesel::delete`] in `clippy.toml`:
+///
+/// ```toml
+/// disallowed-methods = ["diesel::delete"]
+/// ```
+#[derive(Debug)]
+pub struct AllowHardDelete {
+    _private: (),
+}
+
+impl AllowHardDelete {
+    pub fn i_really_want_to_destroy_rows() -> Self {
+        Self { _private: () }
+    }
+}
+
+/// Creates a `DELETE` statement, actually removing the rows of `target` from a soft-deletable
+/// table, alive or not.
+pub fn hard_delete<T>(
+    target: T,
+    _token: AllowHardDelete,
+) -> DeleteStatement<T::Table, T::WhereClause>
+where
+    T: IntoUpdateTarget,
+    T::Table: SoftDelete,
+{
+    diesel::delete(target)
+}
+
+/// Hard-delete the trashed rows of the tables of `config` whose retention elapsed, returning the
+/// number of rows deleted per table, in registration order.
+///
+/// The tables on hold, and those without a retention or a deletion timestamp, are left out. The
+/// tables are purged in one transaction.
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+pub fn purge_expired<Conn>(
+    config: &SoftDeleteConfig<Conn::Backend>,
+    _token: AllowHardDelete,
+    conn: &Conn,
+) -> QueryResult<Vec<(&'static str, usize)>>
+where
+    Conn: HookConnection,
+    Conn::Backend: MigrationBackend,
+    <Conn::Backend as Backend>::QueryBuilder: Default,
+{
+    conn.transaction(|| {
+        let mut purged = Vec::new();
+        for table in config.tables() {
+            let (retention, deleted_at) = match (table.retention(), table.deleted_at()) {
+                (Some(retention), Some(deleted_at)) if !table.is_held() => (retention, deleted_at),
+                _ => continue,
+            };
+            let quoted_name = table.quoted_name()?;
+            let mut out = <Conn::Backend as Backend>::QueryBuilder::default();
+            out.push_sql(&quoted_name);
+            out.push_sql(".");
+            out.push_identifier(deleted_at)?;
+            let older = <Conn::Backend as MigrationBackend>::older_than(&out.finish(), retention);
+            let sql = format!(
+                "DELETE FROM {} WHERE {} AND {}",
+                quoted_name,
+                table.deleted_sql()?,
+                older,
+            );
+            let target = Target { name: table.name(), key: table.primary_key() };
+            let deleted = trace::write(Action::Purge, target, diesel::sql_query(sql), conn)?;
+            purged.push((table.name(), deleted));
+        }
+        Ok(purged)
+    })
+}
+
+/// The purge of the expired trash of [`purge_expired`], in batches, each in its own transaction so
+/// that the locks are held briefly.
+///
+/// On PostgreSQL, each batch can be bounded by a `statement_timeout` and a `lock_timeout`, set
+/// with `SET LOCAL`, so that a runaway `DELETE` is cancelled rather than blocking the production
+/// traffic. A cancelled batch is retried, up to 3 times by default, then the purge stops with
+/// [`ExpiredPurgeError::Timeout`]. SQLite has no such settings, and ignores the timeouts.
+///
+/// MySQL does not support the `LIMIT` of the subquery selecting a batch: it is not available with
+/// the `mysql` feature alone.
+///
+/// ```rust,ignore
+/// let purged = ExpiredPurge::new(&config, AllowHardDelete::i_really_want_to_destroy_rows())
+///     .batch_size(500)
+///     .lock_timeout(Duration::from_millis(100))
+///     .run(&conn)?;
+/// ```
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+#[derive(Debug)]
+pub struct ExpiredPurge<'a, DB: Backend> {
+    config: &'a SoftDeleteConfig<DB>,
+    batch_size: usize,
+    statement_timeout: Option<std::time::Duration>,
+    lock_timeout: Option<std::time::Duration>,
+    max_retries: usize,
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+impl<'a, DB: MigrationBackend> ExpiredPurge<'a, DB>
+where
+    DB::QueryBuilder: Default,
+{
+    /// Purge the expired trash of the tables of `config` in batches of 1000 rows.
+    pub fn new(config: &'a SoftDeleteConfig<DB>, _token: AllowHardDelete) -> Self {
+        Self {
+            config,
+            batch_size: 1000,
+            statement_timeout: None,
+            lock_timeout: None,
+            max_retries: 3,
+        }
+    }
+
+    /// Purge `batch_size` rows at a time.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "the batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Cancel the statements of a batch running for longer than `timeout`.
+    pub fn statement_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.statement_timeout = Some(timeout);
+        self
+    }
+
+    /// Cancel the statements of a batch waiting for a lock for longer than `timeout`.
+    pub fn lock_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    /// Retry a cancelled batch up to `max_retries` times.
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Run the purge, returning the number of rows deleted per table, in registration order.
+    pub fn run<Conn>(&self, conn: &Conn) -> Result<Vec<(&'static str, usize)>, ExpiredPurgeError>
+    where
+        Conn: HookConnection<Backend = DB>,
+    {
+        let mut purged = Vec::new();
+        for table in self.config.tables() {
+            let (retention, deleted_at) = match (table.retention(), table.deleted_at()) {
+                (Some(retention), Some(deleted_at)) if !table.is_held() => (retention, deleted_at),
+                _ => continue,
+            };
+            let quoted_name = table.quoted_name()?;
+            let mut out = DB::QueryBuilder::default();
+            out.push_sql(&quoted_name);
+            out.push_sql(".");
+            out.push_identifier(deleted_at)?;
+            let older = DB::older_than(&out.finish(), retention);
+            let mut primary_key = DB::QueryBuilder::default();
+            primary_key.push_identifier(table.primary_key())?;
+            let primary_key = primary_key.finish();
+            let sql = format!(
+                "DELETE FROM {0} WHERE {1} IN (SELECT {1} FROM {0} WHERE {2} AND {3} LIMIT {4})",
+                quoted_name,
+                primary_key,
+                table.deleted_sql()?,
+                older,
+                self.batch_size,
+            );
+
+            purged.push((table.name(), 0));
+            loop {
+                let target = Target { name: table.name(), key: table.primary_key() };
+                let deleted = match self.batch(target, &sql, conn) {
+                    Ok(deleted) => deleted,
+                    Err(error) if DB::is_timeout(&error) => {
+                        return Err(ExpiredPurgeError::Timeout {
+                            table: table.name(),
+                            purged,
+                            error,
+                        })
+                    }
+                    Err(error) => return Err(error.into()),
+                };
+                purged.last_mut().expect("the table was pushed").1 += deleted;
+                if deleted < self.batch_size {
+                    break;
+                }
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Run a batch in its own transaction, retrying it while it is cancelled.
+    fn batch<Conn>(&self, target: Target, sql: &str, conn: &Conn) -> QueryResult<usize>
+    where
+        Conn: HookConnection<Backend = DB>,
+    {
+        let mut retries = 0;
+        loop {
+            let deleted = conn.transaction(|| {
+                for timeout in DB::local_timeouts(self.statement_timeout, self.lock_timeout) {
+                    conn.execute(&timeout)?;
+                }
+                trace::write_with(Action::Purge, target, conn, |keyed| match keyed {
+                    false => DB::purge_batch(sql, conn).map(trace::Affected::Count),
+                    true => DB::purge_batch_keys(sql, target.key, conn).map(trace::Affected::Keys),
+                })
+            });
+            match deleted {
+                Err(error) if DB::is_timeout(&error) && retries < self.max_retries => retries += 1,
+                deleted => return deleted,
+            }
+        }
+    }
+}
+
+/// Whether `error` is the cancellation of a batch of an [`ExpiredPurge`] by its
+/// `statement_timeout` or its `lock_timeout`, after which the batch can be retried.
+#[cfg(feature = "postgres")]
+pub fn is_timeout(error: &Error) -> bool {
+    <diesel::pg::Pg as MigrationBackend>::is_timeout(error)
+}
+
+/// The error of an [`ExpiredPurge`].
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+#[derive(Debug)]
+pub enum ExpiredPurgeError {
+    /// A batch of `table` was still cancelled after its retries. The batches run before it stay
+    /// purged, and are counted in `purged`.
+    Timeout {
+        table: &'static str,
+        purged: Vec<(&'static str, usize)>,
+        error: Error,
+    },
+    Query(Error),
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+impl fmt::Display for ExpiredPurgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Timeout { table, error, .. } => {
+                write!(f, "purge of `{}` timed out: {}", table, error)
+            }
+            Self::Query(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+impl StdError for ExpiredPurgeError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Timeout { error, .. } | Self::Query(error) => Some(error),
+        }
+    }
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+impl From<Error> for ExpiredPurgeError {
+    fn from(e: Error) -> Self {
+        Self::Query(e)
+    }
+}
+
+/// The confirmation required by [`empty_trash`], optionally capping the number of rows it may
+/// destroy.
+///
+/// Like [`AllowHardDelete`], it can only be built with a loudly named constructor.
+#[derive(Debug)]
+pub struct ConfirmEmptyTrash {
+    max_rows: Option<i64>,
+}
+
+impl ConfirmEmptyTrash {
+    pub fn i_really_want_to_destroy_trashed_rows() -> Self {
+        Self { max_rows: None }
+    }
+
+    /// Refuse to empty the trash if it holds more than `max_rows` rows.
+    pub fn max_rows(mut self, max_rows: i64) -> Self {
+        self.max_rows = Some(max_rows);
+        self
+    }
+}
+
+/// The error of [`empty_trash`].
+#[derive(Debug)]
+pub enum EmptyTrashError {
+    /// The trash holds more rows than allowed, and was left untouched.
+    TooManyRows {
+        count: i64,
+        max_rows: i64,
+    },
+    Query(Error),
+}
+
+impl fmt::Display for EmptyTrashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooManyRows { count, max_rows } => {
+                write!(f, "the trash holds {} rows, more than the {} allowed", count, max_rows)
+            }
+            Self::Query(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for EmptyTrashError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::TooManyRows { .. } => None,
+            Self::Query(e) => Some(e),
+        }
+    }
+}
+
+impl From<Error> for EmptyTrashError {
+    fn from(e: Error) -> Self {
+        Self::Query(e)
+    }
+}
+
+/// Hard-delete all the soft-deleted rows of `table`, returning their number.
+///
+/// With a [cap](ConfirmEmptyTrash::max_rows), the trashed rows are counted first, in the same
+/// transaction, and none are deleted if there are too many of them.
+pub fn empty_trash<T, Stmt, Conn>(
+    table: T,
+    confirm: ConfirmEmptyTrash,
+    conn: &Conn,
+) -> Result<usize, EmptyTrashError>
+where
+    T: Table<FromClause = Identifier<'static>> + IntoUpdateTarget + OnlyDeletedDsl + Copy,
+    <T as Table>::PrimaryKey: Column,
+    T::Table: SoftDelete,
+    T::Output: SelectDsl<CountStar>,
+    Select<T::Output, CountStar>: LoadQuery<Conn, i64> + RunQueryDsl<Conn>,
+    PurgeStatement<T>: FilterDsl<Deleted<T>, Output = Stmt>,
+    Stmt: QueryFragment<Conn::Backend> + QueryId,
+    Conn: HookConnection,
+{
+    conn.transaction(|| {
+        if let Some(max_rows) = confirm.max_rows {
+            let count = table.only_deleted().select(count_star()).get_result(conn)?;
+            if count > max_rows {
+                return Err(EmptyTrashError::TooManyRows { count, max_rows });
+            }
+        }
+        Ok(trace::write(Action::Purge, Target::of(table), purge(table), conn)?)
+    })
+}
+
+/// What [`upsert_alive`](UpsertAliveDsl::upsert_alive) did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upserted {
+    /// An alive row matched the key, and was updated.
+    Updated,
+    /// Only soft-deleted rows matched the key: the latest one was restored and updated.
+    Restored,
+    /// No row matched the key, and the values were inserted.
+    Inserted,
+}
+
+type TrashedKeys<T, K> = Select<
+    Order<Filter<Filter<T, K>, <T as SoftDeleteWrite>::Restorable>, Desc<<T as Table>::PrimaryKey>>,
+    <T as Table>::PrimaryKey,
+>;
+
+/// The `upsert_alive` method.
+pub trait UpsertAliveDsl: SoftDeleteWrite + Copy {
+    /// Insert `values`, or update the row matching `key` with `changes`, restoring it if needed.
+    ///
+    /// The alive rows matching `key` are updated first. If there are none, the latest
+    /// soft-deleted row matching it, by primary key, is restored and updated, e.g. to bring back
+    /// the deleted account of a user signing up again. Otherwise, the values are inserted. This
+    /// all runs in a transaction. As with [`restore`], the expired rows of the tables whose rows
+    /// [expire](crate::expiry) are not restored.
+    ///
+    /// Unlike an `ON CONFLICT` clause, this only builds plain statements, so the same application
+    /// code runs on SQLite, whose upserts Diesel does not support, and on PostgreSQL, where
+    /// `migration::UniqueAliveIndex::conflict_target` is the one-statement alternative.
+    ///
+    /// With a unique key on the alive rows, e.g. the generated column of
+    /// `migration::unique_alive_key` on MySQL, an insert racing with another one for the same key
+    /// violates the key: the row inserted by the other one is updated instead.
+    ///
+    /// `PK` is the type of the primary key, to load the key of the row to restore.
+    ///
+    /// ```rust,ignore
+    /// let upserted = user::table.upsert_alive::<i32, _, _, _, _>(
+    ///     user::email.eq(email),
+    ///     &NewUser { email, name },
+    ///     user::name.eq(name),
+    ///     &conn,
+    /// )?;
+    /// ```
+    fn upsert_alive<PK, K, V, C, Conn>(
+        self,
+        key: K,
+        values: V,
+        changes: C,
+        conn: &Conn,
+    ) -> QueryResult<Upserted>
+    where
+        Self: Table<FromClause = Identifier<'static>> + SoftFilterDsl<K> + FilterDsl<K>,
+        Self::PrimaryKey: Column + ExpressionMethods,
+        K: Clone,
+        C: AsChangeset<Target = Self> + Clone,
+        V: Insertable<Self>,
+        <Self as SoftFilterDsl<K>>::Output: IntoUpdateTarget<Table = Self>,
+        Update<<Self as SoftFilterDsl<K>>::Output, C>: ExecuteDsl<Conn>,
+        Filter<Self, K>: FilterDsl<Self::Restorable>,
+        Filter<Filter<Self, K>, Self::Restorable>: OrderDsl<Desc<Self::PrimaryKey>>,
+        Order<Filter<Filter<Self, K>, Self::Restorable>, Desc<Self::PrimaryKey>>:
+            SelectDsl<Self::PrimaryKey>,
+        TrashedKeys<Self, K>: LimitDsl + RunQueryDsl<Conn>,
+        Limit<TrashedKeys<Self, K>>: LoadQuery<Conn, PK>,
+        Self: FindDsl<PK>,
+        Find<Self, PK>: IntoUpdateTarget<Table = Self>,
+        Update<Find<Self, PK>, (C, Self::RestoreChangeset)>: QueryFragment<Conn::Backend> + QueryId,
+        InsertStatement<Self, V::Values>: QueryFragment<Conn::Backend> + QueryId,
+        Conn: HookConnection,
+    {
+        let table = self.from_clause().0;
+        conn.transaction(|| {
+            let alive = self.soft_filter(key.clone());
+            let update = diesel::update(alive).set(changes.clone());
+            if trace::execute("update", table, update, conn)? > 0 {
+                return Ok(Upserted::Updated);
+            }
+
+            let trashed = self
+                .filter(key.clone())
+                .filter(self.restorable())
+                .order(self.primary_key().desc())
+                .select(self.primary_key())
+                .first(conn)
+                .optional()?;
+            if let Some(id) = trashed {
+                let restore =
+                    diesel::update(self.find(id)).set((changes, self.restore_changeset()));
+                trace::write(Action::Restore, Target::of(self), restore, conn)?;
+                return Ok(Upserted::Restored);
+            }
+
+            // A row inserted concurrently with the same key is only caught by a unique key on the
+            // alive rows, which rejects the insert: that row is then updated instead. The
+            // savepoint keeps the transaction usable after the violation.
+            let insert = diesel::insert_into(self).values(values);
+            match conn.transaction(|| trace::execute("insert", table, insert, conn)) {
+                Ok(_) => Ok(Upserted::Inserted),
+                Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info)) => {
+                    let update = diesel::update(self.soft_filter(key)).set(changes);
+                    match trace::execute("update", table, update, conn)? {
+                        0 => Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info)),
+                        _ => Ok(Upserted::Updated),
+                    }
+                }
+                Err(e) => Err(e),
+            }
+        })
+    }
+}
+
+impl<T> UpsertAliveDsl for T where T: SoftDeleteWrite + Copy {}
+
+/// An expression incrementing a numeric column by one, as in `col + 1`.
+#[derive(Debug, Clone, Copy)]
+pub struct Incremented<C>(C);
+
+impl<C: Expression> Expression for Incremented<C> {
+    type SqlType = C::SqlType;
+}
+
+impl<C: NonAggregate> NonAggregate for Incremented<C> {}
+
+impl<C: AppearsOnTable<QS>, QS> AppearsOnTable<QS> for Incremented<C> {}
+
+impl<C: SelectableExpression<QS>, QS> SelectableExpression<QS> for Incremented<C> {}
+
+impl<C: QueryId> QueryId for Incremented<C> {
+    type QueryId = Incremented<C::QueryId>;
+    const HAS_STATIC_QUERY_ID: bool = C::HAS_STATIC_QUERY_ID;
+}
+
+impl<C: QueryFragment<DB>, DB: Backend> QueryFragment<DB> for Incremented<C> {
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        self.0.walk_ast(out.reborrow())?;
+        out.push_sql(" + 1");
+        Ok(())
+    }
+}
+
+/// The changeset setting a boolean flag column and incrementing a revision column.
+pub type RevisionChangeset<C, R> = (FlagChangeset<C>, diesel::dsl::Eq<R, Incremented<R>>);
+
+/// Build the changeset setting a boolean flag column to `value` and incrementing the `revision`
+/// column.
+pub fn revision_changeset<C, R>(col: C, value: bool, revision: R) -> RevisionChangeset<C, R>
+where
+    C: Column<SqlType = Bool>,
+    R: Column + Copy,
+    R::SqlType: SingleValue,
+{
+    (flag_changeset(col, value), revision.eq(Incremented(revision)))
+}